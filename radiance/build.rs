@@ -1,11 +1,39 @@
 use std::process::Command;
 
 fn main() {
+    println!("cargo:rerun-if-changed=src/shaders/lights.glsl");
+
     build_shader("simple_triangle.vert");
     build_shader("simple_triangle.frag");
+    build_shader("skinned.vert");
+    build_shader("morph.vert");
+    build_shader("lit.vert");
+    build_shader("lit.frag");
+    build_shader("pbr.frag");
+    build_shader("fullscreen.vert");
+    build_shader("bloom_down.frag");
+    build_shader("bloom_up.frag");
+    build_shader("composite.frag");
+    build_shader("fxaa.frag");
+    build_shader("skybox.vert");
+    build_shader("skybox.frag");
+    build_shader("sprite.vert");
+    build_shader("sprite.frag");
+    build_shader("sprite_screen.vert");
+
+    build_shader_variant("simple_triangle.vert", "instanced.vert", &["INSTANCED"]);
+    build_shader_variant("simple_triangle.frag", "instanced.frag", &["INSTANCED"]);
+    build_shader_variant("lit.vert", "instanced_lit.vert", &["INSTANCED"]);
+    build_shader_variant("lit.frag", "instanced_lit.frag", &["INSTANCED"]);
+    build_shader_variant("pbr.frag", "instanced_pbr.frag", &["INSTANCED"]);
 }
 
 fn build_shader(shader_name: &str) {
+    build_shader_variant(shader_name, shader_name, &[]);
+}
+
+// Compiles the shader with the given macros defined, into `<variant_name>.spv`
+fn build_shader_variant(shader_name: &str, variant_name: &str, defines: &[&str]) {
     let out_dir = std::env::var("OUT_DIR").unwrap();
     let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
     let path = std::fs::canonicalize(
@@ -16,9 +44,10 @@ fn build_shader(shader_name: &str) {
     )
     .unwrap();
     println!("cargo:rerun-if-changed={}", path.to_str().unwrap());
-    let shader_out_dir = format!("{}/{}.spv", out_dir, shader_name);
+    let shader_out_dir = format!("{}/{}.spv", out_dir, variant_name);
 
     let output = Command::new("glslc")
+        .args(defines.iter().map(|define| format!("-D{}", define)))
         .args(&[
             path.to_str().unwrap().to_owned(),
             "-o".to_string(),
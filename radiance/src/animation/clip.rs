@@ -0,0 +1,151 @@
+use super::{BonePose, Easing};
+use crate::math::{Quaternion, Vec3};
+
+#[derive(Copy, Clone, Debug)]
+pub struct Keyframe<T> {
+    pub time: f32,
+    pub value: T,
+
+    /// Curve used when interpolating towards the next keyframe
+    pub easing: Easing,
+}
+
+impl<T> Keyframe<T> {
+    pub fn new(time: f32, value: T) -> Self {
+        Self {
+            time,
+            value,
+            easing: Easing::Linear,
+        }
+    }
+
+    pub fn with_easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+}
+
+pub trait Interpolate: Copy {
+    fn interpolate(lhs: &Self, rhs: &Self, t: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn interpolate(lhs: &Self, rhs: &Self, t: f32) -> Self {
+        lhs + (rhs - lhs) * t
+    }
+}
+
+impl Interpolate for Vec3 {
+    fn interpolate(lhs: &Self, rhs: &Self, t: f32) -> Self {
+        Vec3::lerp(lhs, rhs, t)
+    }
+}
+
+impl Interpolate for Quaternion {
+    fn interpolate(lhs: &Self, rhs: &Self, t: f32) -> Self {
+        Quaternion::slerp(lhs, rhs, t)
+    }
+}
+
+/// Samples keyframes sorted by time. Times outside of the keyframe range are clamped.
+pub fn sample_keyframes<T: Interpolate>(keyframes: &[Keyframe<T>], time: f32) -> Option<T> {
+    let first = keyframes.first()?;
+    let last = keyframes.last()?;
+    if time <= first.time {
+        return Some(first.value);
+    } else if time >= last.time {
+        return Some(last.value);
+    }
+
+    let next = keyframes
+        .iter()
+        .position(|k| k.time > time)
+        .unwrap_or(keyframes.len() - 1);
+    let prev = &keyframes[next - 1];
+    let next = &keyframes[next];
+    let t = prev
+        .easing
+        .apply((time - prev.time) / (next.time - prev.time));
+
+    Some(T::interpolate(&prev.value, &next.value, t))
+}
+
+/// Keyframed local transform of a single bone. Empty channels leave the bone's rest pose
+/// untouched.
+#[derive(Clone, Debug)]
+pub struct BoneChannel {
+    pub bone: usize,
+    pub translations: Vec<Keyframe<Vec3>>,
+    pub rotations: Vec<Keyframe<Quaternion>>,
+    pub scales: Vec<Keyframe<Vec3>>,
+}
+
+impl BoneChannel {
+    pub fn new(bone: usize) -> Self {
+        Self {
+            bone,
+            translations: vec![],
+            rotations: vec![],
+            scales: vec![],
+        }
+    }
+
+    fn end_time(&self) -> f32 {
+        fn last_time<T>(keyframes: &[Keyframe<T>]) -> f32 {
+            keyframes.last().map_or(0., |k| k.time)
+        }
+
+        last_time(&self.translations)
+            .max(last_time(&self.rotations))
+            .max(last_time(&self.scales))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AnimationClip {
+    name: String,
+    duration: f32,
+    channels: Vec<BoneChannel>,
+}
+
+impl AnimationClip {
+    pub fn new(name: &str, channels: Vec<BoneChannel>) -> Self {
+        let duration = channels.iter().map(|c| c.end_time()).fold(0., f32::max);
+        Self {
+            name: name.to_owned(),
+            duration,
+            channels,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    pub fn channels(&self) -> &[BoneChannel] {
+        &self.channels
+    }
+
+    /// Overwrites the animated bones of `pose` with the clip's values at `time`
+    pub fn sample(&self, time: f32, pose: &mut [BonePose]) {
+        for channel in &self.channels {
+            if let Some(bone) = pose.get_mut(channel.bone) {
+                if let Some(t) = sample_keyframes(&channel.translations, time) {
+                    bone.translation = t;
+                }
+
+                if let Some(r) = sample_keyframes(&channel.rotations, time) {
+                    bone.rotation = r;
+                }
+
+                if let Some(s) = sample_keyframes(&channel.scales, time) {
+                    bone.scale = s;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use std::f32::consts::PI;
+
+/// Easing curve applied between two keyframes
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Easing {
+    Linear,
+
+    /// Holds the value until the next keyframe
+    Step,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    SineIn,
+    SineOut,
+    SineInOut,
+
+    /// CSS style cubic bezier with control points (x1, y1) and (x2, y2)
+    CubicBezier(f32, f32, f32, f32),
+}
+
+impl Easing {
+    /// Maps the linear progress `t` in [0, 1] onto the curve
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.max(0.).min(1.);
+        match *self {
+            Easing::Linear => t,
+            Easing::Step => {
+                if t < 1. {
+                    0.
+                } else {
+                    1.
+                }
+            }
+            Easing::QuadIn => t * t,
+            Easing::QuadOut => t * (2. - t),
+            Easing::QuadInOut => {
+                if t < 0.5 {
+                    2. * t * t
+                } else {
+                    1. - (-2. * t + 2.).powi(2) / 2.
+                }
+            }
+            Easing::CubicIn => t * t * t,
+            Easing::CubicOut => 1. - (1. - t).powi(3),
+            Easing::CubicInOut => {
+                if t < 0.5 {
+                    4. * t * t * t
+                } else {
+                    1. - (-2. * t + 2.).powi(3) / 2.
+                }
+            }
+            Easing::SineIn => 1. - (t * PI / 2.).cos(),
+            Easing::SineOut => (t * PI / 2.).sin(),
+            Easing::SineInOut => -((PI * t).cos() - 1.) / 2.,
+            Easing::CubicBezier(x1, y1, x2, y2) => cubic_bezier(x1, y1, x2, y2, t),
+        }
+    }
+}
+
+impl Default for Easing {
+    fn default() -> Self {
+        Easing::Linear
+    }
+}
+
+fn cubic_bezier(x1: f32, y1: f32, x2: f32, y2: f32, x: f32) -> f32 {
+    let bezier = |p1: f32, p2: f32, s: f32| {
+        let r = 1. - s;
+        3. * r * r * s * p1 + 3. * r * s * s * p2 + s * s * s
+    };
+
+    // Solve bezier_x(s) = x with bisection, the curve is monotonic in x for x1, x2 in [0, 1]
+    let (mut low, mut high) = (0f32, 1f32);
+    let mut s = x;
+    for _ in 0..24 {
+        let current = bezier(x1, x2, s);
+        if (current - x).abs() < 1e-5 {
+            break;
+        }
+
+        if current < x {
+            low = s;
+        } else {
+            high = s;
+        }
+
+        s = (low + high) / 2.;
+    }
+
+    bezier(y1, y2, s)
+}
@@ -0,0 +1,15 @@
+mod clip;
+mod easing;
+mod skeletal_animator;
+mod skeleton;
+mod sprite_animator;
+mod system;
+mod transform_animator;
+
+pub use clip::{sample_keyframes, AnimationClip, BoneChannel, Interpolate, Keyframe};
+pub use easing::Easing;
+pub use skeletal_animator::{AnimationLayerId, SkeletalAnimator};
+pub use skeleton::{Bone, BonePose, Skeleton, MAX_BONES};
+pub use sprite_animator::{SpriteAnimation, SpriteAnimator, SpriteFrame};
+pub use system::update_animations;
+pub use transform_animator::{AnimationEvent, LoopMode, TransformAnimation, TransformAnimator};
@@ -0,0 +1,310 @@
+use super::{AnimationClip, BonePose, Skeleton};
+use crate::math::Mat44;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Identifies a layer added with `SkeletalAnimator::add_layer`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AnimationLayerId(u64);
+
+struct AnimationLayer {
+    id: AnimationLayerId,
+    clip: Rc<AnimationClip>,
+    time: f32,
+    speed: f32,
+    looping: bool,
+    weight: f32,
+    target_weight: f32,
+
+    // Weight change per second, 0 when not fading
+    fade_rate: f32,
+
+    // Added with `add_layer`, kept at a zero weight until removed
+    kept: bool,
+}
+
+impl AnimationLayer {
+    fn new(id: AnimationLayerId, clip: Rc<AnimationClip>, weight: f32, looping: bool) -> Self {
+        Self {
+            id,
+            clip,
+            time: 0.,
+            speed: 1.,
+            looping,
+            weight,
+            target_weight: weight,
+            fade_rate: 0.,
+            kept: false,
+        }
+    }
+
+    fn fade_to(&mut self, target_weight: f32, duration: f32) {
+        self.target_weight = target_weight;
+        if duration <= 0. {
+            self.weight = target_weight;
+            self.fade_rate = 0.;
+        } else {
+            self.fade_rate = (target_weight - self.weight).abs() / duration;
+        }
+    }
+
+    fn update(&mut self, delta_sec: f32) {
+        let duration = self.clip.duration();
+        self.time += delta_sec * self.speed;
+        if self.looping && duration > 0. {
+            self.time = self.time.rem_euclid(duration);
+        } else {
+            self.time = self.time.max(0.).min(duration);
+        }
+
+        if self.fade_rate > 0. {
+            let step = self.fade_rate * delta_sec;
+            if (self.target_weight - self.weight).abs() <= step {
+                self.weight = self.target_weight;
+                self.fade_rate = 0.;
+            } else if self.target_weight > self.weight {
+                self.weight += step;
+            } else {
+                self.weight -= step;
+            }
+        }
+    }
+
+    fn faded_out(&self) -> bool {
+        !self.kept && self.weight <= 0. && self.target_weight <= 0.
+    }
+}
+
+/// Component that plays and blends `AnimationClip`s on a `Skeleton`. Attach it to an
+/// entity with a skinned `RenderingComponent` and the resulting bone matrices are
+/// uploaded when rendering.
+pub struct SkeletalAnimator {
+    skeleton: Skeleton,
+    clips: HashMap<String, Rc<AnimationClip>>,
+    layers: Vec<AnimationLayer>,
+    next_layer_id: u64,
+    pose: Vec<BonePose>,
+    skinning_matrices: Vec<Mat44>,
+}
+
+impl SkeletalAnimator {
+    pub fn new(skeleton: Skeleton) -> Self {
+        let pose = skeleton.rest_pose();
+        let mut skinning_matrices = vec![];
+        skeleton.compute_skinning_matrices(&pose, &mut skinning_matrices);
+
+        Self {
+            skeleton,
+            clips: HashMap::new(),
+            layers: vec![],
+            next_layer_id: 0,
+            pose,
+            skinning_matrices,
+        }
+    }
+
+    pub fn skeleton(&self) -> &Skeleton {
+        &self.skeleton
+    }
+
+    pub fn add_clip(&mut self, clip: AnimationClip) {
+        self.clips.insert(clip.name().to_owned(), Rc::new(clip));
+    }
+
+    pub fn clip(&self, name: &str) -> Option<&AnimationClip> {
+        self.clips.get(name).map(|c| c.as_ref())
+    }
+
+    pub fn clip_names(&self) -> Vec<&str> {
+        self.clips.keys().map(|k| k.as_str()).collect()
+    }
+
+    /// Stops all the playing clips and plays `name` from the beginning
+    pub fn play(&mut self, name: &str, looping: bool) -> bool {
+        self.cross_fade(name, 0., looping)
+    }
+
+    /// Fades in `name` while fading out all the other playing clips
+    pub fn cross_fade(&mut self, name: &str, duration: f32, looping: bool) -> bool {
+        let clip = match self.clips.get(name) {
+            Some(clip) => clip.clone(),
+            None => return false,
+        };
+
+        for layer in &mut self.layers {
+            layer.fade_to(0., duration);
+        }
+
+        let mut layer = AnimationLayer::new(self.new_layer_id(), clip, 0., looping);
+        layer.fade_to(1., duration);
+        self.layers.push(layer);
+        self.layers.retain(|l| !l.faded_out());
+        true
+    }
+
+    /// Plays `name` on top of the current clips, blended with the given weight. The layer
+    /// stays at a zero weight until `remove_layer` or `stop` is called.
+    pub fn add_layer(
+        &mut self,
+        name: &str,
+        weight: f32,
+        looping: bool,
+    ) -> Option<AnimationLayerId> {
+        let clip = self.clips.get(name)?.clone();
+        let id = self.new_layer_id();
+        let mut layer = AnimationLayer::new(id, clip, weight, looping);
+        layer.kept = true;
+        self.layers.push(layer);
+        Some(id)
+    }
+
+    pub fn set_layer_weight(&mut self, layer: AnimationLayerId, weight: f32) {
+        if let Some(layer) = self.layers.iter_mut().find(|l| l.id == layer) {
+            layer.fade_to(weight, 0.);
+        }
+    }
+
+    pub fn remove_layer(&mut self, layer: AnimationLayerId) {
+        self.layers.retain(|l| l.id != layer);
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        for layer in &mut self.layers {
+            layer.speed = speed;
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.layers.clear();
+    }
+
+    pub fn is_playing(&self) -> bool {
+        !self.layers.is_empty()
+    }
+
+    pub fn pose(&self) -> &[BonePose] {
+        &self.pose
+    }
+
+    pub fn skinning_matrices(&self) -> &[Mat44] {
+        &self.skinning_matrices
+    }
+
+    pub fn update(&mut self, delta_sec: f32) {
+        for layer in &mut self.layers {
+            layer.update(delta_sec);
+        }
+
+        self.layers.retain(|l| !l.faded_out());
+
+        // Weighted average of all the layers, unanimated bones stay in the rest pose
+        let rest_pose = self.skeleton.rest_pose();
+        self.pose.clone_from(&rest_pose);
+        let mut total_weight = 0.;
+        let mut sampled = rest_pose.clone();
+        for layer in &self.layers {
+            if layer.weight <= 0. {
+                continue;
+            }
+
+            sampled.clone_from(&rest_pose);
+            layer.clip.sample(layer.time, &mut sampled);
+            total_weight += layer.weight;
+            let t = layer.weight / total_weight;
+            for (pose, sampled) in self.pose.iter_mut().zip(&sampled) {
+                *pose = BonePose::blend(pose, sampled, t);
+            }
+        }
+
+        self.skeleton
+            .compute_skinning_matrices(&self.pose, &mut self.skinning_matrices);
+    }
+
+    fn new_layer_id(&mut self) -> AnimationLayerId {
+        self.next_layer_id += 1;
+        AnimationLayerId(self.next_layer_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animation::{Bone, BoneChannel, Keyframe};
+    use crate::math::Vec3;
+
+    // One bone moved to `x` on the X axis by each clip
+    fn animator(clips: &[(&str, f32)]) -> SkeletalAnimator {
+        let mut animator = SkeletalAnimator::new(Skeleton::new(vec![Bone {
+            name: "root".to_owned(),
+            parent: None,
+            rest_pose: BonePose::new_identity(),
+            inverse_bind_matrix: Mat44::new_identity(),
+        }]));
+
+        for (name, x) in clips {
+            let mut channel = BoneChannel::new(0);
+            channel.translations = vec![
+                Keyframe::new(0., Vec3::new(*x, 0., 0.)),
+                Keyframe::new(1., Vec3::new(*x, 0., 0.)),
+            ];
+            animator.add_clip(AnimationClip::new(name, vec![channel]));
+        }
+
+        animator
+    }
+
+    fn x(animator: &SkeletalAnimator) -> f32 {
+        animator.pose()[0].translation.x
+    }
+
+    #[test]
+    fn layers_blend_by_weight() {
+        let mut animator = animator(&[("a", 1.), ("b", 2.)]);
+        animator.update(0.1);
+        assert_eq!(x(&animator), 0.);
+
+        animator.add_layer("a", 1., true).unwrap();
+        animator.add_layer("b", 3., true).unwrap();
+        animator.update(0.1);
+        assert!((x(&animator) - 1.75).abs() < 1e-6);
+        assert!(animator.add_layer("missing", 1., true).is_none());
+    }
+
+    #[test]
+    fn cross_fade_blends_then_drops_the_old_clip() {
+        let mut animator = animator(&[("a", 1.), ("b", 2.)]);
+        assert!(animator.play("a", true));
+        animator.update(0.1);
+        assert_eq!(x(&animator), 1.);
+
+        assert!(animator.cross_fade("b", 1., true));
+        animator.update(0.5);
+        assert!((x(&animator) - 1.5).abs() < 1e-6);
+
+        animator.update(0.5);
+        assert_eq!(x(&animator), 2.);
+        assert_eq!(animator.layers.len(), 1);
+    }
+
+    #[test]
+    fn layer_ids_survive_faded_out_layers() {
+        let mut animator = animator(&[("a", 1.), ("b", 2.), ("c", 3.)]);
+        animator.play("a", true);
+        let b = animator.add_layer("b", 0., true).unwrap();
+        animator.update(0.1);
+
+        // "a" fades out and is removed, "b" is kept at a zero weight
+        animator.cross_fade("c", 0.5, true);
+        animator.update(1.);
+        assert_eq!(animator.layers.len(), 2);
+        assert_eq!(x(&animator), 3.);
+
+        animator.set_layer_weight(b, 1.);
+        animator.update(0.1);
+        assert!((x(&animator) - 2.5).abs() < 1e-6);
+
+        animator.remove_layer(b);
+        animator.update(0.1);
+        assert_eq!(x(&animator), 3.);
+    }
+}
@@ -0,0 +1,130 @@
+use crate::math::{Mat44, Quaternion, Vec3};
+
+/// Maximum number of bones a skinned render object can upload to the GPU
+pub const MAX_BONES: usize = 64;
+
+/// Local transform of a bone relative to its parent
+#[derive(Copy, Clone, Debug)]
+pub struct BonePose {
+    pub translation: Vec3,
+    pub rotation: Quaternion,
+    pub scale: Vec3,
+}
+
+impl BonePose {
+    pub fn new(translation: Vec3, rotation: Quaternion, scale: Vec3) -> Self {
+        Self {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    pub fn new_identity() -> Self {
+        Self::new(
+            Vec3::new_zeros(),
+            Quaternion::new_identity(),
+            Vec3::new(1., 1., 1.),
+        )
+    }
+
+    pub fn blend(lhs: &BonePose, rhs: &BonePose, t: f32) -> Self {
+        Self::new(
+            Vec3::lerp(&lhs.translation, &rhs.translation, t),
+            Quaternion::nlerp(&lhs.rotation, &rhs.rotation, t),
+            Vec3::lerp(&lhs.scale, &rhs.scale, t),
+        )
+    }
+
+    pub fn to_matrix(&self) -> Mat44 {
+        Mat44::from_trs(&self.translation, &self.rotation, &self.scale)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Bone {
+    pub name: String,
+    pub parent: Option<usize>,
+    pub rest_pose: BonePose,
+
+    /// Transforms a vertex from model space into the bone's space at bind time
+    pub inverse_bind_matrix: Mat44,
+}
+
+#[derive(Clone, Debug)]
+pub struct Skeleton {
+    bones: Vec<Bone>,
+
+    // Bone indices sorted so that parents always come before their children
+    order: Vec<usize>,
+}
+
+impl Skeleton {
+    pub fn new(mut bones: Vec<Bone>) -> Self {
+        let bone_count = bones.len();
+        for bone in &mut bones {
+            bone.parent = bone.parent.filter(|p| *p < bone_count);
+        }
+
+        if bones.len() > MAX_BONES {
+            log::warn!(
+                "Skeleton has {} bones, only the first {} can be skinned to",
+                bones.len(),
+                MAX_BONES
+            );
+        }
+
+        let mut order = vec![];
+        let mut visited = vec![false; bones.len()];
+        for i in 0..bones.len() {
+            Self::visit(&bones, i, &mut visited, &mut order);
+        }
+
+        Self { bones, order }
+    }
+
+    pub fn bones(&self) -> &[Bone] {
+        &self.bones
+    }
+
+    pub fn bone_index(&self, name: &str) -> Option<usize> {
+        self.bones.iter().position(|b| b.name == name)
+    }
+
+    pub fn rest_pose(&self) -> Vec<BonePose> {
+        self.bones.iter().map(|b| b.rest_pose).collect()
+    }
+
+    /// Computes the matrices that move vertices from bind pose to `pose`, in model space
+    pub fn compute_skinning_matrices(&self, pose: &[BonePose], matrices: &mut Vec<Mat44>) {
+        let mut globals = vec![Mat44::new_identity(); self.bones.len()];
+        for &i in &self.order {
+            let local = pose.get(i).unwrap_or(&self.bones[i].rest_pose).to_matrix();
+            globals[i] = match self.bones[i].parent {
+                Some(parent) => Mat44::multiplied(&globals[parent], &local),
+                None => local,
+            };
+        }
+
+        matrices.clear();
+        matrices.extend(
+            globals
+                .iter()
+                .zip(&self.bones)
+                .map(|(global, bone)| Mat44::multiplied(global, &bone.inverse_bind_matrix)),
+        );
+    }
+
+    fn visit(bones: &[Bone], index: usize, visited: &mut Vec<bool>, order: &mut Vec<usize>) {
+        if visited[index] {
+            return;
+        }
+
+        visited[index] = true;
+        if let Some(parent) = bones[index].parent {
+            Self::visit(bones, parent, visited, order);
+        }
+
+        order.push(index);
+    }
+}
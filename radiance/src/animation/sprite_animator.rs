@@ -0,0 +1,217 @@
+use super::{AnimationEvent, LoopMode};
+use crate::rendering::{AtlasRegion, Sprite};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SpriteFrame {
+    pub region: AtlasRegion,
+
+    /// Seconds the frame stays on screen
+    pub duration: f32,
+}
+
+/// Sequence of atlas regions shown one after another, such as the frames of a sprite sheet
+/// animation
+#[derive(Clone, Debug)]
+pub struct SpriteAnimation {
+    name: String,
+    frames: Vec<SpriteFrame>,
+    duration: f32,
+}
+
+impl SpriteAnimation {
+    pub fn new(name: &str, frames: Vec<SpriteFrame>) -> Self {
+        let duration = frames.iter().map(|frame| frame.duration).sum();
+        Self {
+            name: name.to_owned(),
+            frames,
+            duration,
+        }
+    }
+
+    /// Frames shown for the same duration
+    pub fn with_frame_rate(name: &str, regions: &[AtlasRegion], frames_per_second: f32) -> Self {
+        let duration = 1. / frames_per_second;
+        Self::new(
+            name,
+            regions
+                .iter()
+                .map(|region| SpriteFrame {
+                    region: *region,
+                    duration,
+                })
+                .collect(),
+        )
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn frames(&self) -> &[SpriteFrame] {
+        &self.frames
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    /// Index of the frame shown `time` seconds after the start
+    pub fn frame_at(&self, time: f32) -> usize {
+        let mut end = 0.;
+        for (i, frame) in self.frames.iter().enumerate() {
+            end += frame.duration;
+            if time < end {
+                return i;
+            }
+        }
+
+        self.frames.len().saturating_sub(1)
+    }
+}
+
+/// Component switching the region of the entity's `Sprite` as a `SpriteAnimation` plays. The
+/// regions are applied with `AtlasRegion::apply`, so trimmed frames stay in place. Events are
+/// dropped on the next update.
+pub struct SpriteAnimator {
+    animations: HashMap<String, Rc<SpriteAnimation>>,
+    current: Option<Rc<SpriteAnimation>>,
+    loop_mode: LoopMode,
+    time: f32,
+    speed: f32,
+    direction: f32,
+    paused: bool,
+    pixels_per_unit: f32,
+    frame: Option<usize>,
+    events: Vec<AnimationEvent>,
+}
+
+impl SpriteAnimator {
+    pub fn new(pixels_per_unit: f32) -> Self {
+        Self {
+            animations: HashMap::new(),
+            current: None,
+            loop_mode: LoopMode::Loop,
+            time: 0.,
+            speed: 1.,
+            direction: 1.,
+            paused: false,
+            pixels_per_unit,
+            frame: None,
+            events: vec![],
+        }
+    }
+
+    pub fn add_animation(&mut self, animation: SpriteAnimation) {
+        self.animations
+            .insert(animation.name().to_owned(), Rc::new(animation));
+    }
+
+    pub fn play(&mut self, name: &str, loop_mode: LoopMode) -> bool {
+        let animation = match self.animations.get(name) {
+            Some(animation) => animation.clone(),
+            None => return false,
+        };
+
+        self.current = Some(animation);
+        self.loop_mode = loop_mode;
+        self.time = 0.;
+        self.direction = 1.;
+        self.paused = false;
+        self.frame = None;
+        true
+    }
+
+    /// Stops the animation, leaving the sprite on its current frame
+    pub fn stop(&mut self) {
+        self.current = None;
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.max(0.);
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.current.is_some() && !self.paused
+    }
+
+    pub fn current_animation(&self) -> Option<&str> {
+        self.current.as_ref().map(|a| a.name())
+    }
+
+    /// Index of the frame shown in the current animation
+    pub fn current_frame(&self) -> Option<usize> {
+        self.frame
+    }
+
+    pub fn drain_events(&mut self) -> Vec<AnimationEvent> {
+        std::mem::replace(&mut self.events, vec![])
+    }
+
+    pub fn update(&mut self, delta_sec: f32, sprite: &mut Sprite) {
+        self.events.clear();
+        let animation = match &self.current {
+            Some(animation) if !self.paused => animation.clone(),
+            _ => return,
+        };
+
+        let duration = animation.duration();
+        self.time += delta_sec * self.speed * self.direction;
+
+        let mut completed = false;
+        match self.loop_mode {
+            LoopMode::Once => {
+                if self.time >= duration {
+                    self.time = duration;
+                    completed = true;
+                }
+            }
+            LoopMode::Loop => {
+                if self.time >= duration {
+                    self.time = if duration > 0. {
+                        self.time % duration
+                    } else {
+                        0.
+                    };
+                    self.events
+                        .push(AnimationEvent::Looped(animation.name().to_owned()));
+                }
+            }
+            LoopMode::PingPong => {
+                if self.time >= duration {
+                    self.time = (2. * duration - self.time).max(0.);
+                    self.direction = -1.;
+                } else if self.time <= 0. {
+                    self.time = (-self.time).min(duration);
+                    self.direction = 1.;
+                    self.events
+                        .push(AnimationEvent::Looped(animation.name().to_owned()));
+                }
+            }
+        }
+
+        let frame = animation.frame_at(self.time);
+        if self.frame != Some(frame) {
+            if let Some(frame) = animation.frames().get(frame) {
+                frame.region.apply(sprite, self.pixels_per_unit);
+            }
+
+            self.frame = Some(frame);
+        }
+
+        if completed {
+            self.events
+                .push(AnimationEvent::Completed(animation.name().to_owned()));
+            self.stop();
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use super::{SkeletalAnimator, SpriteAnimator, TransformAnimator};
+use crate::rendering::{morph_weight_property, MorphWeights, Sprite};
+use crate::scene::{
+    entity_get_component, entity_get_component_mut, entity_with_component_mut, Entity, Scene,
+};
+
+/// Plays the animator components of every entity in the scene, children included. Runs
+/// before the scene updates, so that `EntityExtension::on_updating` sees the animated state.
+pub fn update_animations(scene: &mut dyn Scene, delta_sec: f32) {
+    for entity in scene.root_entities_mut() {
+        update_entity(entity.as_mut(), delta_sec);
+    }
+}
+
+fn update_entity(entity: &mut dyn Entity, delta_sec: f32) {
+    if let Some(animator) = entity_get_component_mut::<SkeletalAnimator>(entity) {
+        animator.update(delta_sec);
+    }
+
+    entity_with_component_mut::<TransformAnimator, _>(entity, |animator, entity| {
+        animator.update(delta_sec, entity.transform_mut())
+    });
+    sync_morph_weights(entity);
+
+    entity_with_component_mut::<SpriteAnimator, _>(entity, |animator, entity| {
+        if let Some(sprite) = entity_get_component_mut::<Sprite>(entity) {
+            animator.update(delta_sec, sprite);
+        }
+    });
+
+    for child in entity.children_mut() {
+        update_entity(child, delta_sec);
+    }
+}
+
+// Copies the animated morph weights into the entity's `MorphWeights`
+fn sync_morph_weights(entity: &mut dyn Entity) {
+    let count = match entity_get_component::<MorphWeights>(entity) {
+        Some(morph_weights) => morph_weights.weights().len(),
+        None => return,
+    };
+    let weights: Vec<Option<f32>> = match entity_get_component::<TransformAnimator>(entity) {
+        Some(animator) => (0..count)
+            .map(|i| animator.property(&morph_weight_property(i)))
+            .collect(),
+        None => return,
+    };
+
+    if let Some(morph_weights) = entity_get_component_mut::<MorphWeights>(entity) {
+        for (i, weight) in weights.into_iter().enumerate() {
+            if let Some(weight) = weight {
+                morph_weights.set_weight(i, weight);
+            }
+        }
+    }
+}
@@ -0,0 +1,410 @@
+use super::{sample_keyframes, Keyframe};
+use crate::math::{Quaternion, Transform, Vec3};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LoopMode {
+    Once,
+    Loop,
+    PingPong,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AnimationEvent {
+    /// A marker added with `TransformAnimation::with_marker` has been passed
+    Marker { animation: String, name: String },
+
+    /// A looping animation started over
+    Looped(String),
+
+    /// A non-looping animation reached its end and stopped
+    Completed(String),
+}
+
+/// Keyframed tracks for an entity's transform and arbitrary numeric properties. Missing
+/// transform tracks keep the values the entity had when the animation started.
+#[derive(Clone, Debug)]
+pub struct TransformAnimation {
+    name: String,
+    positions: Vec<Keyframe<Vec3>>,
+    rotations: Vec<Keyframe<Quaternion>>,
+    scales: Vec<Keyframe<Vec3>>,
+    properties: HashMap<String, Vec<Keyframe<f32>>>,
+    markers: Vec<(f32, String)>,
+    duration: f32,
+}
+
+impl TransformAnimation {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            positions: vec![],
+            rotations: vec![],
+            scales: vec![],
+            properties: HashMap::new(),
+            markers: vec![],
+            duration: 0.,
+        }
+    }
+
+    pub fn with_positions(mut self, keyframes: Vec<Keyframe<Vec3>>) -> Self {
+        self.positions = keyframes;
+        self.update_duration()
+    }
+
+    pub fn with_rotations(mut self, keyframes: Vec<Keyframe<Quaternion>>) -> Self {
+        self.rotations = keyframes;
+        self.update_duration()
+    }
+
+    pub fn with_scales(mut self, keyframes: Vec<Keyframe<Vec3>>) -> Self {
+        self.scales = keyframes;
+        self.update_duration()
+    }
+
+    pub fn with_property(mut self, name: &str, keyframes: Vec<Keyframe<f32>>) -> Self {
+        self.properties.insert(name.to_owned(), keyframes);
+        self.update_duration()
+    }
+
+    pub fn with_marker(mut self, time: f32, name: &str) -> Self {
+        self.markers.push((time, name.to_owned()));
+        self.update_duration()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    fn update_duration(mut self) -> Self {
+        fn last_time<T>(keyframes: &[Keyframe<T>]) -> f32 {
+            keyframes.last().map_or(0., |k| k.time)
+        }
+
+        self.duration = last_time(&self.positions)
+            .max(last_time(&self.rotations))
+            .max(last_time(&self.scales));
+        for keyframes in self.properties.values() {
+            self.duration = self.duration.max(last_time(keyframes));
+        }
+
+        for (time, _) in &self.markers {
+            self.duration = self.duration.max(*time);
+        }
+
+        self
+    }
+}
+
+/// Component playing a `TransformAnimation` on the entity it is attached to. Animated
+/// property values and events are available to `EntityExtension::on_updating` in the same
+/// frame, events are dropped on the next update.
+pub struct TransformAnimator {
+    animations: HashMap<String, Rc<TransformAnimation>>,
+    current: Option<Rc<TransformAnimation>>,
+    loop_mode: LoopMode,
+    time: f32,
+    speed: f32,
+    direction: f32,
+    paused: bool,
+    base: Option<(Vec3, Quaternion, Vec3)>,
+    properties: HashMap<String, f32>,
+    events: Vec<AnimationEvent>,
+}
+
+impl TransformAnimator {
+    pub fn new() -> Self {
+        Self {
+            animations: HashMap::new(),
+            current: None,
+            loop_mode: LoopMode::Once,
+            time: 0.,
+            speed: 1.,
+            direction: 1.,
+            paused: false,
+            base: None,
+            properties: HashMap::new(),
+            events: vec![],
+        }
+    }
+
+    pub fn add_animation(&mut self, animation: TransformAnimation) {
+        self.animations
+            .insert(animation.name().to_owned(), Rc::new(animation));
+    }
+
+    pub fn play(&mut self, name: &str, loop_mode: LoopMode) -> bool {
+        let animation = match self.animations.get(name) {
+            Some(animation) => animation.clone(),
+            None => return false,
+        };
+
+        self.current = Some(animation);
+        self.loop_mode = loop_mode;
+        self.time = 0.;
+        self.direction = 1.;
+        self.paused = false;
+        self.base = None;
+        true
+    }
+
+    /// Stops the animation, leaving the transform where it is
+    pub fn stop(&mut self) {
+        self.current = None;
+        self.base = None;
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.max(0.);
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.current.is_some() && !self.paused
+    }
+
+    pub fn current_animation(&self) -> Option<&str> {
+        self.current.as_ref().map(|a| a.name())
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn property(&self, name: &str) -> Option<f32> {
+        self.properties.get(name).copied()
+    }
+
+    pub fn drain_events(&mut self) -> Vec<AnimationEvent> {
+        std::mem::replace(&mut self.events, vec![])
+    }
+
+    pub fn update(&mut self, delta_sec: f32, transform: &mut Transform) {
+        self.events.clear();
+        let animation = match &self.current {
+            Some(animation) if !self.paused => animation.clone(),
+            _ => return,
+        };
+
+        if self.base.is_none() {
+            self.base = Some(transform.decompose());
+        }
+
+        let duration = animation.duration();
+        let previous = self.time;
+        self.time += delta_sec * self.speed * self.direction;
+
+        let mut completed = false;
+        if duration <= 0. {
+            // Nothing to play over time, looping animations hold their only pose
+            self.time = 0.;
+            completed = self.loop_mode == LoopMode::Once;
+        } else {
+            match self.loop_mode {
+                LoopMode::Once => {
+                    if self.time >= duration {
+                        self.time = duration;
+                        completed = true;
+                    }
+
+                    self.fire_markers(&animation, previous, self.time);
+                }
+                LoopMode::Loop => {
+                    // Deltas longer than the animation go through several cycles
+                    let mut from = previous;
+                    while self.time >= duration {
+                        self.fire_markers(&animation, from, duration);
+                        self.time -= duration;
+                        self.events
+                            .push(AnimationEvent::Looped(animation.name().to_owned()));
+                        from = -1.;
+                    }
+
+                    self.fire_markers(&animation, from, self.time);
+                }
+                LoopMode::PingPong => {
+                    let mut from = previous;
+                    loop {
+                        if self.direction > 0. && self.time >= duration {
+                            self.fire_markers(&animation, from, duration);
+                            self.time = 2. * duration - self.time;
+                            self.direction = -1.;
+                            from = duration;
+                        } else if self.direction < 0. && self.time <= 0. {
+                            self.fire_markers(&animation, from, 0.);
+                            self.time = -self.time;
+                            self.direction = 1.;
+                            self.events
+                                .push(AnimationEvent::Looped(animation.name().to_owned()));
+                            from = 0.;
+                        } else {
+                            break;
+                        }
+                    }
+
+                    self.fire_markers(&animation, from, self.time);
+                }
+            }
+        }
+
+        self.apply(&animation, transform);
+
+        if completed {
+            self.events
+                .push(AnimationEvent::Completed(animation.name().to_owned()));
+            self.stop();
+        }
+    }
+
+    fn apply(&mut self, animation: &TransformAnimation, transform: &mut Transform) {
+        let (position, rotation, scale) = self.base.unwrap();
+        let position = sample_keyframes(&animation.positions, self.time).unwrap_or(position);
+        let rotation = sample_keyframes(&animation.rotations, self.time).unwrap_or(rotation);
+        let scale = sample_keyframes(&animation.scales, self.time).unwrap_or(scale);
+        if !animation.positions.is_empty()
+            || !animation.rotations.is_empty()
+            || !animation.scales.is_empty()
+        {
+            transform.set_trs(&position, &rotation, &scale);
+        }
+
+        for (name, keyframes) in &animation.properties {
+            if let Some(value) = sample_keyframes(keyframes, self.time) {
+                self.properties.insert(name.clone(), value);
+            }
+        }
+    }
+
+    // Fires the markers in (from, to] when moving forward, or [to, from) backward
+    fn fire_markers(&mut self, animation: &TransformAnimation, from: f32, to: f32) {
+        for (time, name) in &animation.markers {
+            let passed = if to >= from {
+                *time > from && *time <= to
+            } else {
+                *time >= to && *time < from
+            };
+
+            if passed {
+                self.events.push(AnimationEvent::Marker {
+                    animation: animation.name().to_owned(),
+                    name: name.clone(),
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animation::Keyframe;
+
+    fn animator(loop_mode: LoopMode) -> TransformAnimator {
+        let mut animator = TransformAnimator::new();
+        animator.add_animation(
+            TransformAnimation::new("a")
+                .with_property("value", vec![Keyframe::new(0., 0.), Keyframe::new(1., 1.)])
+                .with_marker(0.5, "half"),
+        );
+        animator.add_animation(
+            TransformAnimation::new("still").with_property("value", vec![Keyframe::new(0., 2.)]),
+        );
+        assert!(animator.play("a", loop_mode));
+        animator
+    }
+
+    fn update(animator: &mut TransformAnimator, delta_sec: f32) -> Vec<AnimationEvent> {
+        animator.update(delta_sec, &mut Transform::new());
+        animator.drain_events()
+    }
+
+    fn marker() -> AnimationEvent {
+        AnimationEvent::Marker {
+            animation: "a".to_owned(),
+            name: "half".to_owned(),
+        }
+    }
+
+    fn looped() -> AnimationEvent {
+        AnimationEvent::Looped("a".to_owned())
+    }
+
+    #[test]
+    fn markers_fire_once_passed() {
+        let mut animator = animator(LoopMode::Once);
+        assert_eq!(update(&mut animator, 0.4), vec![]);
+        assert_eq!(update(&mut animator, 0.2), vec![marker()]);
+        assert_eq!(update(&mut animator, 0.2), vec![]);
+
+        assert_eq!(
+            update(&mut animator, 0.5),
+            vec![AnimationEvent::Completed("a".to_owned())]
+        );
+        assert_eq!(animator.property("value"), Some(1.));
+        assert!(!animator.is_playing());
+    }
+
+    #[test]
+    fn loop_reports_every_cycle() {
+        let mut animator = animator(LoopMode::Loop);
+        assert_eq!(
+            update(&mut animator, 2.75),
+            vec![marker(), looped(), marker(), looped(), marker()]
+        );
+        assert!((animator.time() - 0.75).abs() < 1e-5);
+        assert!(animator.is_playing());
+    }
+
+    #[test]
+    fn ping_pong_plays_backward_then_loops() {
+        let mut animator = animator(LoopMode::PingPong);
+        assert_eq!(update(&mut animator, 1.25), vec![marker()]);
+        assert!((animator.time() - 0.75).abs() < 1e-5);
+
+        assert_eq!(update(&mut animator, 0.5), vec![marker()]);
+        assert_eq!(update(&mut animator, 0.5), vec![looped()]);
+        assert!((animator.time() - 0.25).abs() < 1e-5);
+        assert!((animator.property("value").unwrap() - 0.25).abs() < 1e-5);
+
+        // A full forward and backward trip in one update
+        assert_eq!(
+            update(&mut animator, 2.),
+            vec![marker(), marker(), looped()]
+        );
+        assert!((animator.time() - 0.25).abs() < 1e-5);
+    }
+
+    #[test]
+    fn zero_length_animations_hold_or_complete() {
+        for loop_mode in &[LoopMode::Loop, LoopMode::PingPong] {
+            let mut animator = animator(*loop_mode);
+            animator.play("still", *loop_mode);
+            for _ in 0..3 {
+                assert_eq!(update(&mut animator, 0.1), vec![]);
+            }
+
+            assert_eq!(animator.property("value"), Some(2.));
+            assert!(animator.is_playing());
+        }
+
+        let mut animator = animator(LoopMode::Once);
+        animator.play("still", LoopMode::Once);
+        assert_eq!(
+            update(&mut animator, 0.1),
+            vec![AnimationEvent::Completed("still".to_owned())]
+        );
+        assert!(!animator.is_playing());
+    }
+}
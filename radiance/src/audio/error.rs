@@ -0,0 +1,27 @@
+use crate::constants;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum AudioEngineError {
+    LibraryNotFound,
+    NoOutputDevice,
+    ContextCreationFailed,
+}
+
+impl fmt::Display for AudioEngineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AudioEngineError::LibraryNotFound => {
+                write!(f, "{}", constants::STR_OPENAL_LIBRARY_NOT_FOUND)
+            }
+            AudioEngineError::NoOutputDevice => {
+                write!(f, "{}", constants::STR_NO_AUDIO_OUTPUT_DEVICE)
+            }
+            AudioEngineError::ContextCreationFailed => {
+                write!(f, "{}", constants::STR_FAILED_CREATE_AUDIO_CONTEXT)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AudioEngineError {}
@@ -1,7 +1,11 @@
 mod decoders;
+mod error;
 mod openal;
+mod silent;
 
+pub use error::AudioEngineError;
 pub use openal::OpenAlAudioEngine;
+pub use silent::SilentAudioEngine;
 
 #[derive(Copy, Clone)]
 pub enum Codec {
@@ -12,6 +16,7 @@ pub enum Codec {
 
 pub trait AudioEngine {
     fn create_source(&self) -> Box<dyn AudioSource>;
+    fn update(&self, delta_sec: f32);
 }
 
 #[derive(PartialEq, Copy, Clone, Debug)]
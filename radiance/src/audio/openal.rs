@@ -1,40 +1,139 @@
 use super::{
     decoders::{Decoder, Mp3Decoder, OggDecoder, Samples, WavDecoder},
-    Codec,
+    AudioEngineError, Codec,
 };
 use super::{AudioEngine, AudioSource, AudioSourceState};
 use alto::{Alto, Context, Mono, OutputDevice, Source, Stereo};
+use std::cell::{Cell, RefCell};
+use std::ffi::CString;
 use std::rc::Rc;
 
+const DEVICE_POLL_INTERVAL_SEC: f32 = 1.;
+
 pub struct OpenAlAudioEngine {
-    alto: Alto,
-    device: OutputDevice,
-    context: Rc<Context>,
+    device: Rc<RefCell<OpenAlDevice>>,
+    poll_elapsed: Cell<f32>,
 }
 
 impl AudioEngine for OpenAlAudioEngine {
     fn create_source(&self) -> Box<dyn AudioSource> {
-        Box::new(OpenAlAudioSource::new(self.context.clone()))
+        Box::new(OpenAlAudioSource::new(self.device.clone()))
+    }
+
+    fn update(&self, delta_sec: f32) {
+        let elapsed = self.poll_elapsed.get() + delta_sec;
+        if elapsed < DEVICE_POLL_INTERVAL_SEC {
+            self.poll_elapsed.set(elapsed);
+            return;
+        }
+
+        self.poll_elapsed.set(0.);
+
+        let mut device = self.device.borrow_mut();
+        if device.is_connected() && !device.default_device_changed() {
+            return;
+        }
+
+        // The device is gone or the user switched the default output: move every
+        // source over to the current default device, or stay silent until one shows up.
+        device.disconnect();
+        if let Err(e) = device.connect() {
+            log::warn!("{}", e);
+        }
     }
 }
 
 impl OpenAlAudioEngine {
-    pub fn new() -> Self {
-        let alto = Alto::load_default().unwrap();
-        let device = alto.open(None).unwrap();
-        let context = Rc::new(device.new_context(None).unwrap());
+    /// Only fails when OpenAL isn't installed. Without an output device, the engine starts
+    /// silent and picks up the first device plugged in.
+    pub fn new() -> Result<Self, AudioEngineError> {
+        let alto = Alto::load_default().map_err(|_| AudioEngineError::LibraryNotFound)?;
+        let mut device = OpenAlDevice::new(alto);
+        if let Err(e) = device.connect() {
+            log::warn!("{}", e);
+        }
 
+        Ok(Self {
+            device: Rc::new(RefCell::new(device)),
+            poll_elapsed: Cell::new(0.),
+        })
+    }
+}
+
+struct OpenAlDevice {
+    alto: Alto,
+    specifier: Option<CString>,
+    device: Option<OutputDevice>,
+    context: Option<Context>,
+
+    // Bumped on every connect/disconnect so the sources know when to rebuild
+    generation: u32,
+}
+
+impl OpenAlDevice {
+    fn new(alto: Alto) -> Self {
         Self {
             alto,
-            device,
-            context,
+            specifier: None,
+            device: None,
+            context: None,
+            generation: 0,
         }
     }
+
+    fn connect(&mut self) -> Result<(), AudioEngineError> {
+        let specifier = self.alto.default_output().ok();
+        let device = self
+            .alto
+            .open(specifier.as_deref())
+            .map_err(|_| AudioEngineError::NoOutputDevice)?;
+        let context = device
+            .new_context(None)
+            .map_err(|_| AudioEngineError::ContextCreationFailed)?;
+
+        self.specifier = specifier;
+        self.device = Some(device);
+        self.context = Some(context);
+        self.generation += 1;
+
+        Ok(())
+    }
+
+    fn disconnect(&mut self) {
+        if self.device.is_none() {
+            return;
+        }
+
+        self.context = None;
+        self.device = None;
+        self.generation += 1;
+    }
+
+    fn is_connected(&self) -> bool {
+        // Drivers without ALC_EXT_disconnect cannot tell us, assume it is still there
+        self.device
+            .as_ref()
+            .map(|d| d.is_connected().unwrap_or(true))
+            .unwrap_or(false)
+    }
+
+    fn default_device_changed(&self) -> bool {
+        self.alto.default_output().ok() != self.specifier
+    }
+
+    fn context(&self) -> Option<&Context> {
+        self.context.as_ref()
+    }
+
+    fn generation(&self) -> u32 {
+        self.generation
+    }
 }
 
 pub struct OpenAlAudioSource {
-    context: Rc<Context>,
-    streaming_source: alto::StreamingSource,
+    device: Rc<RefCell<OpenAlDevice>>,
+    generation: u32,
+    streaming_source: Option<alto::StreamingSource>,
     decoder: Option<Box<dyn Decoder>>,
     state: AudioSourceState,
     looping: bool,
@@ -44,11 +143,18 @@ pub struct OpenAlAudioSource {
 
 impl AudioSource for OpenAlAudioSource {
     fn update(&mut self) {
+        self.sync_device();
         if self.decoder.is_none() {
             return;
         }
 
-        if self.streaming_source.buffers_queued() == 0 {
+        // Silent until an output device is available again
+        let streaming_source = match self.streaming_source.as_mut() {
+            Some(s) => s,
+            None => return,
+        };
+
+        if streaming_source.buffers_queued() == 0 {
             self.state = AudioSourceState::Stopped;
         }
 
@@ -56,7 +162,7 @@ impl AudioSource for OpenAlAudioSource {
             return;
         }
 
-        let mut processed = self.streaming_source.buffers_processed();
+        let mut processed = streaming_source.buffers_processed();
         while processed > 0 {
             let frame = self.decoder.as_mut().unwrap().fetch_samples();
             if let Ok(None) = frame {
@@ -66,7 +172,7 @@ impl AudioSource for OpenAlAudioSource {
                 }
             }
 
-            if let Ok(mut buffer) = self.streaming_source.unqueue_buffer() {
+            if let Ok(mut buffer) = streaming_source.unqueue_buffer() {
                 match frame {
                     Ok(Some(samples)) => {
                         match samples.channels {
@@ -79,7 +185,7 @@ impl AudioSource for OpenAlAudioSource {
                             _ => {}
                         }
 
-                        self.streaming_source.queue_buffer(buffer).unwrap();
+                        streaming_source.queue_buffer(buffer).unwrap();
                     }
                     Ok(None) => {}
                     Err(e) => println!("Error: {}", e),
@@ -90,8 +196,8 @@ impl AudioSource for OpenAlAudioSource {
         }
 
         // The state changes when the buffers are exhausted
-        if self.streaming_source.state() == alto::SourceState::Stopped {
-            self.streaming_source.play();
+        if streaming_source.state() == alto::SourceState::Stopped {
+            streaming_source.play();
         }
     }
 
@@ -116,8 +222,10 @@ impl AudioSource for OpenAlAudioSource {
 
     fn stop(&mut self) {
         self.state = AudioSourceState::Stopped;
-        self.streaming_source.stop();
-        while self.streaming_source.unqueue_buffer().is_ok() {}
+        if let Some(streaming_source) = self.streaming_source.as_mut() {
+            streaming_source.stop();
+            while streaming_source.unqueue_buffer().is_ok() {}
+        }
     }
 
     fn state(&self) -> AudioSourceState {
@@ -126,23 +234,46 @@ impl AudioSource for OpenAlAudioSource {
 
     fn pause(&mut self) {
         self.state = AudioSourceState::Paused;
-        self.streaming_source.pause();
+        if let Some(streaming_source) = self.streaming_source.as_mut() {
+            streaming_source.pause();
+        }
     }
 
     fn resume(&mut self) {
-        if self.state == AudioSourceState::Paused {
+        if self.state != AudioSourceState::Paused {
+            return;
+        }
+
+        // A source paused before a device switch has nothing queued on the new device
+        let queued = self
+            .streaming_source
+            .as_ref()
+            .map(|s| s.buffers_queued())
+            .unwrap_or(0);
+        if queued == 0 && self.decoder.is_some() {
+            self.play_internal();
+        } else {
             self.state = AudioSourceState::Playing;
-            self.streaming_source.play();
+            if let Some(streaming_source) = self.streaming_source.as_mut() {
+                streaming_source.play();
+            }
         }
     }
 }
 
 impl OpenAlAudioSource {
-    pub fn new(context: Rc<Context>) -> Self {
-        let streaming_source = context.new_streaming_source().unwrap();
+    fn new(device: Rc<RefCell<OpenAlDevice>>) -> Self {
+        let (generation, streaming_source) = {
+            let d = device.borrow();
+            (
+                d.generation(),
+                d.context().and_then(|c| c.new_streaming_source().ok()),
+            )
+        };
 
         Self {
-            context,
+            device,
+            generation,
             streaming_source,
             decoder: None,
             state: AudioSourceState::Stopped,
@@ -153,23 +284,48 @@ impl OpenAlAudioSource {
     }
 
     fn play_internal(&mut self) {
+        self.state = AudioSourceState::Playing;
+
+        let device = self.device.clone();
+        let device = device.borrow();
+        let (context, streaming_source) = match (device.context(), self.streaming_source.as_mut()) {
+            (Some(c), Some(s)) => (c, s),
+            _ => return,
+        };
+
         for _ in 0..20 {
             let frame = self.decoder.as_mut().unwrap().fetch_samples();
             match frame {
                 Ok(Some(samples)) => {
-                    let buffer = create_buffer_from_samples(samples, self.context.as_ref());
+                    let buffer = create_buffer_from_samples(samples, context);
                     if buffer.is_none() {
                         continue;
                     }
 
-                    self.streaming_source.queue_buffer(buffer.unwrap()).unwrap();
+                    streaming_source.queue_buffer(buffer.unwrap()).unwrap();
                 }
                 _ => break,
             }
         }
 
-        self.streaming_source.play();
-        self.state = AudioSourceState::Playing;
+        streaming_source.play();
+    }
+
+    fn sync_device(&mut self) {
+        let device = self.device.clone();
+        let device = device.borrow();
+        if device.generation() == self.generation {
+            return;
+        }
+
+        self.generation = device.generation();
+        self.streaming_source = device.context().and_then(|c| c.new_streaming_source().ok());
+
+        // Keep playing from where the decoder is. Buffers queued on the old
+        // device are lost, paused sources are requeued on resume.
+        if self.state == AudioSourceState::Playing && self.decoder.is_some() {
+            self.play_internal();
+        }
     }
 }
 
@@ -0,0 +1,66 @@
+use super::{AudioEngine, AudioSource, AudioSourceState, Codec};
+
+/// An audio engine that accepts every request and outputs nothing. Used when
+/// no real audio backend can be created on this machine.
+pub struct SilentAudioEngine;
+
+impl AudioEngine for SilentAudioEngine {
+    fn create_source(&self) -> Box<dyn AudioSource> {
+        Box::new(SilentAudioSource::new())
+    }
+
+    fn update(&self, _delta_sec: f32) {}
+}
+
+impl SilentAudioEngine {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+pub struct SilentAudioSource {
+    state: AudioSourceState,
+    loaded: bool,
+}
+
+impl AudioSource for SilentAudioSource {
+    fn update(&mut self) {}
+
+    fn play(&mut self, _data: Vec<u8>, _codec: Codec, _looping: bool) {
+        self.loaded = true;
+        self.state = AudioSourceState::Playing;
+    }
+
+    fn restart(&mut self) {
+        if self.loaded {
+            self.state = AudioSourceState::Playing;
+        }
+    }
+
+    fn pause(&mut self) {
+        self.state = AudioSourceState::Paused;
+    }
+
+    fn resume(&mut self) {
+        if self.state == AudioSourceState::Paused {
+            self.state = AudioSourceState::Playing;
+        }
+    }
+
+    fn stop(&mut self) {
+        self.state = AudioSourceState::Stopped;
+    }
+
+    fn state(&self) -> AudioSourceState {
+        self.state
+    }
+}
+
+impl SilentAudioSource {
+    pub fn new() -> Self {
+        Self {
+            state: AudioSourceState::Stopped,
+            loaded: false,
+        }
+    }
+}
@@ -15,3 +15,20 @@ pub const STR_NO_SUITABLE_MEMORY_TYPE: &str =
     "There is no suitable memory type found on your machine.";
 pub const STR_NO_SUITABLE_FORMAT: &str =
     "There is no suitable format supported by your graphic card.";
+pub const STR_OPENAL_LIBRARY_NOT_FOUND: &str =
+    "Unable to load the OpenAL library. Audio will be disabled.";
+pub const STR_NO_AUDIO_OUTPUT_DEVICE: &str = "There is no audio output device on your machine.";
+pub const STR_FAILED_CREATE_AUDIO_CONTEXT: &str =
+    "Unable to create an audio context on the output device.";
+pub const STR_INVALID_TEXTURE_FILE: &str = "The texture file is truncated or corrupted.";
+pub const STR_UNSUPPORTED_TEXTURE_FORMAT: &str =
+    "The texture is stored in a pixel format Radiance doesn't support.";
+pub const STR_SUPERCOMPRESSED_TEXTURE: &str =
+    "Supercompressed KTX2 textures are not supported, re-export them without supercompression.";
+pub const STR_INVALID_FONT: &str = "The font file is not a valid TrueType or OpenType font.";
+pub const STR_ATLAS_TOO_SMALL: &str =
+    "The images don't fit in a texture atlas of the maximum size.";
+pub const STR_INVALID_SPRITE_SHEET: &str = "The sprite sheet description is invalid.";
+pub const STR_ROTATED_SPRITE_FRAME: &str =
+    "Rotated sprite sheet frames are not supported, export the sheet without rotation.";
+pub const STR_UNKNOWN_SPRITE_FRAME: &str = "The sprite sheet animation refers to a missing frame.";
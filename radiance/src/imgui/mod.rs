@@ -55,6 +55,17 @@ impl ImguiContext {
         Self { context, platform }
     }
 
+    /// Converts the style colors for renderers writing to sRGB images, which would otherwise
+    /// encode them a second time
+    pub(crate) fn use_linear_colors(&mut self) {
+        let mut context = self.context.borrow_mut();
+        for color in context.style_mut().colors.iter_mut() {
+            for channel in color.iter_mut().take(3) {
+                *channel = channel.powf(2.2);
+            }
+        }
+    }
+
     pub fn draw_ui<F: FnOnce(&mut Ui)>(&mut self, delta_sec: f32, draw: F) -> ImguiFrame {
         self.platform.borrow_mut().new_frame(delta_sec);
 
@@ -4,10 +4,12 @@
 #[macro_use]
 mod macros;
 
+pub mod animation;
 pub mod application;
 pub mod audio;
 pub mod imgui;
 pub mod input;
+pub mod loaders;
 pub mod math;
 pub mod radiance;
 pub mod rendering;
@@ -0,0 +1,170 @@
+use super::error::TextureLoaderError;
+use super::read_u32;
+use crate::rendering::{max_mip_levels, CompressedFormat, CompressedImage};
+use std::error::Error;
+use std::path::Path;
+
+const DDS_MAGIC: &[u8] = b"DDS ";
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDPF_FOURCC: u32 = 0x4;
+const HEADER_SIZE: usize = 128;
+const DX10_HEADER_SIZE: usize = 20;
+
+/// Loads a block compressed DDS texture with its mip levels. Only the first image of texture
+/// arrays and cubemaps is read.
+pub fn load_dds<P: AsRef<Path>>(path: P) -> Result<CompressedImage, Box<dyn Error>> {
+    load_dds_from_memory(&std::fs::read(path)?)
+}
+
+pub fn load_dds_from_memory(data: &[u8]) -> Result<CompressedImage, Box<dyn Error>> {
+    if data.len() < HEADER_SIZE || &data[0..4] != DDS_MAGIC {
+        return Err(TextureLoaderError::InvalidTextureFile)?;
+    }
+
+    let height = read_u32(data, 12)?;
+    let width = read_u32(data, 16)?;
+    let mip_levels = if read_u32(data, 8)? & DDSD_MIPMAPCOUNT != 0 {
+        read_u32(data, 28)?.max(1)
+    } else {
+        1
+    };
+    if mip_levels > max_mip_levels(width, height) {
+        return Err(TextureLoaderError::InvalidTextureFile)?;
+    }
+
+    let pixel_format_flags = read_u32(data, 80)?;
+    let four_cc = &data[84..88];
+    if pixel_format_flags & DDPF_FOURCC == 0 {
+        return Err(TextureLoaderError::UnsupportedTextureFormat(0))?;
+    }
+
+    let (format, srgb, mut offset) = if four_cc == b"DX10" {
+        let dxgi_format = read_u32(data, HEADER_SIZE)?;
+        let (format, srgb) = dxgi_format_to_compressed(dxgi_format)
+            .ok_or(TextureLoaderError::UnsupportedTextureFormat(dxgi_format))?;
+        (format, srgb, HEADER_SIZE + DX10_HEADER_SIZE)
+    } else {
+        let format = match four_cc {
+            b"DXT1" => CompressedFormat::Bc1,
+            b"DXT2" | b"DXT3" => CompressedFormat::Bc2,
+            b"DXT4" | b"DXT5" => CompressedFormat::Bc3,
+            b"ATI1" | b"BC4U" => CompressedFormat::Bc4,
+            b"ATI2" | b"BC5U" => CompressedFormat::Bc5,
+            _ => {
+                return Err(TextureLoaderError::UnsupportedTextureFormat(read_u32(
+                    data, 84,
+                )?))?
+            }
+        };
+
+        // Legacy headers can't tell, color textures are usually authored in sRGB while the
+        // one and two channel formats hold data such as normals
+        let srgb = match format {
+            CompressedFormat::Bc4 | CompressedFormat::Bc5 => false,
+            _ => true,
+        };
+        (format, srgb, HEADER_SIZE)
+    };
+
+    let mut levels = vec![];
+    for level in 0..mip_levels {
+        let size = format.level_size((width >> level).max(1), (height >> level).max(1));
+        let end = offset
+            .checked_add(size)
+            .ok_or(TextureLoaderError::InvalidTextureFile)?;
+        let bytes = data
+            .get(offset..end)
+            .ok_or(TextureLoaderError::InvalidTextureFile)?;
+        levels.push(bytes.to_vec());
+        offset = end;
+    }
+
+    Ok(CompressedImage::new(format, srgb, width, height, levels)?)
+}
+
+fn dxgi_format_to_compressed(format: u32) -> Option<(CompressedFormat, bool)> {
+    match format {
+        71 => Some((CompressedFormat::Bc1, false)),
+        72 => Some((CompressedFormat::Bc1, true)),
+        74 => Some((CompressedFormat::Bc2, false)),
+        75 => Some((CompressedFormat::Bc2, true)),
+        77 => Some((CompressedFormat::Bc3, false)),
+        78 => Some((CompressedFormat::Bc3, true)),
+        80 => Some((CompressedFormat::Bc4, false)),
+        83 => Some((CompressedFormat::Bc5, false)),
+        95 => Some((CompressedFormat::Bc6hUfloat, false)),
+        96 => Some((CompressedFormat::Bc6hSfloat, false)),
+        98 => Some((CompressedFormat::Bc7, false)),
+        99 => Some((CompressedFormat::Bc7, true)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dds(width: u32, height: u32, mip_levels: u32, four_cc: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut file = vec![0u8; HEADER_SIZE];
+        file[0..4].copy_from_slice(DDS_MAGIC);
+        file[8..12].copy_from_slice(&DDSD_MIPMAPCOUNT.to_le_bytes());
+        file[12..16].copy_from_slice(&height.to_le_bytes());
+        file[16..20].copy_from_slice(&width.to_le_bytes());
+        file[28..32].copy_from_slice(&mip_levels.to_le_bytes());
+        file[80..84].copy_from_slice(&DDPF_FOURCC.to_le_bytes());
+        file[84..88].copy_from_slice(four_cc);
+        file.extend_from_slice(data);
+        file
+    }
+
+    #[test]
+    fn reads_every_mip_level() {
+        let data: Vec<u8> = (0..24).collect();
+        let image = load_dds_from_memory(&dds(4, 4, 3, b"DXT1", &data)).unwrap();
+
+        assert_eq!(image.format(), CompressedFormat::Bc1);
+        assert!(image.srgb());
+        assert_eq!((image.width(), image.height()), (4, 4));
+        assert_eq!(image.levels().len(), 3);
+        assert_eq!(image.levels()[2], data[16..24].to_vec());
+    }
+
+    #[test]
+    fn legacy_two_channel_formats_are_linear() {
+        let image = load_dds_from_memory(&dds(4, 4, 1, b"ATI2", &[0; 16])).unwrap();
+
+        assert_eq!(image.format(), CompressedFormat::Bc5);
+        assert!(!image.srgb());
+    }
+
+    #[test]
+    fn reads_the_dx10_format() {
+        let mut dx10 = vec![0u8; DX10_HEADER_SIZE];
+        dx10[0..4].copy_from_slice(&99u32.to_le_bytes());
+        dx10.extend_from_slice(&[0; 16]);
+        let image = load_dds_from_memory(&dds(4, 4, 1, b"DX10", &dx10)).unwrap();
+
+        assert_eq!(image.format(), CompressedFormat::Bc7);
+        assert!(image.srgb());
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        // Truncated level
+        assert!(load_dds_from_memory(&dds(8, 8, 1, b"DXT5", &[0; 32])).is_err());
+
+        // More levels than a 4x4 image has
+        assert!(load_dds_from_memory(&dds(4, 4, 40, b"DXT1", &[0; 64])).is_err());
+
+        // Level too large to address
+        assert!(
+            load_dds_from_memory(&dds(u32::max_value(), u32::max_value(), 1, b"DXT5", &[]))
+                .is_err()
+        );
+
+        // Unknown format and bad magic
+        assert!(load_dds_from_memory(&dds(4, 4, 1, b"RGBA", &[0; 16])).is_err());
+        assert!(load_dds_from_memory(b"DDS").is_err());
+        assert!(load_dds_from_memory(&[0; HEADER_SIZE]).is_err());
+    }
+}
@@ -0,0 +1,57 @@
+use crate::constants;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum TextureLoaderError {
+    InvalidTextureFile,
+    UnsupportedTextureFormat(u32),
+    SupercompressedTexture,
+}
+
+impl fmt::Display for TextureLoaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextureLoaderError::InvalidTextureFile => {
+                write!(f, "{}", constants::STR_INVALID_TEXTURE_FILE)
+            }
+            TextureLoaderError::UnsupportedTextureFormat(format) => {
+                write!(
+                    f,
+                    "{} ({})",
+                    constants::STR_UNSUPPORTED_TEXTURE_FORMAT,
+                    format
+                )
+            }
+            TextureLoaderError::SupercompressedTexture => {
+                write!(f, "{}", constants::STR_SUPERCOMPRESSED_TEXTURE)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TextureLoaderError {}
+
+#[derive(Debug)]
+pub enum SpriteSheetLoaderError {
+    InvalidSpriteSheet(String),
+    RotatedFrame(String),
+    UnknownFrame(String),
+}
+
+impl fmt::Display for SpriteSheetLoaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SpriteSheetLoaderError::InvalidSpriteSheet(reason) => {
+                write!(f, "{} ({})", constants::STR_INVALID_SPRITE_SHEET, reason)
+            }
+            SpriteSheetLoaderError::RotatedFrame(name) => {
+                write!(f, "{} ({})", constants::STR_ROTATED_SPRITE_FRAME, name)
+            }
+            SpriteSheetLoaderError::UnknownFrame(name) => {
+                write!(f, "{} ({})", constants::STR_UNKNOWN_SPRITE_FRAME, name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SpriteSheetLoaderError {}
@@ -0,0 +1,608 @@
+use super::{fill_attribute, solid_color_image};
+use crate::animation::{
+    AnimationClip, Bone, BoneChannel, BonePose, Easing, Keyframe, SkeletalAnimator, Skeleton,
+    TransformAnimation, TransformAnimator, MAX_BONES,
+};
+use crate::math::{Mat44, Quaternion, Vec3};
+use crate::rendering::{
+    morph_weight_property, AlphaMode as MaterialAlphaMode, ComponentFactory, MaterialDef,
+    MorphTarget, MorphWeights, PbrMaterialDef, RenderObject, TextureDef, TextureSource,
+    VertexBuffer, VertexSemantic, MORPH_SHADER_DEF, SIMPLE_SHADER_DEF, SKINNED_SHADER_DEF,
+};
+use crate::scene::{DefaultEntity, Entity};
+use gltf::animation::{util::ReadOutputs, Interpolation};
+use gltf::{image::Format, material::AlphaMode};
+use image::RgbaImage;
+use std::error::Error;
+use std::path::Path;
+
+/// Loads a glTF 2.0 file (.gltf or .glb) as an entity hierarchy. The returned root entity
+/// holds one child per root node of the default scene.
+pub fn load_gltf<P: AsRef<Path>>(
+    path: P,
+    factory: &dyn ComponentFactory,
+) -> Result<Box<dyn Entity>, Box<dyn Error>> {
+    let name = path
+        .as_ref()
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("gltf")
+        .to_owned();
+    let (document, buffers, images) = gltf::import(path)?;
+    Ok(GltfLoader::new(&document, &buffers, &images, factory).load(&name))
+}
+
+pub fn load_gltf_from_memory(
+    data: &[u8],
+    name: &str,
+    factory: &dyn ComponentFactory,
+) -> Result<Box<dyn Entity>, Box<dyn Error>> {
+    let (document, buffers, images) = gltf::import_slice(data)?;
+    Ok(GltfLoader::new(&document, &buffers, &images, factory).load(name))
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum MeshKind {
+    Static,
+    Skinned,
+    Morph,
+}
+
+struct GltfLoader<'a> {
+    document: &'a gltf::Document,
+    buffers: &'a [gltf::buffer::Data],
+    images: &'a [gltf::image::Data],
+    factory: &'a dyn ComponentFactory,
+    parents: Vec<Option<usize>>,
+}
+
+impl<'a> GltfLoader<'a> {
+    fn new(
+        document: &'a gltf::Document,
+        buffers: &'a [gltf::buffer::Data],
+        images: &'a [gltf::image::Data],
+        factory: &'a dyn ComponentFactory,
+    ) -> Self {
+        let mut parents = vec![None; document.nodes().len()];
+        for node in document.nodes() {
+            for child in node.children() {
+                parents[child.index()] = Some(node.index());
+            }
+        }
+
+        Self {
+            document,
+            buffers,
+            images,
+            factory,
+            parents,
+        }
+    }
+
+    fn load(&self, name: &str) -> Box<dyn Entity> {
+        let document = self.document;
+        let mut root = DefaultEntity::create(name);
+        let scene = document
+            .default_scene()
+            .or_else(|| document.scenes().next());
+        if let Some(scene) = scene {
+            for node in scene.nodes() {
+                root.attach(self.load_node(&node));
+            }
+        }
+
+        Box::new(root)
+    }
+
+    fn load_node(&self, node: &gltf::Node) -> Box<dyn Entity> {
+        let name = node
+            .name()
+            .map(|n| n.to_owned())
+            .unwrap_or_else(|| format!("node_{}", node.index()));
+        let mut entity = DefaultEntity::create(&name);
+        entity
+            .transform_mut()
+            .set_matrix(to_mat44(&node.transform().matrix()));
+
+        if let Some(mesh) = node.mesh() {
+            let skinned = node.skin().is_some();
+            let objects: Vec<Box<dyn RenderObject>> = mesh
+                .primitives()
+                .filter_map(|p| self.load_primitive(&p, skinned))
+                .collect();
+
+            if !objects.is_empty() {
+                entity.add_component(Box::new(self.factory.create_rendering_component(objects)));
+            }
+
+            let target_count = mesh
+                .primitives()
+                .map(|p| p.morph_targets().len())
+                .max()
+                .unwrap_or(0);
+            if target_count > 0 {
+                let weights = match mesh.weights() {
+                    Some(weights) => weights.to_vec(),
+                    None => vec![0.; target_count],
+                };
+                entity.add_component(Box::new(MorphWeights::new(&weights)));
+
+                let animations = self.load_morph_animations(node);
+                if !animations.is_empty() {
+                    let mut animator = TransformAnimator::new();
+                    for animation in animations {
+                        animator.add_animation(animation);
+                    }
+
+                    entity.add_component(Box::new(animator));
+                }
+            }
+        }
+
+        if let Some(skin) = node.skin() {
+            entity.add_component(Box::new(self.load_animator(&skin)));
+        }
+
+        for child in node.children() {
+            entity.attach(self.load_node(&child));
+        }
+
+        Box::new(entity)
+    }
+
+    fn load_primitive(
+        &self,
+        primitive: &gltf::Primitive,
+        skinned: bool,
+    ) -> Option<Box<dyn RenderObject>> {
+        if primitive.mode() != gltf::mesh::Mode::Triangles {
+            log::warn!("Unsupported glTF primitive mode: {:?}", primitive.mode());
+            return None;
+        }
+
+        let buffers = self.buffers;
+        let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()].0[..]));
+        let positions: Vec<[f32; 3]> = reader.read_positions()?.collect();
+        let normals: Option<Vec<[f32; 3]>> = reader.read_normals().map(|n| n.collect());
+        let tangents: Option<Vec<[f32; 4]>> = reader.read_tangents().map(|t| t.collect());
+        let tex_coords: Option<Vec<[f32; 2]>> =
+            reader.read_tex_coords(0).map(|t| t.into_f32().collect());
+        let tex_coords2: Option<Vec<[f32; 2]>> =
+            reader.read_tex_coords(1).map(|t| t.into_f32().collect());
+        let colors: Option<Vec<[f32; 4]>> =
+            reader.read_colors(0).map(|c| c.into_rgba_f32().collect());
+        let mut joints: Option<Vec<[u16; 4]>> =
+            reader.read_joints(0).map(|j| j.into_u16().collect());
+        let mut weights: Option<Vec<[f32; 4]>> =
+            reader.read_weights(0).map(|w| w.into_f32().collect());
+        if let (Some(joints), Some(weights)) = (joints.as_mut(), weights.as_mut()) {
+            if drop_unsupported_bones(joints, weights) {
+                log::warn!(
+                    "glTF mesh is skinned to bones past the first {}, their influence is dropped",
+                    MAX_BONES
+                );
+            }
+        }
+        let morph_targets: Vec<MorphTarget> = reader
+            .read_morph_targets()
+            .enumerate()
+            .map(|(i, (p, n, _))| MorphTarget {
+                name: format!("target_{}", i),
+                position_deltas: p
+                    .map(|p| p.map(|v| Vec3::new(v[0], v[1], v[2])).collect())
+                    .unwrap_or_else(|| vec![Vec3::new_zeros(); positions.len()]),
+                normal_deltas: n.map(|n| n.map(|v| Vec3::new(v[0], v[1], v[2])).collect()),
+            })
+            .collect();
+        let indices: Vec<u32> = reader
+            .read_indices()
+            .map(|i| i.into_u32().collect())
+            .unwrap_or_else(|| (0..positions.len() as u32).collect());
+
+        let kind = if skinned && joints.is_some() && weights.is_some() {
+            if !morph_targets.is_empty() {
+                log::warn!("Morph targets on skinned glTF meshes are not supported");
+            }
+
+            MeshKind::Skinned
+        } else if !morph_targets.is_empty() {
+            MeshKind::Morph
+        } else {
+            MeshKind::Static
+        };
+
+        let material_def = if kind != MeshKind::Skinned && normals.is_some() {
+            self.load_pbr_material(&primitive.material(), kind)
+        } else {
+            self.load_material(&primitive.material(), kind)
+        };
+        let layout = material_def.shader().vertex_layout().clone();
+        let mut vertices = VertexBuffer::new(layout, positions.len());
+
+        fill_attribute(&mut vertices, VertexSemantic::Position, Some(&positions));
+        fill_attribute(&mut vertices, VertexSemantic::Normal, normals.as_ref());
+        fill_attribute(&mut vertices, VertexSemantic::Tangent, tangents.as_ref());
+        fill_attribute(&mut vertices, VertexSemantic::TexCoord, tex_coords.as_ref());
+        fill_attribute(
+            &mut vertices,
+            VertexSemantic::TexCoord2,
+            tex_coords2.as_ref(),
+        );
+        fill_attribute(&mut vertices, VertexSemantic::Color, colors.as_ref());
+        fill_attribute(&mut vertices, VertexSemantic::BoneIndices, joints.as_ref());
+        fill_attribute(&mut vertices, VertexSemantic::BoneWeights, weights.as_ref());
+        if kind == MeshKind::Morph {
+            Some(self.factory.create_morph_render_object(
+                vertices,
+                indices,
+                &material_def,
+                &morph_targets,
+            ))
+        } else {
+            Some(
+                self.factory
+                    .create_render_object(vertices, indices, &material_def, false),
+            )
+        }
+    }
+
+    fn load_material(&self, material: &gltf::Material, kind: MeshKind) -> MaterialDef {
+        let pbr = material.pbr_metallic_roughness();
+        let base_color = pbr
+            .base_color_texture()
+            .and_then(|info| self.load_image(info.texture().source().index()))
+            .unwrap_or_else(|| solid_color_image(pbr.base_color_factor()));
+
+        let (name, shader) = match kind {
+            MeshKind::Static => ("simple_material", SIMPLE_SHADER_DEF.clone()),
+            MeshKind::Skinned => ("skinned_material", SKINNED_SHADER_DEF.clone()),
+            MeshKind::Morph => ("morph_material", MORPH_SHADER_DEF.clone()),
+        };
+
+        MaterialDef::new(
+            name,
+            shader,
+            vec![TextureDef::new(TextureSource::Image(Some(base_color)))],
+            material.alpha_mode() == AlphaMode::Blend,
+        )
+    }
+
+    fn load_pbr_material(&self, material: &gltf::Material, kind: MeshKind) -> MaterialDef {
+        let pbr = material.pbr_metallic_roughness();
+        let texture = |texture: Option<gltf::Texture>| {
+            texture.and_then(|t| self.load_image(t.source().index()))
+        };
+
+        let material_def = PbrMaterialDef {
+            base_color_factor: pbr.base_color_factor(),
+            base_color_texture: texture(pbr.base_color_texture().map(|i| i.texture())),
+            metallic_factor: pbr.metallic_factor(),
+            roughness_factor: pbr.roughness_factor(),
+            metallic_roughness_texture: texture(
+                pbr.metallic_roughness_texture().map(|i| i.texture()),
+            ),
+            normal_scale: material.normal_texture().map_or(1., |t| t.scale()),
+            normal_texture: texture(material.normal_texture().map(|t| t.texture())),
+            occlusion_strength: material.occlusion_texture().map_or(1., |t| t.strength()),
+            occlusion_texture: texture(material.occlusion_texture().map(|t| t.texture())),
+            emissive_factor: material.emissive_factor(),
+            emissive_texture: texture(material.emissive_texture().map(|i| i.texture())),
+            alpha_mode: match material.alpha_mode() {
+                AlphaMode::Opaque => MaterialAlphaMode::Opaque,
+                AlphaMode::Mask => MaterialAlphaMode::Mask(material.alpha_cutoff()),
+                AlphaMode::Blend => MaterialAlphaMode::Blend,
+            },
+        };
+
+        if kind == MeshKind::Morph {
+            material_def.create_morph()
+        } else {
+            material_def.create()
+        }
+    }
+
+    fn load_animator(&self, skin: &gltf::Skin) -> SkeletalAnimator {
+        let joints: Vec<usize> = skin.joints().map(|j| j.index()).collect();
+        let buffers = self.buffers;
+        let inverse_bind_matrices: Vec<Mat44> = skin
+            .reader(|buffer| Some(&buffers[buffer.index()].0[..]))
+            .read_inverse_bind_matrices()
+            .map(|m| m.map(|m| to_mat44(&m)).collect())
+            .unwrap_or_default();
+
+        let bones = skin
+            .joints()
+            .enumerate()
+            .map(|(i, joint)| {
+                let (t, r, s) = joint.transform().decomposed();
+                Bone {
+                    name: joint
+                        .name()
+                        .map(|n| n.to_owned())
+                        .unwrap_or_else(|| format!("bone_{}", i)),
+                    parent: self.parents[joint.index()]
+                        .and_then(|p| joints.iter().position(|j| *j == p)),
+                    rest_pose: BonePose::new(
+                        Vec3::new(t[0], t[1], t[2]),
+                        Quaternion::new(r[0], r[1], r[2], r[3]),
+                        Vec3::new(s[0], s[1], s[2]),
+                    ),
+                    inverse_bind_matrix: inverse_bind_matrices
+                        .get(i)
+                        .copied()
+                        .unwrap_or_else(Mat44::new_identity),
+                }
+            })
+            .collect();
+
+        let mut animator = SkeletalAnimator::new(Skeleton::new(bones));
+        for clip in self.load_clips(&joints) {
+            animator.add_clip(clip);
+        }
+
+        animator
+    }
+
+    // Morph weight channels become animated properties named by `morph_weight_property`
+    fn load_morph_animations(&self, node: &gltf::Node) -> Vec<TransformAnimation> {
+        let buffers = self.buffers;
+        let target_count = node
+            .mesh()
+            .and_then(|m| m.primitives().map(|p| p.morph_targets().len()).max())
+            .unwrap_or(0);
+        if target_count == 0 {
+            return vec![];
+        }
+
+        let mut animations = vec![];
+        for animation in self.document.animations() {
+            let name = animation
+                .name()
+                .map(|n| n.to_owned())
+                .unwrap_or_else(|| format!("animation_{}", animation.index()));
+            let mut transform_animation = TransformAnimation::new(&name);
+            let mut found = false;
+            for channel in animation.channels() {
+                if channel.target().node().index() != node.index() {
+                    continue;
+                }
+
+                let reader = channel.reader(|buffer| Some(&buffers[buffer.index()].0[..]));
+                let (times, values): (Vec<f32>, Vec<f32>) =
+                    match (reader.read_inputs(), reader.read_outputs()) {
+                        (Some(inputs), Some(ReadOutputs::MorphTargetWeights(weights))) => {
+                            (inputs.collect(), weights.into_f32().collect())
+                        }
+                        _ => continue,
+                    };
+
+                // Cubic spline keyframes hold in-tangents, values and out-tangents for all
+                // the targets
+                let interpolation = channel.sampler().interpolation();
+                let (stride, offset) = if interpolation == Interpolation::CubicSpline {
+                    (target_count * 3, target_count)
+                } else {
+                    (target_count, 0)
+                };
+
+                for target in 0..target_count {
+                    let weights: Vec<f32> = values
+                        .chunks(stride)
+                        .filter_map(|c| c.get(offset + target).copied())
+                        .collect();
+                    let keyframes = to_keyframes(&times, weights, Interpolation::Linear)
+                        .into_iter()
+                        .map(|k| match interpolation {
+                            Interpolation::Step => k.with_easing(Easing::Step),
+                            _ => k,
+                        })
+                        .collect();
+                    transform_animation = transform_animation
+                        .with_property(&morph_weight_property(target), keyframes);
+                }
+
+                found = true;
+            }
+
+            if found {
+                animations.push(transform_animation);
+            }
+        }
+
+        animations
+    }
+
+    fn load_clips(&self, joints: &[usize]) -> Vec<AnimationClip> {
+        let buffers = self.buffers;
+        let mut clips = vec![];
+        for animation in self.document.animations() {
+            let mut channels: Vec<BoneChannel> = vec![];
+            for channel in animation.channels() {
+                let bone = match joints
+                    .iter()
+                    .position(|j| *j == channel.target().node().index())
+                {
+                    Some(bone) => bone,
+                    None => continue,
+                };
+
+                let reader = channel.reader(|buffer| Some(&buffers[buffer.index()].0[..]));
+                let times: Vec<f32> = match reader.read_inputs() {
+                    Some(inputs) => inputs.collect(),
+                    None => continue,
+                };
+
+                let interpolation = channel.sampler().interpolation();
+                let index = match channels.iter().position(|c| c.bone == bone) {
+                    Some(index) => index,
+                    None => {
+                        channels.push(BoneChannel::new(bone));
+                        channels.len() - 1
+                    }
+                };
+
+                let bone_channel = &mut channels[index];
+                match reader.read_outputs() {
+                    Some(ReadOutputs::Translations(t)) => {
+                        let values = t.map(|v| Vec3::new(v[0], v[1], v[2])).collect();
+                        bone_channel.translations = to_keyframes(&times, values, interpolation);
+                    }
+                    Some(ReadOutputs::Rotations(r)) => {
+                        let values = r
+                            .into_f32()
+                            .map(|q| Quaternion::new(q[0], q[1], q[2], q[3]))
+                            .collect();
+                        bone_channel.rotations = to_keyframes(&times, values, interpolation);
+                    }
+                    Some(ReadOutputs::Scales(s)) => {
+                        let values = s.map(|v| Vec3::new(v[0], v[1], v[2])).collect();
+                        bone_channel.scales = to_keyframes(&times, values, interpolation);
+                    }
+                    _ => {}
+                }
+            }
+
+            if !channels.is_empty() {
+                let name = animation
+                    .name()
+                    .map(|n| n.to_owned())
+                    .unwrap_or_else(|| format!("animation_{}", animation.index()));
+                clips.push(AnimationClip::new(&name, channels));
+            }
+        }
+
+        clips
+    }
+
+    fn load_image(&self, index: usize) -> Option<RgbaImage> {
+        self.images.get(index).and_then(to_rgba_image)
+    }
+}
+
+// Cubic spline samplers store (in-tangent, value, out-tangent) triplets, only the values
+// are kept and interpolated linearly
+fn to_keyframes<T: Copy>(
+    times: &[f32],
+    values: Vec<T>,
+    interpolation: Interpolation,
+) -> Vec<Keyframe<T>> {
+    let (values, easing): (Vec<T>, Easing) = match interpolation {
+        Interpolation::CubicSpline => (
+            values.chunks(3).filter_map(|c| c.get(1).copied()).collect(),
+            Easing::Linear,
+        ),
+        Interpolation::Step => (values, Easing::Step),
+        Interpolation::Linear => (values, Easing::Linear),
+    };
+
+    times
+        .iter()
+        .zip(values)
+        .map(|(time, value)| Keyframe::new(*time, value).with_easing(easing))
+        .collect()
+}
+
+// Only the first `MAX_BONES` bones are uploaded for skinning, influences of the others are
+// removed and the remaining weights normalized again. Returns whether any was removed.
+fn drop_unsupported_bones(joints: &mut [[u16; 4]], weights: &mut [[f32; 4]]) -> bool {
+    let mut dropped = false;
+    for (joints, weights) in joints.iter_mut().zip(weights.iter_mut()) {
+        let mut vertex_dropped = false;
+        for (joint, weight) in joints.iter_mut().zip(weights.iter_mut()) {
+            if *joint as usize >= MAX_BONES {
+                *joint = 0;
+                vertex_dropped |= *weight != 0.;
+                *weight = 0.;
+            }
+        }
+
+        let total: f32 = weights.iter().sum();
+        if vertex_dropped && total > 0. {
+            for weight in weights.iter_mut() {
+                *weight /= total;
+            }
+        }
+
+        dropped |= vertex_dropped;
+    }
+
+    dropped
+}
+
+// glTF matrices are column major
+fn to_mat44(m: &[[f32; 4]; 4]) -> Mat44 {
+    let mut mat = Mat44::new_zero();
+    for i in 0..4usize {
+        for j in 0..4usize {
+            mat[i][j] = m[j][i];
+        }
+    }
+
+    mat
+}
+
+fn to_rgba_image(data: &gltf::image::Data) -> Option<RgbaImage> {
+    let (channels, bytes_per_channel) = match data.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 | Format::B8G8R8 => (3, 1),
+        Format::R8G8B8A8 | Format::B8G8R8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+    };
+
+    let bgr = data.format == Format::B8G8R8 || data.format == Format::B8G8R8A8;
+    let pixel_count = (data.width * data.height) as usize;
+    if data.pixels.len() < pixel_count * channels * bytes_per_channel {
+        return None;
+    }
+
+    // Keep the most significant byte of 16-bit channels
+    let channel = |pixel: usize, c: usize| -> u8 {
+        data.pixels[(pixel * channels + c) * bytes_per_channel + bytes_per_channel - 1]
+    };
+
+    let mut rgba = Vec::with_capacity(pixel_count * 4);
+    for i in 0..pixel_count {
+        let pixel = match channels {
+            1 => [channel(i, 0), channel(i, 0), channel(i, 0), 255],
+            2 => [channel(i, 0), channel(i, 0), channel(i, 0), channel(i, 1)],
+            3 => [channel(i, 0), channel(i, 1), channel(i, 2), 255],
+            _ => [channel(i, 0), channel(i, 1), channel(i, 2), channel(i, 3)],
+        };
+
+        if bgr {
+            rgba.extend_from_slice(&[pixel[2], pixel[1], pixel[0], pixel[3]]);
+        } else {
+            rgba.extend_from_slice(&pixel);
+        }
+    }
+
+    RgbaImage::from_raw(data.width, data.height, rgba)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bones_past_the_limit_are_dropped() {
+        let last = MAX_BONES as u16 - 1;
+        let mut joints = vec![[0, 1, 2, 3], [last, last + 1, 2, 300]];
+        let mut weights = vec![[0.25; 4], [0.5, 0.25, 0.25, 0.]];
+        assert!(drop_unsupported_bones(&mut joints, &mut weights));
+
+        assert_eq!(joints, vec![[0, 1, 2, 3], [last, 0, 2, 0]]);
+        assert_eq!(weights[0], [0.25; 4]);
+        assert!((weights[1][0] - 2. / 3.).abs() < 1e-6);
+        assert_eq!(weights[1][1], 0.);
+        assert!((weights[1][2] - 1. / 3.).abs() < 1e-6);
+        assert_eq!(weights[1][3], 0.);
+
+        let mut joints = vec![[0, 1, 2, 3]];
+        let mut weights = vec![[1., 0., 0., 0.]];
+        assert!(!drop_unsupported_bones(&mut joints, &mut weights));
+    }
+}
@@ -0,0 +1,23 @@
+use crate::rendering::HdrImage;
+use image::codecs::hdr::HdrDecoder;
+use std::error::Error;
+use std::io::{BufRead, BufReader, Cursor};
+use std::path::Path;
+
+/// Loads a Radiance HDR image with its linear floating point colors, typically an
+/// equirectangular panorama for `TextureDef::from_equirectangular`
+pub fn load_hdr<P: AsRef<Path>>(path: P) -> Result<HdrImage, Box<dyn Error>> {
+    read_hdr(BufReader::new(std::fs::File::open(path)?))
+}
+
+pub fn load_hdr_from_memory(data: &[u8]) -> Result<HdrImage, Box<dyn Error>> {
+    read_hdr(Cursor::new(data))
+}
+
+fn read_hdr<R: BufRead>(reader: R) -> Result<HdrImage, Box<dyn Error>> {
+    let decoder = HdrDecoder::new(reader)?;
+    let metadata = decoder.metadata();
+    let pixels = decoder.read_image_hdr()?;
+    let data = pixels.iter().flat_map(|p| p.0.iter().copied()).collect();
+    Ok(HdrImage::from_raw(metadata.width, metadata.height, data).unwrap())
+}
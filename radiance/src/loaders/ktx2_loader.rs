@@ -0,0 +1,194 @@
+use super::error::TextureLoaderError;
+use super::{read_u32, read_u64};
+use crate::rendering::{max_mip_levels, CompressedFormat, CompressedImage};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::path::Path;
+
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+const LEVEL_INDEX_OFFSET: usize = 80;
+const LEVEL_INDEX_ENTRY_SIZE: usize = 24;
+
+// Block sizes of the VK_FORMAT_ASTC_*_BLOCK formats, each one having a UNORM then an SRGB
+// variant starting from VK_FORMAT_ASTC_4x4_UNORM_BLOCK
+const ASTC_FIRST_FORMAT: u32 = 157;
+const ASTC_BLOCK_SIZES: [(u32, u32); 14] = [
+    (4, 4),
+    (5, 4),
+    (5, 5),
+    (6, 5),
+    (6, 6),
+    (8, 5),
+    (8, 6),
+    (8, 8),
+    (10, 5),
+    (10, 6),
+    (10, 8),
+    (10, 10),
+    (12, 10),
+    (12, 12),
+];
+
+/// Loads a block compressed KTX2 texture with its mip levels. Only the first image of
+/// texture arrays and cubemaps is read.
+pub fn load_ktx2<P: AsRef<Path>>(path: P) -> Result<CompressedImage, Box<dyn Error>> {
+    load_ktx2_from_memory(&std::fs::read(path)?)
+}
+
+pub fn load_ktx2_from_memory(data: &[u8]) -> Result<CompressedImage, Box<dyn Error>> {
+    if data.len() < LEVEL_INDEX_OFFSET || data[0..12] != KTX2_IDENTIFIER {
+        return Err(TextureLoaderError::InvalidTextureFile)?;
+    }
+
+    let vk_format = read_u32(data, 12)?;
+    let width = read_u32(data, 20)?;
+    let height = read_u32(data, 24)?.max(1);
+    let mip_levels = read_u32(data, 40)?.max(1);
+    if mip_levels > max_mip_levels(width, height) {
+        return Err(TextureLoaderError::InvalidTextureFile)?;
+    }
+    if read_u32(data, 44)? != 0 {
+        return Err(TextureLoaderError::SupercompressedTexture)?;
+    }
+
+    let (format, srgb) = vk_format_to_compressed(vk_format)
+        .ok_or(TextureLoaderError::UnsupportedTextureFormat(vk_format))?;
+
+    // Each level stores its layers and faces one after another, the first one comes first
+    let mut levels = vec![];
+    for level in 0..mip_levels as usize {
+        let entry = LEVEL_INDEX_OFFSET + level * LEVEL_INDEX_ENTRY_SIZE;
+        let offset = usize::try_from(read_u64(data, entry)?)
+            .map_err(|_| TextureLoaderError::InvalidTextureFile)?;
+        let size = format.level_size((width >> level).max(1), (height >> level).max(1));
+        let bytes = offset
+            .checked_add(size)
+            .and_then(|end| data.get(offset..end))
+            .ok_or(TextureLoaderError::InvalidTextureFile)?;
+        levels.push(bytes.to_vec());
+    }
+
+    Ok(CompressedImage::new(format, srgb, width, height, levels)?)
+}
+
+// The sRGB variants of the color formats have even values
+fn vk_format_to_compressed(format: u32) -> Option<(CompressedFormat, bool)> {
+    let srgb = format % 2 == 0;
+    let format = match format {
+        131..=134 => CompressedFormat::Bc1,
+        135 | 136 => CompressedFormat::Bc2,
+        137 | 138 => CompressedFormat::Bc3,
+        139 => CompressedFormat::Bc4,
+        141 => CompressedFormat::Bc5,
+        143 => return Some((CompressedFormat::Bc6hUfloat, false)),
+        144 => return Some((CompressedFormat::Bc6hSfloat, false)),
+        145 | 146 => CompressedFormat::Bc7,
+        147 | 148 => CompressedFormat::Etc2Rgb8,
+        149 | 150 => CompressedFormat::Etc2Rgb8A1,
+        151 | 152 => CompressedFormat::Etc2Rgba8,
+        ASTC_FIRST_FORMAT..=184 => {
+            let (block_width, block_height) =
+                ASTC_BLOCK_SIZES[((format - ASTC_FIRST_FORMAT) / 2) as usize];
+            CompressedFormat::Astc {
+                block_width,
+                block_height,
+            }
+        }
+        _ => return None,
+    };
+
+    Some((format, srgb))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VK_FORMAT_BC7_SRGB_BLOCK: u32 = 146;
+    const VK_FORMAT_BC5_UNORM_BLOCK: u32 = 141;
+
+    // Header and level index, followed by the data, with the levels at the given offsets
+    fn ktx2(vk_format: u32, width: u32, height: u32, offsets: &[u64], data: &[u8]) -> Vec<u8> {
+        let mut file = vec![0u8; LEVEL_INDEX_OFFSET];
+        file[0..12].copy_from_slice(&KTX2_IDENTIFIER);
+        file[12..16].copy_from_slice(&vk_format.to_le_bytes());
+        file[20..24].copy_from_slice(&width.to_le_bytes());
+        file[24..28].copy_from_slice(&height.to_le_bytes());
+        file[40..44].copy_from_slice(&(offsets.len() as u32).to_le_bytes());
+        for offset in offsets {
+            file.extend_from_slice(&offset.to_le_bytes());
+            file.extend_from_slice(&[0; LEVEL_INDEX_ENTRY_SIZE - 8]);
+        }
+
+        file.extend_from_slice(data);
+        file
+    }
+
+    fn data_offset(level_count: usize) -> u64 {
+        (LEVEL_INDEX_OFFSET + level_count * LEVEL_INDEX_ENTRY_SIZE) as u64
+    }
+
+    #[test]
+    fn reads_every_mip_level() {
+        let start = data_offset(2);
+        let data: Vec<u8> = (0..80).collect();
+        let file = ktx2(VK_FORMAT_BC7_SRGB_BLOCK, 8, 8, &[start, start + 64], &data);
+        let image = load_ktx2_from_memory(&file).unwrap();
+
+        assert_eq!(image.format(), CompressedFormat::Bc7);
+        assert!(image.srgb());
+        assert_eq!(image.levels().len(), 2);
+        assert_eq!(image.levels()[0].len(), 64);
+        assert_eq!(image.levels()[1], data[64..80].to_vec());
+    }
+
+    #[test]
+    fn maps_vulkan_formats() {
+        let file = ktx2(VK_FORMAT_BC5_UNORM_BLOCK, 4, 4, &[data_offset(1)], &[0; 16]);
+        let image = load_ktx2_from_memory(&file).unwrap();
+        assert_eq!(image.format(), CompressedFormat::Bc5);
+        assert!(!image.srgb());
+
+        assert_eq!(
+            vk_format_to_compressed(ASTC_FIRST_FORMAT + 3),
+            Some((
+                CompressedFormat::Astc {
+                    block_width: 5,
+                    block_height: 4
+                },
+                true
+            ))
+        );
+        assert_eq!(vk_format_to_compressed(37), None);
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        // Offsets past the end of the file, or overflowing
+        let file = ktx2(VK_FORMAT_BC7_SRGB_BLOCK, 4, 4, &[1000], &[0; 16]);
+        assert!(load_ktx2_from_memory(&file).is_err());
+        let file = ktx2(
+            VK_FORMAT_BC7_SRGB_BLOCK,
+            4,
+            4,
+            &[u64::max_value()],
+            &[0; 16],
+        );
+        assert!(load_ktx2_from_memory(&file).is_err());
+
+        // More levels than a 4x4 image has
+        let offsets = vec![data_offset(40); 40];
+        let file = ktx2(VK_FORMAT_BC7_SRGB_BLOCK, 4, 4, &offsets, &[0; 16]);
+        assert!(load_ktx2_from_memory(&file).is_err());
+
+        // Supercompressed, unsupported format and truncated header
+        let mut file = ktx2(VK_FORMAT_BC7_SRGB_BLOCK, 4, 4, &[data_offset(1)], &[0; 16]);
+        file[44] = 1;
+        assert!(load_ktx2_from_memory(&file).is_err());
+        let file = ktx2(37, 4, 4, &[data_offset(1)], &[0; 16]);
+        assert!(load_ktx2_from_memory(&file).is_err());
+        assert!(load_ktx2_from_memory(&KTX2_IDENTIFIER).is_err());
+    }
+}
@@ -0,0 +1,61 @@
+use crate::rendering::{VertexBuffer, VertexSemantic};
+use image::RgbaImage;
+use std::convert::TryInto;
+
+mod dds_loader;
+mod error;
+mod gltf_loader;
+mod hdr_loader;
+mod ktx2_loader;
+mod obj_loader;
+mod sprite_sheet_loader;
+
+pub use dds_loader::{load_dds, load_dds_from_memory};
+pub use error::{SpriteSheetLoaderError, TextureLoaderError};
+pub use gltf_loader::{load_gltf, load_gltf_from_memory};
+pub use hdr_loader::{load_hdr, load_hdr_from_memory};
+pub use ktx2_loader::{load_ktx2, load_ktx2_from_memory};
+pub use obj_loader::load_obj;
+pub use sprite_sheet_loader::{load_sprite_sheet, load_sprite_sheet_from_memory};
+
+// Attributes the shader layout doesn't declare in a matching format are skipped, and
+// those missing from the primitive are left zeroed
+fn fill_attribute<T: Copy>(
+    vertices: &mut VertexBuffer,
+    semantic: VertexSemantic,
+    data: Option<&Vec<T>>,
+) {
+    match vertices.layout().attribute(semantic) {
+        Some(a) if a.format.size() == std::mem::size_of::<T>() => {}
+        _ => return,
+    }
+
+    if let Some(data) = data {
+        for (i, v) in data.iter().enumerate() {
+            vertices.set_attribute(i, semantic, |d: &mut T| *d = *v);
+        }
+    }
+}
+
+fn solid_color_image(color: [f32; 4]) -> RgbaImage {
+    let pixel = image::Rgba([
+        (color[0] * 255.) as u8,
+        (color[1] * 255.) as u8,
+        (color[2] * 255.) as u8,
+        (color[3] * 255.) as u8,
+    ]);
+
+    RgbaImage::from_pixel(1, 1, pixel)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, TextureLoaderError> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or(TextureLoaderError::InvalidTextureFile)
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64, TextureLoaderError> {
+    data.get(offset..offset + 8)
+        .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or(TextureLoaderError::InvalidTextureFile)
+}
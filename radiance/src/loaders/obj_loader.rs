@@ -0,0 +1,105 @@
+use super::{fill_attribute, solid_color_image};
+use crate::rendering::{
+    ComponentFactory, MaterialDef, RenderingComponent, SimpleMaterialDef, TextureDef,
+    TextureSource, VertexBuffer, VertexSemantic, SIMPLE_SHADER_DEF,
+};
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// Loads a Wavefront OBJ file together with the MTL libraries it references. Each model
+/// in the file becomes a named `RenderingComponent`, ready to be attached to an entity.
+pub fn load_obj<P: AsRef<Path>>(
+    path: P,
+    factory: &dyn ComponentFactory,
+) -> Result<Vec<(String, RenderingComponent)>, Box<dyn Error>> {
+    let path = path.as_ref();
+    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let (models, materials) = tobj::load_obj(path, true)?;
+
+    let mut components = vec![];
+    for model in &models {
+        let mesh = &model.mesh;
+        let material = mesh.material_id.and_then(|id| materials.get(id));
+        let material_def = load_material(material, base_dir);
+        let vertices = load_vertices(mesh, &material_def);
+
+        let object =
+            factory.create_render_object(vertices, mesh.indices.clone(), &material_def, false);
+        components.push((
+            model.name.clone(),
+            factory.create_rendering_component(vec![object]),
+        ));
+    }
+
+    Ok(components)
+}
+
+fn load_vertices(mesh: &tobj::Mesh, material_def: &MaterialDef) -> VertexBuffer {
+    let positions: Vec<[f32; 3]> = mesh
+        .positions
+        .chunks_exact(3)
+        .map(|p| [p[0], p[1], p[2]])
+        .collect();
+    let normals: Vec<[f32; 3]> = mesh
+        .normals
+        .chunks_exact(3)
+        .map(|n| [n[0], n[1], n[2]])
+        .collect();
+
+    // OBJ texture coordinates have their origin at the bottom left
+    let tex_coords: Vec<[f32; 2]> = mesh
+        .texcoords
+        .chunks_exact(2)
+        .map(|t| [t[0], 1. - t[1]])
+        .collect();
+
+    let layout = material_def.shader().vertex_layout().clone();
+    let mut vertices = VertexBuffer::new(layout, positions.len());
+    fill_attribute(&mut vertices, VertexSemantic::Position, Some(&positions));
+    fill_attribute(
+        &mut vertices,
+        VertexSemantic::Normal,
+        Some(&normals).filter(|n| n.len() == positions.len()),
+    );
+    fill_attribute(
+        &mut vertices,
+        VertexSemantic::TexCoord,
+        Some(&tex_coords).filter(|t| t.len() == positions.len()),
+    );
+
+    vertices
+}
+
+fn load_material(material: Option<&tobj::Material>, base_dir: &Path) -> MaterialDef {
+    let (diffuse, texture, use_alpha) = match material {
+        Some(m) => (
+            [m.diffuse[0], m.diffuse[1], m.diffuse[2], m.dissolve],
+            m.diffuse_texture.as_str(),
+            m.dissolve < 1.,
+        ),
+        None => ([1., 1., 1., 1.], "", false),
+    };
+
+    if !texture.is_empty() {
+        let texture_path = base_dir.join(texture.replace('\\', "/"));
+        match File::open(&texture_path) {
+            Ok(file) => return SimpleMaterialDef::create(&mut BufReader::new(file), use_alpha),
+            Err(e) => log::warn!(
+                "Unable to open texture {}: {}",
+                texture_path.to_string_lossy(),
+                e
+            ),
+        }
+    }
+
+    MaterialDef::new(
+        "simple_material",
+        SIMPLE_SHADER_DEF.clone(),
+        vec![TextureDef::new(TextureSource::Image(Some(
+            solid_color_image(diffuse),
+        )))],
+        use_alpha,
+    )
+}
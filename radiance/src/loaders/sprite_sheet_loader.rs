@@ -0,0 +1,356 @@
+use super::SpriteSheetLoaderError;
+use crate::animation::{SpriteAnimation, SpriteFrame};
+use crate::rendering::{AtlasRegion, TextureAtlas, TextureDef, TextureSource};
+use image::RgbaImage;
+use serde::de::{Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use std::rc::Rc;
+
+// Sheets without timings, such as TexturePacker's, play their animations at 10 frames per
+// second, which is also Aseprite's default
+const DEFAULT_FRAME_DURATION: f32 = 0.1;
+
+/// Loads a sprite sheet exported as TexturePacker or Aseprite JSON, in either the hash or the
+/// array layout, with the image named in its `meta` next to it. Aseprite tags and the
+/// `animations` of TexturePacker become the atlas' animations.
+pub fn load_sprite_sheet<P: AsRef<Path>>(path: P) -> Result<TextureAtlas, Box<dyn Error>> {
+    let json = std::fs::read(path.as_ref())?;
+    let sheet: SpriteSheet = serde_json::from_slice(&json)?;
+    let image_name = sheet.meta.image.as_ref().ok_or_else(|| {
+        SpriteSheetLoaderError::InvalidSpriteSheet("meta.image is missing".to_owned())
+    })?;
+    let image_path = path
+        .as_ref()
+        .parent()
+        .map_or_else(|| image_name.into(), |dir| dir.join(image_name));
+    let image = image::open(image_path)?.to_rgba8();
+
+    build_atlas(sheet, image)
+}
+
+/// Same as `load_sprite_sheet` with the image already loaded, `meta.image` is ignored
+pub fn load_sprite_sheet_from_memory(
+    json: &[u8],
+    image: RgbaImage,
+) -> Result<TextureAtlas, Box<dyn Error>> {
+    let sheet: SpriteSheet = serde_json::from_slice(json)?;
+    build_atlas(sheet, image)
+}
+
+fn build_atlas(sheet: SpriteSheet, image: RgbaImage) -> Result<TextureAtlas, Box<dyn Error>> {
+    let (width, height) = image.dimensions();
+    let mut frames = vec![];
+    for (name, frame) in sheet.frames.0.iter() {
+        if frame.rotated {
+            return Err(SpriteSheetLoaderError::RotatedFrame(name.clone()).into());
+        }
+
+        let rect = &frame.frame;
+        let mut region = AtlasRegion::from_pixels(rect.x, rect.y, rect.w, rect.h, width, height);
+        if let (Some(offset), Some(source_size)) = (&frame.sprite_source_size, &frame.source_size) {
+            region.offset = (offset.x, offset.y);
+            region.source_size = (source_size.w, source_size.h);
+        }
+
+        let duration = frame
+            .duration
+            .map_or(DEFAULT_FRAME_DURATION, |milliseconds| milliseconds / 1000.);
+        frames.push((name.as_str(), SpriteFrame { region, duration }));
+    }
+
+    let regions = frames
+        .iter()
+        .map(|(name, frame)| (name.to_string(), frame.region))
+        .collect();
+    let mut atlas = TextureAtlas::new(
+        Rc::new(TextureDef::new(TextureSource::Image(Some(image)))),
+        width,
+        height,
+        regions,
+    );
+
+    for tag in sheet.meta.frame_tags.iter() {
+        let range = frames.get(tag.from..=tag.to).ok_or_else(|| {
+            SpriteSheetLoaderError::UnknownFrame(format!("{} {}..{}", tag.name, tag.from, tag.to))
+        })?;
+        let mut sequence: Vec<SpriteFrame> = range.iter().map(|(_, frame)| *frame).collect();
+        match tag.direction.as_str() {
+            "reverse" => sequence.reverse(),
+            "pingpong" => {
+                let back: Vec<SpriteFrame> = sequence
+                    .iter()
+                    .rev()
+                    .skip(1)
+                    .take(sequence.len().saturating_sub(2))
+                    .copied()
+                    .collect();
+                sequence.extend(back);
+            }
+            _ => (),
+        }
+
+        atlas.add_animation(SpriteAnimation::new(&tag.name, sequence));
+    }
+
+    for (name, frame_names) in sheet.animations.iter() {
+        let sequence = frame_names
+            .iter()
+            .map(|frame_name| {
+                frames
+                    .iter()
+                    .find(|(other, _)| *other == frame_name.as_str())
+                    .map(|(_, frame)| *frame)
+                    .ok_or_else(|| SpriteSheetLoaderError::UnknownFrame(frame_name.clone()))
+            })
+            .collect::<Result<Vec<SpriteFrame>, SpriteSheetLoaderError>>()?;
+        atlas.add_animation(SpriteAnimation::new(name, sequence));
+    }
+
+    Ok(atlas)
+}
+
+#[derive(Deserialize)]
+struct SpriteSheet {
+    frames: Frames,
+
+    #[serde(default)]
+    meta: Meta,
+
+    /// TexturePacker's frame names per animation
+    #[serde(default)]
+    animations: HashMap<String, Vec<String>>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct Meta {
+    image: Option<String>,
+
+    /// Aseprite's tags, indexing the frames in the order they are listed
+    #[serde(default)]
+    frame_tags: Vec<FrameTag>,
+}
+
+#[derive(Deserialize)]
+struct FrameTag {
+    name: String,
+    from: usize,
+    to: usize,
+
+    #[serde(default)]
+    direction: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Frame {
+    /// Only in the array layout, the hash one keys the frames by name
+    filename: Option<String>,
+    frame: Rect,
+
+    #[serde(default)]
+    rotated: bool,
+    sprite_source_size: Option<Rect>,
+    source_size: Option<Size>,
+
+    /// Aseprite only, in milliseconds
+    duration: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct Rect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Deserialize)]
+struct Size {
+    w: u32,
+    h: u32,
+}
+
+// Named frames in the order of the document, from either an object or an array
+struct Frames(Vec<(String, Frame)>);
+
+impl<'de> Deserialize<'de> for Frames {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FramesVisitor;
+
+        impl<'de> Visitor<'de> for FramesVisitor {
+            type Value = Frames;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "an object or an array of frames")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Frames, A::Error> {
+                let mut frames = vec![];
+                while let Some((name, frame)) = map.next_entry::<String, Frame>()? {
+                    frames.push((name, frame));
+                }
+
+                Ok(Frames(frames))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Frames, A::Error> {
+                let mut frames = vec![];
+                while let Some(frame) = seq.next_element::<Frame>()? {
+                    let name = frame
+                        .filename
+                        .clone()
+                        .unwrap_or_else(|| frames.len().to_string());
+                    frames.push((name, frame));
+                }
+
+                Ok(Frames(frames))
+            }
+        }
+
+        deserializer.deserialize_any(FramesVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(json: &str) -> Result<TextureAtlas, Box<dyn Error>> {
+        load_sprite_sheet_from_memory(json.as_bytes(), RgbaImage::new(64, 32))
+    }
+
+    fn error(json: &str) -> SpriteSheetLoaderError {
+        match load(json) {
+            Ok(_) => panic!("expected an error"),
+            Err(error) => match error.downcast::<SpriteSheetLoaderError>() {
+                Ok(error) => *error,
+                Err(error) => panic!("unexpected error {}", error),
+            },
+        }
+    }
+
+    fn regions(animation: &SpriteAnimation) -> Vec<AtlasRegion> {
+        animation
+            .frames()
+            .iter()
+            .map(|frame| frame.region)
+            .collect()
+    }
+
+    #[test]
+    fn hash_layout_with_trimmed_frames() {
+        let atlas = load(
+            r#"{
+                "frames": {
+                    "walk_0": {
+                        "frame": { "x": 0, "y": 0, "w": 16, "h": 16 },
+                        "rotated": false,
+                        "trimmed": true,
+                        "spriteSourceSize": { "x": 2, "y": 4, "w": 16, "h": 16 },
+                        "sourceSize": { "w": 20, "h": 24 }
+                    },
+                    "walk_1": { "frame": { "x": 16, "y": 0, "w": 16, "h": 32 } }
+                },
+                "animations": { "walk": ["walk_1", "walk_0"] },
+                "meta": { "image": "sheet.png", "size": { "w": 64, "h": 32 } }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!((atlas.width(), atlas.height()), (64, 32));
+        let walk_0 = atlas.region("walk_0").unwrap();
+        assert_eq!(walk_0.uv, [0., 0., 0.25, 0.5]);
+        assert_eq!(walk_0.offset, (2, 4));
+        assert_eq!(walk_0.source_size, (20, 24));
+        let walk_1 = atlas.region("walk_1").unwrap();
+        assert_eq!(*walk_1, AtlasRegion::from_pixels(16, 0, 16, 32, 64, 32));
+
+        let walk = atlas.animation("walk").unwrap();
+        assert_eq!(regions(walk), vec![*walk_1, *walk_0]);
+        assert_eq!(walk.frames()[0].duration, DEFAULT_FRAME_DURATION);
+    }
+
+    #[test]
+    fn array_layout_with_aseprite_tags() {
+        let atlas = load(
+            r#"{
+                "frames": [
+                    { "filename": "a", "frame": { "x": 0, "y": 0, "w": 8, "h": 8 }, "duration": 100 },
+                    { "filename": "b", "frame": { "x": 8, "y": 0, "w": 8, "h": 8 }, "duration": 200 },
+                    { "filename": "c", "frame": { "x": 16, "y": 0, "w": 8, "h": 8 }, "duration": 100 },
+                    { "frame": { "x": 24, "y": 0, "w": 8, "h": 8 }, "duration": 50 }
+                ],
+                "meta": {
+                    "frameTags": [
+                        { "name": "forward", "from": 0, "to": 2, "direction": "forward" },
+                        { "name": "reverse", "from": 1, "to": 3, "direction": "reverse" },
+                        { "name": "pingpong", "from": 0, "to": 3, "direction": "pingpong" },
+                        { "name": "single", "from": 2, "to": 2, "direction": "pingpong" }
+                    ]
+                }
+            }"#,
+        )
+        .unwrap();
+
+        // Frames without a filename are named by their index
+        let [a, b, c, d] = [
+            *atlas.region("a").unwrap(),
+            *atlas.region("b").unwrap(),
+            *atlas.region("c").unwrap(),
+            *atlas.region("3").unwrap(),
+        ];
+
+        let forward = atlas.animation("forward").unwrap();
+        assert_eq!(regions(forward), vec![a, b, c]);
+        assert!((forward.duration() - 0.4).abs() < 1e-6);
+        assert_eq!(forward.frame_at(0.15), 1);
+
+        assert_eq!(regions(atlas.animation("reverse").unwrap()), vec![d, c, b]);
+        assert_eq!(
+            regions(atlas.animation("pingpong").unwrap()),
+            vec![a, b, c, d, c, b]
+        );
+        assert_eq!(regions(atlas.animation("single").unwrap()), vec![c]);
+    }
+
+    #[test]
+    fn rejects_rotated_and_unknown_frames() {
+        match error(
+            r#"{ "frames": { "a": { "frame": { "x": 0, "y": 0, "w": 8, "h": 8 }, "rotated": true } } }"#,
+        ) {
+            SpriteSheetLoaderError::RotatedFrame(name) => assert_eq!(name, "a"),
+            error => panic!("unexpected error {}", error),
+        }
+
+        match error(
+            r#"{
+                "frames": { "a": { "frame": { "x": 0, "y": 0, "w": 8, "h": 8 } } },
+                "animations": { "run": ["a", "b"] }
+            }"#,
+        ) {
+            SpriteSheetLoaderError::UnknownFrame(name) => assert_eq!(name, "b"),
+            error => panic!("unexpected error {}", error),
+        }
+
+        match error(
+            r#"{
+                "frames": [{ "frame": { "x": 0, "y": 0, "w": 8, "h": 8 } }],
+                "meta": { "frameTags": [{ "name": "run", "from": 0, "to": 1 }] }
+            }"#,
+        ) {
+            SpriteSheetLoaderError::UnknownFrame(_) => (),
+            error => panic!("unexpected error {}", error),
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_json() {
+        assert!(load(r#"{ "frames": 3 }"#).is_err());
+        assert!(load(r#"{ "meta": {} }"#).is_err());
+    }
+}
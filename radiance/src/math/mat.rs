@@ -1,3 +1,4 @@
+use super::{Quaternion, Vec3};
 use serde::{Serialize, Deserialize};
 use std::ops::{Index, IndexMut};
 use std::ptr::swap;
@@ -21,6 +22,29 @@ impl Mat44 {
         ])
     }
 
+    /// Composes translation * rotation * scale
+    pub fn from_trs(translation: &Vec3, rotation: &Quaternion, scale: &Vec3) -> Self {
+        let Quaternion { x, y, z, w } = Quaternion::normalized(rotation);
+        let scale = [scale.x, scale.y, scale.z];
+        let translation = [translation.x, translation.y, translation.z];
+        let rotation = [
+            [1. - 2. * (y * y + z * z), 2. * (x * y - w * z), 2. * (x * z + w * y)],
+            [2. * (x * y + w * z), 1. - 2. * (x * x + z * z), 2. * (y * z - w * x)],
+            [2. * (x * z - w * y), 2. * (y * z + w * x), 1. - 2. * (x * x + y * y)],
+        ];
+
+        let mut mat = Mat44::new_identity();
+        for i in 0..3usize {
+            for j in 0..3usize {
+                mat.0[i][j] = rotation[i][j] * scale[j];
+            }
+
+            mat.0[i][3] = translation[i];
+        }
+
+        mat
+    }
+
     pub fn multiply(&mut self, rhs: &Mat44) -> &mut Self {
         *self = Mat44::multiplied(self, rhs);
         self
@@ -65,6 +89,14 @@ impl Mat44 {
         new_mat
     }
 
+    /// Transforms a point, ignoring the projective row
+    pub fn transform_point(&self, point: &Vec3) -> Vec3 {
+        let row = |i: usize| {
+            self.0[i][0] * point.x + self.0[i][1] * point.y + self.0[i][2] * point.z + self.0[i][3]
+        };
+        Vec3::new(row(0), row(1), row(2))
+    }
+
     pub fn floats_mut(&mut self) -> &mut [[f32; 4]; 4] {
         &mut self.0
     }
@@ -37,6 +37,50 @@ impl Quaternion {
         }
     }
 
+    pub fn new_identity() -> Self {
+        Self::new(0., 0., 0., 1.)
+    }
+
+    /// Extracts the rotation from a matrix without scaling
+    pub fn from_rotation_matrix(m: &Mat44) -> Self {
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        let q = if trace > 0. {
+            let s = (trace + 1.).sqrt() * 2.;
+            Self::new(
+                (m[2][1] - m[1][2]) / s,
+                (m[0][2] - m[2][0]) / s,
+                (m[1][0] - m[0][1]) / s,
+                0.25 * s,
+            )
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = (1. + m[0][0] - m[1][1] - m[2][2]).sqrt() * 2.;
+            Self::new(
+                0.25 * s,
+                (m[0][1] + m[1][0]) / s,
+                (m[0][2] + m[2][0]) / s,
+                (m[2][1] - m[1][2]) / s,
+            )
+        } else if m[1][1] > m[2][2] {
+            let s = (1. + m[1][1] - m[0][0] - m[2][2]).sqrt() * 2.;
+            Self::new(
+                (m[0][1] + m[1][0]) / s,
+                0.25 * s,
+                (m[1][2] + m[2][1]) / s,
+                (m[0][2] - m[2][0]) / s,
+            )
+        } else {
+            let s = (1. + m[2][2] - m[0][0] - m[1][1]).sqrt() * 2.;
+            Self::new(
+                (m[0][2] + m[2][0]) / s,
+                (m[1][2] + m[2][1]) / s,
+                0.25 * s,
+                (m[1][0] - m[0][1]) / s,
+            )
+        };
+
+        Quaternion::normalized(&q)
+    }
+
     pub fn inverse(&mut self) -> &mut Self {
         self.x = -self.x;
         self.y = -self.y;
@@ -45,6 +89,62 @@ impl Quaternion {
         self
     }
 
+    pub fn normalize(&mut self) -> &mut Self {
+        *self = Quaternion::normalized(self);
+        self
+    }
+
+    pub fn normalized(q: &Quaternion) -> Self {
+        let norm = Quaternion::dot(q, q).sqrt();
+        if norm == 0. {
+            *q
+        } else {
+            Self::new(q.x / norm, q.y / norm, q.z / norm, q.w / norm)
+        }
+    }
+
+    pub fn dot(lhs: &Quaternion, rhs: &Quaternion) -> f32 {
+        lhs.x * rhs.x + lhs.y * rhs.y + lhs.z * rhs.z + lhs.w * rhs.w
+    }
+
+    /// Normalized linear interpolation along the shortest path
+    pub fn nlerp(lhs: &Quaternion, rhs: &Quaternion, t: f32) -> Self {
+        let sign = if Quaternion::dot(lhs, rhs) < 0. { -1. } else { 1. };
+        Quaternion::normalized(&Self::new(
+            lhs.x + (sign * rhs.x - lhs.x) * t,
+            lhs.y + (sign * rhs.y - lhs.y) * t,
+            lhs.z + (sign * rhs.z - lhs.z) * t,
+            lhs.w + (sign * rhs.w - lhs.w) * t,
+        ))
+    }
+
+    /// Spherical linear interpolation along the shortest path
+    pub fn slerp(lhs: &Quaternion, rhs: &Quaternion, t: f32) -> Self {
+        let mut cos_theta = Quaternion::dot(lhs, rhs);
+        let mut rhs = *rhs;
+        if cos_theta < 0. {
+            cos_theta = -cos_theta;
+            rhs = Self::new(-rhs.x, -rhs.y, -rhs.z, -rhs.w);
+        }
+
+        // Fall back to nlerp when the quaternions are too close
+        if cos_theta > 0.9995 {
+            return Quaternion::nlerp(lhs, &rhs, t);
+        }
+
+        let theta = cos_theta.acos();
+        let sin_theta = theta.sin();
+        let a = ((1. - t) * theta).sin() / sin_theta;
+        let b = (t * theta).sin() / sin_theta;
+
+        Self::new(
+            lhs.x * a + rhs.x * b,
+            lhs.y * a + rhs.y * b,
+            lhs.z * a + rhs.z * b,
+            lhs.w * a + rhs.w * b,
+        )
+    }
+
     pub fn to_rotate_matrix(&self) -> Mat44 {
         let x2 = self.x * self.x;
         let y2 = self.y * self.y;
@@ -29,6 +29,37 @@ impl Transform {
         self
     }
 
+    pub fn set_trs(
+        &mut self,
+        translation: &Vec3,
+        rotation: &Quaternion,
+        scale: &Vec3,
+    ) -> &mut Self {
+        self.mat = Mat44::from_trs(translation, rotation, scale);
+        self
+    }
+
+    /// Splits the matrix into translation, rotation and scale. Shearing is discarded.
+    pub fn decompose(&self) -> (Vec3, Quaternion, Vec3) {
+        let column = |j: usize| Vec3::new(self.mat[0][j], self.mat[1][j], self.mat[2][j]);
+        let (x, y, z) = (column(0), column(1), column(2));
+        let mut scale = Vec3::new(x.norm(), y.norm(), z.norm());
+        let xy = Vec3::cross(&x, &y);
+        if xy.x * z.x + xy.y * z.y + xy.z * z.z < 0. {
+            scale.x = -scale.x;
+        }
+
+        let mut rotation = Mat44::new_identity();
+        let s = [scale.x, scale.y, scale.z];
+        for i in 0..3usize {
+            for j in 0..3usize {
+                rotation[i][j] = if s[j] == 0. { 0. } else { self.mat[i][j] / s[j] };
+            }
+        }
+
+        (self.position(), Quaternion::from_rotation_matrix(&rotation), scale)
+    }
+
     pub fn set_position(&mut self, pos: &Vec3) -> &mut Self {
         self.mat[0][3] = pos.x;
         self.mat[1][3] = pos.y;
@@ -88,6 +88,14 @@ impl Vec3 {
         )
     }
 
+    pub fn lerp(lhs: &Vec3, rhs: &Vec3, t: f32) -> Self {
+        Vec3::new(
+            lhs.x + (rhs.x - lhs.x) * t,
+            lhs.y + (rhs.y - lhs.y) * t,
+            lhs.z + (rhs.z - lhs.z) * t,
+        )
+    }
+
     pub fn normalized(vec: &Vec3) -> Self {
         let norm = (vec.x * vec.x + vec.y * vec.y + vec.z * vec.z).sqrt();
         if norm == 0. {
@@ -1,4 +1,5 @@
 use crate::{
+    animation,
     audio::AudioEngine,
     imgui::ImguiContext,
     input::{InputEngine, InputEngineInternal},
@@ -38,6 +39,14 @@ impl CoreRadianceEngine {
         self.rendering_engine.component_factory()
     }
 
+    pub fn set_msaa(&mut self, msaa: rendering::Msaa) {
+        self.rendering_engine.set_msaa(msaa);
+    }
+
+    pub fn msaa(&self) -> rendering::Msaa {
+        self.rendering_engine.msaa()
+    }
+
     pub fn audio_engine(&mut self) -> Rc<dyn AudioEngine> {
         self.audio_engine.clone()
     }
@@ -52,8 +61,13 @@ impl CoreRadianceEngine {
 
     pub fn update(&mut self, delta_sec: f32) {
         self.input_engine.borrow_mut().update(delta_sec);
+        self.audio_engine.update(delta_sec);
 
         let scene_manager = self.scene_manager.as_mut().unwrap();
+        if let Some(scene) = scene_manager.scene_mut() {
+            animation::update_animations(scene, delta_sec);
+        }
+
         let ui_frame = self.imgui_context.borrow_mut().draw_ui(delta_sec, |ui| {
             scene_manager.update(ui, delta_sec);
         });
@@ -61,7 +75,14 @@ impl CoreRadianceEngine {
         let scene = self.scene_manager.as_mut().unwrap().scene_mut();
         if let Some(s) = scene {
             let extent = self.rendering_engine.view_extent();
-            s.camera_mut().set_aspect(extent.0 as f32 / extent.1 as f32);
+            for (i, camera) in s.cameras_mut().iter_mut().enumerate() {
+                let (width, height) = match camera.target() {
+                    Some(target) if i > 0 => (target.width(), target.height()),
+                    _ => extent,
+                };
+                let (_, _, width, height) = camera.viewport().pixel_rect(width, height);
+                camera.set_view_size(width, height);
+            }
             self.rendering_engine.render(s, ui_frame);
         }
     }
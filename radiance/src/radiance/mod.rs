@@ -3,7 +3,7 @@ pub use core_engine::CoreRadianceEngine;
 
 use crate::{
     application::Platform,
-    audio::OpenAlAudioEngine,
+    audio::{AudioEngine, OpenAlAudioEngine, SilentAudioEngine},
     imgui::ImguiContext,
     input::WindowsInputEngine,
     rendering::{VulkanRenderingEngine, Window},
@@ -20,7 +20,13 @@ pub fn create_radiance_engine(
 
     let imgui_context = Rc::new(RefCell::new(ImguiContext::new(platform)));
     let rendering_engine = Box::new(VulkanRenderingEngine::new(&window, imgui_context.clone())?);
-    let audio_engine = Rc::new(OpenAlAudioEngine::new());
+    let audio_engine: Rc<dyn AudioEngine> = match OpenAlAudioEngine::new() {
+        Ok(engine) => Rc::new(engine),
+        Err(e) => {
+            log::error!("{}", e);
+            Rc::new(SilentAudioEngine::new())
+        }
+    };
     let input_engine = WindowsInputEngine::new(platform);
     let scene_manager = Box::new(DefaultSceneManager::new());
 
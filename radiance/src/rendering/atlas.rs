@@ -0,0 +1,451 @@
+use super::{Sprite, TextureDef, TextureSource};
+use crate::animation::SpriteAnimation;
+use crate::constants;
+use crate::math::Vec2;
+use image::RgbaImage;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+/// Part of an atlas holding one image. Images trimmed when packed keep the size and offset
+/// they had in their source, so that they stay in place once drawn.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AtlasRegion {
+    /// Left, top, width and height in texture coordinates, as `Sprite::region`
+    pub uv: [f32; 4],
+
+    /// Width and height in pixels
+    pub size: (u32, u32),
+
+    /// Width and height of the image before trimming
+    pub source_size: (u32, u32),
+
+    /// Position of the region's top left corner in the untrimmed image
+    pub offset: (u32, u32),
+}
+
+impl AtlasRegion {
+    /// Region of a `width` x `height` texture covering the given pixels, without trimming
+    pub fn from_pixels(x: u32, y: u32, w: u32, h: u32, width: u32, height: u32) -> Self {
+        Self {
+            uv: [
+                x as f32 / width as f32,
+                y as f32 / height as f32,
+                w as f32 / width as f32,
+                h as f32 / height as f32,
+            ],
+            size: (w, h),
+            source_size: (w, h),
+            offset: (0, 0),
+        }
+    }
+
+    /// Shows the region on the sprite, sized at `pixels_per_unit` with the center of the
+    /// untrimmed image on the sprite's origin
+    pub fn apply(&self, sprite: &mut Sprite, pixels_per_unit: f32) {
+        let (width, height) = (self.size.0.max(1) as f32, self.size.1.max(1) as f32);
+        let (source_width, source_height) = (self.source_size.0 as f32, self.source_size.1 as f32);
+        let (x, y) = (self.offset.0 as f32, self.offset.1 as f32);
+
+        sprite.region = self.uv;
+        sprite.size = Vec2::new(width / pixels_per_unit, height / pixels_per_unit);
+        sprite.pivot = Vec2::new(
+            (source_width / 2. - x) / width,
+            (y + height - source_height / 2.) / height,
+        );
+    }
+}
+
+/// Texture made of several images, looked up by name, and the frame sequences playing them
+pub struct TextureAtlas {
+    texture: Rc<TextureDef>,
+    width: u32,
+    height: u32,
+    regions: HashMap<String, AtlasRegion>,
+    animations: HashMap<String, SpriteAnimation>,
+}
+
+impl TextureAtlas {
+    pub fn new(
+        texture: Rc<TextureDef>,
+        width: u32,
+        height: u32,
+        regions: HashMap<String, AtlasRegion>,
+    ) -> Self {
+        Self {
+            texture,
+            width,
+            height,
+            regions,
+            animations: HashMap::new(),
+        }
+    }
+
+    pub fn texture(&self) -> &Rc<TextureDef> {
+        &self.texture
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn region(&self, name: &str) -> Option<&AtlasRegion> {
+        self.regions.get(name)
+    }
+
+    pub fn regions(&self) -> &HashMap<String, AtlasRegion> {
+        &self.regions
+    }
+
+    pub fn add_animation(&mut self, animation: SpriteAnimation) {
+        self.animations
+            .insert(animation.name().to_owned(), animation);
+    }
+
+    pub fn animation(&self, name: &str) -> Option<&SpriteAnimation> {
+        self.animations.get(name)
+    }
+
+    pub fn animations(&self) -> &HashMap<String, SpriteAnimation> {
+        &self.animations
+    }
+
+    /// Sprite showing a region, see `AtlasRegion::apply`
+    pub fn sprite(&self, name: &str, pixels_per_unit: f32) -> Option<Sprite> {
+        self.regions.get(name).map(|region| {
+            let mut sprite = Sprite::new(self.texture.clone(), Vec2::new(1., 1.));
+            region.apply(&mut sprite, pixels_per_unit);
+            sprite
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum AtlasPackerError {
+    /// The images don't fit in an atlas of the maximum size
+    AtlasTooSmall(u32),
+}
+
+impl fmt::Display for AtlasPackerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AtlasPackerError::AtlasTooSmall(size) => {
+                write!(f, "{} ({}x{})", constants::STR_ATLAS_TOO_SMALL, size, size)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AtlasPackerError {}
+
+/// Combines images into a square power of two atlas, either at runtime with `build` or
+/// offline by saving the image returned from `pack` next to its regions.
+pub struct AtlasPacker {
+    images: Vec<(String, RgbaImage)>,
+    padding: u32,
+    max_size: u32,
+    trim: bool,
+}
+
+impl AtlasPacker {
+    pub fn new() -> Self {
+        Self {
+            images: vec![],
+            padding: 2,
+            max_size: 4096,
+            trim: false,
+        }
+    }
+
+    /// Pixels left between the images, filled by repeating their edges so that filtering
+    /// and mipmapping don't bleed the neighbours in
+    pub fn with_padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    pub fn with_max_size(mut self, max_size: u32) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Drops the fully transparent borders of the images, their regions remember them
+    pub fn with_trim(mut self, trim: bool) -> Self {
+        self.trim = trim;
+        self
+    }
+
+    /// Images added under an existing name replace it
+    pub fn add(&mut self, name: &str, image: RgbaImage) {
+        self.images.retain(|(n, _)| n != name);
+        self.images.push((name.to_owned(), image));
+    }
+
+    /// Packs the images into the smallest atlas they fit in
+    pub fn pack(&self) -> Result<(RgbaImage, HashMap<String, AtlasRegion>), AtlasPackerError> {
+        let trimmed: Vec<(u32, u32, u32, u32)> = self
+            .images
+            .iter()
+            .map(|(_, image)| {
+                if self.trim {
+                    opaque_bounds(image)
+                } else {
+                    (0, 0, image.width(), image.height())
+                }
+            })
+            .collect();
+
+        // Taller images first, so that the shelves waste less space
+        let mut order: Vec<usize> = (0..self.images.len()).collect();
+        order.sort_by_key(|&i| {
+            (
+                std::cmp::Reverse(trimmed[i].3),
+                std::cmp::Reverse(trimmed[i].2),
+            )
+        });
+        let sizes: Vec<(u32, u32)> = order
+            .iter()
+            .map(|&i| {
+                (
+                    trimmed[i].2 + self.padding * 2,
+                    trimmed[i].3 + self.padding * 2,
+                )
+            })
+            .collect();
+
+        let mut size = 64.min(self.max_size.max(1));
+        let positions = loop {
+            if let Some(positions) = pack_shelves(&sizes, size) {
+                break positions;
+            }
+
+            if size >= self.max_size {
+                return Err(AtlasPackerError::AtlasTooSmall(self.max_size));
+            }
+
+            size = (size * 2).min(self.max_size);
+        };
+
+        let mut atlas = RgbaImage::new(size, size);
+        let mut regions = HashMap::new();
+        for (&i, &(x, y)) in order.iter().zip(positions.iter()) {
+            let (name, image) = &self.images[i];
+            let (left, top, width, height) = trimmed[i];
+            let (x, y) = (x + self.padding, y + self.padding);
+            blit_extruded(
+                &mut atlas,
+                image,
+                (left, top, width, height),
+                x,
+                y,
+                self.padding,
+            );
+
+            let mut region = AtlasRegion::from_pixels(x, y, width, height, size, size);
+            region.source_size = (image.width(), image.height());
+            region.offset = (left, top);
+            regions.insert(name.clone(), region);
+        }
+
+        Ok((atlas, regions))
+    }
+
+    pub fn build(&self) -> Result<TextureAtlas, AtlasPackerError> {
+        let (image, regions) = self.pack()?;
+        let (width, height) = image.dimensions();
+        Ok(TextureAtlas::new(
+            Rc::new(TextureDef::new(TextureSource::Image(Some(image)))),
+            width,
+            height,
+            regions,
+        ))
+    }
+}
+
+// Top left corners of the rectangles placed in rows, or None when they overflow the atlas
+fn pack_shelves(sizes: &[(u32, u32)], atlas_size: u32) -> Option<Vec<(u32, u32)>> {
+    let mut positions = vec![];
+    let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+    for &(width, height) in sizes {
+        if width > atlas_size {
+            return None;
+        }
+
+        if x + width > atlas_size {
+            x = 0;
+            y += shelf_height;
+            shelf_height = 0;
+        }
+
+        if y + height > atlas_size {
+            return None;
+        }
+
+        positions.push((x, y));
+        x += width;
+        shelf_height = shelf_height.max(height);
+    }
+
+    Some(positions)
+}
+
+// Left, top, width and height of the smallest rectangle holding the non transparent pixels,
+// a single pixel for fully transparent images
+fn opaque_bounds(image: &RgbaImage) -> (u32, u32, u32, u32) {
+    let (mut left, mut top, mut right, mut bottom) = (u32::max_value(), u32::max_value(), 0, 0);
+    for (x, y, pixel) in image.enumerate_pixels() {
+        if pixel.0[3] != 0 {
+            left = left.min(x);
+            top = top.min(y);
+            right = right.max(x + 1);
+            bottom = bottom.max(y + 1);
+        }
+    }
+
+    if left > right {
+        (0, 0, 1.min(image.width()), 1.min(image.height()))
+    } else {
+        (left, top, right - left, bottom - top)
+    }
+}
+
+// Copies the source rectangle to (x, y) and repeats its edges over `padding` pixels around it
+fn blit_extruded(
+    atlas: &mut RgbaImage,
+    image: &RgbaImage,
+    (left, top, width, height): (u32, u32, u32, u32),
+    x: u32,
+    y: u32,
+    padding: u32,
+) {
+    if width == 0 || height == 0 {
+        return;
+    }
+
+    let padding = padding as i64;
+    for dy in -padding..height as i64 + padding {
+        for dx in -padding..width as i64 + padding {
+            let sx = left + dx.max(0).min(width as i64 - 1) as u32;
+            let sy = top + dy.max(0).min(height as i64 - 1) as u32;
+            atlas.put_pixel(
+                (x as i64 + dx) as u32,
+                (y as i64 + dy) as u32,
+                *image.get_pixel(sx, sy),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+    const GREEN: Rgba<u8> = Rgba([0, 255, 0, 255]);
+    const BLUE: Rgba<u8> = Rgba([0, 0, 255, 255]);
+    const CLEAR: Rgba<u8> = Rgba([0, 0, 0, 0]);
+
+    #[test]
+    fn packs_tallest_first_with_extruded_padding() {
+        let mut red = RgbaImage::from_pixel(10, 10, RED);
+        red.put_pixel(0, 0, BLUE);
+        let mut packer = AtlasPacker::new();
+        packer.add("wide", RgbaImage::from_pixel(20, 5, GREEN));
+        packer.add("square", red);
+        let (atlas, regions) = packer.pack().unwrap();
+
+        assert_eq!(atlas.dimensions(), (64, 64));
+        assert_eq!(
+            regions["square"],
+            AtlasRegion::from_pixels(2, 2, 10, 10, 64, 64)
+        );
+        assert_eq!(
+            regions["wide"],
+            AtlasRegion::from_pixels(16, 2, 20, 5, 64, 64)
+        );
+
+        // The corner pixel fills the padding diagonally, the edges outwards
+        assert_eq!(*atlas.get_pixel(0, 0), BLUE);
+        assert_eq!(*atlas.get_pixel(2, 0), BLUE);
+        assert_eq!(*atlas.get_pixel(13, 5), RED);
+        assert_eq!(*atlas.get_pixel(14, 5), GREEN);
+        assert_eq!(*atlas.get_pixel(37, 8), GREEN);
+        assert_eq!(*atlas.get_pixel(38, 2), CLEAR);
+    }
+
+    #[test]
+    fn grows_until_the_images_fit() {
+        let mut packer = AtlasPacker::new().with_padding(0);
+        for name in &["a", "b", "c", "d"] {
+            packer.add(name, RgbaImage::from_pixel(40, 40, RED));
+        }
+
+        let (atlas, regions) = packer.pack().unwrap();
+        assert_eq!(atlas.dimensions(), (128, 128));
+        assert_eq!(regions.len(), 4);
+
+        let mut too_small = AtlasPacker::new().with_max_size(16);
+        too_small.add("a", RgbaImage::from_pixel(20, 20, RED));
+        match too_small.pack() {
+            Err(AtlasPackerError::AtlasTooSmall(16)) => (),
+            _ => panic!("expected AtlasTooSmall"),
+        }
+    }
+
+    #[test]
+    fn images_added_twice_are_replaced() {
+        let mut packer = AtlasPacker::new();
+        packer.add("a", RgbaImage::from_pixel(4, 4, RED));
+        packer.add("a", RgbaImage::from_pixel(8, 8, GREEN));
+        let (_, regions) = packer.pack().unwrap();
+
+        assert_eq!(regions.len(), 1);
+        assert_eq!(regions["a"].size, (8, 8));
+    }
+
+    #[test]
+    fn trims_transparent_borders() {
+        let mut image = RgbaImage::from_pixel(8, 8, CLEAR);
+        for (x, y) in &[(2, 3), (4, 4)] {
+            image.put_pixel(*x, *y, RED);
+        }
+
+        let mut packer = AtlasPacker::new().with_trim(true);
+        packer.add("trimmed", image);
+        packer.add("empty", RgbaImage::from_pixel(8, 8, CLEAR));
+        let (_, regions) = packer.pack().unwrap();
+
+        let trimmed = regions["trimmed"];
+        assert_eq!(trimmed.size, (3, 2));
+        assert_eq!(trimmed.offset, (2, 3));
+        assert_eq!(trimmed.source_size, (8, 8));
+        assert_eq!(regions["empty"].size, (1, 1));
+    }
+
+    #[test]
+    fn trimmed_regions_keep_the_source_center_on_the_pivot() {
+        let region = AtlasRegion {
+            uv: [0., 0., 0.25, 0.125],
+            size: (3, 2),
+            source_size: (8, 8),
+            offset: (2, 3),
+        };
+        let mut sprite = Sprite::new(
+            Rc::new(TextureDef::new(TextureSource::Image(None))),
+            Vec2::new(1., 1.),
+        );
+        region.apply(&mut sprite, 2.);
+
+        assert_eq!(sprite.region, region.uv);
+        assert_eq!((sprite.size.x, sprite.size.y), (1.5, 1.));
+
+        // The source center (4, 4) is 2 pixels from the left of the region and 1 pixel above
+        // its bottom edge at y = 5
+        assert_eq!((sprite.pivot.x, sprite.pivot.y), (2. / 3., 0.5));
+    }
+}
@@ -0,0 +1,225 @@
+use crate::loaders::TextureLoaderError;
+use image::RgbaImage;
+use std::error::Error;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CompressedFormat {
+    /// DXT1, opaque or with 1-bit alpha
+    Bc1,
+
+    /// DXT3, explicit 4-bit alpha
+    Bc2,
+
+    /// DXT5, interpolated alpha
+    Bc3,
+
+    /// Single channel, decompressed to red
+    Bc4,
+
+    /// Two channels, decompressed to red and green
+    Bc5,
+    Bc6hUfloat,
+    Bc6hSfloat,
+    Bc7,
+    Etc2Rgb8,
+    Etc2Rgb8A1,
+    Etc2Rgba8,
+    Astc {
+        block_width: u32,
+        block_height: u32,
+    },
+}
+
+impl CompressedFormat {
+    pub fn block_size(&self) -> (u32, u32) {
+        match self {
+            CompressedFormat::Astc {
+                block_width,
+                block_height,
+            } => (*block_width, *block_height),
+            _ => (4, 4),
+        }
+    }
+
+    pub fn bytes_per_block(&self) -> usize {
+        match self {
+            CompressedFormat::Bc1
+            | CompressedFormat::Bc4
+            | CompressedFormat::Etc2Rgb8
+            | CompressedFormat::Etc2Rgb8A1 => 8,
+            _ => 16,
+        }
+    }
+
+    /// Size of a `width` x `height` image, partial blocks at the edges are stored whole.
+    /// Saturates for sizes that can't be addressed.
+    pub fn level_size(&self, width: u32, height: u32) -> usize {
+        let (block_width, block_height) = self.block_size();
+        let blocks_x = (width as usize + block_width as usize - 1) / block_width as usize;
+        let blocks_y = (height as usize + block_height as usize - 1) / block_height as usize;
+        blocks_x
+            .saturating_mul(blocks_y)
+            .saturating_mul(self.bytes_per_block())
+    }
+}
+
+/// Number of levels in a full mip chain, down to 1x1. 0 for empty images.
+pub fn max_mip_levels(width: u32, height: u32) -> u32 {
+    32 - width.max(height).leading_zeros()
+}
+
+/// Block compressed image, uploaded as is to the GPU when the device can sample its format
+pub struct CompressedImage {
+    format: CompressedFormat,
+    srgb: bool,
+    width: u32,
+    height: u32,
+    levels: Vec<Vec<u8>>,
+}
+
+impl CompressedImage {
+    /// `levels` holds the blocks of each mip level, the largest first. Fails when there are
+    /// no levels, more than the image can have, or when one of them is truncated.
+    pub fn new(
+        format: CompressedFormat,
+        srgb: bool,
+        width: u32,
+        height: u32,
+        levels: Vec<Vec<u8>>,
+    ) -> Result<Self, TextureLoaderError> {
+        if levels.is_empty() || levels.len() > max_mip_levels(width, height) as usize {
+            return Err(TextureLoaderError::InvalidTextureFile);
+        }
+
+        for (i, level) in levels.iter().enumerate() {
+            let expected = format.level_size((width >> i).max(1), (height >> i).max(1));
+            if level.len() < expected {
+                return Err(TextureLoaderError::InvalidTextureFile);
+            }
+        }
+
+        Ok(Self {
+            format,
+            srgb,
+            width,
+            height,
+            levels,
+        })
+    }
+
+    pub fn format(&self) -> CompressedFormat {
+        self.format
+    }
+
+    /// Whether the colors are sRGB encoded
+    pub fn srgb(&self) -> bool {
+        self.srgb
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn levels(&self) -> &[Vec<u8>] {
+        &self.levels
+    }
+
+    /// Decodes the largest mip level. HDR formats are clamped to the displayable range.
+    pub fn decompress(&self) -> Result<RgbaImage, Box<dyn Error>> {
+        let width = self.width as usize;
+        let height = self.height as usize;
+        let data = &self.levels[0];
+        let mut pixels = vec![0u32; width * height];
+        match self.format {
+            CompressedFormat::Bc1 => texture2ddecoder::decode_bc1(data, width, height, &mut pixels),
+            CompressedFormat::Bc2 => texture2ddecoder::decode_bc2(data, width, height, &mut pixels),
+            CompressedFormat::Bc3 => texture2ddecoder::decode_bc3(data, width, height, &mut pixels),
+            CompressedFormat::Bc4 => texture2ddecoder::decode_bc4(data, width, height, &mut pixels),
+            CompressedFormat::Bc5 => texture2ddecoder::decode_bc5(data, width, height, &mut pixels),
+            CompressedFormat::Bc6hUfloat => {
+                texture2ddecoder::decode_bc6_unsigned(data, width, height, &mut pixels)
+            }
+            CompressedFormat::Bc6hSfloat => {
+                texture2ddecoder::decode_bc6_signed(data, width, height, &mut pixels)
+            }
+            CompressedFormat::Bc7 => texture2ddecoder::decode_bc7(data, width, height, &mut pixels),
+            CompressedFormat::Etc2Rgb8 => {
+                texture2ddecoder::decode_etc2_rgb(data, width, height, &mut pixels)
+            }
+            CompressedFormat::Etc2Rgb8A1 => {
+                texture2ddecoder::decode_etc2_rgba1(data, width, height, &mut pixels)
+            }
+            CompressedFormat::Etc2Rgba8 => {
+                texture2ddecoder::decode_etc2_rgba8(data, width, height, &mut pixels)
+            }
+            CompressedFormat::Astc {
+                block_width,
+                block_height,
+            } => texture2ddecoder::decode_astc(
+                data,
+                width,
+                height,
+                block_width as usize,
+                block_height as usize,
+                &mut pixels,
+            ),
+        }?;
+
+        // The decoder packs pixels as BGRA
+        let rgba = pixels
+            .iter()
+            .flat_map(|p| {
+                let [b, g, r, a] = p.to_le_bytes();
+                [r, g, b, a]
+            })
+            .collect();
+        Ok(RgbaImage::from_raw(self.width, self.height, rgba).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_blocks_are_stored_whole() {
+        assert_eq!(CompressedFormat::Bc1.level_size(5, 5), 4 * 8);
+        assert_eq!(CompressedFormat::Bc7.level_size(1, 1), 16);
+        let astc = CompressedFormat::Astc {
+            block_width: 10,
+            block_height: 8,
+        };
+        assert_eq!(astc.level_size(20, 9), 2 * 2 * 16);
+    }
+
+    #[test]
+    fn full_mip_chains() {
+        assert_eq!(max_mip_levels(1024, 512), 11);
+        assert_eq!(max_mip_levels(1, 1), 1);
+        assert_eq!(max_mip_levels(0, 0), 0);
+        assert_eq!(max_mip_levels(u32::max_value(), 1), 32);
+    }
+
+    #[test]
+    fn validates_the_levels() {
+        let level = |size: usize| vec![0u8; size];
+        assert!(CompressedImage::new(
+            CompressedFormat::Bc1,
+            false,
+            8,
+            8,
+            vec![level(32), level(8)]
+        )
+        .is_ok());
+        assert!(CompressedImage::new(CompressedFormat::Bc1, false, 8, 8, vec![]).is_err());
+        assert!(CompressedImage::new(CompressedFormat::Bc1, false, 8, 8, vec![level(31)]).is_err());
+        assert!(
+            CompressedImage::new(CompressedFormat::Bc1, false, 1, 1, vec![level(8), level(8)])
+                .is_err()
+        );
+    }
+}
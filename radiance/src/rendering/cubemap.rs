@@ -0,0 +1,76 @@
+use super::HdrImage;
+use image::Rgb;
+use std::f32::consts::PI;
+
+/// Projects an equirectangular panorama onto the six faces of a cubemap, in +X, -X, +Y, -Y,
+/// +Z, -Z order. The center of the panorama ends up on the -Z face.
+pub fn equirectangular_to_cubemap(panorama: &HdrImage, face_size: u32) -> [HdrImage; 6] {
+    let face = |index: usize| {
+        HdrImage::from_fn(face_size, face_size, |x, y| {
+            let (direction, _) = texel_direction(index, x, y, face_size);
+            let u = direction[0].atan2(-direction[2]) / (2. * PI) + 0.5;
+            let v = direction[1].max(-1.).min(1.).acos() / PI;
+            sample_bilinear(panorama, u, v)
+        })
+    };
+
+    [face(0), face(1), face(2), face(3), face(4), face(5)]
+}
+
+// Direction through the texel center following the Vulkan cubemap face layout, and the
+// solid angle the texel covers
+pub(crate) fn texel_direction(face: usize, x: u32, y: u32, size: u32) -> ([f32; 3], f32) {
+    let u = 2. * (x as f32 + 0.5) / size as f32 - 1.;
+    let v = 2. * (y as f32 + 0.5) / size as f32 - 1.;
+    let direction = match face {
+        0 => [1., -v, -u],
+        1 => [-1., -v, u],
+        2 => [u, 1., v],
+        3 => [u, -1., -v],
+        4 => [u, -v, 1.],
+        _ => [-u, -v, -1.],
+    };
+
+    let length2 = 1. + u * u + v * v;
+    let length = length2.sqrt();
+    let texel_area = (2. / size as f32) * (2. / size as f32);
+    (
+        [
+            direction[0] / length,
+            direction[1] / length,
+            direction[2] / length,
+        ],
+        texel_area / (length2 * length),
+    )
+}
+
+// Wraps horizontally around the panorama and clamps at the poles
+fn sample_bilinear(image: &HdrImage, u: f32, v: f32) -> Rgb<f32> {
+    let width = image.width();
+    let height = image.height();
+    let x = u * width as f32 - 0.5;
+    let y = (v * height as f32 - 0.5).max(0.).min((height - 1) as f32);
+    let x0 = x.floor();
+    let y0 = y.floor();
+    let tx = x - x0;
+    let ty = y - y0;
+
+    let texel = |x: f32, y: f32| {
+        let x = (x as i64).rem_euclid(width as i64) as u32;
+        let y = (y as u32).min(height - 1);
+        image.get_pixel(x, y).0
+    };
+
+    let a = texel(x0, y0);
+    let b = texel(x0 + 1., y0);
+    let c = texel(x0, y0 + 1.);
+    let d = texel(x0 + 1., y0 + 1.);
+    let mut color = [0.; 3];
+    for i in 0..3 {
+        let top = a[i] + (b[i] - a[i]) * tx;
+        let bottom = c[i] + (d[i] - c[i]) * tx;
+        color[i] = top + (bottom - top) * ty;
+    }
+
+    Rgb(color)
+}
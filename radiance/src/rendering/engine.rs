@@ -2,8 +2,32 @@ use super::ComponentFactory;
 use crate::{imgui::ImguiFrame, scene::Scene};
 use std::rc::Rc;
 
+/// Samples per pixel used to smooth the edges of the scene's geometry
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Msaa {
+    Off,
+    X2,
+    X4,
+    X8,
+}
+
+impl Msaa {
+    pub fn sample_count(&self) -> u32 {
+        match self {
+            Msaa::Off => 1,
+            Msaa::X2 => 2,
+            Msaa::X4 => 4,
+            Msaa::X8 => 8,
+        }
+    }
+}
+
 pub trait RenderingEngine {
     fn render(&mut self, scene: &mut dyn Scene, ui_frame: ImguiFrame);
     fn view_extent(&self) -> (u32, u32);
     fn component_factory(&self) -> Rc<dyn ComponentFactory>;
+
+    /// Lowered to the highest level the device supports, takes effect on the next frame
+    fn set_msaa(&mut self, msaa: Msaa);
+    fn msaa(&self) -> Msaa;
 }
@@ -0,0 +1,256 @@
+use super::cubemap::texel_direction;
+use super::{TextureDef, TextureSource};
+use image::{Rgba, RgbaImage};
+use std::rc::Rc;
+
+const IRRADIANCE_SIZE: u32 = 16;
+const PREFILTERED_SIZE: u32 = 64;
+const PREFILTERED_MIP_LEVELS: u32 = 5;
+
+// Specular convolution never reads faces larger than this, it keeps the CPU cost bounded
+const MAX_CONVOLUTION_SOURCE_SIZE: u32 = 32;
+
+/// Image based lighting prefiltered from an environment cubemap. Faces are in
+/// +X, -X, +Y, -Y, +Z, -Z order.
+pub struct EnvironmentMap {
+    irradiance: TextureDef,
+    prefiltered: TextureDef,
+    prefiltered_mip_levels: u32,
+}
+
+impl EnvironmentMap {
+    pub fn new(faces: &[RgbaImage; 6]) -> Self {
+        let source = LinearCube::from_faces(faces);
+
+        let irradiance_source = source.resized(IRRADIANCE_SIZE);
+        let irradiance = irradiance_source.convolved(IRRADIANCE_SIZE, |cos| cos);
+
+        let base_size = source.size.min(PREFILTERED_SIZE);
+        let mip_levels = PREFILTERED_MIP_LEVELS.min(32 - base_size.leading_zeros());
+        let prefiltered = (0..mip_levels)
+            .map(|level| {
+                let size = (base_size >> level).max(1);
+                if level == 0 {
+                    return source.resized(size).to_faces();
+                }
+
+                // glTF roughness is perceptual, the GGX alpha is its square
+                let roughness = level as f32 / (mip_levels - 1) as f32;
+                let alpha2 = roughness.powi(4);
+                let convolution_size = size.max(8).min(MAX_CONVOLUTION_SOURCE_SIZE);
+                source
+                    .resized(convolution_size)
+                    .convolved(size, |cos| {
+                        let d = cos * cos * (alpha2 - 1.) + 1.;
+                        cos * alpha2 / (d * d)
+                    })
+                    .to_faces()
+            })
+            .collect();
+
+        Self {
+            irradiance: TextureDef::new(TextureSource::Cubemap(vec![irradiance.to_faces()])),
+            prefiltered: TextureDef::new(TextureSource::Cubemap(prefiltered)),
+            prefiltered_mip_levels: mip_levels,
+        }
+    }
+
+    /// Environment with the same color in every direction
+    pub fn from_color(color: [u8; 4]) -> Self {
+        let face = RgbaImage::from_pixel(1, 1, Rgba(color));
+        Self::new(&[
+            face.clone(),
+            face.clone(),
+            face.clone(),
+            face.clone(),
+            face.clone(),
+            face,
+        ])
+    }
+
+    /// Cosine weighted average radiance, indexed by the surface normal
+    pub fn irradiance(&self) -> &TextureDef {
+        &self.irradiance
+    }
+
+    /// GGX prefiltered radiance, the roughness grows linearly with the mip level
+    pub fn prefiltered(&self) -> &TextureDef {
+        &self.prefiltered
+    }
+
+    pub fn prefiltered_mip_levels(&self) -> u32 {
+        self.prefiltered_mip_levels
+    }
+}
+
+/// Component lighting the scene with an environment map. Only the first one found in the
+/// scene is used.
+#[derive(Clone)]
+pub struct EnvironmentLight {
+    pub map: Rc<EnvironmentMap>,
+    pub intensity: f32,
+}
+
+impl EnvironmentLight {
+    pub fn new(map: Rc<EnvironmentMap>, intensity: f32) -> Self {
+        Self { map, intensity }
+    }
+}
+
+struct LinearCube {
+    size: u32,
+    faces: Vec<Vec<[f32; 3]>>,
+}
+
+impl LinearCube {
+    fn from_faces(faces: &[RgbaImage; 6]) -> Self {
+        let size = faces[0].width();
+        let faces = faces
+            .iter()
+            .map(|face| {
+                if face.width() != size || face.height() != size {
+                    panic!("Cubemap faces must be squares of the same size");
+                }
+
+                face.pixels()
+                    .map(|p| {
+                        [
+                            srgb_to_linear(p[0]),
+                            srgb_to_linear(p[1]),
+                            srgb_to_linear(p[2]),
+                        ]
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Self { size, faces }
+    }
+
+    fn to_faces(&self) -> [RgbaImage; 6] {
+        let face = |index: usize| {
+            let texels = &self.faces[index];
+            RgbaImage::from_fn(self.size, self.size, |x, y| {
+                let t = texels[(y * self.size + x) as usize];
+                Rgba([
+                    linear_to_srgb(t[0]),
+                    linear_to_srgb(t[1]),
+                    linear_to_srgb(t[2]),
+                    255,
+                ])
+            })
+        };
+
+        [face(0), face(1), face(2), face(3), face(4), face(5)]
+    }
+
+    // Box filter when shrinking, nearest neighbour when growing
+    fn resized(&self, size: u32) -> Self {
+        let faces = self
+            .faces
+            .iter()
+            .map(|texels| {
+                let mut resized = Vec::with_capacity((size * size) as usize);
+                for y in 0..size {
+                    for x in 0..size {
+                        let x0 = x * self.size / size;
+                        let x1 = ((x + 1) * self.size / size).max(x0 + 1);
+                        let y0 = y * self.size / size;
+                        let y1 = ((y + 1) * self.size / size).max(y0 + 1);
+                        let mut sum = [0.; 3];
+                        for sy in y0..y1 {
+                            for sx in x0..x1 {
+                                let t = texels[(sy * self.size + sx) as usize];
+                                sum = [sum[0] + t[0], sum[1] + t[1], sum[2] + t[2]];
+                            }
+                        }
+
+                        let count = ((x1 - x0) * (y1 - y0)) as f32;
+                        resized.push([sum[0] / count, sum[1] / count, sum[2] / count]);
+                    }
+                }
+
+                resized
+            })
+            .collect();
+
+        Self { size, faces }
+    }
+
+    // Brute force convolution over every texel, `weight` maps the cosine between the
+    // output direction and the texel direction to a lobe weight
+    fn convolved<F: Fn(f32) -> f32>(&self, size: u32, weight: F) -> Self {
+        let samples: Vec<([f32; 3], f32, [f32; 3])> = (0..6)
+            .flat_map(|face| {
+                (0..self.size * self.size).map(move |i| (face, i % self.size, i / self.size))
+            })
+            .map(|(face, x, y)| {
+                let (direction, solid_angle) = texel_direction(face, x, y, self.size);
+                let radiance = self.faces[face][(y * self.size + x) as usize];
+                (direction, solid_angle, radiance)
+            })
+            .collect();
+
+        let faces = (0..6)
+            .map(|face| {
+                (0..size * size)
+                    .map(|i| {
+                        let (normal, _) = texel_direction(face, i % size, i / size, size);
+                        let mut sum = [0.; 3];
+                        let mut total_weight = 0.;
+                        for (direction, solid_angle, radiance) in &samples {
+                            let cos = dot(&normal, direction);
+                            if cos <= 0. {
+                                continue;
+                            }
+
+                            let w = weight(cos) * solid_angle;
+                            sum = [
+                                sum[0] + radiance[0] * w,
+                                sum[1] + radiance[1] * w,
+                                sum[2] + radiance[2] * w,
+                            ];
+                            total_weight += w;
+                        }
+
+                        if total_weight > 0. {
+                            [
+                                sum[0] / total_weight,
+                                sum[1] / total_weight,
+                                sum[2] / total_weight,
+                            ]
+                        } else {
+                            [0.; 3]
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Self { size, faces }
+    }
+}
+
+fn dot(a: &[f32; 3], b: &[f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+// The exact sRGB transfer functions, so that the GPU decodes the faces back to these values
+fn srgb_to_linear(value: u8) -> f32 {
+    let value = value as f32 / 255.;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> u8 {
+    let value = value.max(0.).min(1.);
+    let encoded = if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1. / 2.4) - 0.055
+    };
+    (encoded * 255. + 0.5) as u8
+}
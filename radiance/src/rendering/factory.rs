@@ -1,10 +1,12 @@
 use super::{
-    texture::TextureDef, Material, MaterialDef, RenderObject, RenderingComponent, Shader,
-    ShaderDef, Texture, VertexBuffer,
+    texture::TextureDef, Material, MaterialDef, MorphTarget, RenderObject, RenderTarget,
+    RenderingComponent, Shader, ShaderDef, Texture, VertexBuffer,
 };
+use std::rc::Rc;
 
 pub trait ComponentFactory {
     fn create_texture(&self, texture_def: &TextureDef) -> Box<dyn Texture>;
+    fn create_render_target(&self, width: u32, height: u32) -> Rc<dyn RenderTarget>;
     fn create_shader(&self, shader_def: &ShaderDef) -> Box<dyn Shader>;
     fn create_material(&self, material_def: &MaterialDef) -> Box<dyn Material>;
     fn create_render_object(
@@ -14,6 +16,16 @@ pub trait ComponentFactory {
         material_def: &MaterialDef,
         host_dynamic: bool,
     ) -> Box<dyn RenderObject>;
+
+    /// Render object blending `targets` with the `MorphWeights` of its entity. The material's
+    /// shader must blend morph targets, such as `MORPH_SHADER_DEF`.
+    fn create_morph_render_object(
+        &self,
+        vertices: VertexBuffer,
+        indices: Vec<u32>,
+        material_def: &MaterialDef,
+        targets: &[MorphTarget],
+    ) -> Box<dyn RenderObject>;
     fn create_rendering_component(&self, objects: Vec<Box<dyn RenderObject>>)
         -> RenderingComponent;
 }
@@ -0,0 +1,91 @@
+use crate::math::Mat44;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Shared by every `Instances`, so that a replaced component is never mistaken for the one
+// already uploaded
+static NEXT_VERSION: AtomicU64 = AtomicU64::new(1);
+
+/// One copy of a mesh drawn by an instanced material
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+pub struct Instance {
+    /// Relative to the entity's transform
+    pub transform: Mat44,
+
+    /// Multiplies the color of the material
+    pub color: [f32; 4],
+}
+
+impl Instance {
+    pub fn new(transform: Mat44) -> Self {
+        Self {
+            transform,
+            color: [1., 1., 1., 1.],
+        }
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+impl Default for Instance {
+    fn default() -> Self {
+        Self::new(Mat44::new_identity())
+    }
+}
+
+/// Component drawing the entity's render objects once per instance in a single draw call,
+/// for foliage, crowds or particles. Only render objects whose material was made with
+/// `MaterialDef::with_instancing` use it, the others are drawn once. The instances are
+/// uploaded again only after they changed.
+#[derive(Clone, Debug)]
+pub struct Instances {
+    instances: Vec<Instance>,
+    version: u64,
+}
+
+impl Instances {
+    pub fn new(instances: Vec<Instance>) -> Self {
+        Self {
+            instances,
+            version: next_version(),
+        }
+    }
+
+    pub fn instances(&self) -> &[Instance] {
+        &self.instances
+    }
+
+    /// Marks the instances as changed
+    pub fn instances_mut(&mut self) -> &mut Vec<Instance> {
+        self.version = next_version();
+        &mut self.instances
+    }
+
+    pub fn set_instances(&mut self, instances: Vec<Instance>) {
+        *self.instances_mut() = instances;
+    }
+
+    pub fn push(&mut self, instance: Instance) {
+        self.instances_mut().push(instance);
+    }
+
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+
+    /// Changes every time the instances are modified
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+}
+
+fn next_version() -> u64 {
+    NEXT_VERSION.fetch_add(1, Ordering::Relaxed)
+}
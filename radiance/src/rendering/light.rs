@@ -0,0 +1,81 @@
+use crate::math::Vec3;
+
+/// Maximum number of lights uploaded per frame, the rest are ignored
+pub const MAX_LIGHTS: usize = 16;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LightType {
+    /// Uniform light added to every surface
+    Ambient,
+
+    /// Infinitely far light shining along the entity's forward (-Z) axis
+    Directional,
+
+    /// Light emitted in all directions from the entity's position
+    Point,
+
+    /// Cone of light along the entity's forward (-Z) axis. Angles are half angles in radian.
+    Spot { inner_angle: f32, outer_angle: f32 },
+}
+
+/// Light component for entities. The position and direction come from the entity's world
+/// transform.
+#[derive(Clone, Debug)]
+pub struct LightComponent {
+    pub light_type: LightType,
+    pub color: Vec3,
+    pub intensity: f32,
+
+    /// Distance at which point and spot lights fade out completely
+    pub range: f32,
+
+    /// Directional and spot lights only
+    pub cast_shadows: bool,
+}
+
+impl LightComponent {
+    pub fn ambient(color: Vec3, intensity: f32) -> Self {
+        Self::new(LightType::Ambient, color, intensity, 0.)
+    }
+
+    pub fn directional(color: Vec3, intensity: f32) -> Self {
+        Self::new(LightType::Directional, color, intensity, 0.)
+    }
+
+    pub fn point(color: Vec3, intensity: f32, range: f32) -> Self {
+        Self::new(LightType::Point, color, intensity, range)
+    }
+
+    pub fn spot(
+        color: Vec3,
+        intensity: f32,
+        range: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+    ) -> Self {
+        Self::new(
+            LightType::Spot {
+                inner_angle,
+                outer_angle,
+            },
+            color,
+            intensity,
+            range,
+        )
+    }
+
+    pub fn with_shadows(mut self) -> Self {
+        self.cast_shadows = true;
+        self
+    }
+
+    fn new(light_type: LightType, color: Vec3, intensity: f32, range: f32) -> Self {
+        Self {
+            light_type,
+            color,
+            intensity,
+            range,
+            cast_shadows: false,
+        }
+    }
+}
@@ -1,8 +1,15 @@
-use image::ImageFormat;
+use image::{ImageFormat, Rgba, RgbaImage};
 
-use super::{texture::TextureDef, ShaderDef, SIMPLE_SHADER_DEF};
+use super::{
+    texture::{ColorSpace, TextureDef, TextureSource},
+    ShaderDef, LIT_SHADER_DEF, PBR_MORPH_SHADER_DEF, PBR_SHADER_DEF, SIMPLE_SHADER_DEF,
+};
+use crate::math::Vec3;
 use std::io::Read;
 
+/// Maximum number of floats in `MaterialDef::parameters`
+pub const MAX_MATERIAL_PARAMETERS: usize = 28;
+
 pub trait Material: downcast_rs::Downcast + std::fmt::Debug {}
 
 downcast_rs::impl_downcast!(Material);
@@ -12,6 +19,7 @@ pub struct MaterialDef {
     shader: ShaderDef,
     textures: Vec<TextureDef>,
     use_alpha: bool,
+    parameters: Vec<f32>,
 }
 
 impl MaterialDef {
@@ -21,9 +29,40 @@ impl MaterialDef {
             textures,
             shader,
             use_alpha,
+            parameters: vec![],
         }
     }
 
+    /// Sets the constants passed to the fragment shader, they are pushed right after the
+    /// vertex push constants at offset 16. Materials sharing a name must use the same
+    /// number of parameters.
+    pub fn with_parameters(mut self, parameters: Vec<f32>) -> Self {
+        if parameters.len() > MAX_MATERIAL_PARAMETERS {
+            panic!("Too many material parameters: {}", parameters.len());
+        }
+
+        self.parameters = parameters;
+        self
+    }
+
+    /// Switches to the instanced version of the shader, so that render objects using the
+    /// material draw every `Instances` of their entity at once. Only the built-in shaders have
+    /// one, the others are left unchanged.
+    pub fn with_instancing(mut self) -> Self {
+        match self.shader.instanced_variant() {
+            Some(shader) => {
+                if !self.shader.is_instanced() {
+                    self.name.push_str("_instanced");
+                }
+
+                self.shader = shader;
+            }
+            None => log::warn!("Shader {} has no instanced variant", self.shader.name()),
+        }
+
+        self
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
@@ -39,23 +78,161 @@ impl MaterialDef {
     pub fn use_alpha(&self) -> bool {
         self.use_alpha
     }
+
+    pub fn parameters(&self) -> &[f32] {
+        &self.parameters
+    }
 }
 
 pub struct SimpleMaterialDef;
 impl SimpleMaterialDef {
     pub fn create<R: Read>(reader: &mut R, use_alpha: bool) -> MaterialDef {
-        let mut buf = Vec::new();
-        reader.read_to_end(&mut buf).unwrap();
-        let data = image::load_from_memory(&buf)
-            .or_else(|_| image::load_from_memory_with_format(&buf, ImageFormat::Tga))
-            .and_then(|img| Ok(img.to_rgba8()))
-            .ok();
-
         MaterialDef::new(
             "simple_material",
             SIMPLE_SHADER_DEF.clone(),
-            vec![TextureDef::ImageTextureDef(data)],
+            vec![TextureDef::new(TextureSource::Image(load_image(reader)))],
             use_alpha,
         )
     }
 }
+
+/// Blinn-Phong material lit by the scene's `LightComponent`s. Needs vertex normals.
+pub struct LitMaterialDef;
+impl LitMaterialDef {
+    pub fn create<R: Read>(reader: &mut R, use_alpha: bool) -> MaterialDef {
+        Self::create_with_image(
+            load_image(reader),
+            &Vec3::new(0.5, 0.5, 0.5),
+            32.,
+            use_alpha,
+        )
+    }
+
+    pub fn create_with_image(
+        image: Option<RgbaImage>,
+        specular: &Vec3,
+        shininess: f32,
+        use_alpha: bool,
+    ) -> MaterialDef {
+        MaterialDef::new(
+            "lit_material",
+            LIT_SHADER_DEF.clone(),
+            vec![TextureDef::new(TextureSource::Image(image))],
+            use_alpha,
+        )
+        .with_parameters(vec![specular.x, specular.y, specular.z, shininess])
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AlphaMode {
+    Opaque,
+
+    /// Fragments with an alpha below the cutoff are discarded
+    Mask(f32),
+    Blend,
+}
+
+/// glTF metallic-roughness material. Missing maps default to white, or to a flat normal for
+/// the normal map, so the factors are used as is.
+pub struct PbrMaterialDef {
+    pub base_color_factor: [f32; 4],
+    pub base_color_texture: Option<RgbaImage>,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+
+    /// Metalness in the blue channel and roughness in the green channel
+    pub metallic_roughness_texture: Option<RgbaImage>,
+    pub normal_scale: f32,
+    pub normal_texture: Option<RgbaImage>,
+    pub occlusion_strength: f32,
+    pub occlusion_texture: Option<RgbaImage>,
+    pub emissive_factor: [f32; 3],
+    pub emissive_texture: Option<RgbaImage>,
+    pub alpha_mode: AlphaMode,
+}
+
+impl PbrMaterialDef {
+    pub fn create(self) -> MaterialDef {
+        self.create_with_shader("pbr_material", PBR_SHADER_DEF.clone())
+    }
+
+    /// For meshes blending morph targets, see `ComponentFactory::create_morph_render_object`
+    pub fn create_morph(self) -> MaterialDef {
+        self.create_with_shader("pbr_morph_material", PBR_MORPH_SHADER_DEF.clone())
+    }
+
+    fn create_with_shader(self, name: &str, shader: ShaderDef) -> MaterialDef {
+        let white = || RgbaImage::from_pixel(1, 1, Rgba([255, 255, 255, 255]));
+        let flat_normal = || RgbaImage::from_pixel(1, 1, Rgba([128, 128, 255, 255]));
+        let alpha_cutoff = match self.alpha_mode {
+            AlphaMode::Mask(cutoff) => cutoff,
+            _ => 0.,
+        };
+
+        let mut parameters = self.base_color_factor.to_vec();
+        parameters.extend_from_slice(&self.emissive_factor);
+        parameters.push(self.normal_scale);
+        parameters.extend_from_slice(&[
+            self.metallic_factor,
+            self.roughness_factor,
+            self.occlusion_strength,
+            alpha_cutoff,
+        ]);
+
+        MaterialDef::new(
+            name,
+            shader,
+            vec![
+                TextureDef::new(TextureSource::Image(Some(
+                    self.base_color_texture.unwrap_or_else(white),
+                ))),
+                TextureDef::new(TextureSource::Image(Some(
+                    self.normal_texture.unwrap_or_else(flat_normal),
+                )))
+                .with_color_space(ColorSpace::Linear),
+                TextureDef::new(TextureSource::Image(Some(
+                    self.metallic_roughness_texture.unwrap_or_else(white),
+                )))
+                .with_color_space(ColorSpace::Linear),
+                TextureDef::new(TextureSource::Image(Some(
+                    self.occlusion_texture.unwrap_or_else(white),
+                )))
+                .with_color_space(ColorSpace::Linear),
+                TextureDef::new(TextureSource::Image(Some(
+                    self.emissive_texture.unwrap_or_else(white),
+                ))),
+            ],
+            self.alpha_mode == AlphaMode::Blend,
+        )
+        .with_parameters(parameters)
+    }
+}
+
+impl Default for PbrMaterialDef {
+    fn default() -> Self {
+        Self {
+            base_color_factor: [1., 1., 1., 1.],
+            base_color_texture: None,
+            metallic_factor: 1.,
+            roughness_factor: 1.,
+            metallic_roughness_texture: None,
+            normal_scale: 1.,
+            normal_texture: None,
+            occlusion_strength: 1.,
+            occlusion_texture: None,
+            emissive_factor: [0., 0., 0.],
+            emissive_texture: None,
+            alpha_mode: AlphaMode::Opaque,
+        }
+    }
+}
+
+fn load_image<R: Read>(reader: &mut R) -> Option<RgbaImage> {
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf).unwrap();
+    image::load_from_memory(&buf)
+        .or_else(|_| image::load_from_memory_with_format(&buf, ImageFormat::Tga))
+        .and_then(|img| Ok(img.to_rgba8()))
+        .ok()
+}
@@ -1,21 +1,59 @@
+mod atlas;
+mod compressed_image;
+mod cubemap;
 mod engine;
+mod environment;
 mod factory;
+mod instancing;
+mod light;
 mod material;
+mod morph;
 mod platform;
+mod post_processing;
 mod render_object;
+mod render_target;
 mod rendering_component;
 mod shader;
+mod shadow;
+mod skybox;
+mod sprite;
+mod text;
 mod texture;
 mod vertex_buffer;
 mod vulkan;
 
-pub use engine::RenderingEngine;
+pub use atlas::{AtlasPacker, AtlasPackerError, AtlasRegion, TextureAtlas};
+pub use compressed_image::{max_mip_levels, CompressedFormat, CompressedImage};
+pub use engine::{Msaa, RenderingEngine};
+pub use environment::{EnvironmentLight, EnvironmentMap};
 pub use factory::ComponentFactory;
-pub use material::{Material, MaterialDef, SimpleMaterialDef};
+pub use instancing::{Instance, Instances};
+pub use light::{LightComponent, LightType, MAX_LIGHTS};
+pub use material::{
+    AlphaMode, LitMaterialDef, Material, MaterialDef, PbrMaterialDef, SimpleMaterialDef,
+    MAX_MATERIAL_PARAMETERS,
+};
+pub use morph::{morph_weight_property, MorphTarget, MorphWeights};
 pub use platform::Window;
+pub use post_processing::{
+    BloomSettings, ColorGradingLut, PostProcessing, ToneMapping, VignetteSettings,
+};
 pub use render_object::RenderObject;
+pub use render_target::{RenderTarget, MAX_CAMERAS_PER_TARGET};
 pub use rendering_component::RenderingComponent;
-pub use shader::{Shader, ShaderDef, SIMPLE_SHADER_DEF};
-pub use texture::{Texture, TextureDef};
-pub use vertex_buffer::{VertexBuffer, VertexComponents};
+pub use shader::{
+    Shader, ShaderDef, INSTANCED_LIT_SHADER_DEF, INSTANCED_PBR_SHADER_DEF, INSTANCED_SHADER_DEF,
+    LIT_SHADER_DEF, MORPH_SHADER_DEF, PBR_MORPH_SHADER_DEF, PBR_SHADER_DEF, SIMPLE_SHADER_DEF,
+    SKINNED_SHADER_DEF,
+};
+pub use shadow::{MAX_SHADOW_MAPS, SHADOW_CASCADE_COUNT, SHADOW_MAP_SIZE};
+pub use skybox::Skybox;
+pub use sprite::Sprite;
+pub use text::{Font, FontError, GlyphQuad, Text, TextAlign, TextSpace};
+pub use texture::{
+    AddressMode, ColorSpace, FilterMode, HdrImage, SamplerDef, Texture, TextureDef, TextureSource,
+};
+pub use vertex_buffer::{
+    VertexAttribute, VertexBuffer, VertexComponents, VertexFormat, VertexLayout, VertexSemantic,
+};
 pub use vulkan::VulkanRenderingEngine;
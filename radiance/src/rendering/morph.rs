@@ -0,0 +1,51 @@
+use crate::math::Vec3;
+
+/// Per-vertex offsets from the base mesh, scaled by the target's weight when rendering
+#[derive(Clone, Debug)]
+pub struct MorphTarget {
+    pub name: String,
+    pub position_deltas: Vec<Vec3>,
+    pub normal_deltas: Option<Vec<Vec3>>,
+}
+
+/// Returns the name of the animated property driving the n-th morph target weight
+pub fn morph_weight_property(index: usize) -> String {
+    format!("morph_weight_{}", index)
+}
+
+/// Component holding the morph target weights applied to all the render objects of the
+/// entity. Render objects ignore the weights past their own targets.
+#[derive(Clone, Debug, Default)]
+pub struct MorphWeights {
+    weights: Vec<f32>,
+}
+
+impl MorphWeights {
+    pub fn new(weights: &[f32]) -> Self {
+        Self {
+            weights: weights.to_vec(),
+        }
+    }
+
+    pub fn weights(&self) -> &[f32] {
+        &self.weights
+    }
+
+    pub fn weight(&self, index: usize) -> f32 {
+        self.weights.get(index).copied().unwrap_or(0.)
+    }
+
+    /// Targets before `index` without a weight yet get 0
+    pub fn set_weight(&mut self, index: usize, weight: f32) {
+        if index >= self.weights.len() {
+            self.weights.resize(index + 1, 0.);
+        }
+
+        self.weights[index] = weight;
+    }
+
+    pub fn set_weights(&mut self, weights: &[f32]) {
+        self.weights.clear();
+        self.weights.extend_from_slice(weights);
+    }
+}
@@ -0,0 +1,107 @@
+use super::{AddressMode, ColorSpace, SamplerDef, TextureDef, TextureSource};
+use image::RgbaImage;
+use std::rc::Rc;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ToneMapping {
+    /// Clamps to the displayable range
+    None,
+    Reinhard,
+
+    /// Filmic curve fitted to the ACES reference transform
+    Aces,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct BloomSettings {
+    /// Brightness above which pixels start to bleed
+    pub threshold: f32,
+    pub intensity: f32,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct VignetteSettings {
+    /// Darkening in the corners, from 0 to 1
+    pub intensity: f32,
+
+    /// Width of the falloff, from 0 to 1
+    pub smoothness: f32,
+}
+
+/// 3D color lookup table applied after tone mapping and gamma correction. The image is a strip
+/// of `size` slices of `size` x `size` texels, blue selecting the slice, red growing to the
+/// right and green growing downwards.
+pub struct ColorGradingLut {
+    size: u32,
+    texture: TextureDef,
+}
+
+impl ColorGradingLut {
+    pub fn new(image: RgbaImage) -> Self {
+        let size = image.height();
+        if size < 2 || image.width() != size * size {
+            panic!("Color grading LUTs must be strips of square slices, one slice per row");
+        }
+
+        Self {
+            size,
+            // Mip levels would blend neighbouring slices together. The graded colors are looked
+            // up and stored gamma encoded, so the texels must not be decoded.
+            texture: TextureDef::new(TextureSource::Image(Some(image)))
+                .with_sampler(SamplerDef {
+                    max_anisotropy: None,
+                    max_lod: Some(0.),
+                    ..SamplerDef::default().with_address_mode(AddressMode::ClampToEdge)
+                })
+                .with_color_space(ColorSpace::Linear),
+        }
+    }
+
+    /// LUT leaving the colors unchanged, a starting point for grading in an image editor
+    pub fn identity(size: u32) -> Self {
+        let scale = 255. / (size - 1) as f32;
+        Self::new(RgbaImage::from_fn(size * size, size, |x, y| {
+            image::Rgba([
+                ((x % size) as f32 * scale).round() as u8,
+                (y as f32 * scale).round() as u8,
+                ((x / size) as f32 * scale).round() as u8,
+                255,
+            ])
+        }))
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn texture(&self) -> &TextureDef {
+        &self.texture
+    }
+}
+
+/// Component configuring the effects applied to the scene after it's rendered in HDR. Only
+/// the first one found in the scene is used, and the defaults apply when there is none.
+#[derive(Clone)]
+pub struct PostProcessing {
+    pub exposure: f32,
+    pub tone_mapping: ToneMapping,
+    pub gamma: f32,
+    pub bloom: Option<BloomSettings>,
+    pub fxaa: bool,
+    pub color_grading: Option<Rc<ColorGradingLut>>,
+    pub vignette: Option<VignetteSettings>,
+}
+
+impl Default for PostProcessing {
+    fn default() -> Self {
+        Self {
+            exposure: 1.,
+            tone_mapping: ToneMapping::None,
+            gamma: 2.2,
+            bloom: None,
+            fxaa: false,
+            color_grading: None,
+            vignette: None,
+        }
+    }
+}
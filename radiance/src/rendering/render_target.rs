@@ -0,0 +1,13 @@
+/// Cameras drawn into the same target each frame, the screen included. Cameras beyond it are
+/// skipped.
+pub const MAX_CAMERAS_PER_TARGET: usize = 8;
+
+/// Offscreen image a `Camera` renders into, which materials then sample through
+/// `TextureSource::RenderTarget`. It holds the image after post-processing, without
+/// the UI.
+pub trait RenderTarget: downcast_rs::Downcast {
+    fn width(&self) -> u32;
+    fn height(&self) -> u32;
+}
+
+downcast_rs::impl_downcast!(RenderTarget);
@@ -2,11 +2,26 @@ use super::RenderObject;
 
 pub struct RenderingComponent {
     objects: Vec<Box<dyn RenderObject>>,
+    layer: u32,
 }
 
 impl RenderingComponent {
     pub fn new() -> Self {
-        RenderingComponent { objects: vec![] }
+        RenderingComponent {
+            objects: vec![],
+            layer: 0,
+        }
+    }
+
+    /// Index of the layer the objects belong to, from 0 to 31. Cameras only draw the layers in
+    /// their layer mask.
+    pub fn layer(&self) -> u32 {
+        self.layer
+    }
+
+    /// Layers past 31 are clamped to 31
+    pub fn set_layer(&mut self, layer: u32) {
+        self.layer = clamp_layer(layer);
     }
 
     pub fn push_render_object(&mut self, object: Box<dyn RenderObject>) {
@@ -21,3 +36,15 @@ impl RenderingComponent {
         &mut self.objects
     }
 }
+
+/// Rule shared by everything drawn on a layer, so that `1 << layer` is always a valid mask bit
+pub(crate) fn clamp_layer(layer: u32) -> u32 {
+    if layer > 31 {
+        log::warn!(
+            "Rendering layers range from 0 to 31, {} is clamped to 31",
+            layer
+        );
+    }
+
+    layer.min(31)
+}
@@ -1,4 +1,4 @@
-use super::VertexComponents;
+use super::{VertexComponents, VertexLayout};
 
 pub trait Shader: downcast_rs::Downcast {
     fn name(&self) -> &str;
@@ -9,15 +9,32 @@ downcast_rs::impl_downcast!(Shader);
 #[derive(Clone)]
 pub struct ShaderDef {
     name: String,
-    vertex_components: VertexComponents,
+    vertex_layout: VertexLayout,
     vert_src: Vec<u8>,
     frag_src: Vec<u8>,
+    morph: bool,
+    instanced: bool,
 }
 
 static SIMPLE_TRIANGLE_VERT: &'static [u8] =
     include_bytes!(concat!(env!("OUT_DIR"), "/simple_triangle.vert.spv"));
 static SIMPLE_TRIANGLE_FRAG: &'static [u8] =
     include_bytes!(concat!(env!("OUT_DIR"), "/simple_triangle.frag.spv"));
+static SKINNED_VERT: &'static [u8] = include_bytes!(concat!(env!("OUT_DIR"), "/skinned.vert.spv"));
+static LIT_VERT: &'static [u8] = include_bytes!(concat!(env!("OUT_DIR"), "/lit.vert.spv"));
+static LIT_FRAG: &'static [u8] = include_bytes!(concat!(env!("OUT_DIR"), "/lit.frag.spv"));
+static PBR_FRAG: &'static [u8] = include_bytes!(concat!(env!("OUT_DIR"), "/pbr.frag.spv"));
+static MORPH_VERT: &'static [u8] = include_bytes!(concat!(env!("OUT_DIR"), "/morph.vert.spv"));
+static INSTANCED_VERT: &'static [u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/instanced.vert.spv"));
+static INSTANCED_FRAG: &'static [u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/instanced.frag.spv"));
+static INSTANCED_LIT_VERT: &'static [u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/instanced_lit.vert.spv"));
+static INSTANCED_LIT_FRAG: &'static [u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/instanced_lit.frag.spv"));
+static INSTANCED_PBR_FRAG: &'static [u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/instanced_pbr.frag.spv"));
 
 lazy_static! {
     pub static ref SIMPLE_SHADER_DEF: ShaderDef = ShaderDef::new(
@@ -26,29 +43,126 @@ lazy_static! {
         SIMPLE_TRIANGLE_VERT,
         SIMPLE_TRIANGLE_FRAG,
     );
+    pub static ref SKINNED_SHADER_DEF: ShaderDef = ShaderDef::new(
+        "skinned",
+        VertexComponents::POSITION
+            | VertexComponents::TEXCOORD
+            | VertexComponents::BONE_INDICES
+            | VertexComponents::BONE_WEIGHTS,
+        SKINNED_VERT,
+        SIMPLE_TRIANGLE_FRAG,
+    );
+    pub static ref LIT_SHADER_DEF: ShaderDef = ShaderDef::new(
+        "lit",
+        VertexComponents::POSITION | VertexComponents::NORMAL | VertexComponents::TEXCOORD,
+        LIT_VERT,
+        LIT_FRAG,
+    );
+    pub static ref PBR_SHADER_DEF: ShaderDef = ShaderDef::new(
+        "pbr",
+        VertexComponents::POSITION | VertexComponents::NORMAL | VertexComponents::TEXCOORD,
+        LIT_VERT,
+        PBR_FRAG,
+    );
+    pub static ref MORPH_SHADER_DEF: ShaderDef = ShaderDef::new(
+        "morph",
+        VertexComponents::POSITION | VertexComponents::NORMAL | VertexComponents::TEXCOORD,
+        MORPH_VERT,
+        SIMPLE_TRIANGLE_FRAG,
+    )
+    .with_morph_targets();
+    pub static ref PBR_MORPH_SHADER_DEF: ShaderDef = ShaderDef::new(
+        "pbr_morph",
+        VertexComponents::POSITION | VertexComponents::NORMAL | VertexComponents::TEXCOORD,
+        MORPH_VERT,
+        PBR_FRAG,
+    )
+    .with_morph_targets();
+    pub static ref INSTANCED_SHADER_DEF: ShaderDef = ShaderDef::new(
+        "instanced",
+        VertexComponents::POSITION | VertexComponents::TEXCOORD,
+        INSTANCED_VERT,
+        INSTANCED_FRAG,
+    )
+    .with_instancing();
+    pub static ref INSTANCED_LIT_SHADER_DEF: ShaderDef = ShaderDef::new(
+        "instanced_lit",
+        VertexComponents::POSITION | VertexComponents::NORMAL | VertexComponents::TEXCOORD,
+        INSTANCED_LIT_VERT,
+        INSTANCED_LIT_FRAG,
+    )
+    .with_instancing();
+    pub static ref INSTANCED_PBR_SHADER_DEF: ShaderDef = ShaderDef::new(
+        "instanced_pbr",
+        VertexComponents::POSITION | VertexComponents::NORMAL | VertexComponents::TEXCOORD,
+        INSTANCED_LIT_VERT,
+        INSTANCED_PBR_FRAG,
+    )
+    .with_instancing();
 }
 
 impl ShaderDef {
-    pub fn new(
+    pub fn new<L: Into<VertexLayout>>(
         name: &str,
-        vertex_components: VertexComponents,
+        vertex_layout: L,
         vert_src: &[u8],
         frag_src: &[u8],
     ) -> Self {
         Self {
             name: name.to_string(),
-            vertex_components,
+            vertex_layout: vertex_layout.into(),
             vert_src: Vec::from(vert_src),
             frag_src: Vec::from(frag_src),
+            morph: false,
+            instanced: false,
         }
     }
 
+    /// Reads an `Instance` per drawn copy from a second vertex binding, its transform at
+    /// locations 8 to 11 and its color at location 12
+    pub fn with_instancing(mut self) -> Self {
+        self.instanced = true;
+        self
+    }
+
+    pub fn is_instanced(&self) -> bool {
+        self.instanced
+    }
+
+    /// The instanced version of a built-in shader
+    pub fn instanced_variant(&self) -> Option<ShaderDef> {
+        if self.instanced {
+            return Some(self.clone());
+        }
+
+        match self.name.as_str() {
+            "simple_triangle" => Some(INSTANCED_SHADER_DEF.clone()),
+            "lit" => Some(INSTANCED_LIT_SHADER_DEF.clone()),
+            "pbr" => Some(INSTANCED_PBR_SHADER_DEF.clone()),
+            _ => None,
+        }
+    }
+
+    /// The vertex shader blends the morph targets of the render object, see `morph.vert`
+    pub fn with_morph_targets(mut self) -> Self {
+        self.morph = true;
+        self
+    }
+
+    pub fn is_morph(&self) -> bool {
+        self.morph
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
 
     pub fn vertex_components(&self) -> VertexComponents {
-        self.vertex_components
+        self.vertex_layout.components()
+    }
+
+    pub fn vertex_layout(&self) -> &VertexLayout {
+        &self.vertex_layout
     }
 
     pub fn vert_src(&self) -> &[u8] {
@@ -0,0 +1,227 @@
+use super::{LightComponent, LightType};
+use crate::math::{Mat44, Vec3};
+use crate::scene::Camera;
+
+/// Number of cascades rendered for the first shadow casting directional light
+pub const SHADOW_CASCADE_COUNT: usize = 4;
+
+/// Size of the shadow map array, shared by the cascades and the spot lights
+pub const MAX_SHADOW_MAPS: usize = 8;
+
+/// Width and height of each shadow map
+pub const SHADOW_MAP_SIZE: u32 = 2048;
+
+// Cascades cover the view frustum up to this distance
+const SHADOW_DISTANCE: f32 = 100.;
+
+// Blend between uniform (0) and logarithmic (1) cascade splits
+const CASCADE_SPLIT_LAMBDA: f32 = 0.75;
+
+// Casters behind the cascade bounds still need to be rendered into it
+const CASTER_DISTANCE: f32 = 100.;
+
+pub struct ShadowView {
+    pub view: Mat44,
+    pub projection: Mat44,
+}
+
+/// Shadow maps to render this frame
+pub struct ShadowSetup {
+    views: Vec<ShadowView>,
+
+    // First shadow map and shadow map count for each input light
+    light_maps: Vec<Option<(usize, usize)>>,
+    cascade_splits: [f32; SHADOW_CASCADE_COUNT],
+}
+
+impl ShadowSetup {
+    pub fn new(camera: &Camera, lights: &[(&LightComponent, &Mat44)]) -> Self {
+        let mut setup = Self {
+            views: vec![],
+            light_maps: vec![None; lights.len()],
+            cascade_splits: Self::compute_cascade_splits(camera),
+        };
+
+        let mut has_cascades = false;
+        for (i, (light, world)) in lights.iter().enumerate() {
+            if !light.cast_shadows {
+                continue;
+            }
+
+            let views = match light.light_type {
+                LightType::Directional if !has_cascades => {
+                    has_cascades = true;
+                    setup.cascade_views(camera, world)
+                }
+                LightType::Spot { outer_angle, .. } => {
+                    vec![Self::spot_view(world, outer_angle, light.range)]
+                }
+                _ => continue,
+            };
+
+            if setup.views.len() + views.len() > MAX_SHADOW_MAPS {
+                log::warn!("Too many shadow casting lights, the rest are ignored");
+                break;
+            }
+
+            setup.light_maps[i] = Some((setup.views.len(), views.len()));
+            setup.views.extend(views);
+        }
+
+        setup
+    }
+
+    pub fn views(&self) -> &[ShadowView] {
+        &self.views
+    }
+
+    pub fn light_maps(&self, light_index: usize) -> Option<(usize, usize)> {
+        self.light_maps.get(light_index).copied().flatten()
+    }
+
+    /// Far distance of each cascade along the camera's forward axis
+    pub fn cascade_splits(&self) -> &[f32; SHADOW_CASCADE_COUNT] {
+        &self.cascade_splits
+    }
+
+    fn compute_cascade_splits(camera: &Camera) -> [f32; SHADOW_CASCADE_COUNT] {
+        let near = camera.near_clip();
+        let far = camera.far_clip().min(SHADOW_DISTANCE);
+        let mut splits = [0.; SHADOW_CASCADE_COUNT];
+        for (i, split) in splits.iter_mut().enumerate() {
+            let p = (i + 1) as f32 / SHADOW_CASCADE_COUNT as f32;
+            let log = near * (far / near).powf(p);
+            let uniform = near + (far - near) * p;
+            *split = CASCADE_SPLIT_LAMBDA * log + (1. - CASCADE_SPLIT_LAMBDA) * uniform;
+        }
+
+        splits
+    }
+
+    // Fits an orthographic projection around the bounding sphere of each frustum slice, the
+    // sphere keeps the projection size stable while the camera rotates
+    fn cascade_views(&self, camera: &Camera, light_world: &Mat44) -> Vec<ShadowView> {
+        let view = Mat44::inversed(&without_translation(light_world));
+        let camera_world = camera.transform().matrix();
+
+        let mut near = camera.near_clip();
+        self.cascade_splits
+            .iter()
+            .map(|&far| {
+                let corners: Vec<Vec3> = [near, far]
+                    .iter()
+                    .flat_map(|&d| {
+                        [(-1., -1.), (1., -1.), (-1., 1.), (1., 1.)]
+                            .iter()
+                            .map(move |(x, y)| camera.view_point(*x, *y, d))
+                            .collect::<Vec<Vec3>>()
+                    })
+                    .map(|corner| view.transform_point(&camera_world.transform_point(&corner)))
+                    .collect();
+                near = far;
+
+                let mut center = Vec3::new_zeros();
+                for corner in &corners {
+                    center = Vec3::add(&center, corner);
+                }
+                center = Vec3::dot(1. / corners.len() as f32, &center);
+                let radius = corners
+                    .iter()
+                    .map(|c| Vec3::sub(c, &center).norm())
+                    .fold(0., f32::max);
+
+                // Snap to whole texels so that shadow edges don't shimmer
+                let texel = 2. * radius / SHADOW_MAP_SIZE as f32;
+                center.x = (center.x / texel).floor() * texel;
+                center.y = (center.y / texel).floor() * texel;
+
+                ShadowView {
+                    view,
+                    projection: orthographic(
+                        center.x - radius,
+                        center.x + radius,
+                        center.y - radius,
+                        center.y + radius,
+                        -center.z - radius - CASTER_DISTANCE,
+                        -center.z + radius,
+                    ),
+                }
+            })
+            .collect()
+    }
+
+    fn spot_view(light_world: &Mat44, outer_angle: f32, range: f32) -> ShadowView {
+        let far = if range > 0. { range } else { SHADOW_DISTANCE };
+        let fov = (2. * outer_angle).min(std::f32::consts::PI * 0.95);
+        ShadowView {
+            view: Mat44::inversed(light_world),
+            projection: perspective(fov, far * 0.001, far),
+        }
+    }
+}
+
+fn without_translation(mat: &Mat44) -> Mat44 {
+    let mut mat = *mat;
+    mat[0][3] = 0.;
+    mat[1][3] = 0.;
+    mat[2][3] = 0.;
+    mat
+}
+
+// Same depth convention as the camera projection, looking down -Z
+fn orthographic(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Mat44 {
+    let mut mat = Mat44::new_zero();
+    mat[0][0] = 2. / (right - left);
+    mat[0][3] = -(right + left) / (right - left);
+    mat[1][1] = 2. / (top - bottom);
+    mat[1][3] = -(top + bottom) / (top - bottom);
+    mat[2][2] = -2. / (far - near);
+    mat[2][3] = -(far + near) / (far - near);
+    mat[3][3] = 1.;
+
+    mat
+}
+
+fn perspective(fov: f32, near: f32, far: f32) -> Mat44 {
+    let mut mat = Mat44::new_zero();
+    let fti = 1. / (fov / 2.).tan();
+    mat[0][0] = fti;
+    mat[1][1] = fti;
+    mat[2][2] = -(far + near) / (far - near);
+    mat[2][3] = -2. * near * far / (far - near);
+    mat[3][2] = -1.;
+
+    mat
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn splits(near: f32, far: f32) -> [f32; SHADOW_CASCADE_COUNT] {
+        ShadowSetup::compute_cascade_splits(&Camera::new_with_params(1., 1., near, far))
+    }
+
+    #[test]
+    fn splits_blend_logarithmic_and_uniform() {
+        // Logarithmic splits of 2, 4, 8, 16 and uniform ones of 4.75, 8.5, 12.25, 16
+        let expected = [2.6875, 5.125, 9.0625, 16.];
+        for (split, expected) in splits(1., 16.).iter().zip(&expected) {
+            assert!((split - expected).abs() < 1e-4, "{} != {}", split, expected);
+        }
+    }
+
+    #[test]
+    fn splits_stop_at_shadow_distance() {
+        let splits = splits(0.1, 100000.);
+        assert!((splits[SHADOW_CASCADE_COUNT - 1] - SHADOW_DISTANCE).abs() < 1e-3);
+        assert!(splits[0] > 0.1);
+        assert!(splits.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn splits_stop_at_far_clip_when_closer() {
+        let splits = splits(0.1, 50.);
+        assert!((splits[SHADOW_CASCADE_COUNT - 1] - 50.).abs() < 1e-3);
+    }
+}
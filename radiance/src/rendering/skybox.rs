@@ -0,0 +1,22 @@
+use super::TextureDef;
+use std::rc::Rc;
+
+/// Component drawing a cubemap behind the scene, oriented by the camera rotation, when the
+/// camera background is `Background::Skybox`. Only the first one found in the scene is used.
+#[derive(Clone)]
+pub struct Skybox {
+    /// `TextureSource::Cubemap` or `TextureSource::HdrCubemap`
+    pub cubemap: Rc<TextureDef>,
+
+    /// Multiplies the cubemap colors, before exposure
+    pub intensity: f32,
+}
+
+impl Skybox {
+    pub fn new(cubemap: Rc<TextureDef>) -> Self {
+        Self {
+            cubemap,
+            intensity: 1.,
+        }
+    }
+}
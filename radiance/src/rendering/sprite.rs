@@ -0,0 +1,114 @@
+use super::rendering_component::clamp_layer;
+use super::TextureDef;
+use crate::math::{Mat44, Vec2, Vec3};
+use std::rc::Rc;
+
+/// Textured quad component facing the entity's +Z axis. Sprites sharing an atlas are drawn
+/// together from a single vertex buffer, after the objects of the scene and without writing
+/// the depth, blended in `z_order`.
+#[derive(Clone)]
+pub struct Sprite {
+    /// Texture the region is cut from, usually shared by many sprites
+    pub atlas: Rc<TextureDef>,
+
+    /// Left, top, width and height of the region in texture coordinates
+    pub region: [f32; 4],
+
+    /// Size of the quad in world units, before the entity's scale
+    pub size: Vec2,
+
+    /// Point of the quad placed at the entity's origin, from (0, 0) at the bottom left corner
+    /// to (1, 1) at the top right one
+    pub pivot: Vec2,
+
+    /// Multiplies the texels, alpha included
+    pub tint: [f32; 4],
+    pub flip_x: bool,
+    pub flip_y: bool,
+
+    /// Sprites with a higher order are drawn over the lower ones, equal orders keep the order
+    /// of the entities
+    pub z_order: i32,
+    layer: u32,
+}
+
+impl Sprite {
+    /// Sprite showing the whole texture, centered on the entity
+    pub fn new(atlas: Rc<TextureDef>, size: Vec2) -> Self {
+        Self {
+            atlas,
+            region: [0., 0., 1., 1.],
+            size,
+            pivot: Vec2::new(0.5, 0.5),
+            tint: [1., 1., 1., 1.],
+            flip_x: false,
+            flip_y: false,
+            z_order: 0,
+            layer: 0,
+        }
+    }
+
+    pub fn with_region(mut self, region: [f32; 4]) -> Self {
+        self.region = region;
+        self
+    }
+
+    pub fn with_pivot(mut self, pivot: Vec2) -> Self {
+        self.pivot = pivot;
+        self
+    }
+
+    pub fn with_tint(mut self, tint: [f32; 4]) -> Self {
+        self.tint = tint;
+        self
+    }
+
+    pub fn with_z_order(mut self, z_order: i32) -> Self {
+        self.z_order = z_order;
+        self
+    }
+
+    /// Same as `RenderingComponent::layer`, matched against the cameras' layer masks
+    pub fn layer(&self) -> u32 {
+        self.layer
+    }
+
+    /// Layers past 31 are clamped to 31, like `RenderingComponent::set_layer`
+    pub fn set_layer(&mut self, layer: u32) {
+        self.layer = clamp_layer(layer);
+    }
+
+    /// World positions and texture coordinates of the corners, counterclockwise from the
+    /// bottom left one
+    pub fn corners(&self, transform: &Mat44) -> [(Vec3, Vec2); 4] {
+        let left = -self.pivot.x * self.size.x;
+        let bottom = -self.pivot.y * self.size.y;
+        let right = left + self.size.x;
+        let top = bottom + self.size.y;
+
+        let [u, v, width, height] = self.region;
+        let (u0, u1) = if self.flip_x {
+            (u + width, u)
+        } else {
+            (u, u + width)
+        };
+        let (v_top, v_bottom) = if self.flip_y {
+            (v + height, v)
+        } else {
+            (v, v + height)
+        };
+
+        let corner = |x: f32, y: f32, u: f32, v: f32| {
+            (
+                transform.transform_point(&Vec3::new(x, y, 0.)),
+                Vec2::new(u, v),
+            )
+        };
+        [
+            corner(left, bottom, u0, v_bottom),
+            corner(right, bottom, u1, v_bottom),
+            corner(right, top, u1, v_top),
+            corner(left, top, u0, v_top),
+        ]
+    }
+}
@@ -0,0 +1,521 @@
+use super::rendering_component::clamp_layer;
+use super::{AddressMode, SamplerDef, Sprite, TextureDef, TextureSource};
+use crate::constants;
+use crate::math::{Mat44, Vec2};
+use image::{Rgba, RgbaImage};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+const INITIAL_GLYPH_ATLAS_SIZE: u32 = 512;
+const MAX_GLYPH_ATLAS_SIZE: u32 = 4096;
+
+// Keeps the linear filtering of a glyph from reading its neighbours
+const GLYPH_PADDING: u32 = 1;
+
+#[derive(Debug)]
+pub enum FontError {
+    InvalidFont(String),
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FontError::InvalidFont(reason) => {
+                write!(f, "{} ({})", constants::STR_INVALID_FONT, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FontError {}
+
+/// Glyph placed by `Font::layout`, in pixels from the text origin with Y pointing down
+#[derive(Copy, Clone, Debug)]
+pub struct GlyphQuad {
+    /// Top left corner
+    pub position: Vec2,
+    pub size: Vec2,
+
+    /// Left, top, width and height in the font's texture, as `Sprite::region`
+    pub uv: [f32; 4],
+}
+
+#[derive(Copy, Clone)]
+struct Glyph {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    xmin: i32,
+    ymin: i32,
+    advance: f32,
+}
+
+// Shelves of white glyphs with their coverage in the alpha channel, grown as glyphs come in
+struct GlyphAtlas {
+    image: RgbaImage,
+    glyphs: HashMap<(char, u32), Option<Glyph>>,
+    cursor: (u32, u32),
+    shelf_height: u32,
+    texture: Option<Rc<TextureDef>>,
+}
+
+impl GlyphAtlas {
+    fn new() -> Self {
+        Self {
+            image: RgbaImage::new(INITIAL_GLYPH_ATLAS_SIZE, INITIAL_GLYPH_ATLAS_SIZE),
+            glyphs: HashMap::new(),
+            cursor: (0, 0),
+            shelf_height: 0,
+            texture: None,
+        }
+    }
+
+    // Top left corner of a free `width` x `height` area, None once the atlas is full
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let (width, height) = (width + GLYPH_PADDING, height + GLYPH_PADDING);
+        loop {
+            let size = self.image.width();
+            if self.cursor.0 + width > size {
+                self.cursor = (0, self.cursor.1 + self.shelf_height);
+                self.shelf_height = 0;
+            }
+
+            if width <= size && self.cursor.1 + height <= size {
+                let position = self.cursor;
+                self.cursor.0 += width;
+                self.shelf_height = self.shelf_height.max(height);
+                return Some(position);
+            }
+
+            if size >= MAX_GLYPH_ATLAS_SIZE {
+                return None;
+            }
+
+            let mut image = RgbaImage::new(size * 2, size * 2);
+            image::imageops::replace(&mut image, &self.image, 0, 0);
+            self.image = image;
+        }
+    }
+}
+
+/// TrueType or OpenType font rasterized on demand into a texture shared by every text using it.
+/// Glyphs are laid out one after another without shaping, which suits Latin and CJK scripts.
+pub struct Font {
+    font: fontdue::Font,
+    atlas: RefCell<GlyphAtlas>,
+}
+
+impl Font {
+    pub fn from_bytes(data: &[u8]) -> Result<Self, FontError> {
+        let font = fontdue::Font::from_bytes(data, fontdue::FontSettings::default())
+            .map_err(|reason| FontError::InvalidFont(reason.to_owned()))?;
+        Ok(Self {
+            font,
+            atlas: RefCell::new(GlyphAtlas::new()),
+        })
+    }
+
+    /// Source Han Serif bundled with the engine, covering CJK characters as well as Latin ones
+    pub fn source_han_serif() -> Result<Self, FontError> {
+        Self::from_bytes(radiance_assets::FONT_SOURCE_HAN_SERIF)
+    }
+
+    /// Distance between two baselines at `size` pixels per em
+    pub fn line_height(&self, size: f32) -> f32 {
+        self.font
+            .horizontal_line_metrics(pixel_size(size))
+            .map_or(size, |metrics| metrics.new_line_size)
+    }
+
+    /// Texture of the glyphs rasterized so far, replaced by a new one when glyphs are added
+    pub fn texture(&self) -> Rc<TextureDef> {
+        let mut atlas = self.atlas.borrow_mut();
+        if atlas.texture.is_none() {
+            let sampler = SamplerDef {
+                max_anisotropy: None,
+                max_lod: Some(0.),
+                ..SamplerDef::default()
+            }
+            .with_address_mode(AddressMode::ClampToEdge);
+            atlas.texture = Some(Rc::new(
+                TextureDef::new(TextureSource::Image(Some(atlas.image.clone())))
+                    .with_sampler(sampler),
+            ));
+        }
+
+        atlas.texture.clone().unwrap()
+    }
+
+    /// Adds the missing glyphs of `text` to the texture. The texture coordinates of the quads
+    /// laid out before are only valid until then, as the texture may grow.
+    pub fn rasterize(&self, text: &str, size: f32) {
+        for c in text.chars() {
+            self.glyph(c, size);
+        }
+    }
+
+    /// Places the glyphs of `text` in lines starting at the origin, aligned around it. Lines
+    /// wider than `max_width` pixels are broken at spaces, or between CJK characters.
+    pub fn layout(
+        &self,
+        text: &str,
+        size: f32,
+        align: TextAlign,
+        max_width: Option<f32>,
+    ) -> Vec<GlyphQuad> {
+        self.rasterize(text, size);
+
+        let px = pixel_size(size);
+        let ascent = self
+            .font
+            .horizontal_line_metrics(px)
+            .map_or(px, |metrics| metrics.ascent);
+        let line_height = self.line_height(size);
+        let atlas_size = self.atlas.borrow().image.width() as f32;
+
+        let mut quads = vec![];
+        for (index, line) in self.wrap(text, size, max_width).iter().enumerate() {
+            let baseline = ascent + index as f32 * line_height;
+            let line_start = quads.len();
+            let mut x = 0.;
+            let mut previous = None;
+            for &c in line {
+                if let Some(previous) = previous {
+                    x += self.font.horizontal_kern(previous, c, px).unwrap_or(0.);
+                }
+
+                previous = Some(c);
+                let glyph = match self.glyph(c, size) {
+                    Some(glyph) => glyph,
+                    None => continue,
+                };
+
+                if glyph.width > 0 && glyph.height > 0 {
+                    quads.push(GlyphQuad {
+                        position: Vec2::new(
+                            x + glyph.xmin as f32,
+                            baseline - (glyph.ymin + glyph.height as i32) as f32,
+                        ),
+                        size: Vec2::new(glyph.width as f32, glyph.height as f32),
+                        uv: [
+                            glyph.x as f32 / atlas_size,
+                            glyph.y as f32 / atlas_size,
+                            glyph.width as f32 / atlas_size,
+                            glyph.height as f32 / atlas_size,
+                        ],
+                    });
+                }
+
+                x += glyph.advance;
+            }
+
+            let shift = match align {
+                TextAlign::Left => 0.,
+                TextAlign::Center => -x / 2.,
+                TextAlign::Right => -x,
+            };
+            for quad in quads[line_start..].iter_mut() {
+                quad.position.x += shift;
+            }
+        }
+
+        quads
+    }
+
+    // Rasterizes the glyph on first use, None for glyphs that no longer fit in the atlas
+    fn glyph(&self, c: char, size: f32) -> Option<Glyph> {
+        let px = pixel_size(size);
+        let key = (c, px as u32);
+        let mut atlas = self.atlas.borrow_mut();
+        if let Some(glyph) = atlas.glyphs.get(&key) {
+            return *glyph;
+        }
+
+        let (metrics, coverage) = self.font.rasterize(c, px);
+        let (width, height) = (metrics.width as u32, metrics.height as u32);
+        let position = if width == 0 || height == 0 {
+            Some((0, 0))
+        } else {
+            atlas.allocate(width, height)
+        };
+
+        let glyph = position.map(|(x, y)| {
+            for (i, alpha) in coverage.iter().enumerate() {
+                let (dx, dy) = (i as u32 % width, i as u32 / width);
+                atlas
+                    .image
+                    .put_pixel(x + dx, y + dy, Rgba([255, 255, 255, *alpha]));
+            }
+
+            Glyph {
+                x,
+                y,
+                width,
+                height,
+                xmin: metrics.xmin,
+                ymin: metrics.ymin,
+                advance: metrics.advance_width,
+            }
+        });
+
+        if glyph.is_none() {
+            log::warn!("The glyph atlas is full, '{}' is not drawn", c);
+        }
+
+        atlas.glyphs.insert(key, glyph);
+        atlas.texture = None;
+        glyph
+    }
+
+    fn wrap(&self, text: &str, size: f32, max_width: Option<f32>) -> Vec<Vec<char>> {
+        let px = pixel_size(size);
+        wrap_lines(text, max_width, |c| self.font.metrics(c, px).advance_width)
+    }
+}
+
+// Splits the text into lines no wider than `max_width`, given the advance of each character
+fn wrap_lines(
+    text: &str,
+    max_width: Option<f32>,
+    advance_width: impl Fn(char) -> f32,
+) -> Vec<Vec<char>> {
+    let mut lines = vec![];
+    for paragraph in text.split('\n') {
+        let chars: Vec<char> = paragraph.chars().collect();
+        let mut start = 0;
+        let mut width = 0.;
+        let mut break_at = None;
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            if i > start && (chars[i - 1] == ' ' || is_cjk(c) || is_cjk(chars[i - 1])) {
+                break_at = Some(i);
+            }
+
+            let advance = advance_width(c);
+            match max_width {
+                Some(max_width) if width + advance > max_width && i > start => {
+                    let end = break_at.unwrap_or(i);
+                    lines.push(trim_end(&chars[start..end]));
+                    start = end;
+                    while start < chars.len() && chars[start] == ' ' {
+                        start += 1;
+                    }
+
+                    i = start;
+                    width = 0.;
+                    break_at = None;
+                }
+                _ => {
+                    width += advance;
+                    i += 1;
+                }
+            }
+        }
+
+        lines.push(trim_end(&chars[start..]));
+    }
+
+    lines
+}
+
+fn pixel_size(size: f32) -> f32 {
+    size.round().max(1.)
+}
+
+fn trim_end(chars: &[char]) -> Vec<char> {
+    let end = chars.iter().rposition(|c| *c != ' ').map_or(0, |i| i + 1);
+    chars[..end].to_vec()
+}
+
+// Scripts written without spaces, whose lines can break between any two characters
+fn is_cjk(c: char) -> bool {
+    match c as u32 {
+        0x3000..=0x30FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xAC00..=0xD7AF
+        | 0xF900..=0xFAFF
+        | 0xFF00..=0xFFEF
+        | 0x20000..=0x2FFFF => true,
+        _ => false,
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TextAlign {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TextSpace {
+    /// In the entity's XY plane, drawn among the sprites with `pixels_per_unit` font pixels per
+    /// world unit
+    World { pixels_per_unit: f32 },
+
+    /// Over the final image of the main camera, after post-processing and before imgui. The
+    /// position is in pixels from the top left corner and the entity's transform is ignored.
+    Screen { x: f32, y: f32 },
+}
+
+/// Component drawing a string with a `Font`. The origin is at the top of the first line, on
+/// its left edge, center or right edge depending on the alignment.
+#[derive(Clone)]
+pub struct Text {
+    pub font: Rc<Font>,
+    pub text: String,
+
+    /// Pixels per em the glyphs are rasterized at, rounded to a whole number
+    pub size: f32,
+    pub color: [f32; 4],
+    pub align: TextAlign,
+
+    /// Width in pixels lines are wrapped at
+    pub max_width: Option<f32>,
+    pub space: TextSpace,
+
+    /// Same as `Sprite::z_order`, texts in screen space are ordered among themselves
+    pub z_order: i32,
+    layer: u32,
+}
+
+impl Text {
+    pub fn new(font: Rc<Font>, text: &str, size: f32, space: TextSpace) -> Self {
+        Self {
+            font,
+            text: text.to_owned(),
+            size,
+            color: [1., 1., 1., 1.],
+            align: TextAlign::Left,
+            max_width: None,
+            space,
+            z_order: 0,
+            layer: 0,
+        }
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_align(mut self, align: TextAlign) -> Self {
+        self.align = align;
+        self
+    }
+
+    pub fn with_max_width(mut self, max_width: f32) -> Self {
+        self.max_width = Some(max_width);
+        self
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        self.text = text.to_owned();
+    }
+
+    /// Same as `Sprite::layer`, world space texts only
+    pub fn layer(&self) -> u32 {
+        self.layer
+    }
+
+    /// Layers past 31 are clamped to 31, like `Sprite::set_layer`
+    pub fn set_layer(&mut self, layer: u32) {
+        self.layer = clamp_layer(layer);
+    }
+
+    /// One sprite per glyph with its world transform, or its transform in pixels with Y
+    /// pointing down in screen space. Sprites of texts laid out before new glyphs are
+    /// rasterized may sample the wrong texels, see `Font::rasterize`.
+    pub fn sprites(&self, transform: &Mat44) -> Vec<(Sprite, Mat44)> {
+        let quads = self
+            .font
+            .layout(&self.text, self.size, self.align, self.max_width);
+        let texture = self.font.texture();
+        quads
+            .iter()
+            .map(|quad| {
+                let mut local = Mat44::new_identity();
+                let (sprite_size, transform) = match self.space {
+                    TextSpace::World { pixels_per_unit } => {
+                        local[0][3] = quad.position.x / pixels_per_unit;
+                        local[1][3] = -quad.position.y / pixels_per_unit;
+                        (
+                            Vec2::new(quad.size.x / pixels_per_unit, quad.size.y / pixels_per_unit),
+                            Mat44::multiplied(transform, &local),
+                        )
+                    }
+                    TextSpace::Screen { x, y } => {
+                        local[0][3] = x + quad.position.x;
+                        local[1][1] = -1.;
+                        local[1][3] = y + quad.position.y;
+                        (quad.size, local)
+                    }
+                };
+
+                let mut sprite = Sprite::new(texture.clone(), sprite_size)
+                    .with_region(quad.uv)
+                    .with_pivot(Vec2::new(0., 1.))
+                    .with_tint(self.color)
+                    .with_z_order(self.z_order);
+                sprite.set_layer(self.layer);
+                (sprite, transform)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every character 10 pixels wide
+    fn wrap(text: &str, max_width: Option<f32>) -> Vec<String> {
+        wrap_lines(text, max_width, |_| 10.)
+            .into_iter()
+            .map(|line| line.into_iter().collect())
+            .collect()
+    }
+
+    #[test]
+    fn breaks_at_spaces() {
+        assert_eq!(
+            wrap("hello world foo", Some(60.)),
+            vec!["hello", "world", "foo"]
+        );
+        assert_eq!(wrap("ab   cd", Some(25.)), vec!["ab", "cd"]);
+    }
+
+    #[test]
+    fn breaks_between_cjk_characters() {
+        assert_eq!(wrap("你好世界", Some(25.)), vec!["你好", "世界"]);
+        assert_eq!(wrap("ab你好", Some(35.)), vec!["ab你", "好"]);
+        assert_eq!(wrap("こんにちは", Some(30.)), vec!["こんに", "ちは"]);
+    }
+
+    #[test]
+    fn splits_words_longer_than_a_line() {
+        assert_eq!(wrap("abcdef", Some(25.)), vec!["ab", "cd", "ef"]);
+        assert_eq!(wrap("abc", Some(5.)), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn keeps_paragraphs() {
+        assert_eq!(wrap("a b \nc", None), vec!["a b", "c"]);
+        assert_eq!(wrap("a\n\nb", Some(100.)), vec!["a", "", "b"]);
+    }
+
+    #[test]
+    fn cjk_ranges() {
+        assert!(is_cjk('漢'));
+        assert!(is_cjk('カ'));
+        assert!(is_cjk('한'));
+        assert!(is_cjk('，'));
+        assert!(!is_cjk('a'));
+        assert!(!is_cjk(' '));
+    }
+}
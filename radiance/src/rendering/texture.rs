@@ -1,4 +1,9 @@
-use image::RgbaImage;
+use super::{cubemap, CompressedImage, RenderTarget};
+use image::{ImageBuffer, Rgb, RgbaImage};
+use std::rc::Rc;
+
+/// Linear floating point image, as loaded from Radiance HDR files
+pub type HdrImage = ImageBuffer<Rgb<f32>, Vec<f32>>;
 
 pub trait Texture: downcast_rs::Downcast {
     fn width(&self) -> u32;
@@ -7,7 +12,142 @@ pub trait Texture: downcast_rs::Downcast {
 
 downcast_rs::impl_downcast!(Texture);
 
-pub enum TextureDef {
-    // PathTextureDef(PathBuf),
-    ImageTextureDef(Option<RgbaImage>),
+/// Where the texels of a texture come from
+pub enum TextureSource {
+    // Path(PathBuf),
+    /// Image with a full mip chain generated when it's uploaded
+    Image(Option<RgbaImage>),
+
+    /// Square faces in +X, -X, +Y, -Y, +Z, -Z order, one set of faces per mip level
+    Cubemap(Vec<[RgbaImage; 6]>),
+
+    /// Linear HDR faces in the same order as `Cubemap`, without mip levels
+    HdrCubemap([HdrImage; 6]),
+
+    /// Block compressed image with its prebuilt mip levels
+    Compressed(CompressedImage),
+
+    /// What was last rendered into the target, its camera doesn't draw the objects sampling it
+    RenderTarget(Rc<dyn RenderTarget>),
+}
+
+/// Contents of a texture and how it is sampled
+pub struct TextureDef {
+    pub source: TextureSource,
+
+    /// `None` for `SamplerDef::default()`, or clamping to the edges for cubemaps
+    pub sampler: Option<SamplerDef>,
+
+    /// `None` for the color space of the source
+    pub color_space: Option<ColorSpace>,
+}
+
+impl TextureDef {
+    pub fn new(source: TextureSource) -> Self {
+        Self {
+            source,
+            sampler: None,
+            color_space: None,
+        }
+    }
+
+    /// HDR cubemap with `face_size` square faces projected from an equirectangular panorama
+    pub fn from_equirectangular(panorama: &HdrImage, face_size: u32) -> Self {
+        Self::new(TextureSource::HdrCubemap(
+            cubemap::equirectangular_to_cubemap(panorama, face_size),
+        ))
+    }
+
+    pub fn with_sampler(mut self, sampler: SamplerDef) -> Self {
+        self.sampler = Some(sampler);
+        self
+    }
+
+    /// Normal, roughness or other data maps must be `ColorSpace::Linear`
+    pub fn with_color_space(mut self, color_space: ColorSpace) -> Self {
+        self.color_space = Some(color_space);
+        self
+    }
+}
+
+/// Encoding of the texels. Images and cubemaps are sRGB unless told otherwise, compressed
+/// images follow their format.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Decoded to linear values by the sampler
+    Srgb,
+
+    /// Sampled as is
+    Linear,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FilterMode {
+    Nearest,
+    Linear,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AddressMode {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+
+    /// Reads past the edges return opaque black
+    ClampToBorder,
+}
+
+/// How a texture is filtered and addressed. The defaults filter linearly between mip levels,
+/// repeat the texture and use 16x anisotropic filtering.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SamplerDef {
+    pub mag_filter: FilterMode,
+    pub min_filter: FilterMode,
+    pub mipmap_filter: FilterMode,
+    pub address_mode_u: AddressMode,
+    pub address_mode_v: AddressMode,
+
+    /// Clamped to the device limit, `None` disables anisotropic filtering
+    pub max_anisotropy: Option<f32>,
+    pub lod_bias: f32,
+    pub min_lod: f32,
+
+    /// Highest mip level used, `None` for the smallest level of the texture
+    pub max_lod: Option<f32>,
+}
+
+impl SamplerDef {
+    /// Unfiltered sampling for pixel art, without mipmaps
+    pub fn pixelated() -> Self {
+        Self {
+            mag_filter: FilterMode::Nearest,
+            min_filter: FilterMode::Nearest,
+            mipmap_filter: FilterMode::Nearest,
+            max_anisotropy: None,
+            max_lod: Some(0.),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_address_mode(mut self, address_mode: AddressMode) -> Self {
+        self.address_mode_u = address_mode;
+        self.address_mode_v = address_mode;
+        self
+    }
+}
+
+impl Default for SamplerDef {
+    fn default() -> Self {
+        Self {
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            address_mode_u: AddressMode::Repeat,
+            address_mode_v: AddressMode::Repeat,
+            max_anisotropy: Some(16.),
+            lod_bias: 0.,
+            min_lod: 0.,
+            max_lod: None,
+        }
+    }
 }
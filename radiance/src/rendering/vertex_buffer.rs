@@ -10,61 +10,219 @@ bitflags! {
         const NORMAL = 0x2;
         const TEXCOORD = 0x4;
         const TEXCOORD2 = 0x8;
+        const COLOR = 0x10;
+        const TANGENT = 0x20;
+        const BONE_INDICES = 0x40;
+        const BONE_WEIGHTS = 0x80;
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum VertexFormat {
+    Float32,
+    Float32x2,
+    Float32x3,
+    Float32x4,
+    UNorm8x4,
+    UInt8x4,
+    UNorm16x4,
+    UInt16x4,
+}
+
+impl VertexFormat {
+    pub fn size(&self) -> usize {
+        match self {
+            VertexFormat::Float32 => 4,
+            VertexFormat::Float32x2 => 8,
+            VertexFormat::Float32x3 => 12,
+            VertexFormat::Float32x4 => 16,
+            VertexFormat::UNorm8x4 => 4,
+            VertexFormat::UInt8x4 => 4,
+            VertexFormat::UNorm16x4 => 8,
+            VertexFormat::UInt16x4 => 8,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum VertexSemantic {
+    Position,
+    Normal,
+    TexCoord,
+    TexCoord2,
+    Color,
+    Tangent,
+    BoneIndices,
+    BoneWeights,
+    Custom(u32),
+}
+
+impl VertexSemantic {
+    const STANDARD: [(VertexComponents, VertexSemantic); 8] = [
+        (VertexComponents::POSITION, VertexSemantic::Position),
+        (VertexComponents::NORMAL, VertexSemantic::Normal),
+        (VertexComponents::TEXCOORD, VertexSemantic::TexCoord),
+        (VertexComponents::TEXCOORD2, VertexSemantic::TexCoord2),
+        (VertexComponents::COLOR, VertexSemantic::Color),
+        (VertexComponents::TANGENT, VertexSemantic::Tangent),
+        (VertexComponents::BONE_INDICES, VertexSemantic::BoneIndices),
+        (VertexComponents::BONE_WEIGHTS, VertexSemantic::BoneWeights),
+    ];
+
+    pub fn from_component(component: VertexComponents) -> Option<Self> {
+        Self::STANDARD
+            .iter()
+            .find(|(c, _)| *c == component)
+            .map(|(_, s)| *s)
+    }
+
+    pub fn component(&self) -> Option<VertexComponents> {
+        Self::STANDARD
+            .iter()
+            .find(|(_, s)| s == self)
+            .map(|(c, _)| *c)
+    }
+
+    /// The format and shader location used when a layout is built from `VertexComponents`
+    pub fn default_format_and_location(&self) -> Option<(VertexFormat, u32)> {
+        match self {
+            VertexSemantic::Position => Some((VertexFormat::Float32x3, 0)),
+            VertexSemantic::Normal => Some((VertexFormat::Float32x3, 1)),
+            VertexSemantic::TexCoord => Some((VertexFormat::Float32x2, 2)),
+            VertexSemantic::TexCoord2 => Some((VertexFormat::Float32x2, 3)),
+            VertexSemantic::Color => Some((VertexFormat::Float32x4, 4)),
+            VertexSemantic::Tangent => Some((VertexFormat::Float32x4, 5)),
+            VertexSemantic::BoneIndices => Some((VertexFormat::UInt16x4, 6)),
+            VertexSemantic::BoneWeights => Some((VertexFormat::Float32x4, 7)),
+            VertexSemantic::Custom(_) => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct VertexAttribute {
+    pub semantic: VertexSemantic,
+    pub format: VertexFormat,
+    pub location: u32,
+}
+
+/// Interleaved vertex layout. Attributes are stored in the declared order.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct VertexLayout {
+    attributes: Vec<VertexAttribute>,
+}
+
+impl VertexLayout {
+    pub fn new() -> Self {
+        Self { attributes: vec![] }
+    }
+
+    pub fn from_components(components: VertexComponents) -> Self {
+        let mut layout = Self::new();
+        for (component, semantic) in VertexSemantic::STANDARD.iter() {
+            if components.contains(*component) {
+                let (format, location) = semantic.default_format_and_location().unwrap();
+                layout = layout.with_attribute(*semantic, format, location);
+            }
+        }
+
+        layout
+    }
+
+    /// Appends a standard attribute with an explicit format at its default location
+    pub fn with(self, semantic: VertexSemantic, format: VertexFormat) -> Self {
+        let location = match semantic.default_format_and_location() {
+            Some((_, location)) => location,
+            None => panic!("Custom vertex attributes need an explicit location"),
+        };
+
+        self.with_attribute(semantic, format, location)
+    }
+
+    pub fn with_attribute(
+        mut self,
+        semantic: VertexSemantic,
+        format: VertexFormat,
+        location: u32,
+    ) -> Self {
+        if self.attributes.iter().any(|a| a.semantic == semantic) {
+            panic!("Duplicated vertex attribute: {:?}", semantic);
+        }
+
+        if self.attributes.iter().any(|a| a.location == location) {
+            panic!("Duplicated vertex attribute location: {}", location);
+        }
+
+        self.attributes.push(VertexAttribute {
+            semantic,
+            format,
+            location,
+        });
+        self
+    }
+
+    pub fn attributes(&self) -> &[VertexAttribute] {
+        &self.attributes
+    }
+
+    pub fn attribute(&self, semantic: VertexSemantic) -> Option<&VertexAttribute> {
+        self.attributes.iter().find(|a| a.semantic == semantic)
+    }
+
+    pub fn contains(&self, semantic: VertexSemantic) -> bool {
+        self.attribute(semantic).is_some()
+    }
+
+    /// The standard components in this layout, custom attributes are not included
+    pub fn components(&self) -> VertexComponents {
+        self.attributes
+            .iter()
+            .filter_map(|a| a.semantic.component())
+            .fold(VertexComponents::empty(), |acc, c| acc | c)
+    }
+}
+
+impl From<VertexComponents> for VertexLayout {
+    fn from(components: VertexComponents) -> Self {
+        VertexLayout::from_components(components)
     }
 }
 
 pub struct VertexMetadata {
     pub size: usize,
-    pub offsets: HashMap<VertexComponents, usize>,
+    pub offsets: HashMap<VertexSemantic, usize>,
+    pub layout: VertexLayout,
 }
 
 lazy_static! {
-    static ref METADATA_CACHE: Mutex<HashMap<VertexComponents, Arc<VertexMetadata>>> =
+    static ref METADATA_CACHE: Mutex<HashMap<VertexLayout, Arc<VertexMetadata>>> =
         Mutex::new(HashMap::new());
 }
 
 impl VertexMetadata {
-    pub fn get(components: VertexComponents) -> Arc<Self> {
+    pub fn get(layout: &VertexLayout) -> Arc<Self> {
         let mut cache = METADATA_CACHE.lock().unwrap();
-        if !cache.contains_key(&components) {
-            cache.insert(components, Arc::new(Self::calc_metadata(components)));
+        if !cache.contains_key(layout) {
+            cache.insert(layout.clone(), Arc::new(Self::calc_metadata(layout)));
         }
 
-        Arc::clone(cache.get(&components).unwrap())
+        Arc::clone(cache.get(layout).unwrap())
+    }
+
+    pub fn attribute(&self, semantic: VertexSemantic) -> Option<&VertexAttribute> {
+        self.layout.attribute(semantic)
     }
 
-    fn calc_metadata(components: VertexComponents) -> Self {
+    fn calc_metadata(layout: &VertexLayout) -> Self {
         let mut metadata = Self {
             size: 0,
             offsets: HashMap::new(),
+            layout: layout.clone(),
         };
 
-        if components.contains(VertexComponents::POSITION) {
-            metadata
-                .offsets
-                .insert(VertexComponents::POSITION, metadata.size);
-            metadata.size += std::mem::size_of::<Vec3>();
-        }
-
-        if components.contains(VertexComponents::NORMAL) {
-            metadata
-                .offsets
-                .insert(VertexComponents::NORMAL, metadata.size);
-            metadata.size += std::mem::size_of::<Vec3>();
-        }
-
-        if components.contains(VertexComponents::TEXCOORD) {
-            metadata
-                .offsets
-                .insert(VertexComponents::TEXCOORD, metadata.size);
-            metadata.size += std::mem::size_of::<Vec2>();
-        }
-
-        if components.contains(VertexComponents::TEXCOORD2) {
-            metadata
-                .offsets
-                .insert(VertexComponents::TEXCOORD2, metadata.size);
-            metadata.size += std::mem::size_of::<Vec2>();
+        for attribute in layout.attributes() {
+            metadata.offsets.insert(attribute.semantic, metadata.size);
+            metadata.size += attribute.format.size();
         }
 
         metadata
@@ -73,31 +231,33 @@ impl VertexMetadata {
 
 #[derive(Debug, Clone)]
 pub struct VertexBuffer {
-    components: VertexComponents,
+    layout: VertexLayout,
     data: Vec<u8>,
     count: usize,
 }
 
 impl VertexBuffer {
-    pub fn new(components: VertexComponents, count: usize) -> Self {
-        let size = VertexMetadata::get(components).size;
+    pub fn new<L: Into<VertexLayout>>(layout: L, count: usize) -> Self {
+        let layout = layout.into();
+        let size = VertexMetadata::get(&layout).size;
         let data = vec![0u8; size * count];
         Self {
-            components,
+            layout,
             data,
             count,
         }
     }
 
-    pub fn new_with_data_blob(components: VertexComponents, data: Vec<u8>) -> Self {
-        let size = VertexMetadata::get(components).size;
+    pub fn new_with_data_blob<L: Into<VertexLayout>>(layout: L, data: Vec<u8>) -> Self {
+        let layout = layout.into();
+        let size = VertexMetadata::get(&layout).size;
         let len = data.len();
         if len % size != 0 {
             panic!("Vertex len mismatch when creating vertex with data");
         }
 
         Self {
-            components,
+            layout,
             data,
             count: len / size,
         }
@@ -111,41 +271,41 @@ impl VertexBuffer {
         tex_coord: Option<&Vec2>,
         tex_coord2: Option<&Vec2>,
     ) {
-        let mut data: Vec<u8> = vec![];
         let mut components = VertexComponents::empty();
+        components.set(VertexComponents::POSITION, position.is_some());
+        components.set(VertexComponents::NORMAL, normal.is_some());
+        components.set(VertexComponents::TEXCOORD, tex_coord.is_some());
+        components.set(VertexComponents::TEXCOORD2, tex_coord2.is_some());
+
+        if components != self.components() {
+            panic!("Vertex component mismatch when setting vertex data");
+        }
+
         if let Some(p) = position {
-            data.extend(p.as_slice());
-            components |= VertexComponents::POSITION;
+            self.set_attribute_blob(index, VertexSemantic::Position, p.as_slice());
         }
 
         if let Some(n) = normal {
-            data.extend(n.as_slice());
-            components |= VertexComponents::NORMAL;
+            self.set_attribute_blob(index, VertexSemantic::Normal, n.as_slice());
         }
 
         if let Some(t) = tex_coord {
-            data.extend(t.as_slice());
-            components |= VertexComponents::TEXCOORD;
+            self.set_attribute_blob(index, VertexSemantic::TexCoord, t.as_slice());
         }
 
         if let Some(t) = tex_coord2 {
-            data.extend(t.as_slice());
-            components |= VertexComponents::TEXCOORD2;
-        }
-
-        if components != self.components {
-            panic!("Vertex component mismatch when setting vertex data");
+            self.set_attribute_blob(index, VertexSemantic::TexCoord2, t.as_slice());
         }
-
-        self.set_vertex_blob(index, |v: &mut [u8]| {
-            v.copy_from_slice(&data);
-        });
     }
 
     pub fn position(&self, index: usize) -> Option<&Vec3> {
         self.get_component(index, VertexComponents::POSITION)
     }
 
+    pub fn normal(&self, index: usize) -> Option<&Vec3> {
+        self.get_component(index, VertexComponents::NORMAL)
+    }
+
     pub fn tex_coord(&self, index: usize) -> Option<&Vec2> {
         self.get_component(index, VertexComponents::TEXCOORD)
     }
@@ -155,14 +315,27 @@ impl VertexBuffer {
         index: usize,
         component: VertexComponents,
     ) -> Option<&TData> {
-        let component_size = VertexMetadata::get(component).size;
-        if component_size != std::mem::size_of::<TData>() {
+        self.get_attribute(index, Self::component_semantic(component))
+    }
+
+    pub fn set_component<TData, F: Fn(&mut TData)>(
+        &mut self,
+        index: usize,
+        component: VertexComponents,
+        update: F,
+    ) {
+        self.set_attribute(index, Self::component_semantic(component), update)
+    }
+
+    pub fn get_attribute<TData>(&self, index: usize, semantic: VertexSemantic) -> Option<&TData> {
+        let metadata = self.metadata();
+        let attribute_size = metadata.attribute(semantic)?.format.size();
+        if attribute_size != std::mem::size_of::<TData>() {
             panic!("Wrong size when get vertex data");
         }
 
-        let metadata = self.metadata();
         let vertex_size = metadata.size;
-        match metadata.offsets.get(&component) {
+        match metadata.offsets.get(&semantic) {
             None => None,
             Some(&offset) => Some(unsafe {
                 &*(self
@@ -174,17 +347,22 @@ impl VertexBuffer {
         }
     }
 
-    pub fn set_component<TData, F: Fn(&mut TData)>(
+    pub fn set_attribute<TData, F: Fn(&mut TData)>(
         &mut self,
         index: usize,
-        component: VertexComponents,
+        semantic: VertexSemantic,
         update: F,
     ) {
-        let component_size = VertexMetadata::get(component).size;
-        if component_size != std::mem::size_of::<TData>() {
+        let metadata = self.metadata();
+        let attribute_size = match metadata.attribute(semantic) {
+            Some(a) => a.format.size(),
+            None => panic!("Vertex attribute not in layout: {:?}", semantic),
+        };
+
+        if attribute_size != std::mem::size_of::<TData>() {
             panic!(
-                "Wrong size when set vertex data: component size {}, TData.size {}",
-                component_size,
+                "Wrong size when set vertex data: attribute size {}, TData.size {}",
+                attribute_size,
                 std::mem::size_of::<TData>()
             );
         }
@@ -193,8 +371,7 @@ impl VertexBuffer {
             panic!("Index out of range: {}", index);
         }
 
-        let metadata = self.metadata();
-        let offset = *metadata.offsets.get(&component).unwrap();
+        let offset = *metadata.offsets.get(&semantic).unwrap();
         let vertex_size = metadata.size;
         let data: &mut TData = unsafe {
             &mut *(self
@@ -205,6 +382,29 @@ impl VertexBuffer {
         update(data);
     }
 
+    pub fn set_attribute_blob(&mut self, index: usize, semantic: VertexSemantic, blob: &[u8]) {
+        let metadata = self.metadata();
+        let attribute_size = match metadata.attribute(semantic) {
+            Some(a) => a.format.size(),
+            None => panic!("Vertex attribute not in layout: {:?}", semantic),
+        };
+
+        if attribute_size != blob.len() {
+            panic!(
+                "Wrong size when set vertex data: attribute size {}, blob size {}",
+                attribute_size,
+                blob.len()
+            );
+        }
+
+        if index >= self.count {
+            panic!("Index out of range: {}", index);
+        }
+
+        let begin = index * metadata.size + *metadata.offsets.get(&semantic).unwrap();
+        self.data[begin..begin + attribute_size].copy_from_slice(blob);
+    }
+
     pub fn set_vertex_blob<F: Fn(&mut [u8])>(&mut self, index: usize, update: F) {
         let metadata = self.metadata();
         let vertex_size = metadata.size;
@@ -228,10 +428,107 @@ impl VertexBuffer {
     }
 
     pub fn components(&self) -> VertexComponents {
-        self.components
+        self.layout.components()
+    }
+
+    pub fn layout(&self) -> &VertexLayout {
+        &self.layout
     }
 
     pub fn metadata(&self) -> Arc<VertexMetadata> {
-        VertexMetadata::get(self.components)
+        VertexMetadata::get(&self.layout)
+    }
+
+    fn component_semantic(component: VertexComponents) -> VertexSemantic {
+        match VertexSemantic::from_component(component) {
+            Some(s) => s,
+            None => panic!("Expect a single vertex component: {:?}", component),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn components_follow_the_standard_order_and_locations() {
+        let layout = VertexLayout::from_components(
+            VertexComponents::TEXCOORD | VertexComponents::POSITION | VertexComponents::NORMAL,
+        );
+        let attributes: Vec<(VertexSemantic, u32)> = layout
+            .attributes()
+            .iter()
+            .map(|a| (a.semantic, a.location))
+            .collect();
+
+        assert_eq!(
+            attributes,
+            vec![
+                (VertexSemantic::Position, 0),
+                (VertexSemantic::Normal, 1),
+                (VertexSemantic::TexCoord, 2),
+            ]
+        );
+        assert_eq!(
+            layout.components(),
+            VertexComponents::POSITION | VertexComponents::NORMAL | VertexComponents::TEXCOORD
+        );
+    }
+
+    #[test]
+    fn offsets_and_stride_use_the_declared_formats() {
+        let layout = VertexLayout::new()
+            .with(VertexSemantic::Position, VertexFormat::Float32x3)
+            .with(VertexSemantic::Color, VertexFormat::UNorm8x4)
+            .with(VertexSemantic::BoneIndices, VertexFormat::UInt16x4)
+            .with_attribute(VertexSemantic::Custom(0), VertexFormat::Float32, 20);
+        let metadata = VertexMetadata::get(&layout);
+
+        assert_eq!(metadata.size, 12 + 4 + 8 + 4);
+        assert_eq!(metadata.offsets[&VertexSemantic::Position], 0);
+        assert_eq!(metadata.offsets[&VertexSemantic::Color], 12);
+        assert_eq!(metadata.offsets[&VertexSemantic::BoneIndices], 16);
+        assert_eq!(metadata.offsets[&VertexSemantic::Custom(0)], 24);
+        assert_eq!(
+            layout.components(),
+            VertexComponents::POSITION | VertexComponents::COLOR | VertexComponents::BONE_INDICES
+        );
+    }
+
+    #[test]
+    fn attributes_are_written_at_their_offset() {
+        let layout = VertexLayout::new()
+            .with(VertexSemantic::Position, VertexFormat::Float32x3)
+            .with(VertexSemantic::Color, VertexFormat::UNorm8x4);
+        let mut vertices = VertexBuffer::new(layout, 2);
+        vertices.set_attribute(1, VertexSemantic::Color, |c: &mut [u8; 4]| {
+            *c = [1, 2, 3, 4]
+        });
+
+        assert_eq!(vertices.data().len(), 32);
+        assert_eq!(&vertices.data()[28..32], &[1, 2, 3, 4]);
+        assert_eq!(
+            vertices.get_attribute::<[u8; 4]>(1, VertexSemantic::Color),
+            Some(&[1, 2, 3, 4])
+        );
+        assert_eq!(
+            vertices.get_attribute::<[u8; 4]>(0, VertexSemantic::Normal),
+            None
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn duplicated_locations_are_rejected() {
+        VertexLayout::new()
+            .with(VertexSemantic::Position, VertexFormat::Float32x3)
+            .with_attribute(VertexSemantic::Custom(0), VertexFormat::Float32, 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn custom_attributes_need_a_location() {
+        VertexLayout::new().with(VertexSemantic::Custom(0), VertexFormat::Float32);
     }
 }
@@ -10,6 +10,7 @@ pub enum BufferType {
     Index = 0,
     Vertex = 1,
     Uniform = 2,
+    Storage = 3,
 }
 
 impl BufferType {
@@ -18,6 +19,7 @@ impl BufferType {
             BufferType::Index => vk::BufferUsageFlags::INDEX_BUFFER,
             BufferType::Vertex => vk::BufferUsageFlags::VERTEX_BUFFER,
             BufferType::Uniform => vk::BufferUsageFlags::UNIFORM_BUFFER,
+            BufferType::Storage => vk::BufferUsageFlags::STORAGE_BUFFER,
         }
     }
 }
@@ -93,6 +95,7 @@ impl Buffer {
             BufferType::Index => vk::BufferUsageFlags::INDEX_BUFFER,
             BufferType::Vertex => vk::BufferUsageFlags::VERTEX_BUFFER,
             BufferType::Uniform => vk::BufferUsageFlags::UNIFORM_BUFFER,
+            BufferType::Storage => vk::BufferUsageFlags::STORAGE_BUFFER,
         };
 
         let mut buffer = Buffer::new_buffer(
@@ -7,7 +7,7 @@ use ash::extensions::khr::{Surface, Swapchain};
 use ash::prelude::VkResult;
 use ash::version::{DeviceV1_0, EntryV1_0, InstanceV1_0};
 use ash::vk::{
-    Extent2D, PhysicalDevice, PresentModeKHR, SurfaceCapabilitiesKHR, SurfaceFormatKHR, SurfaceKHR,
+    PhysicalDevice, PresentModeKHR, SurfaceCapabilitiesKHR, SurfaceFormatKHR, SurfaceKHR,
     SwapchainKHR,
 };
 use ash::{vk, Device, Entry, Instance, InstanceError};
@@ -87,8 +87,14 @@ pub fn create_device(
         .build();
     let extension_names = helpers::device_extension_names();
     let queue_create_info = [queue_create_info];
+
+    // Compressed formats are enabled where available, textures fall back to RGBA otherwise
+    let supported_features = unsafe { instance.get_physical_device_features(physical_device) };
     let physical_device_features = vk::PhysicalDeviceFeatures::builder()
         .sampler_anisotropy(true)
+        .texture_compression_bc(supported_features.texture_compression_bc == vk::TRUE)
+        .texture_compression_etc2(supported_features.texture_compression_etc2 == vk::TRUE)
+        .texture_compression_astc_ldr(supported_features.texture_compression_astc_ldr == vk::TRUE)
         .build();
     let create_info = vk::DeviceCreateInfo::builder()
         .queue_create_infos(&queue_create_info)
@@ -110,16 +116,33 @@ pub fn get_surface_format(
         return Err(VulkanBackendError::NoSurfaceFormatSupported)?;
     }
 
+    // sRGB formats encode the linear colors written to them and blend them before encoding
+    let preferred = [
+        vk::Format::B8G8R8A8_SRGB,
+        vk::Format::R8G8B8A8_SRGB,
+        vk::Format::R8G8B8A8_UNORM,
+    ];
     let default = formats[0];
-    Ok(formats
-        .into_iter()
-        .find(|f| {
-            f.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
-                && f.format == vk::Format::R8G8B8A8_UNORM
+    Ok(preferred
+        .iter()
+        .find_map(|format| {
+            formats
+                .iter()
+                .copied()
+                .find(|f| f.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR && f.format == *format)
         })
         .unwrap_or(default))
 }
 
+pub fn is_srgb_format(format: vk::Format) -> bool {
+    match format {
+        vk::Format::R8G8B8A8_SRGB
+        | vk::Format::B8G8R8A8_SRGB
+        | vk::Format::A8B8G8R8_SRGB_PACK32 => true,
+        _ => false,
+    }
+}
+
 pub fn get_present_mode(
     physical_device: PhysicalDevice,
     surface_entry: &Surface,
@@ -186,29 +209,6 @@ pub fn create_shader_module(
     unsafe { Ok(device.create_shader_module(&create_info, None)?) }
 }
 
-pub fn create_framebuffers(
-    device: &super::device::Device,
-    image_views: &Vec<ImageView>,
-    depth_image_view: &ImageView,
-    extent: &Extent2D,
-    render_pass: vk::RenderPass,
-) -> VkResult<Vec<vk::Framebuffer>> {
-    image_views
-        .iter()
-        .map(|view| {
-            let attachments = [view.vk_image_view(), depth_image_view.vk_image_view()];
-            let create_info = vk::FramebufferCreateInfo::builder()
-                .render_pass(render_pass)
-                .attachments(&attachments)
-                .layers(1)
-                .width(extent.width)
-                .height(extent.height)
-                .build();
-            device.create_framebuffer(&create_info)
-        })
-        .collect()
-}
-
 fn enabled_layer_names() -> Vec<*const i8> {
     unsafe {
         vec![
@@ -2,8 +2,14 @@ use super::{
     buffer::Buffer, descriptor_pool::DescriptorPool, descriptor_pool::DescriptorPoolCreateInfo,
     descriptor_set_layout::DescriptorSetLayout, device::Device,
 };
+use crate::rendering::shadow::MAX_SHADOW_MAPS;
 use crate::rendering::vulkan::material::VulkanMaterial;
-use crate::rendering::vulkan::uniform_buffers::PerFrameUniformBuffer;
+use crate::rendering::vulkan::shadow_map::ShadowMap;
+use crate::rendering::vulkan::texture::VulkanTexture;
+use crate::rendering::vulkan::uniform_buffers::{
+    PerFrameLightUniformBuffer, PerFrameUniformBuffer,
+};
+use crate::rendering::MAX_CAMERAS_PER_TARGET;
 use ash::prelude::VkResult;
 use ash::vk;
 use std::collections::HashMap;
@@ -19,30 +25,34 @@ pub struct DescriptorManager {
     device: Rc<Device>,
     per_frame_pool: vk::DescriptorPool,
     per_object_pool: vk::DescriptorPool,
+    morph_pool: vk::DescriptorPool,
     per_frame_layout: vk::DescriptorSetLayout,
+    morph_layout: vk::DescriptorSetLayout,
     per_material_layouts: Arc<Mutex<HashMap<String, vk::DescriptorSetLayout>>>,
     dub_descriptor_manager: DynamicUniformBufferDescriptorManager,
+    bone_descriptor_manager: DynamicUniformBufferDescriptorManager,
 }
 
 impl DescriptorManager {
     pub fn new(device: Rc<Device>) -> VkResult<Self> {
         let per_frame_pool = Self::create_per_frame_descriptor_pool(&device).unwrap();
         let per_object_pool = Self::create_per_object_descriptor_pool(&device).unwrap();
-        let per_frame_layout = Self::create_descriptor_set_layout(
-            &device,
-            vk::DescriptorType::UNIFORM_BUFFER,
-            vk::ShaderStageFlags::VERTEX,
-            1,
-        )?;
+        let morph_pool = Self::create_morph_descriptor_pool(&device)?;
+        let per_frame_layout = Self::create_per_frame_descriptor_set_layout(&device)?;
+        let morph_layout = Self::create_morph_descriptor_set_layout(&device)?;
         let dub_descriptor_manager = DynamicUniformBufferDescriptorManager::new(device.clone());
+        let bone_descriptor_manager = DynamicUniformBufferDescriptorManager::new(device.clone());
 
         Ok(Self {
             device,
             per_frame_pool,
             per_object_pool,
+            morph_pool,
             per_frame_layout,
+            morph_layout,
             per_material_layouts: Arc::new(Mutex::new(HashMap::new())),
             dub_descriptor_manager,
+            bone_descriptor_manager,
         })
     }
 
@@ -50,6 +60,15 @@ impl DescriptorManager {
         &self.dub_descriptor_manager
     }
 
+    pub fn bone_descriptor_manager(&self) -> &DynamicUniformBufferDescriptorManager {
+        &self.bone_descriptor_manager
+    }
+
+    /// Layout of the per-frame sets, for pipelines reading the camera outside of materials
+    pub fn per_frame_layout(&self) -> vk::DescriptorSetLayout {
+        self.per_frame_layout
+    }
+
     pub fn allocate_per_object_descriptor_set(
         &self,
         material: &VulkanMaterial,
@@ -62,26 +81,67 @@ impl DescriptorManager {
             .build();
         let descriptor_sets = self.device.allocate_descriptor_sets(&create_info)?;
 
-        let image_info: Vec<vk::DescriptorImageInfo> = material
+        // Each texture has its own binding so that shaders can mix sampler types
+        let image_infos: Vec<[vk::DescriptorImageInfo; 1]> = material
             .textures()
             .iter()
-            .map(|t| {
-                vk::DescriptorImageInfo::builder()
-                    .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
-                    .image_view(t.image_view().vk_image_view())
-                    .sampler(t.sampler().vk_sampler())
+            .map(|t| [Self::image_info(t)])
+            .collect();
+        let write_descriptor_sets: Vec<vk::WriteDescriptorSet> = image_infos
+            .iter()
+            .enumerate()
+            .map(|(i, image_info)| {
+                vk::WriteDescriptorSet::builder()
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .dst_set(descriptor_sets[0])
+                    .dst_binding(i as u32)
+                    .dst_array_element(0)
+                    .image_info(image_info)
                     .build()
             })
             .collect();
+        self.device
+            .update_descriptor_sets(&write_descriptor_sets, &[]);
 
-        let write_descriptor_set = vk::WriteDescriptorSet::builder()
-            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-            .dst_set(descriptor_sets[0])
-            .dst_binding(0)
-            .dst_array_element(0)
-            .image_info(&image_info)
+        Ok(descriptor_sets[0])
+    }
+
+    /// Set of the morph target deltas and weights of a render object, see `morph.vert`
+    pub fn allocate_morph_descriptor_set(
+        &self,
+        deltas: &Buffer,
+        weights: &Buffer,
+    ) -> VkResult<vk::DescriptorSet> {
+        let layouts = [self.morph_layout];
+        let create_info = vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(self.morph_pool)
+            .set_layouts(&layouts)
             .build();
-        let write_descriptor_sets = [write_descriptor_set];
+        let descriptor_sets = self.device.allocate_descriptor_sets(&create_info)?;
+
+        let buffer_infos: Vec<[vk::DescriptorBufferInfo; 1]> = [deltas, weights]
+            .iter()
+            .map(|buffer| {
+                [vk::DescriptorBufferInfo::builder()
+                    .buffer(buffer.vk_buffer())
+                    .offset(0)
+                    .range(buffer.size())
+                    .build()]
+            })
+            .collect();
+        let write_descriptor_sets: Vec<vk::WriteDescriptorSet> = buffer_infos
+            .iter()
+            .enumerate()
+            .map(|(binding, buffer_info)| {
+                vk::WriteDescriptorSet::builder()
+                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                    .dst_set(descriptor_sets[0])
+                    .dst_binding(binding as u32)
+                    .dst_array_element(0)
+                    .buffer_info(buffer_info)
+                    .build()
+            })
+            .collect();
         self.device
             .update_descriptor_sets(&write_descriptor_sets, &[]);
 
@@ -91,6 +151,10 @@ impl DescriptorManager {
     pub fn allocate_per_frame_descriptor_sets(
         &self,
         uniform_buffers: &[Buffer],
+        light_buffers: &[Buffer],
+        irradiance: &VulkanTexture,
+        prefiltered: &VulkanTexture,
+        shadow_map: &ShadowMap,
     ) -> VkResult<Vec<vk::DescriptorSet>> {
         let layouts = vec![self.per_frame_layout; uniform_buffers.len()];
         let create_info = vk::DescriptorSetAllocateInfo::builder()
@@ -106,7 +170,21 @@ impl DescriptorManager {
                 .range(std::mem::size_of::<PerFrameUniformBuffer>() as u64)
                 .build();
 
+            let light_buffer_info = vk::DescriptorBufferInfo::builder()
+                .buffer(light_buffers[i].vk_buffer())
+                .offset(0)
+                .range(std::mem::size_of::<PerFrameLightUniformBuffer>() as u64)
+                .build();
+
             let buffer_info_array = [uniform_buffer_info];
+            let shadow_map_info = vk::DescriptorImageInfo::builder()
+                .image_layout(vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL)
+                .image_view(shadow_map.image_view().vk_image_view())
+                .sampler(shadow_map.sampler().vk_sampler())
+                .build();
+
+            let light_buffer_info_array = [light_buffer_info];
+            let shadow_map_info_array = [shadow_map_info];
             let write_descriptor_set = vk::WriteDescriptorSet::builder()
                 .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
                 .dst_set(descriptor_sets[i])
@@ -114,14 +192,97 @@ impl DescriptorManager {
                 .dst_array_element(0)
                 .buffer_info(&buffer_info_array)
                 .build();
-            let write_descriptor_sets = [write_descriptor_set];
+            let light_write_descriptor_set = vk::WriteDescriptorSet::builder()
+                .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                .dst_set(descriptor_sets[i])
+                .dst_binding(1)
+                .dst_array_element(0)
+                .buffer_info(&light_buffer_info_array)
+                .build();
+            let shadow_map_write_descriptor_set = vk::WriteDescriptorSet::builder()
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .dst_set(descriptor_sets[i])
+                .dst_binding(4)
+                .dst_array_element(0)
+                .image_info(&shadow_map_info_array)
+                .build();
+            let write_descriptor_sets = [
+                write_descriptor_set,
+                light_write_descriptor_set,
+                shadow_map_write_descriptor_set,
+            ];
             self.device
                 .update_descriptor_sets(&write_descriptor_sets, &[])
         }
 
+        self.update_per_frame_environment(&descriptor_sets, irradiance, prefiltered);
         Ok(descriptor_sets)
     }
 
+    /// Per-frame sets used by the shadow passes, only the camera binding is written since
+    /// depth only pipelines don't read the others
+    pub fn allocate_shadow_descriptor_sets(
+        &self,
+        uniform_buffers: &[Buffer],
+    ) -> VkResult<Vec<vk::DescriptorSet>> {
+        let layouts = vec![self.per_frame_layout; uniform_buffers.len()];
+        let create_info = vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(self.per_frame_pool)
+            .set_layouts(layouts.as_slice())
+            .build();
+        let descriptor_sets = self.device.allocate_descriptor_sets(&create_info)?;
+
+        for (descriptor_set, uniform_buffer) in descriptor_sets.iter().zip(uniform_buffers) {
+            let buffer_info_array = [vk::DescriptorBufferInfo::builder()
+                .buffer(uniform_buffer.vk_buffer())
+                .offset(0)
+                .range(std::mem::size_of::<PerFrameUniformBuffer>() as u64)
+                .build()];
+            let write_descriptor_sets = [vk::WriteDescriptorSet::builder()
+                .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                .dst_set(*descriptor_set)
+                .dst_binding(0)
+                .dst_array_element(0)
+                .buffer_info(&buffer_info_array)
+                .build()];
+            self.device
+                .update_descriptor_sets(&write_descriptor_sets, &[]);
+        }
+
+        Ok(descriptor_sets)
+    }
+
+    /// Points the per-frame sets to new IBL cubemaps. The sets must not be in use.
+    pub fn update_per_frame_environment(
+        &self,
+        descriptor_sets: &[vk::DescriptorSet],
+        irradiance: &VulkanTexture,
+        prefiltered: &VulkanTexture,
+    ) {
+        let irradiance_info = [Self::image_info(irradiance)];
+        let prefiltered_info = [Self::image_info(prefiltered)];
+        for descriptor_set in descriptor_sets {
+            let write_descriptor_sets = [
+                vk::WriteDescriptorSet::builder()
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .dst_set(*descriptor_set)
+                    .dst_binding(2)
+                    .dst_array_element(0)
+                    .image_info(&irradiance_info)
+                    .build(),
+                vk::WriteDescriptorSet::builder()
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .dst_set(*descriptor_set)
+                    .dst_binding(3)
+                    .dst_array_element(0)
+                    .image_info(&prefiltered_info)
+                    .build(),
+            ];
+            self.device
+                .update_descriptor_sets(&write_descriptor_sets, &[]);
+        }
+    }
+
     pub fn reset_per_frame_descriptor_pool(&self) {
         self.device
             .reset_descriptor_pool(self.per_frame_pool)
@@ -131,22 +292,39 @@ impl DescriptorManager {
     pub fn get_vk_descriptor_set_layouts(
         &self,
         material: &VulkanMaterial,
-    ) -> [vk::DescriptorSetLayout; 3] {
+    ) -> Vec<vk::DescriptorSetLayout> {
         let per_material_layout = self.get_per_material_descriptor_layout(material);
-        [
+        let mut layouts = vec![
             self.per_frame_layout,
             self.dub_descriptor_manager.layout().vk_layout(),
             per_material_layout,
-        ]
+        ];
+
+        if material.shader().is_skinned() {
+            layouts.push(self.bone_descriptor_manager.layout().vk_layout());
+        } else if material.shader().is_morph() {
+            layouts.push(self.morph_layout);
+        }
+
+        layouts
     }
 
     fn create_per_frame_descriptor_pool(device: &Device) -> VkResult<vk::DescriptorPool> {
+        // Camera and light buffers for each swapchain image, and a camera for each shadow map,
+        // all of them repeated for every camera drawn into a target
+        let cameras = MAX_CAMERAS_PER_TARGET as u32;
         let uniform_pool_size = vk::DescriptorPoolSize::builder()
-            .descriptor_count(MAX_SWAPCHAIN_IMAGE_COUNT)
+            .descriptor_count((MAX_SWAPCHAIN_IMAGE_COUNT * 2 + MAX_SHADOW_MAPS as u32) * cameras)
             .ty(vk::DescriptorType::UNIFORM_BUFFER)
             .build();
 
-        let pool_sizes = [uniform_pool_size];
+        // Irradiance and prefiltered environment maps, and the shadow maps
+        let sampler_pool_size = vk::DescriptorPoolSize::builder()
+            .descriptor_count(MAX_SWAPCHAIN_IMAGE_COUNT * 3 * cameras)
+            .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .build();
+
+        let pool_sizes = [uniform_pool_size, sampler_pool_size];
         let create_info = vk::DescriptorPoolCreateInfo::builder()
             .pool_sizes(&pool_sizes)
             .max_sets(MAX_DESCRIPTOR_SET_COUNT)
@@ -170,20 +348,75 @@ impl DescriptorManager {
         device.create_descriptor_pool(&create_info)
     }
 
-    fn create_descriptor_set_layout(
+    fn create_morph_descriptor_pool(device: &Device) -> VkResult<vk::DescriptorPool> {
+        let storage_pool_size = vk::DescriptorPoolSize::builder()
+            .descriptor_count(MAX_DESCRIPTOR_COUNT)
+            .ty(vk::DescriptorType::STORAGE_BUFFER)
+            .build();
+
+        let pool_sizes = [storage_pool_size];
+        let create_info = vk::DescriptorPoolCreateInfo::builder()
+            .pool_sizes(&pool_sizes)
+            .max_sets(MAX_DESCRIPTOR_SET_COUNT)
+            .build();
+
+        device.create_descriptor_pool(&create_info)
+    }
+
+    // Binding 0 holds the deltas, binding 1 the weights
+    fn create_morph_descriptor_set_layout(device: &Device) -> VkResult<vk::DescriptorSetLayout> {
+        let bindings: Vec<vk::DescriptorSetLayoutBinding> = (0..2)
+            .map(|binding| {
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(binding)
+                    .descriptor_count(1)
+                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                    .stage_flags(vk::ShaderStageFlags::VERTEX)
+                    .build()
+            })
+            .collect();
+
+        let create_info = vk::DescriptorSetLayoutCreateInfo::builder()
+            .bindings(&bindings)
+            .build();
+
+        device.create_descriptor_set_layout(&create_info)
+    }
+
+    fn create_per_frame_descriptor_set_layout(
         device: &Device,
-        descriptor_type: vk::DescriptorType,
-        stage_flags: vk::ShaderStageFlags,
-        descriptor_count: u32,
     ) -> VkResult<vk::DescriptorSetLayout> {
-        let layout_binding = vk::DescriptorSetLayoutBinding::builder()
+        let camera_binding = vk::DescriptorSetLayoutBinding::builder()
             .binding(0)
-            .descriptor_count(descriptor_count)
-            .descriptor_type(descriptor_type)
-            .stage_flags(stage_flags)
+            .descriptor_count(1)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .stage_flags(vk::ShaderStageFlags::VERTEX)
+            .build();
+        let light_binding = vk::DescriptorSetLayoutBinding::builder()
+            .binding(1)
+            .descriptor_count(1)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
             .build();
 
-        let bindings = [layout_binding];
+        let sampler_bindings: Vec<vk::DescriptorSetLayoutBinding> = (2..5)
+            .map(|binding| {
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(binding)
+                    .descriptor_count(1)
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .build()
+            })
+            .collect();
+
+        let bindings = [
+            camera_binding,
+            light_binding,
+            sampler_bindings[0],
+            sampler_bindings[1],
+            sampler_bindings[2],
+        ];
         let create_info = vk::DescriptorSetLayoutCreateInfo::builder()
             .bindings(&bindings)
             .build();
@@ -197,24 +430,43 @@ impl DescriptorManager {
     ) -> vk::DescriptorSetLayout {
         let mut per_material_layouts = self.per_material_layouts.lock().unwrap();
         if !per_material_layouts.contains_key(material.name()) {
-            let layout = Self::create_descriptor_set_layout(
-                &self.device,
-                vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-                vk::ShaderStageFlags::FRAGMENT,
-                material.textures().len() as u32,
-            )
-            .unwrap();
+            let bindings: Vec<vk::DescriptorSetLayoutBinding> = (0..material.textures().len())
+                .map(|i| {
+                    vk::DescriptorSetLayoutBinding::builder()
+                        .binding(i as u32)
+                        .descriptor_count(1)
+                        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                        .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                        .build()
+                })
+                .collect();
+            let create_info = vk::DescriptorSetLayoutCreateInfo::builder()
+                .bindings(&bindings)
+                .build();
+            let layout = self
+                .device
+                .create_descriptor_set_layout(&create_info)
+                .unwrap();
             per_material_layouts.insert(material.name().to_owned(), layout);
         }
 
         *per_material_layouts.get(material.name()).unwrap()
     }
+
+    fn image_info(texture: &VulkanTexture) -> vk::DescriptorImageInfo {
+        vk::DescriptorImageInfo::builder()
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .image_view(texture.image_view().vk_image_view())
+            .sampler(texture.sampler().vk_sampler())
+            .build()
+    }
 }
 
 impl Drop for DescriptorManager {
     fn drop(&mut self) {
         self.device
             .destroy_descriptor_set_layout(self.per_frame_layout);
+        self.device.destroy_descriptor_set_layout(self.morph_layout);
 
         for layout in self.per_material_layouts.lock().unwrap().values() {
             self.device.destroy_descriptor_set_layout(*layout);
@@ -222,6 +474,7 @@ impl Drop for DescriptorManager {
 
         self.device.destroy_descriptor_pool(self.per_frame_pool);
         self.device.destroy_descriptor_pool(self.per_object_pool);
+        self.device.destroy_descriptor_pool(self.morph_pool);
     }
 }
 
@@ -7,12 +7,14 @@ use ash::{
         CommandBufferResetFlags, CommandPool, CommandPoolCreateInfo, CopyDescriptorSet,
         DependencyFlags, DescriptorPool, DescriptorPoolCreateInfo, DescriptorSet,
         DescriptorSetAllocateInfo, DescriptorSetLayout, DescriptorSetLayoutCreateInfo, DeviceSize,
-        Fence, Framebuffer, FramebufferCreateInfo, GraphicsPipelineCreateInfo, Image, ImageLayout,
-        ImageMemoryBarrier, ImageView, ImageViewCreateInfo, IndexType, MemoryBarrier,
-        PhysicalDevice, Pipeline, PipelineBindPoint, PipelineCache, PipelineLayout,
-        PipelineLayoutCreateInfo, PipelineStageFlags, Queue, RenderPass, RenderPassBeginInfo,
-        RenderPassCreateInfo, Sampler, SamplerCreateInfo, Semaphore, SemaphoreCreateInfo,
-        ShaderModule, ShaderModuleCreateInfo, SubmitInfo, SubpassContents, WriteDescriptorSet,
+        Fence, Filter, Format, FormatProperties, Framebuffer, FramebufferCreateInfo,
+        GraphicsPipelineCreateInfo, Image, ImageBlit, ImageLayout, ImageMemoryBarrier, ImageView,
+        ImageViewCreateInfo, IndexType, MemoryBarrier, PhysicalDevice, PhysicalDeviceProperties,
+        Pipeline, PipelineBindPoint, PipelineCache, PipelineLayout, PipelineLayoutCreateInfo,
+        PipelineStageFlags, Queue, Rect2D, RenderPass, RenderPassBeginInfo, RenderPassCreateInfo,
+        Sampler, SamplerCreateInfo, Semaphore, SemaphoreCreateInfo, ShaderModule,
+        ShaderModuleCreateInfo, ShaderStageFlags, SubmitInfo, SubpassContents, Viewport,
+        WriteDescriptorSet,
     },
 };
 use ash::{
@@ -24,6 +26,7 @@ use super::{creation_helpers, instance::Instance};
 
 pub struct Device {
     instance: Rc<Instance>,
+    physical_device: PhysicalDevice,
     device: ash::Device,
 }
 
@@ -40,13 +43,27 @@ impl Device {
         )
         .unwrap();
 
-        Self { instance, device }
+        Self {
+            instance,
+            physical_device,
+            device,
+        }
     }
 
     pub fn vk_device(&self) -> &ash::Device {
         &self.device
     }
 
+    pub fn physical_device_properties(&self) -> PhysicalDeviceProperties {
+        self.instance
+            .get_physical_device_properties(self.physical_device)
+    }
+
+    pub fn format_properties(&self, format: Format) -> FormatProperties {
+        self.instance
+            .get_physical_device_format_properties(self.physical_device, format)
+    }
+
     pub fn get_device_queue(&self, queue_family_index: u32, queue_index: u32) -> Queue {
         unsafe {
             self.device
@@ -204,6 +221,29 @@ impl Device {
         }
     }
 
+    pub fn cmd_blit_image(
+        &self,
+        command_buffer: CommandBuffer,
+        src_image: Image,
+        src_image_layout: ImageLayout,
+        dst_image: Image,
+        dst_image_layout: ImageLayout,
+        regions: &[ImageBlit],
+        filter: Filter,
+    ) {
+        unsafe {
+            self.device.cmd_blit_image(
+                command_buffer,
+                src_image,
+                src_image_layout,
+                dst_image,
+                dst_image_layout,
+                regions,
+                filter,
+            )
+        }
+    }
+
     pub fn cmd_begin_render_pass(
         &self,
         command_buffer: CommandBuffer,
@@ -279,6 +319,20 @@ impl Device {
         }
     }
 
+    pub fn cmd_push_constants(
+        &self,
+        command_buffer: CommandBuffer,
+        layout: PipelineLayout,
+        stage_flags: ShaderStageFlags,
+        offset: u32,
+        constants: &[u8],
+    ) {
+        unsafe {
+            self.device
+                .cmd_push_constants(command_buffer, layout, stage_flags, offset, constants);
+        }
+    }
+
     pub fn cmd_draw_indexed(
         &self,
         command_buffer: CommandBuffer,
@@ -300,6 +354,49 @@ impl Device {
         }
     }
 
+    pub fn cmd_draw(
+        &self,
+        command_buffer: CommandBuffer,
+        vertex_count: u32,
+        instance_count: u32,
+        first_vertex: u32,
+        first_instance: u32,
+    ) {
+        unsafe {
+            self.device.cmd_draw(
+                command_buffer,
+                vertex_count,
+                instance_count,
+                first_vertex,
+                first_instance,
+            );
+        }
+    }
+
+    pub fn cmd_set_viewport(
+        &self,
+        command_buffer: CommandBuffer,
+        first_viewport: u32,
+        viewports: &[Viewport],
+    ) {
+        unsafe {
+            self.device
+                .cmd_set_viewport(command_buffer, first_viewport, viewports);
+        }
+    }
+
+    pub fn cmd_set_scissor(
+        &self,
+        command_buffer: CommandBuffer,
+        first_scissor: u32,
+        scissors: &[Rect2D],
+    ) {
+        unsafe {
+            self.device
+                .cmd_set_scissor(command_buffer, first_scissor, scissors);
+        }
+    }
+
     pub fn queue_submit(&self, queue: Queue, submits: &[SubmitInfo], fence: Fence) -> VkResult<()> {
         unsafe { self.device.queue_submit(queue, submits, fence) }
     }
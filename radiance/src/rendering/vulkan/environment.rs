@@ -0,0 +1,40 @@
+use super::{adhoc_command_runner::AdhocCommandRunner, device::Device, texture::VulkanTexture};
+use crate::rendering::EnvironmentMap;
+use std::error::Error;
+use std::rc::Rc;
+
+pub struct VulkanEnvironment {
+    map: Rc<EnvironmentMap>,
+    irradiance: VulkanTexture,
+    prefiltered: VulkanTexture,
+}
+
+impl VulkanEnvironment {
+    pub fn new(
+        map: Rc<EnvironmentMap>,
+        device: &Rc<Device>,
+        allocator: &Rc<vk_mem::Allocator>,
+        command_runner: &Rc<AdhocCommandRunner>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let irradiance = VulkanTexture::new(map.irradiance(), device, allocator, command_runner)?;
+        let prefiltered = VulkanTexture::new(map.prefiltered(), device, allocator, command_runner)?;
+
+        Ok(Self {
+            map,
+            irradiance,
+            prefiltered,
+        })
+    }
+
+    pub fn map(&self) -> &Rc<EnvironmentMap> {
+        &self.map
+    }
+
+    pub fn irradiance(&self) -> &VulkanTexture {
+        &self.irradiance
+    }
+
+    pub fn prefiltered(&self) -> &VulkanTexture {
+        &self.prefiltered
+    }
+}
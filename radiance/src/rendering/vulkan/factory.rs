@@ -1,12 +1,14 @@
 use super::{
     adhoc_command_runner::AdhocCommandRunner, descriptor_managers::DescriptorManager,
     device::Device, material::VulkanMaterial, render_object::VulkanRenderObject,
-    shader::VulkanShader, texture::VulkanTexture, uniform_buffers::DynamicUniformBufferManager,
+    render_target::VulkanRenderTarget, shader::VulkanShader, texture::VulkanTexture,
+    uniform_buffers::DynamicUniformBufferManager,
 };
 use crate::rendering::{
-    factory::ComponentFactory, texture::TextureDef, Material, MaterialDef, RenderObject,
-    RenderingComponent, Shader, ShaderDef, Texture, VertexBuffer,
+    factory::ComponentFactory, texture::TextureDef, Material, MaterialDef, MorphTarget,
+    RenderObject, RenderTarget, RenderingComponent, Shader, ShaderDef, Texture, VertexBuffer,
 };
+use ash::vk;
 use std::rc::Rc;
 use std::sync::Arc;
 
@@ -15,7 +17,9 @@ pub struct VulkanComponentFactory {
     allocator: Rc<vk_mem::Allocator>,
     descriptor_manager: Rc<DescriptorManager>,
     dub_manager: Arc<DynamicUniformBufferManager>,
+    bone_manager: Arc<DynamicUniformBufferManager>,
     command_runner: Rc<AdhocCommandRunner>,
+    target_format: vk::Format,
 }
 
 impl ComponentFactory for VulkanComponentFactory {
@@ -31,6 +35,20 @@ impl ComponentFactory for VulkanComponentFactory {
         )
     }
 
+    fn create_render_target(&self, width: u32, height: u32) -> Rc<dyn RenderTarget> {
+        Rc::new(
+            VulkanRenderTarget::new(
+                width,
+                height,
+                self.target_format,
+                &self.device,
+                &self.allocator,
+                &self.command_runner,
+            )
+            .unwrap(),
+        )
+    }
+
     fn create_shader(&self, shader_def: &ShaderDef) -> Box<dyn Shader> {
         Box::new(VulkanShader::new(shader_def, self.device.clone()).unwrap())
     }
@@ -58,9 +76,36 @@ impl ComponentFactory for VulkanComponentFactory {
                 indices,
                 material,
                 host_dynamic,
+                &[],
+                &self.allocator,
+                &self.command_runner,
+                &self.dub_manager,
+                &self.bone_manager,
+                &self.descriptor_manager,
+            )
+            .unwrap(),
+        )
+    }
+
+    fn create_morph_render_object(
+        &self,
+        vertices: VertexBuffer,
+        indices: Vec<u32>,
+        material_def: &MaterialDef,
+        targets: &[MorphTarget],
+    ) -> Box<dyn RenderObject> {
+        let material = self.create_material(material_def);
+        Box::new(
+            VulkanRenderObject::new(
+                vertices,
+                indices,
+                material,
+                false,
+                targets,
                 &self.allocator,
                 &self.command_runner,
                 &self.dub_manager,
+                &self.bone_manager,
                 &self.descriptor_manager,
             )
             .unwrap(),
@@ -86,14 +131,18 @@ impl VulkanComponentFactory {
         allocator: &Rc<vk_mem::Allocator>,
         descriptor_manager: &Rc<DescriptorManager>,
         dub_manager: &Arc<DynamicUniformBufferManager>,
+        bone_manager: &Arc<DynamicUniformBufferManager>,
         command_runner: &Rc<AdhocCommandRunner>,
+        target_format: vk::Format,
     ) -> Self {
         Self {
             device,
             allocator: allocator.clone(),
             descriptor_manager: descriptor_manager.clone(),
             dub_manager: dub_manager.clone(),
+            bone_manager: bone_manager.clone(),
             command_runner: command_runner.clone(),
+            target_format,
         }
     }
 
@@ -1,5 +1,6 @@
 use super::adhoc_command_runner::AdhocCommandRunner;
 use super::buffer::Buffer;
+use super::device::Device;
 use super::error::VulkanBackendError;
 use ash::prelude::VkResult;
 use ash::version::InstanceV1_0;
@@ -15,31 +16,119 @@ pub struct Image {
     format: vk::Format,
     width: u32,
     height: u32,
+    mip_levels: u32,
+    layers: u32,
+    samples: vk::SampleCountFlags,
 }
 
 impl Image {
+    /// Transfer source as well, so that the mip levels can be blitted from each other
     pub fn new_color_image(
         allocator: &Rc<vk_mem::Allocator>,
         tex_width: u32,
         tex_height: u32,
+        mip_levels: u32,
+        format: vk::Format,
+    ) -> Result<Self, Box<dyn Error>> {
+        Self::new_with_layers(
+            allocator,
+            tex_width,
+            tex_height,
+            mip_levels,
+            1,
+            vk::ImageCreateFlags::empty(),
+            format,
+            vk::SampleCountFlags::TYPE_1,
+            vk::ImageUsageFlags::TRANSFER_SRC
+                | vk::ImageUsageFlags::TRANSFER_DST
+                | vk::ImageUsageFlags::SAMPLED,
+        )
+    }
+
+    /// Block compressed image whose mip levels are all uploaded
+    pub fn new_compressed_image(
+        allocator: &Rc<vk_mem::Allocator>,
+        tex_width: u32,
+        tex_height: u32,
+        mip_levels: u32,
+        format: vk::Format,
     ) -> Result<Self, Box<dyn Error>> {
-        Self::new(
+        Self::new_with_layers(
             allocator,
             tex_width,
             tex_height,
-            vk::Format::R8G8B8A8_UNORM,
+            mip_levels,
+            1,
+            vk::ImageCreateFlags::empty(),
+            format,
+            vk::SampleCountFlags::TYPE_1,
             vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
         )
     }
 
-    pub fn new_depth_image(
-        instance: &Instance,
-        physical_device: vk::PhysicalDevice,
+    /// Levels down to 1x1 for an image of this size
+    pub fn full_mip_levels(width: u32, height: u32) -> u32 {
+        32 - width.max(height).max(1).leading_zeros()
+    }
+
+    /// Whether the GPU can generate the mip levels of images in this format
+    pub fn supports_linear_blit(device: &Device, format: vk::Format) -> bool {
+        device
+            .format_properties(format)
+            .optimal_tiling_features
+            .contains(
+                vk::FormatFeatureFlags::BLIT_SRC
+                    | vk::FormatFeatureFlags::BLIT_DST
+                    | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR,
+            )
+    }
+
+    pub fn new_cubemap_image(
+        allocator: &Rc<vk_mem::Allocator>,
+        size: u32,
+        mip_levels: u32,
+        format: vk::Format,
+    ) -> Result<Self, Box<dyn Error>> {
+        Self::new_with_layers(
+            allocator,
+            size,
+            size,
+            mip_levels,
+            6,
+            vk::ImageCreateFlags::CUBE_COMPATIBLE,
+            format,
+            vk::SampleCountFlags::TYPE_1,
+            vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
+        )
+    }
+
+    /// Color or depth attachment allocated by the render graph
+    pub fn new_attachment_image(
         allocator: &Rc<vk_mem::Allocator>,
         tex_width: u32,
         tex_height: u32,
+        format: vk::Format,
+        samples: vk::SampleCountFlags,
+        usage: vk::ImageUsageFlags,
     ) -> Result<Self, Box<dyn Error>> {
-        let format = Image::find_supported_format(
+        Self::new_with_layers(
+            allocator,
+            tex_width,
+            tex_height,
+            1,
+            1,
+            vk::ImageCreateFlags::empty(),
+            format,
+            samples,
+            usage,
+        )
+    }
+
+    pub fn find_depth_format(
+        instance: &Instance,
+        physical_device: vk::PhysicalDevice,
+    ) -> Result<vk::Format, Box<dyn Error>> {
+        Image::find_supported_format(
             instance,
             physical_device,
             &vec![
@@ -49,14 +138,25 @@ impl Image {
             ],
             vk::ImageTiling::OPTIMAL,
             vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
-        )?;
+        )
+    }
 
-        Self::new(
+    /// Depth image array sampled by the lighting shaders
+    pub fn new_shadow_map_image(
+        allocator: &Rc<vk_mem::Allocator>,
+        size: u32,
+        layers: u32,
+    ) -> Result<Self, Box<dyn Error>> {
+        Self::new_with_layers(
             allocator,
-            tex_width,
-            tex_height,
-            format,
-            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            size,
+            size,
+            1,
+            layers,
+            vk::ImageCreateFlags::empty(),
+            vk::Format::D32_SFLOAT,
+            vk::SampleCountFlags::TYPE_1,
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
         )
     }
 
@@ -68,6 +168,18 @@ impl Image {
         self.height
     }
 
+    pub fn mip_levels(&self) -> u32 {
+        self.mip_levels
+    }
+
+    pub fn layers(&self) -> u32 {
+        self.layers
+    }
+
+    pub fn samples(&self) -> vk::SampleCountFlags {
+        self.samples
+    }
+
     pub fn vk_image(&self) -> vk::Image {
         self.image
     }
@@ -83,21 +195,7 @@ impl Image {
         command_runner: &AdhocCommandRunner,
     ) -> VkResult<()> {
         command_runner.run_commands_one_shot(|device, command_buffer| {
-            let aspect_mask = {
-                match new_layout {
-                    vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL => {
-                        let mut aspect_mask = vk::ImageAspectFlags::DEPTH;
-                        if self.format == vk::Format::D32_SFLOAT_S8_UINT
-                            || self.format == vk::Format::D24_UNORM_S8_UINT
-                        {
-                            aspect_mask |= vk::ImageAspectFlags::STENCIL;
-                        }
-
-                        aspect_mask
-                    }
-                    _ => vk::ImageAspectFlags::COLOR,
-                }
-            };
+            let aspect_mask = format_aspect_mask(self.format);
 
             let (src_access_mask, src_stage) = {
                 match old_layout {
@@ -128,6 +226,10 @@ impl Image {
                             | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
                         vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
                     ),
+                    vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL => (
+                        vk::AccessFlags::SHADER_READ,
+                        vk::PipelineStageFlags::FRAGMENT_SHADER,
+                    ),
                     _ => panic!("unsupported transfer source layout: {:?}", new_layout),
                 }
             };
@@ -141,10 +243,10 @@ impl Image {
                 .subresource_range(
                     vk::ImageSubresourceRange::builder()
                         .aspect_mask(aspect_mask)
-                        .level_count(1)
+                        .level_count(self.mip_levels)
                         .base_mip_level(0)
                         .base_array_layer(0)
-                        .layer_count(1)
+                        .layer_count(self.layers)
                         .build(),
                 )
                 .src_access_mask(src_access_mask)
@@ -162,51 +264,225 @@ impl Image {
         })
     }
 
+    /// Copies tightly packed RGBA8 texels of the first `mip_levels` levels, ordered by mip
+    /// level then by layer
     pub fn copy_from(
         &mut self,
         buffer: &Buffer,
+        mip_levels: u32,
         command_runner: &AdhocCommandRunner,
     ) -> VkResult<()> {
-        command_runner.run_commands_one_shot(|device, command_buffer| {
-            let region = vk::BufferImageCopy::builder()
-                .image_extent(
-                    vk::Extent3D::builder()
-                        .width(self.width)
-                        .height(self.height)
-                        .depth(1)
-                        .build(),
-                )
-                .image_offset(vk::Offset3D::builder().x(0).y(0).z(0).build())
-                .buffer_offset(0)
-                .buffer_row_length(0)
-                .buffer_image_height(0)
-                .image_subresource(
-                    vk::ImageSubresourceLayers::builder()
-                        .layer_count(1)
-                        .base_array_layer(0)
-                        .mip_level(0)
-                        .aspect_mask(vk::ImageAspectFlags::COLOR)
+        let mut regions = vec![];
+        let mut offset = 0;
+        for level in 0..mip_levels {
+            let width = (self.width >> level).max(1);
+            let height = (self.height >> level).max(1);
+            for layer in 0..self.layers {
+                regions.push(
+                    vk::BufferImageCopy::builder()
+                        .image_extent(
+                            vk::Extent3D::builder()
+                                .width(width)
+                                .height(height)
+                                .depth(1)
+                                .build(),
+                        )
+                        .image_offset(vk::Offset3D::builder().x(0).y(0).z(0).build())
+                        .buffer_offset(offset)
+                        .buffer_row_length(0)
+                        .buffer_image_height(0)
+                        .image_subresource(
+                            vk::ImageSubresourceLayers::builder()
+                                .layer_count(1)
+                                .base_array_layer(layer)
+                                .mip_level(level)
+                                .aspect_mask(vk::ImageAspectFlags::COLOR)
+                                .build(),
+                        )
                         .build(),
-                )
-                .build();
+                );
+                offset += (width * height * 4) as u64;
+            }
+        }
+
+        command_runner.run_commands_one_shot(|device, command_buffer| {
             device.cmd_copy_buffer_to_image(
                 *command_buffer,
                 buffer.vk_buffer(),
                 self.vk_image(),
                 vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                &[region],
+                &regions,
             )
         })
     }
 
-    fn new(
+    /// Copies every mip level of a single layer image, starting at the given buffer offsets
+    pub fn copy_levels_from(
+        &mut self,
+        buffer: &Buffer,
+        level_offsets: &[u64],
+        command_runner: &AdhocCommandRunner,
+    ) -> VkResult<()> {
+        let regions: Vec<vk::BufferImageCopy> = level_offsets
+            .iter()
+            .enumerate()
+            .map(|(level, offset)| {
+                vk::BufferImageCopy::builder()
+                    .image_extent(
+                        vk::Extent3D::builder()
+                            .width((self.width >> level).max(1))
+                            .height((self.height >> level).max(1))
+                            .depth(1)
+                            .build(),
+                    )
+                    .image_offset(vk::Offset3D::builder().x(0).y(0).z(0).build())
+                    .buffer_offset(*offset)
+                    .buffer_row_length(0)
+                    .buffer_image_height(0)
+                    .image_subresource(
+                        vk::ImageSubresourceLayers::builder()
+                            .layer_count(1)
+                            .base_array_layer(0)
+                            .mip_level(level as u32)
+                            .aspect_mask(vk::ImageAspectFlags::COLOR)
+                            .build(),
+                    )
+                    .build()
+            })
+            .collect();
+
+        command_runner.run_commands_one_shot(|device, command_buffer| {
+            device.cmd_copy_buffer_to_image(
+                *command_buffer,
+                buffer.vk_buffer(),
+                self.vk_image(),
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &regions,
+            )
+        })
+    }
+
+    /// Fills every level from the first one by halving it repeatedly. The levels are expected
+    /// in TRANSFER_DST_OPTIMAL and are left in SHADER_READ_ONLY_OPTIMAL.
+    pub fn generate_mipmaps(&mut self, command_runner: &AdhocCommandRunner) -> VkResult<()> {
+        command_runner.run_commands_one_shot(|device, command_buffer| {
+            let barrier = |level: u32,
+                           old_layout: vk::ImageLayout,
+                           new_layout: vk::ImageLayout,
+                           src_access_mask: vk::AccessFlags,
+                           dst_access_mask: vk::AccessFlags| {
+                vk::ImageMemoryBarrier::builder()
+                    .old_layout(old_layout)
+                    .new_layout(new_layout)
+                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .image(self.image)
+                    .subresource_range(
+                        vk::ImageSubresourceRange::builder()
+                            .aspect_mask(vk::ImageAspectFlags::COLOR)
+                            .level_count(1)
+                            .base_mip_level(level)
+                            .base_array_layer(0)
+                            .layer_count(self.layers)
+                            .build(),
+                    )
+                    .src_access_mask(src_access_mask)
+                    .dst_access_mask(dst_access_mask)
+                    .build()
+            };
+            let subresource = |level: u32| {
+                vk::ImageSubresourceLayers::builder()
+                    .aspect_mask(vk::ImageAspectFlags::COLOR)
+                    .mip_level(level)
+                    .base_array_layer(0)
+                    .layer_count(self.layers)
+                    .build()
+            };
+            let corner = |level: u32| vk::Offset3D {
+                x: (self.width >> level).max(1) as i32,
+                y: (self.height >> level).max(1) as i32,
+                z: 1,
+            };
+
+            for level in 1..self.mip_levels {
+                // The previous level was just written, it becomes the source of this one
+                device.cmd_pipeline_barrier(
+                    *command_buffer,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::DependencyFlags::default(),
+                    &[],
+                    &[],
+                    &[barrier(
+                        level - 1,
+                        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                        vk::AccessFlags::TRANSFER_WRITE,
+                        vk::AccessFlags::TRANSFER_READ,
+                    )],
+                );
+
+                let blit = vk::ImageBlit::builder()
+                    .src_subresource(subresource(level - 1))
+                    .src_offsets([vk::Offset3D::default(), corner(level - 1)])
+                    .dst_subresource(subresource(level))
+                    .dst_offsets([vk::Offset3D::default(), corner(level)])
+                    .build();
+                device.cmd_blit_image(
+                    *command_buffer,
+                    self.image,
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    self.image,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &[blit],
+                    vk::Filter::LINEAR,
+                );
+            }
+
+            let last = self.mip_levels - 1;
+            let mut barriers: Vec<vk::ImageMemoryBarrier> = (0..last)
+                .map(|level| {
+                    barrier(
+                        level,
+                        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                        vk::AccessFlags::TRANSFER_READ,
+                        vk::AccessFlags::SHADER_READ,
+                    )
+                })
+                .collect();
+            barriers.push(barrier(
+                last,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                vk::AccessFlags::TRANSFER_WRITE,
+                vk::AccessFlags::SHADER_READ,
+            ));
+            device.cmd_pipeline_barrier(
+                *command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::default(),
+                &[],
+                &[],
+                &barriers,
+            );
+        })
+    }
+
+    fn new_with_layers(
         allocator: &Rc<vk_mem::Allocator>,
         tex_width: u32,
         tex_height: u32,
+        mip_levels: u32,
+        layers: u32,
+        flags: vk::ImageCreateFlags,
         format: vk::Format,
+        samples: vk::SampleCountFlags,
         usage: vk::ImageUsageFlags,
     ) -> Result<Self, Box<dyn Error>> {
         let create_info = vk::ImageCreateInfo::builder()
+            .flags(flags)
             .image_type(vk::ImageType::TYPE_2D)
             .extent(
                 vk::Extent3D::builder()
@@ -215,14 +491,14 @@ impl Image {
                     .depth(1)
                     .build(),
             )
-            .mip_levels(1)
-            .array_layers(1)
+            .mip_levels(mip_levels)
+            .array_layers(layers)
             .format(format)
             .tiling(vk::ImageTiling::OPTIMAL)
             .initial_layout(vk::ImageLayout::UNDEFINED)
             .usage(usage)
             .sharing_mode(vk::SharingMode::EXCLUSIVE)
-            .samples(vk::SampleCountFlags::TYPE_1)
+            .samples(samples)
             .build();
 
         let allcation_create_info = vk_mem::AllocationCreateInfo {
@@ -241,6 +517,9 @@ impl Image {
             format,
             width: tex_width,
             height: tex_height,
+            mip_levels,
+            layers,
+            samples,
         })
     }
 
@@ -269,6 +548,29 @@ impl Image {
     }
 }
 
+pub fn is_depth_format(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::D16_UNORM
+            | vk::Format::D32_SFLOAT
+            | vk::Format::D16_UNORM_S8_UINT
+            | vk::Format::D24_UNORM_S8_UINT
+            | vk::Format::D32_SFLOAT_S8_UINT
+    )
+}
+
+pub fn format_aspect_mask(format: vk::Format) -> vk::ImageAspectFlags {
+    match format {
+        vk::Format::D16_UNORM_S8_UINT
+        | vk::Format::D24_UNORM_S8_UINT
+        | vk::Format::D32_SFLOAT_S8_UINT => {
+            vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+        }
+        _ if is_depth_format(format) => vk::ImageAspectFlags::DEPTH,
+        _ => vk::ImageAspectFlags::COLOR,
+    }
+}
+
 impl Drop for Image {
     fn drop(&mut self) {
         self.allocator
@@ -1,12 +1,16 @@
 use ash::prelude::VkResult;
 use ash::vk;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use super::device::Device;
 
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
 pub struct ImageView {
     device: Rc<Device>,
     image_view: vk::ImageView,
+    id: u64,
 }
 
 impl ImageView {
@@ -15,7 +19,89 @@ impl ImageView {
         image: vk::Image,
         format: vk::Format,
     ) -> VkResult<Self> {
-        Self::new(device, image, format, vk::ImageAspectFlags::COLOR)
+        Self::new(
+            device,
+            image,
+            format,
+            vk::ImageAspectFlags::COLOR,
+            vk::ImageViewType::TYPE_2D,
+            1,
+            0,
+            1,
+        )
+    }
+
+    pub fn new_color_image_view_with_mips(
+        device: Rc<Device>,
+        image: vk::Image,
+        format: vk::Format,
+        mip_levels: u32,
+    ) -> VkResult<Self> {
+        Self::new(
+            device,
+            image,
+            format,
+            vk::ImageAspectFlags::COLOR,
+            vk::ImageViewType::TYPE_2D,
+            mip_levels,
+            0,
+            1,
+        )
+    }
+
+    pub fn new_cube_image_view(
+        device: Rc<Device>,
+        image: vk::Image,
+        format: vk::Format,
+        mip_levels: u32,
+    ) -> VkResult<Self> {
+        Self::new(
+            device,
+            image,
+            format,
+            vk::ImageAspectFlags::COLOR,
+            vk::ImageViewType::CUBE,
+            mip_levels,
+            0,
+            6,
+        )
+    }
+
+    /// View of a single layer of a depth image array
+    pub fn new_depth_layer_image_view(
+        device: Rc<Device>,
+        image: vk::Image,
+        format: vk::Format,
+        layer: u32,
+    ) -> VkResult<Self> {
+        Self::new(
+            device,
+            image,
+            format,
+            vk::ImageAspectFlags::DEPTH,
+            vk::ImageViewType::TYPE_2D,
+            1,
+            layer,
+            1,
+        )
+    }
+
+    pub fn new_depth_array_image_view(
+        device: Rc<Device>,
+        image: vk::Image,
+        format: vk::Format,
+        layers: u32,
+    ) -> VkResult<Self> {
+        Self::new(
+            device,
+            image,
+            format,
+            vk::ImageAspectFlags::DEPTH,
+            vk::ImageViewType::TYPE_2D_ARRAY,
+            1,
+            0,
+            layers,
+        )
     }
 
     pub fn new_depth_image_view(
@@ -23,18 +109,36 @@ impl ImageView {
         image: vk::Image,
         format: vk::Format,
     ) -> VkResult<Self> {
-        Self::new(device, image, format, vk::ImageAspectFlags::DEPTH)
+        Self::new(
+            device,
+            image,
+            format,
+            vk::ImageAspectFlags::DEPTH,
+            vk::ImageViewType::TYPE_2D,
+            1,
+            0,
+            1,
+        )
     }
 
     pub fn vk_image_view(&self) -> vk::ImageView {
         self.image_view
     }
 
+    /// Unique among all the views created, unlike the Vulkan handle
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
     fn new(
         device: Rc<Device>,
         image: vk::Image,
         format: vk::Format,
         aspect_mask: vk::ImageAspectFlags,
+        view_type: vk::ImageViewType,
+        mip_levels: u32,
+        base_layer: u32,
+        layers: u32,
     ) -> VkResult<Self> {
         let component_mapping = vk::ComponentMapping::builder()
             .a(vk::ComponentSwizzle::IDENTITY)
@@ -44,22 +148,23 @@ impl ImageView {
             .build();
         let subres_range = vk::ImageSubresourceRange::builder()
             .aspect_mask(aspect_mask)
-            .base_array_layer(0)
-            .layer_count(1)
+            .base_array_layer(base_layer)
+            .layer_count(layers)
             .base_mip_level(0)
-            .level_count(1)
+            .level_count(mip_levels)
             .build();
         let create_info = vk::ImageViewCreateInfo::builder()
             .format(format)
             .image(image)
             .components(component_mapping)
             .subresource_range(subres_range)
-            .view_type(vk::ImageViewType::TYPE_2D)
+            .view_type(view_type)
             .build();
         let view = device.create_image_view(&create_info)?;
         Ok(Self {
             device,
             image_view: view,
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
         })
     }
 }
@@ -1,7 +1,7 @@
 use super::creation_helpers;
 use ash::{
     version::InstanceV1_0,
-    vk::{PhysicalDevice, PhysicalDeviceProperties},
+    vk::{Format, FormatProperties, PhysicalDevice, PhysicalDeviceProperties},
     Entry,
 };
 use std::rc::Rc;
@@ -30,6 +30,17 @@ impl Instance {
                 .get_physical_device_properties(physical_device)
         }
     }
+
+    pub fn get_physical_device_format_properties(
+        &self,
+        physical_device: PhysicalDevice,
+        format: Format,
+    ) -> FormatProperties {
+        unsafe {
+            self.instance
+                .get_physical_device_format_properties(physical_device, format)
+        }
+    }
 }
 
 impl Drop for Instance {
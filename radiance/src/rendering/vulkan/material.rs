@@ -1,13 +1,18 @@
 use super::{device::Device, shader::VulkanShader, texture::VulkanTexture};
 use crate::rendering::vulkan::adhoc_command_runner::AdhocCommandRunner;
 use crate::rendering::{Material, MaterialDef};
+use ash::vk;
 use std::rc::Rc;
 
+/// Material parameters follow the vertex push constants
+pub const MATERIAL_PARAMETERS_OFFSET: u32 = 16;
+
 pub struct VulkanMaterial {
     name: String,
     shader: VulkanShader,
     textures: Vec<VulkanTexture>,
     use_alpha: bool,
+    parameters: Vec<f32>,
 }
 
 impl Material for VulkanMaterial {}
@@ -36,6 +41,7 @@ impl VulkanMaterial {
             shader,
             textures,
             use_alpha: def.use_alpha(),
+            parameters: def.parameters().to_vec(),
         }
     }
 
@@ -54,4 +60,23 @@ impl VulkanMaterial {
     pub fn use_alpha(&self) -> bool {
         self.use_alpha
     }
+
+    pub fn parameters(&self) -> &[f32] {
+        &self.parameters
+    }
+
+    pub fn get_push_constant_ranges(&self) -> Vec<vk::PushConstantRange> {
+        let mut ranges = self.shader.get_push_constant_ranges();
+        if !self.parameters.is_empty() {
+            ranges.push(
+                vk::PushConstantRange::builder()
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .offset(MATERIAL_PARAMETERS_OFFSET)
+                    .size(std::mem::size_of_val(self.parameters.as_slice()) as u32)
+                    .build(),
+            );
+        }
+
+        ranges
+    }
 }
@@ -5,6 +5,7 @@ mod descriptor_managers;
 mod descriptor_pool;
 mod descriptor_set_layout;
 mod device;
+mod environment;
 mod error;
 mod factory;
 mod helpers;
@@ -13,13 +14,20 @@ mod image_view;
 mod imgui;
 mod instance;
 mod material;
+mod morph;
 mod pipeline;
 mod pipeline_layout;
 mod pipeline_manager;
+mod post_processing;
+mod render_graph;
 mod render_object;
 mod render_pass;
+mod render_target;
 mod sampler;
 mod shader;
+mod shadow_map;
+mod skybox;
+mod sprite;
 mod swapchain;
 mod texture;
 mod uniform_buffers;
@@ -0,0 +1,106 @@
+use super::adhoc_command_runner::AdhocCommandRunner;
+use super::buffer::{Buffer, BufferType};
+use super::descriptor_managers::DescriptorManager;
+use crate::math::Vec3;
+use crate::rendering::MorphTarget;
+use ash::vk;
+use std::error::Error;
+use std::rc::Rc;
+
+// Matches `MorphDelta` in morph.vert, padded to the std430 alignment of vec4
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+struct MorphDelta {
+    position: [f32; 4],
+    normal: [f32; 4],
+}
+
+/// Matches the push constants of morph.vert
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct MorphPushConstants {
+    pub target_count: u32,
+    pub vertex_count: u32,
+}
+
+/// Deltas of all the morph targets of a render object in a storage buffer, target after
+/// target, blended in the vertex shader with the weights of a host visible buffer
+pub struct VulkanMorphTargets {
+    // Only read by the shader, through the descriptor set
+    _delta_buffer: Buffer,
+    weight_buffer: Buffer,
+    descriptor_set: vk::DescriptorSet,
+    target_count: usize,
+    vertex_count: usize,
+}
+
+impl VulkanMorphTargets {
+    pub fn new(
+        targets: &[MorphTarget],
+        vertex_count: usize,
+        allocator: &Rc<vk_mem::Allocator>,
+        command_runner: &AdhocCommandRunner,
+        descriptor_manager: &DescriptorManager,
+    ) -> Result<Self, Box<dyn Error>> {
+        // Buffers can't be empty, a single zero delta stands in for no targets
+        let mut deltas = vec![MorphDelta::default(); (targets.len() * vertex_count).max(1)];
+        for (i, target) in targets.iter().enumerate() {
+            let deltas = &mut deltas[i * vertex_count..(i + 1) * vertex_count];
+            for (delta, position) in deltas.iter_mut().zip(&target.position_deltas) {
+                delta.position = to_vec4(position);
+            }
+
+            for (delta, normal) in deltas.iter_mut().zip(target.normal_deltas.iter().flatten()) {
+                delta.normal = to_vec4(normal);
+            }
+        }
+
+        let delta_buffer = Buffer::new_device_buffer_with_data(
+            allocator,
+            BufferType::Storage,
+            &deltas,
+            command_runner,
+        )?;
+        let weight_buffer = Buffer::new_dynamic_buffer_with_data(
+            allocator,
+            BufferType::Storage,
+            &vec![0f32; targets.len().max(1)],
+        )?;
+        let descriptor_set =
+            descriptor_manager.allocate_morph_descriptor_set(&delta_buffer, &weight_buffer)?;
+
+        Ok(Self {
+            _delta_buffer: delta_buffer,
+            weight_buffer,
+            descriptor_set,
+            target_count: targets.len(),
+            vertex_count,
+        })
+    }
+
+    /// Weights past the targets are ignored, missing ones are 0. The GPU must be done with
+    /// the previous frame.
+    pub fn set_weights(&self, weights: &[f32]) {
+        let mut target_weights = vec![0.; self.target_count];
+        for (target_weight, weight) in target_weights.iter_mut().zip(weights) {
+            *target_weight = *weight;
+        }
+
+        self.weight_buffer.copy_memory_from(&target_weights);
+    }
+
+    pub fn push_constants(&self) -> MorphPushConstants {
+        MorphPushConstants {
+            target_count: self.target_count as u32,
+            vertex_count: self.vertex_count as u32,
+        }
+    }
+
+    pub fn vk_descriptor_set(&self) -> vk::DescriptorSet {
+        self.descriptor_set
+    }
+}
+
+fn to_vec4(v: &Vec3) -> [f32; 4] {
+    [v.x, v.y, v.z, 0.]
+}
@@ -20,16 +20,118 @@ impl Pipeline {
         descriptor_manager: &DescriptorManager,
         render_pass: &RenderPass,
         material: &VulkanMaterial,
-        extent: vk::Extent2D,
+        samples: vk::SampleCountFlags,
+        depth_only: bool,
     ) -> Self {
         let descriptor_set_layouts = descriptor_manager.get_vk_descriptor_set_layouts(material);
-        let pipeline_layout = PipelineLayout::new(device.clone(), &descriptor_set_layouts);
+        let push_constant_ranges = material.get_push_constant_ranges();
+        let pipeline_layout = PipelineLayout::new(
+            device.clone(),
+            &descriptor_set_layouts,
+            &push_constant_ranges,
+        );
         let pipeline = Self::create_pipeline(
             &device,
             render_pass.vk_render_pass(),
             pipeline_layout.vk_pipeline_layout(),
-            &extent,
             material.shader(),
+            samples,
+            depth_only,
+        )
+        .unwrap()[0];
+
+        Self {
+            device,
+            pipeline,
+            pipeline_layout,
+        }
+    }
+
+    /// Pipeline drawing a single triangle covering the viewport, without vertex input or depth.
+    /// The viewport and scissor are dynamic so that it can render to targets of any size.
+    pub fn new_fullscreen(
+        device: Rc<Device>,
+        render_pass: &RenderPass,
+        descriptor_set_layouts: &[vk::DescriptorSetLayout],
+        push_constant_ranges: &[vk::PushConstantRange],
+        vert_shader: vk::ShaderModule,
+        frag_shader: vk::ShaderModule,
+    ) -> Self {
+        let pipeline_layout =
+            PipelineLayout::new(device.clone(), descriptor_set_layouts, push_constant_ranges);
+        let pipeline = Self::create_fullscreen_pipeline(
+            &device,
+            render_pass.vk_render_pass(),
+            pipeline_layout.vk_pipeline_layout(),
+            vert_shader,
+            frag_shader,
+            vk::SampleCountFlags::TYPE_1,
+            false,
+        )
+        .unwrap()[0];
+
+        Self {
+            device,
+            pipeline,
+            pipeline_layout,
+        }
+    }
+
+    /// Full screen pipeline drawn into the scene pass before the objects, it neither tests
+    /// nor writes the depth so that everything else covers it
+    pub fn new_background(
+        device: Rc<Device>,
+        render_pass: &RenderPass,
+        descriptor_set_layouts: &[vk::DescriptorSetLayout],
+        push_constant_ranges: &[vk::PushConstantRange],
+        vert_shader: vk::ShaderModule,
+        frag_shader: vk::ShaderModule,
+        samples: vk::SampleCountFlags,
+    ) -> Self {
+        let pipeline_layout =
+            PipelineLayout::new(device.clone(), descriptor_set_layouts, push_constant_ranges);
+        let pipeline = Self::create_fullscreen_pipeline(
+            &device,
+            render_pass.vk_render_pass(),
+            pipeline_layout.vk_pipeline_layout(),
+            vert_shader,
+            frag_shader,
+            samples,
+            true,
+        )
+        .unwrap()[0];
+
+        Self {
+            device,
+            pipeline,
+            pipeline_layout,
+        }
+    }
+
+    /// Pipeline for alpha blended geometry with its own vertex layout, drawn into the scene pass
+    /// after the objects. It tests the depth without writing it and draws both faces.
+    pub fn new_blended(
+        device: Rc<Device>,
+        render_pass: &RenderPass,
+        descriptor_set_layouts: &[vk::DescriptorSetLayout],
+        push_constant_ranges: &[vk::PushConstantRange],
+        vertex_binding: vk::VertexInputBindingDescription,
+        vertex_attributes: &[vk::VertexInputAttributeDescription],
+        vert_shader: vk::ShaderModule,
+        frag_shader: vk::ShaderModule,
+        samples: vk::SampleCountFlags,
+    ) -> Self {
+        let pipeline_layout =
+            PipelineLayout::new(device.clone(), descriptor_set_layouts, push_constant_ranges);
+        let pipeline = Self::create_blended_pipeline(
+            &device,
+            render_pass.vk_render_pass(),
+            pipeline_layout.vk_pipeline_layout(),
+            vertex_binding,
+            vertex_attributes,
+            vert_shader,
+            frag_shader,
+            samples,
         )
         .unwrap()[0];
 
@@ -52,8 +154,9 @@ impl Pipeline {
         device: &Rc<Device>,
         render_pass: vk::RenderPass,
         layout: vk::PipelineLayout,
-        extent: &vk::Extent2D,
         shader: &VulkanShader,
+        samples: vk::SampleCountFlags,
+        depth_only: bool,
     ) -> Result<Vec<vk::Pipeline>, Box<dyn Error>> {
         let entry_point = CString::new("main").unwrap();
         let vert_shader_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
@@ -67,7 +170,7 @@ impl Pipeline {
             .module(shader.vk_frag_shader_module())
             .build();
 
-        let binding_descriptions = [shader.get_binding_description()];
+        let binding_descriptions = shader.get_binding_descriptions();
         let attribute_descriptions = shader.get_attribute_descriptions();
         let pipeline_vertex_input_create_info = vk::PipelineVertexInputStateCreateInfo::builder()
             .vertex_attribute_descriptions(&attribute_descriptions)
@@ -80,25 +183,14 @@ impl Pipeline {
                 .primitive_restart_enable(false)
                 .build();
 
-        let viewport = vk::Viewport::builder()
-            .x(0f32)
-            .y(0f32)
-            .min_depth(0f32)
-            .max_depth(1f32)
-            .height(extent.height as f32)
-            .width(extent.width as f32)
-            .build();
-
-        let scissor = vk::Rect2D::builder()
-            .extent(*extent)
-            .offset(vk::Offset2D::builder().x(0).y(0).build())
-            .build();
-
-        let viewports = [viewport];
-        let scissors = [scissor];
+        // The viewport is set when recording, so that the pipelines work for targets of any size
         let pipeline_viewport_state_create_info = vk::PipelineViewportStateCreateInfo::builder()
-            .viewports(&viewports)
-            .scissors(&scissors)
+            .viewport_count(1)
+            .scissor_count(1)
+            .build();
+        let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+        let pipeline_dynamic_state_create_info = vk::PipelineDynamicStateCreateInfo::builder()
+            .dynamic_states(&dynamic_states)
             .build();
 
         let pipeline_rasterization_state_create_info =
@@ -107,15 +199,21 @@ impl Pipeline {
                 .rasterizer_discard_enable(false)
                 .polygon_mode(vk::PolygonMode::FILL)
                 .line_width(1f32)
-                .cull_mode(vk::CullModeFlags::BACK)
+                .cull_mode(if depth_only {
+                    vk::CullModeFlags::NONE
+                } else {
+                    vk::CullModeFlags::BACK
+                })
                 .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
-                .depth_bias_enable(false)
+                .depth_bias_enable(depth_only)
+                .depth_bias_constant_factor(1.25)
+                .depth_bias_slope_factor(1.75)
                 .build();
 
         let pipeline_multisample_state_create_info =
             vk::PipelineMultisampleStateCreateInfo::builder()
                 .sample_shading_enable(false)
-                .rasterization_samples(vk::SampleCountFlags::TYPE_1)
+                .rasterization_samples(samples)
                 .build();
 
         let pipeline_color_blend_attachment_state =
@@ -135,7 +233,11 @@ impl Pipeline {
                 .color_blend_op(vk::BlendOp::ADD)
                 .build();
 
-        let attachments = [pipeline_color_blend_attachment_state];
+        let attachments = if depth_only {
+            vec![]
+        } else {
+            vec![pipeline_color_blend_attachment_state]
+        };
         let pipeline_color_blending_state_create_info =
             vk::PipelineColorBlendStateCreateInfo::builder()
                 .logic_op_enable(false)
@@ -150,12 +252,18 @@ impl Pipeline {
             .stencil_test_enable(false)
             .build();
 
-        let stages = [vert_shader_stage_create_info, frag_shader_stage_create_info];
+        // Depth only pipelines skip the fragment shader entirely
+        let stages = if depth_only {
+            vec![vert_shader_stage_create_info]
+        } else {
+            vec![vert_shader_stage_create_info, frag_shader_stage_create_info]
+        };
         let create_info = vk::GraphicsPipelineCreateInfo::builder()
             .stages(&stages)
             .vertex_input_state(&pipeline_vertex_input_create_info)
             .input_assembly_state(&pipeline_input_assembly_create_info)
             .viewport_state(&pipeline_viewport_state_create_info)
+            .dynamic_state(&pipeline_dynamic_state_create_info)
             .rasterization_state(&pipeline_rasterization_state_create_info)
             .multisample_state(&pipeline_multisample_state_create_info)
             .color_blend_state(&pipeline_color_blending_state_create_info)
@@ -178,6 +286,231 @@ impl Pipeline {
 
         return pipelines;
     }
+
+    fn create_fullscreen_pipeline(
+        device: &Rc<Device>,
+        render_pass: vk::RenderPass,
+        layout: vk::PipelineLayout,
+        vert_shader: vk::ShaderModule,
+        frag_shader: vk::ShaderModule,
+        samples: vk::SampleCountFlags,
+        has_depth: bool,
+    ) -> Result<Vec<vk::Pipeline>, Box<dyn Error>> {
+        let entry_point = CString::new("main").unwrap();
+        let stages = [
+            vk::PipelineShaderStageCreateInfo::builder()
+                .name(&entry_point)
+                .stage(vk::ShaderStageFlags::VERTEX)
+                .module(vert_shader)
+                .build(),
+            vk::PipelineShaderStageCreateInfo::builder()
+                .name(&entry_point)
+                .stage(vk::ShaderStageFlags::FRAGMENT)
+                .module(frag_shader)
+                .build(),
+        ];
+
+        let pipeline_vertex_input_create_info =
+            vk::PipelineVertexInputStateCreateInfo::builder().build();
+        let pipeline_input_assembly_create_info =
+            vk::PipelineInputAssemblyStateCreateInfo::builder()
+                .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
+                .primitive_restart_enable(false)
+                .build();
+
+        let pipeline_viewport_state_create_info = vk::PipelineViewportStateCreateInfo::builder()
+            .viewport_count(1)
+            .scissor_count(1)
+            .build();
+        let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+        let pipeline_dynamic_state_create_info = vk::PipelineDynamicStateCreateInfo::builder()
+            .dynamic_states(&dynamic_states)
+            .build();
+
+        let pipeline_rasterization_state_create_info =
+            vk::PipelineRasterizationStateCreateInfo::builder()
+                .depth_clamp_enable(false)
+                .rasterizer_discard_enable(false)
+                .polygon_mode(vk::PolygonMode::FILL)
+                .line_width(1f32)
+                .cull_mode(vk::CullModeFlags::NONE)
+                .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+                .depth_bias_enable(false)
+                .build();
+
+        let pipeline_multisample_state_create_info =
+            vk::PipelineMultisampleStateCreateInfo::builder()
+                .sample_shading_enable(false)
+                .rasterization_samples(samples)
+                .build();
+
+        let attachments = [vk::PipelineColorBlendAttachmentState::builder()
+            .color_write_mask(
+                vk::ColorComponentFlags::R
+                    | vk::ColorComponentFlags::G
+                    | vk::ColorComponentFlags::B
+                    | vk::ColorComponentFlags::A,
+            )
+            .blend_enable(false)
+            .build()];
+        let pipeline_color_blending_state_create_info =
+            vk::PipelineColorBlendStateCreateInfo::builder()
+                .logic_op_enable(false)
+                .attachments(&attachments)
+                .build();
+
+        let depth_stencil_state_create_info = vk::PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(false)
+            .depth_write_enable(false)
+            .depth_bounds_test_enable(false)
+            .stencil_test_enable(false)
+            .build();
+
+        let mut create_info = vk::GraphicsPipelineCreateInfo::builder()
+            .stages(&stages)
+            .vertex_input_state(&pipeline_vertex_input_create_info)
+            .input_assembly_state(&pipeline_input_assembly_create_info)
+            .viewport_state(&pipeline_viewport_state_create_info)
+            .dynamic_state(&pipeline_dynamic_state_create_info)
+            .rasterization_state(&pipeline_rasterization_state_create_info)
+            .multisample_state(&pipeline_multisample_state_create_info)
+            .color_blend_state(&pipeline_color_blending_state_create_info)
+            .layout(layout)
+            .render_pass(render_pass)
+            .subpass(0);
+        if has_depth {
+            create_info = create_info.depth_stencil_state(&depth_stencil_state_create_info);
+        }
+
+        let create_info = create_info.build();
+
+        match device.create_graphics_pipelines(&[create_info]) {
+            Ok(p) => Ok(p),
+            Err((p, e)) => {
+                for x in p.into_iter() {
+                    device.destroy_pipeline(x);
+                }
+
+                Err(Box::new(e) as Box<dyn Error>)
+            }
+        }
+    }
+    fn create_blended_pipeline(
+        device: &Rc<Device>,
+        render_pass: vk::RenderPass,
+        layout: vk::PipelineLayout,
+        vertex_binding: vk::VertexInputBindingDescription,
+        vertex_attributes: &[vk::VertexInputAttributeDescription],
+        vert_shader: vk::ShaderModule,
+        frag_shader: vk::ShaderModule,
+        samples: vk::SampleCountFlags,
+    ) -> Result<Vec<vk::Pipeline>, Box<dyn Error>> {
+        let entry_point = CString::new("main").unwrap();
+        let stages = [
+            vk::PipelineShaderStageCreateInfo::builder()
+                .name(&entry_point)
+                .stage(vk::ShaderStageFlags::VERTEX)
+                .module(vert_shader)
+                .build(),
+            vk::PipelineShaderStageCreateInfo::builder()
+                .name(&entry_point)
+                .stage(vk::ShaderStageFlags::FRAGMENT)
+                .module(frag_shader)
+                .build(),
+        ];
+
+        let binding_descriptions = [vertex_binding];
+        let pipeline_vertex_input_create_info = vk::PipelineVertexInputStateCreateInfo::builder()
+            .vertex_attribute_descriptions(vertex_attributes)
+            .vertex_binding_descriptions(&binding_descriptions)
+            .build();
+        let pipeline_input_assembly_create_info =
+            vk::PipelineInputAssemblyStateCreateInfo::builder()
+                .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
+                .primitive_restart_enable(false)
+                .build();
+
+        let pipeline_viewport_state_create_info = vk::PipelineViewportStateCreateInfo::builder()
+            .viewport_count(1)
+            .scissor_count(1)
+            .build();
+        let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+        let pipeline_dynamic_state_create_info = vk::PipelineDynamicStateCreateInfo::builder()
+            .dynamic_states(&dynamic_states)
+            .build();
+
+        let pipeline_rasterization_state_create_info =
+            vk::PipelineRasterizationStateCreateInfo::builder()
+                .depth_clamp_enable(false)
+                .rasterizer_discard_enable(false)
+                .polygon_mode(vk::PolygonMode::FILL)
+                .line_width(1f32)
+                .cull_mode(vk::CullModeFlags::NONE)
+                .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+                .depth_bias_enable(false)
+                .build();
+
+        let pipeline_multisample_state_create_info =
+            vk::PipelineMultisampleStateCreateInfo::builder()
+                .sample_shading_enable(false)
+                .rasterization_samples(samples)
+                .build();
+
+        let attachments = [vk::PipelineColorBlendAttachmentState::builder()
+            .color_write_mask(
+                vk::ColorComponentFlags::R
+                    | vk::ColorComponentFlags::G
+                    | vk::ColorComponentFlags::B
+                    | vk::ColorComponentFlags::A,
+            )
+            .blend_enable(true)
+            .src_alpha_blend_factor(vk::BlendFactor::ONE)
+            .dst_alpha_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .alpha_blend_op(vk::BlendOp::ADD)
+            .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+            .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .color_blend_op(vk::BlendOp::ADD)
+            .build()];
+        let pipeline_color_blending_state_create_info =
+            vk::PipelineColorBlendStateCreateInfo::builder()
+                .logic_op_enable(false)
+                .attachments(&attachments)
+                .build();
+
+        let depth_stencil_state_create_info = vk::PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(true)
+            .depth_write_enable(false)
+            .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL)
+            .depth_bounds_test_enable(false)
+            .stencil_test_enable(false)
+            .build();
+
+        let create_info = vk::GraphicsPipelineCreateInfo::builder()
+            .stages(&stages)
+            .vertex_input_state(&pipeline_vertex_input_create_info)
+            .input_assembly_state(&pipeline_input_assembly_create_info)
+            .viewport_state(&pipeline_viewport_state_create_info)
+            .dynamic_state(&pipeline_dynamic_state_create_info)
+            .rasterization_state(&pipeline_rasterization_state_create_info)
+            .multisample_state(&pipeline_multisample_state_create_info)
+            .color_blend_state(&pipeline_color_blending_state_create_info)
+            .depth_stencil_state(&depth_stencil_state_create_info)
+            .layout(layout)
+            .render_pass(render_pass)
+            .subpass(0)
+            .build();
+
+        match device.create_graphics_pipelines(&[create_info]) {
+            Ok(p) => Ok(p),
+            Err((p, e)) => {
+                for x in p.into_iter() {
+                    device.destroy_pipeline(x);
+                }
+
+                Err(Box::new(e) as Box<dyn Error>)
+            }
+        }
+    }
 }
 
 impl Drop for Pipeline {
@@ -9,9 +9,14 @@ pub struct PipelineLayout {
 }
 
 impl PipelineLayout {
-    pub fn new(device: Rc<Device>, descriptor_set_layouts: &[vk::DescriptorSetLayout]) -> Self {
+    pub fn new(
+        device: Rc<Device>,
+        descriptor_set_layouts: &[vk::DescriptorSetLayout],
+        push_constant_ranges: &[vk::PushConstantRange],
+    ) -> Self {
         let pipeline_layout =
-            Self::create_pipeline_layout(&device, descriptor_set_layouts).unwrap();
+            Self::create_pipeline_layout(&device, descriptor_set_layouts, push_constant_ranges)
+                .unwrap();
 
         Self {
             device,
@@ -26,9 +31,11 @@ impl PipelineLayout {
     fn create_pipeline_layout(
         device: &Rc<Device>,
         descriptor_set_layouts: &[vk::DescriptorSetLayout],
+        push_constant_ranges: &[vk::PushConstantRange],
     ) -> VkResult<vk::PipelineLayout> {
         let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo::builder()
             .set_layouts(descriptor_set_layouts)
+            .push_constant_ranges(push_constant_ranges)
             .build();
         device.create_pipeline_layout(&pipeline_layout_create_info)
     }
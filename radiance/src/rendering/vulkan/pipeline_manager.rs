@@ -1,7 +1,10 @@
-use super::{device::Device, material::VulkanMaterial};
-use super::{pipeline::Pipeline, render_pass::RenderPass};
+use super::morph::MorphPushConstants;
+use super::{device::Device, material::VulkanMaterial, material::MATERIAL_PARAMETERS_OFFSET};
+use super::{pipeline::Pipeline, render_object::VulkanRenderObject, render_pass::RenderPass};
 use crate::rendering::vulkan::descriptor_managers::DescriptorManager;
+use crate::rendering::vulkan::uniform_buffers::DynamicUniformBufferManager;
 use ash::vk;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::rc::Rc;
 
@@ -10,9 +13,10 @@ pub struct PipelineManager {
     descriptor_manager: Rc<DescriptorManager>,
     color_format: vk::Format,
     depth_format: vk::Format,
-    extent: vk::Extent2D,
+    samples: vk::SampleCountFlags,
     render_pass: RenderPass,
     pipelines: HashMap<String, Pipeline>,
+    depth_only: bool,
 }
 
 impl PipelineManager {
@@ -21,18 +25,39 @@ impl PipelineManager {
         descriptor_manager: &Rc<DescriptorManager>,
         color_format: vk::Format,
         depth_format: vk::Format,
-        extent: vk::Extent2D,
+        samples: vk::SampleCountFlags,
     ) -> Self {
-        let render_pass = RenderPass::new(device.clone(), color_format, depth_format);
+        let render_pass = RenderPass::new(device.clone(), color_format, depth_format, samples);
 
         Self {
             device,
             descriptor_manager: descriptor_manager.clone(),
             color_format,
             depth_format,
-            extent,
+            samples,
             render_pass,
             pipelines: HashMap::new(),
+            depth_only: false,
+        }
+    }
+
+    /// Pipelines rendering depth only, with the material's vertex shader
+    pub fn new_depth_only(
+        device: Rc<Device>,
+        descriptor_manager: &Rc<DescriptorManager>,
+        depth_format: vk::Format,
+    ) -> Self {
+        let render_pass = RenderPass::new_depth_only(device.clone(), depth_format);
+
+        Self {
+            device,
+            descriptor_manager: descriptor_manager.clone(),
+            color_format: vk::Format::UNDEFINED,
+            depth_format,
+            samples: vk::SampleCountFlags::TYPE_1,
+            render_pass,
+            pipelines: HashMap::new(),
+            depth_only: true,
         }
     }
 
@@ -46,7 +71,8 @@ impl PipelineManager {
                     &self.descriptor_manager,
                     &self.render_pass,
                     material,
-                    self.extent,
+                    self.samples,
+                    self.depth_only,
                 ),
             );
         }
@@ -61,4 +87,152 @@ impl PipelineManager {
     pub fn render_pass(&self) -> &RenderPass {
         &self.render_pass
     }
+
+    /// Records the draw calls of the objects into a render pass compatible with this manager,
+    /// transparent materials are drawn last
+    pub fn record_draw_commands(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        viewport: vk::Rect2D,
+        per_frame_descriptor_set: vk::DescriptorSet,
+        objects: &[&VulkanRenderObject],
+        dub_manager: &DynamicUniformBufferManager,
+        bone_manager: &DynamicUniformBufferManager,
+    ) {
+        let mut objects_by_material = vec![];
+        for obj in objects {
+            self.create_pipeline_if_not_exist(obj.material());
+            objects_by_material.push((obj.material(), vec![obj]));
+        }
+
+        self.device.cmd_set_viewport(
+            command_buffer,
+            0,
+            &[vk::Viewport::builder()
+                .x(viewport.offset.x as f32)
+                .y(viewport.offset.y as f32)
+                .width(viewport.extent.width as f32)
+                .height(viewport.extent.height as f32)
+                .min_depth(0.)
+                .max_depth(1.)
+                .build()],
+        );
+        self.device.cmd_set_scissor(command_buffer, 0, &[viewport]);
+
+        objects_by_material.sort_by(|a, b| {
+            if a.0.use_alpha() && !b.0.use_alpha() {
+                Ordering::Greater
+            } else if !a.0.use_alpha() && b.0.use_alpha() {
+                Ordering::Less
+            } else {
+                Ordering::Equal
+            }
+        });
+
+        for (material, object_group) in &objects_by_material {
+            let pipeline = self.pipelines.get(material.name()).unwrap();
+
+            self.device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.vk_pipeline(),
+            );
+
+            for obj in object_group {
+                if obj.instance_count() == 0 {
+                    continue;
+                }
+
+                let vertex_buffer = obj.vertex_buffer();
+                let index_buffer = obj.index_buffer();
+                self.device.cmd_bind_vertex_buffers(
+                    command_buffer,
+                    0,
+                    &[vertex_buffer.vk_buffer()],
+                    &[0],
+                );
+                if let Some(instance_buffer) = obj.instance_buffer() {
+                    self.device.cmd_bind_vertex_buffers(
+                        command_buffer,
+                        1,
+                        &[instance_buffer],
+                        &[0],
+                    );
+                }
+                self.device.cmd_bind_index_buffer(
+                    command_buffer,
+                    index_buffer.vk_buffer(),
+                    0,
+                    vk::IndexType::UINT32,
+                );
+
+                let mut descriptor_sets = vec![
+                    per_frame_descriptor_set,
+                    dub_manager.descriptor_set(),
+                    obj.vk_descriptor_set(),
+                ];
+                let mut dynamic_offsets = vec![dub_manager.get_offset(obj.dub_index()) as u32];
+                if let Some(bone_index) = obj.bone_index() {
+                    descriptor_sets.push(bone_manager.descriptor_set());
+                    dynamic_offsets.push(bone_manager.get_offset(bone_index) as u32);
+                }
+
+                if let Some(morph_targets) = obj.morph_targets() {
+                    descriptor_sets.push(morph_targets.vk_descriptor_set());
+                }
+
+                self.device.cmd_bind_descriptor_sets(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    pipeline.pipeline_layout().vk_pipeline_layout(),
+                    0,
+                    &descriptor_sets,
+                    &dynamic_offsets,
+                );
+
+                if let Some(morph_targets) = obj.morph_targets() {
+                    let push_constants = morph_targets.push_constants();
+                    let constants = unsafe {
+                        std::slice::from_raw_parts(
+                            &push_constants as *const MorphPushConstants as *const u8,
+                            std::mem::size_of_val(&push_constants),
+                        )
+                    };
+                    self.device.cmd_push_constants(
+                        command_buffer,
+                        pipeline.pipeline_layout().vk_pipeline_layout(),
+                        vk::ShaderStageFlags::VERTEX,
+                        0,
+                        constants,
+                    );
+                }
+
+                if !material.parameters().is_empty() {
+                    let parameters = material.parameters();
+                    let constants = unsafe {
+                        std::slice::from_raw_parts(
+                            parameters.as_ptr() as *const u8,
+                            std::mem::size_of_val(parameters),
+                        )
+                    };
+                    self.device.cmd_push_constants(
+                        command_buffer,
+                        pipeline.pipeline_layout().vk_pipeline_layout(),
+                        vk::ShaderStageFlags::FRAGMENT,
+                        MATERIAL_PARAMETERS_OFFSET,
+                        constants,
+                    );
+                }
+
+                self.device.cmd_draw_indexed(
+                    command_buffer,
+                    index_buffer.element_count(),
+                    obj.instance_count(),
+                    0,
+                    0,
+                    0,
+                );
+            }
+        }
+    }
 }
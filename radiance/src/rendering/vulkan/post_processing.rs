@@ -0,0 +1,392 @@
+use super::creation_helpers;
+use super::descriptor_pool::{DescriptorPool, DescriptorPoolCreateInfo};
+use super::descriptor_set_layout::DescriptorSetLayout;
+use super::device::Device;
+use super::pipeline::Pipeline;
+use super::render_graph::{PassContext, RenderGraph, RenderGraphPass, ResourceHandle};
+use super::render_pass::RenderPass;
+use super::sampler::Sampler;
+use super::shader::VulkanShader;
+use super::texture::VulkanTexture;
+use crate::rendering::{PostProcessing, ToneMapping};
+use ash::prelude::VkResult;
+use ash::vk;
+use std::error::Error;
+use std::rc::Rc;
+
+/// Format of the offscreen target the scene is rendered to
+pub const HDR_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+const BLOOM_MIP_COUNT: usize = 5;
+
+// Every post-processing shader declares the same `sampler2D inputs[3]`
+const INPUT_COUNT: usize = 3;
+const MAX_PASSES_PER_FRAME: u32 = 32;
+
+static FULLSCREEN_VERT: &'static [u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/fullscreen.vert.spv"));
+static BLOOM_DOWN_FRAG: &'static [u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/bloom_down.frag.spv"));
+static BLOOM_UP_FRAG: &'static [u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/bloom_up.frag.spv"));
+static COMPOSITE_FRAG: &'static [u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/composite.frag.spv"));
+static FXAA_FRAG: &'static [u8] = include_bytes!(concat!(env!("OUT_DIR"), "/fxaa.frag.spv"));
+
+#[derive(Copy, Clone)]
+enum PassInput<'a> {
+    Resource(ResourceHandle),
+    Texture(&'a VulkanTexture),
+}
+
+/// Full screen passes turning the HDR scene into the displayed image
+pub struct PostProcessor {
+    device: Rc<Device>,
+    descriptor_pool: DescriptorPool,
+    descriptor_set_layout: DescriptorSetLayout,
+    sampler: Sampler,
+    output_render_pass: RenderPass,
+    bloom_down: Pipeline,
+    bloom_up: Pipeline,
+    composite: Pipeline,
+    fxaa: Pipeline,
+}
+
+impl PostProcessor {
+    pub fn new(device: Rc<Device>, output_format: vk::Format) -> Result<Self, Box<dyn Error>> {
+        let descriptor_pool = DescriptorPool::new(
+            device.clone(),
+            &[DescriptorPoolCreateInfo {
+                ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: MAX_PASSES_PER_FRAME * INPUT_COUNT as u32,
+            }],
+        );
+        let descriptor_set_layout = DescriptorSetLayout::new(
+            device.clone(),
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            vk::ShaderStageFlags::FRAGMENT,
+            INPUT_COUNT as u32,
+        );
+        let sampler = Sampler::new_clamp_sampler(device.clone())?;
+
+        // Only used to create compatible pipelines, the render graph creates the real ones
+        let hdr_render_pass = Self::create_render_pass(&device, HDR_FORMAT)?;
+        let output_render_pass = Self::create_render_pass(&device, output_format)?;
+
+        let set_layouts = [descriptor_set_layout.vk_layout()];
+        let push_constant_ranges = [vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .offset(0)
+            .size(std::mem::size_of::<[f32; 8]>() as u32)
+            .build()];
+        let vert_shader = VulkanShader::create_shader_module_from_memory(&device, FULLSCREEN_VERT)?;
+        let create_pipeline = |render_pass: &RenderPass, frag_src: &[u8]| {
+            let frag_shader = VulkanShader::create_shader_module_from_memory(&device, frag_src)?;
+            let pipeline = Pipeline::new_fullscreen(
+                device.clone(),
+                render_pass,
+                &set_layouts,
+                &push_constant_ranges,
+                vert_shader,
+                frag_shader,
+            );
+
+            // Pipelines don't need the modules once they are created
+            device.destroy_shader_module(frag_shader);
+            Ok::<Pipeline, Box<dyn Error>>(pipeline)
+        };
+
+        let bloom_down = create_pipeline(&hdr_render_pass, BLOOM_DOWN_FRAG)?;
+        let bloom_up = create_pipeline(&hdr_render_pass, BLOOM_UP_FRAG)?;
+        let composite = create_pipeline(&output_render_pass, COMPOSITE_FRAG)?;
+        let fxaa = create_pipeline(&output_render_pass, FXAA_FRAG)?;
+        device.destroy_shader_module(vert_shader);
+
+        Ok(Self {
+            device,
+            descriptor_pool,
+            descriptor_set_layout,
+            sampler,
+            output_render_pass,
+            bloom_down,
+            bloom_up,
+            composite,
+            fxaa,
+        })
+    }
+
+    /// Render pass compatible with the passes writing to the output format
+    pub fn output_render_pass(&self) -> &RenderPass {
+        &self.output_render_pass
+    }
+
+    /// Adds the passes reading the HDR `scene` and writing `output`. The descriptor sets of
+    /// the previous frame are released, so it must no longer be in flight.
+    pub fn add_passes<'a>(
+        &'a self,
+        graph: &mut RenderGraph<'a>,
+        scene: ResourceHandle,
+        output: ResourceHandle,
+        output_format: vk::Format,
+        extent: vk::Extent2D,
+        settings: &PostProcessing,
+        lut: Option<&'a VulkanTexture>,
+    ) {
+        self.descriptor_pool.reset();
+
+        let bloom = settings.bloom.map(|bloom| {
+            let blurred = self.add_bloom_passes(graph, scene, extent, bloom.threshold);
+            (blurred, bloom.intensity)
+        });
+
+        // With an sRGB output the tone mapped image is sRGB as well, FXAA then blends the
+        // decoded colors
+        let composite_output = if settings.fxaa {
+            graph.create_transient_image("tone_mapped", output_format, extent)
+        } else {
+            output
+        };
+
+        let tone_mapping = match settings.tone_mapping {
+            ToneMapping::None => 0.,
+            ToneMapping::Reinhard => 1.,
+            ToneMapping::Aces => 2.,
+        };
+        let vignette = settings
+            .vignette
+            .map_or([0., 0.], |v| [v.intensity, v.smoothness]);
+        let params = [
+            settings.exposure,
+            settings.gamma,
+            tone_mapping,
+            bloom.map_or(0., |(_, intensity)| intensity),
+            vignette[0],
+            vignette[1],
+            lut.map_or(0., |lut| lut.image().height() as f32),
+            if creation_helpers::is_srgb_format(output_format) {
+                1.
+            } else {
+                0.
+            },
+        ];
+
+        let mut inputs = vec![PassInput::Resource(scene)];
+        if let Some((blurred, _)) = bloom {
+            inputs.push(PassInput::Resource(blurred));
+        }
+
+        if let Some(lut) = lut {
+            inputs.resize(2, PassInput::Resource(scene));
+            inputs.push(PassInput::Texture(lut));
+        }
+
+        graph.add_pass(self.fullscreen_pass(
+            "composite",
+            &self.composite,
+            inputs,
+            params,
+            composite_output,
+        ));
+
+        if settings.fxaa {
+            graph.add_pass(self.fullscreen_pass(
+                "fxaa",
+                &self.fxaa,
+                vec![PassInput::Resource(composite_output)],
+                [0.; 8],
+                output,
+            ));
+        }
+    }
+
+    // Downsamples the bright parts of the scene through a mip chain, then upsamples it back
+    // while accumulating every level, which gives a wide blur for a few taps per pixel
+    fn add_bloom_passes<'a>(
+        &'a self,
+        graph: &mut RenderGraph<'a>,
+        scene: ResourceHandle,
+        extent: vk::Extent2D,
+        threshold: f32,
+    ) -> ResourceHandle {
+        let mut sizes = vec![];
+        let mut size = extent;
+        while sizes.len() < BLOOM_MIP_COUNT && size.width > 1 && size.height > 1 {
+            size = vk::Extent2D {
+                width: size.width / 2,
+                height: size.height / 2,
+            };
+            sizes.push(size);
+        }
+
+        let mut source = scene;
+        let mut levels = vec![];
+        for (i, size) in sizes.iter().enumerate() {
+            let target = graph.create_transient_image("bloom_down", HDR_FORMAT, *size);
+            let prefilter = if i == 0 { 1. } else { 0. };
+            graph.add_pass(self.fullscreen_pass(
+                "bloom_down",
+                &self.bloom_down,
+                vec![PassInput::Resource(source)],
+                [threshold, prefilter, 0., 0., 0., 0., 0., 0.],
+                target,
+            ));
+            levels.push(target);
+            source = target;
+        }
+
+        let mut blurred = source;
+        for (level, size) in levels.iter().zip(&sizes).rev().skip(1) {
+            let target = graph.create_transient_image("bloom_up", HDR_FORMAT, *size);
+            graph.add_pass(self.fullscreen_pass(
+                "bloom_up",
+                &self.bloom_up,
+                vec![PassInput::Resource(blurred), PassInput::Resource(*level)],
+                [0.; 8],
+                target,
+            ));
+            blurred = target;
+        }
+
+        blurred
+    }
+
+    fn fullscreen_pass<'a>(
+        &'a self,
+        name: &'static str,
+        pipeline: &'a Pipeline,
+        inputs: Vec<PassInput<'a>>,
+        params: [f32; 8],
+        output: ResourceHandle,
+    ) -> RenderGraphPass<'a> {
+        let mut sampled: Vec<ResourceHandle> = vec![];
+        for input in &inputs {
+            if let PassInput::Resource(resource) = input {
+                if !sampled.contains(resource) {
+                    sampled.push(*resource);
+                }
+            }
+        }
+
+        let mut pass = RenderGraphPass::new(name, move |context| {
+            self.record_fullscreen(context, pipeline, &inputs, &params, output)
+        })
+        .with_color_attachment(output, Some([0., 0., 0., 1.]));
+        for resource in sampled {
+            pass = pass.with_sampled_input(resource);
+        }
+
+        pass
+    }
+
+    fn record_fullscreen(
+        &self,
+        context: &PassContext,
+        pipeline: &Pipeline,
+        inputs: &[PassInput],
+        params: &[f32; 8],
+        output: ResourceHandle,
+    ) {
+        let layouts = [self.descriptor_set_layout.vk_layout()];
+        let allocate_info = vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(self.descriptor_pool.vk_pool())
+            .set_layouts(&layouts)
+            .build();
+        let descriptor_set = self
+            .device
+            .allocate_descriptor_sets(&allocate_info)
+            .unwrap()[0];
+
+        // Inputs the shader doesn't read still need a valid descriptor
+        let image_infos: Vec<vk::DescriptorImageInfo> = (0..INPUT_COUNT)
+            .map(|i| {
+                let (view, sampler) = match inputs.get(i).unwrap_or(&inputs[0]) {
+                    PassInput::Resource(resource) => {
+                        (context.image(*resource).view, self.sampler.vk_sampler())
+                    }
+                    PassInput::Texture(texture) => (
+                        texture.image_view().vk_image_view(),
+                        texture.sampler().vk_sampler(),
+                    ),
+                };
+                vk::DescriptorImageInfo::builder()
+                    .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                    .image_view(view)
+                    .sampler(sampler)
+                    .build()
+            })
+            .collect();
+        let write_descriptor_sets = [vk::WriteDescriptorSet::builder()
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .dst_set(descriptor_set)
+            .dst_binding(0)
+            .dst_array_element(0)
+            .image_info(&image_infos)
+            .build()];
+        self.device
+            .update_descriptor_sets(&write_descriptor_sets, &[]);
+
+        let command_buffer = context.command_buffer;
+        let extent = context.image(output).extent;
+        let layout = pipeline.pipeline_layout().vk_pipeline_layout();
+        self.device.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            pipeline.vk_pipeline(),
+        );
+        self.device.cmd_set_viewport(
+            command_buffer,
+            0,
+            &[vk::Viewport::builder()
+                .x(0.)
+                .y(0.)
+                .width(extent.width as f32)
+                .height(extent.height as f32)
+                .min_depth(0.)
+                .max_depth(1.)
+                .build()],
+        );
+        self.device.cmd_set_scissor(
+            command_buffer,
+            0,
+            &[vk::Rect2D::builder()
+                .offset(vk::Offset2D::builder().x(0).y(0).build())
+                .extent(extent)
+                .build()],
+        );
+        self.device.cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            layout,
+            0,
+            &[descriptor_set],
+            &[],
+        );
+        self.device.cmd_push_constants(
+            command_buffer,
+            layout,
+            vk::ShaderStageFlags::FRAGMENT,
+            0,
+            unsafe {
+                std::slice::from_raw_parts(
+                    params.as_ptr() as *const u8,
+                    std::mem::size_of::<[f32; 8]>(),
+                )
+            },
+        );
+        self.device.cmd_draw(command_buffer, 3, 1, 0, 0);
+    }
+
+    fn create_render_pass(device: &Rc<Device>, format: vk::Format) -> VkResult<RenderPass> {
+        let attachment = vk::AttachmentDescription::builder()
+            .format(format)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .final_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .build();
+        RenderPass::new_with_attachments(device.clone(), &[attachment], &[], None)
+    }
+}
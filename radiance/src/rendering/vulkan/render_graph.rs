@@ -0,0 +1,939 @@
+use super::device::Device;
+use super::image::{format_aspect_mask, is_depth_format, Image};
+use super::image_view::ImageView;
+use super::render_pass::RenderPass;
+use ash::vk;
+use std::collections::HashMap;
+use std::error::Error;
+use std::rc::Rc;
+
+/// Image read or written by the passes of a graph
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ResourceHandle(usize);
+
+#[derive(Copy, Clone)]
+pub struct GraphImage {
+    pub image: vk::Image,
+    pub view: vk::ImageView,
+    /// Unlike `view`, never reused by another view once it has been destroyed
+    pub view_id: u64,
+    pub format: vk::Format,
+    pub extent: vk::Extent2D,
+    pub layers: u32,
+    pub samples: vk::SampleCountFlags,
+}
+
+impl GraphImage {
+    pub fn from_image(image: &Image, view: &ImageView) -> Self {
+        Self {
+            image: image.vk_image(),
+            view: view.vk_image_view(),
+            view_id: view.id(),
+            format: image.vk_format(),
+            extent: vk::Extent2D {
+                width: image.width(),
+                height: image.height(),
+            },
+            layers: image.layers(),
+            samples: image.samples(),
+        }
+    }
+}
+
+/// What a pass sees while it is recorded
+pub struct PassContext<'c> {
+    pub command_buffer: vk::CommandBuffer,
+    images: &'c [Option<GraphImage>],
+}
+
+impl<'c> PassContext<'c> {
+    /// The image backing a resource this frame, transient images may change between frames
+    pub fn image(&self, resource: ResourceHandle) -> GraphImage {
+        self.images[resource.0].expect("the resource is not used by any pass")
+    }
+}
+
+/// A step of the frame. Passes with attachments are recorded inside a render pass created by
+/// the graph, the others record their own render passes and declare what they write.
+pub struct RenderGraphPass<'a> {
+    name: &'static str,
+    color_attachments: Vec<(ResourceHandle, Option<[f32; 4]>)>,
+    resolve_attachments: Vec<Option<ResourceHandle>>,
+    depth_attachment: Option<(ResourceHandle, Option<f32>)>,
+    sampled_inputs: Vec<ResourceHandle>,
+    external_outputs: Vec<(ResourceHandle, vk::ImageLayout)>,
+    render_area: Option<vk::Rect2D>,
+    record: Box<dyn FnOnce(&PassContext) + 'a>,
+}
+
+impl<'a> RenderGraphPass<'a> {
+    pub fn new<F: FnOnce(&PassContext) + 'a>(name: &'static str, record: F) -> Self {
+        Self {
+            name,
+            color_attachments: vec![],
+            resolve_attachments: vec![],
+            depth_attachment: None,
+            sampled_inputs: vec![],
+            external_outputs: vec![],
+            render_area: None,
+            record: Box::new(record),
+        }
+    }
+
+    /// Color output, cleared when `clear` is set and loaded otherwise
+    pub fn with_color_attachment(
+        mut self,
+        resource: ResourceHandle,
+        clear: Option<[f32; 4]>,
+    ) -> Self {
+        self.color_attachments.push((resource, clear));
+        self.resolve_attachments.push(None);
+        self
+    }
+
+    /// Single sampled image the previous color attachment is resolved into at the end of the
+    /// pass
+    pub fn with_resolve_attachment(mut self, resource: ResourceHandle) -> Self {
+        *self
+            .resolve_attachments
+            .last_mut()
+            .expect("resolve attachments must follow a color attachment") = Some(resource);
+        self
+    }
+
+    /// Depth output, cleared when `clear` is set and loaded otherwise
+    pub fn with_depth_attachment(mut self, resource: ResourceHandle, clear: Option<f32>) -> Self {
+        self.depth_attachment = Some((resource, clear));
+        self
+    }
+
+    /// Image read by the shaders of the pass
+    pub fn with_sampled_input(mut self, resource: ResourceHandle) -> Self {
+        self.sampled_inputs.push(resource);
+        self
+    }
+
+    /// Image written by render passes the pass records itself, expected in `layout` when the
+    /// pass starts and left in it when the pass ends
+    pub fn with_external_output(
+        mut self,
+        resource: ResourceHandle,
+        layout: vk::ImageLayout,
+    ) -> Self {
+        self.external_outputs.push((resource, layout));
+        self
+    }
+
+    /// Part of the attachments the pass clears, loads and stores, they are left untouched
+    /// outside of it. Defaults to the whole attachments.
+    pub fn with_render_area(mut self, render_area: vk::Rect2D) -> Self {
+        self.render_area = Some(render_area);
+        self
+    }
+
+    fn writes(&self) -> impl Iterator<Item = ResourceHandle> + '_ {
+        self.color_attachments
+            .iter()
+            .map(|(r, _)| *r)
+            .chain(self.resolve_attachments.iter().flatten().copied())
+            .chain(self.depth_attachment.iter().map(|(r, _)| *r))
+            .chain(self.external_outputs.iter().map(|(r, _)| *r))
+    }
+
+    fn reads(&self) -> impl Iterator<Item = ResourceHandle> + '_ {
+        let loaded_color = self
+            .color_attachments
+            .iter()
+            .filter(|(_, clear)| clear.is_none())
+            .map(|(r, _)| *r);
+        let loaded_depth = self
+            .depth_attachment
+            .iter()
+            .filter(|(_, clear)| clear.is_none())
+            .map(|(r, _)| *r);
+        loaded_color
+            .chain(loaded_depth)
+            .chain(self.external_outputs.iter().map(|(r, _)| *r))
+            .chain(self.sampled_inputs.iter().copied())
+    }
+
+    fn has_attachments(&self) -> bool {
+        !self.color_attachments.is_empty() || self.depth_attachment.is_some()
+    }
+}
+
+enum ResourceSource {
+    Imported {
+        image: GraphImage,
+        initial_layout: vk::ImageLayout,
+        final_layout: Option<vk::ImageLayout>,
+    },
+    Transient {
+        format: vk::Format,
+        extent: vk::Extent2D,
+        samples: vk::SampleCountFlags,
+    },
+}
+
+struct GraphResource {
+    name: &'static str,
+    source: ResourceSource,
+}
+
+/// Passes and resources of a frame. Executing the graph culls the passes whose outputs are
+/// never used, inserts the barriers and layout transitions between passes and allocates the
+/// transient images, reusing them across frames and between resources that are not alive at
+/// the same time.
+pub struct RenderGraph<'a> {
+    resources: Vec<GraphResource>,
+    passes: Vec<RenderGraphPass<'a>>,
+}
+
+impl<'a> RenderGraph<'a> {
+    pub fn new() -> Self {
+        Self {
+            resources: vec![],
+            passes: vec![],
+        }
+    }
+
+    /// Image owned outside of the graph. `final_layout` is the layout it is left in at the end
+    /// of the graph, otherwise it keeps the layout of its last use.
+    pub fn import_image(
+        &mut self,
+        name: &'static str,
+        image: GraphImage,
+        initial_layout: vk::ImageLayout,
+        final_layout: Option<vk::ImageLayout>,
+    ) -> ResourceHandle {
+        self.add_resource(
+            name,
+            ResourceSource::Imported {
+                image,
+                initial_layout,
+                final_layout,
+            },
+        )
+    }
+
+    /// Image allocated by the graph, its contents are undefined until a pass writes it
+    pub fn create_transient_image(
+        &mut self,
+        name: &'static str,
+        format: vk::Format,
+        extent: vk::Extent2D,
+    ) -> ResourceHandle {
+        self.create_multisampled_image(name, format, extent, vk::SampleCountFlags::TYPE_1)
+    }
+
+    /// Transient image with several samples per pixel, to be resolved by the pass writing it
+    pub fn create_multisampled_image(
+        &mut self,
+        name: &'static str,
+        format: vk::Format,
+        extent: vk::Extent2D,
+        samples: vk::SampleCountFlags,
+    ) -> ResourceHandle {
+        self.add_resource(
+            name,
+            ResourceSource::Transient {
+                format,
+                extent,
+                samples,
+            },
+        )
+    }
+
+    /// Passes are executed in the order they are added
+    pub fn add_pass(&mut self, pass: RenderGraphPass<'a>) {
+        self.passes.push(pass);
+    }
+
+    pub fn execute(
+        self,
+        cache: &mut RenderGraphCache,
+        command_buffer: vk::CommandBuffer,
+    ) -> Result<(), Box<dyn Error>> {
+        let kept = self.cull_passes();
+        let (images, transients) = self.allocate_images(cache, &kept)?;
+        let RenderGraph { resources, passes } = self;
+        let passes: Vec<RenderGraphPass> = passes
+            .into_iter()
+            .zip(kept)
+            .filter(|(_, kept)| *kept)
+            .map(|(pass, _)| pass)
+            .collect();
+
+        // Checked before recording anything, so that a failing graph leaves no commands
+        for pass in &passes {
+            let mut attachments = pass
+                .color_attachments
+                .iter()
+                .map(|(r, _)| *r)
+                .chain(pass.resolve_attachments.iter().flatten().copied())
+                .chain(pass.depth_attachment.map(|(r, _)| r));
+            if let Some(first) = attachments.next() {
+                let extent = images[first.0].unwrap().extent;
+                if let Some(resource) = attachments.find(|r| images[r.0].unwrap().extent != extent)
+                {
+                    return Err(format!(
+                        "Attachment {} of render graph pass {} has a different size",
+                        resources[resource.0].name, pass.name
+                    )
+                    .into());
+                }
+            }
+        }
+        let reads: Vec<Vec<ResourceHandle>> = passes.iter().map(|p| p.reads().collect()).collect();
+
+        let mut states = HashMap::new();
+        for (i, resource) in resources.iter().enumerate() {
+            let image = match images[i] {
+                Some(image) => image,
+                None => continue,
+            };
+            let state = match (&resource.source, transients[i]) {
+                (ResourceSource::Imported { initial_layout, .. }, _) => {
+                    ImageState::external(*initial_layout)
+                }
+                (_, Some(index)) => cache.transients[index].state,
+                _ => continue,
+            };
+            states.entry(image.image).or_insert(state);
+        }
+
+        let mut written = vec![false; resources.len()];
+        for (order, pass) in passes.into_iter().enumerate() {
+            let mut transitions = vec![];
+            for &resource in &pass.sampled_inputs {
+                let layout = if is_depth_format(images[resource.0].unwrap().format) {
+                    vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL
+                } else {
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+                };
+                transitions.push((resource, ImageState::sampled(layout)));
+            }
+
+            for &(resource, _) in &pass.color_attachments {
+                transitions.push((resource, ImageState::color_attachment()));
+            }
+
+            for &resource in pass.resolve_attachments.iter().flatten() {
+                transitions.push((resource, ImageState::color_attachment()));
+            }
+
+            if let Some((resource, _)) = pass.depth_attachment {
+                transitions.push((resource, ImageState::depth_attachment()));
+            }
+
+            for &(resource, layout) in &pass.external_outputs {
+                transitions.push((resource, ImageState::external(layout)));
+            }
+
+            let mut barrier = Barrier::new();
+            for (resource, next) in transitions {
+                let image = images[resource.0].unwrap();
+                let current = states[&image.image];
+
+                // Transient contents are discarded until their first write
+                let old_layout = if transients[resource.0].is_some() && !written[resource.0] {
+                    vk::ImageLayout::UNDEFINED
+                } else {
+                    current.layout
+                };
+
+                barrier.add(&image, old_layout, &current, &next);
+                states.insert(image.image, next);
+            }
+
+            barrier.record(&cache.device, command_buffer);
+
+            let context = PassContext {
+                command_buffer,
+                images: &images,
+            };
+            if pass.has_attachments() {
+                // Attachments read by a later pass or owned outside of the graph are stored,
+                // resolve attachments are entirely overwritten so they never need loading
+                let attachment = |resource: ResourceHandle, clear: bool, resolve: bool| {
+                    let load_op = if clear {
+                        vk::AttachmentLoadOp::CLEAR
+                    } else if resolve {
+                        vk::AttachmentLoadOp::DONT_CARE
+                    } else if written[resource.0] || transients[resource.0].is_none() {
+                        vk::AttachmentLoadOp::LOAD
+                    } else {
+                        vk::AttachmentLoadOp::DONT_CARE
+                    };
+                    let stored = transients[resource.0].is_none()
+                        || reads[order + 1..].iter().any(|r| r.contains(&resource));
+                    let store_op = if stored {
+                        vk::AttachmentStoreOp::STORE
+                    } else {
+                        vk::AttachmentStoreOp::DONT_CARE
+                    };
+
+                    let image = images[resource.0].unwrap();
+                    (
+                        resource,
+                        image,
+                        AttachmentKey(image.format, image.samples, load_op, store_op),
+                    )
+                };
+
+                let color: Vec<(ResourceHandle, GraphImage, AttachmentKey)> = pass
+                    .color_attachments
+                    .iter()
+                    .map(|(r, clear)| attachment(*r, clear.is_some(), false))
+                    .collect();
+                let resolve: Vec<Option<(ResourceHandle, GraphImage, AttachmentKey)>> = pass
+                    .resolve_attachments
+                    .iter()
+                    .map(|r| r.map(|r| attachment(r, false, true)))
+                    .collect();
+                let depth = pass
+                    .depth_attachment
+                    .map(|(r, clear)| attachment(r, clear.is_some(), false));
+
+                let mut clear_values: Vec<vk::ClearValue> = pass
+                    .color_attachments
+                    .iter()
+                    .map(|(_, clear)| vk::ClearValue {
+                        color: vk::ClearColorValue {
+                            float32: clear.unwrap_or_default(),
+                        },
+                    })
+                    .collect();
+                if let Some((_, clear)) = pass.depth_attachment {
+                    clear_values.push(vk::ClearValue {
+                        depth_stencil: vk::ClearDepthStencilValue {
+                            depth: clear.unwrap_or(1.),
+                            stencil: 0,
+                        },
+                    });
+                }
+
+                let extent = color.first().or_else(|| depth.as_ref()).unwrap().1.extent;
+                let views: Vec<(vk::ImageView, u64)> = color
+                    .iter()
+                    .chain(resolve.iter().flatten())
+                    .chain(depth.iter())
+                    .map(|(_, image, _)| (image.view, image.view_id))
+                    .collect();
+                let render_pass = cache.get_render_pass(
+                    color.iter().map(|(_, _, key)| *key).collect(),
+                    resolve
+                        .iter()
+                        .map(|r| r.as_ref().map(|(_, _, key)| *key))
+                        .collect(),
+                    depth.as_ref().map(|(_, _, key)| *key),
+                )?;
+                let framebuffer = cache.get_framebuffer(render_pass, views, extent)?;
+
+                let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+                    .render_pass(render_pass)
+                    .framebuffer(framebuffer)
+                    .render_area(pass.render_area.unwrap_or_else(|| {
+                        vk::Rect2D::builder()
+                            .offset(vk::Offset2D::builder().x(0).y(0).build())
+                            .extent(extent)
+                            .build()
+                    }))
+                    .clear_values(&clear_values)
+                    .build();
+                cache.device.cmd_begin_render_pass(
+                    command_buffer,
+                    &render_pass_begin_info,
+                    vk::SubpassContents::INLINE,
+                );
+                for resource in pass.writes() {
+                    written[resource.0] = true;
+                }
+
+                (pass.record)(&context);
+                cache.device.cmd_end_render_pass(command_buffer);
+            } else {
+                for resource in pass.writes() {
+                    written[resource.0] = true;
+                }
+
+                (pass.record)(&context);
+            }
+        }
+
+        let mut barrier = Barrier::new();
+        for (resource, image) in resources.iter().zip(&images) {
+            if let (
+                ResourceSource::Imported {
+                    final_layout: Some(layout),
+                    ..
+                },
+                Some(image),
+            ) = (&resource.source, image)
+            {
+                let current = states[&image.image];
+                barrier.add(
+                    image,
+                    current.layout,
+                    &current,
+                    &ImageState::external(*layout),
+                );
+            }
+        }
+        barrier.record(&cache.device, command_buffer);
+
+        for (index, image) in transients.iter().zip(&images) {
+            if let (Some(index), Some(image)) = (index, image) {
+                cache.transients[*index].state = states[&image.image];
+            }
+        }
+
+        Ok(())
+    }
+
+    fn add_resource(&mut self, name: &'static str, source: ResourceSource) -> ResourceHandle {
+        self.resources.push(GraphResource { name, source });
+        ResourceHandle(self.resources.len() - 1)
+    }
+
+    // Walks the passes backwards from the imported images, which are visible outside of the
+    // graph, keeping the passes that write something a kept pass reads
+    fn cull_passes(&self) -> Vec<bool> {
+        let mut needed: Vec<bool> = self
+            .resources
+            .iter()
+            .map(|r| matches!(r.source, ResourceSource::Imported { .. }))
+            .collect();
+        let mut kept = vec![false; self.passes.len()];
+        for (i, pass) in self.passes.iter().enumerate().rev() {
+            if pass.writes().any(|r| needed[r.0]) {
+                kept[i] = true;
+                for resource in pass.reads() {
+                    needed[resource.0] = true;
+                }
+            } else {
+                log::debug!("Render graph pass {} culled", pass.name);
+            }
+        }
+
+        kept
+    }
+
+    // Resolves every used resource to an image. Transient images are given back to the cache
+    // after their last use so that later resources can alias them.
+    fn allocate_images(
+        &self,
+        cache: &mut RenderGraphCache,
+        kept: &[bool],
+    ) -> Result<(Vec<Option<GraphImage>>, Vec<Option<usize>>), Box<dyn Error>> {
+        let passes: Vec<&RenderGraphPass> = self
+            .passes
+            .iter()
+            .zip(kept)
+            .filter(|(_, kept)| **kept)
+            .map(|(pass, _)| pass)
+            .collect();
+
+        let mut first_use = vec![None; self.resources.len()];
+        let mut last_use = vec![None; self.resources.len()];
+        let mut usage = vec![vk::ImageUsageFlags::empty(); self.resources.len()];
+        for (order, pass) in passes.iter().enumerate() {
+            for resource in pass.writes().chain(pass.reads()) {
+                first_use[resource.0].get_or_insert(order);
+                last_use[resource.0] = Some(order);
+            }
+
+            for (resource, _) in &pass.color_attachments {
+                usage[resource.0] |= vk::ImageUsageFlags::COLOR_ATTACHMENT;
+            }
+
+            for resource in pass.resolve_attachments.iter().flatten() {
+                usage[resource.0] |= vk::ImageUsageFlags::COLOR_ATTACHMENT;
+            }
+
+            if let Some((resource, _)) = &pass.depth_attachment {
+                usage[resource.0] |= vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT;
+            }
+
+            for resource in &pass.sampled_inputs {
+                usage[resource.0] |= vk::ImageUsageFlags::SAMPLED;
+            }
+
+            for (resource, layout) in &pass.external_outputs {
+                usage[resource.0] |= match *layout {
+                    vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+                    | vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL => {
+                        vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
+                    }
+                    _ => vk::ImageUsageFlags::COLOR_ATTACHMENT,
+                };
+            }
+        }
+
+        let mut images = vec![None; self.resources.len()];
+        let mut transients = vec![None; self.resources.len()];
+        let mut busy = vec![false; cache.transients.len()];
+        for order in 0..passes.len() {
+            for (i, resource) in self.resources.iter().enumerate() {
+                if first_use[i] != Some(order) {
+                    continue;
+                }
+
+                images[i] = match &resource.source {
+                    ResourceSource::Imported { image, .. } => Some(*image),
+                    ResourceSource::Transient {
+                        format,
+                        extent,
+                        samples,
+                    } => {
+                        let index =
+                            cache.acquire_transient(*format, *extent, *samples, usage[i], &busy)?;
+                        busy.resize(cache.transients.len(), false);
+                        busy[index] = true;
+                        transients[i] = Some(index);
+                        Some(cache.transients[index].graph_image())
+                    }
+                };
+            }
+
+            for (i, index) in transients.iter().enumerate() {
+                if index.is_some() && last_use[i] == Some(order) {
+                    busy[index.unwrap()] = false;
+                }
+            }
+        }
+
+        Ok((images, transients))
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct AttachmentKey(
+    vk::Format,
+    vk::SampleCountFlags,
+    vk::AttachmentLoadOp,
+    vk::AttachmentStoreOp,
+);
+
+#[derive(Copy, Clone)]
+struct ImageState {
+    layout: vk::ImageLayout,
+    access: vk::AccessFlags,
+    stage: vk::PipelineStageFlags,
+}
+
+impl ImageState {
+    fn sampled(layout: vk::ImageLayout) -> Self {
+        Self {
+            layout,
+            access: vk::AccessFlags::SHADER_READ,
+            stage: vk::PipelineStageFlags::FRAGMENT_SHADER,
+        }
+    }
+
+    fn color_attachment() -> Self {
+        Self {
+            layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            access: vk::AccessFlags::COLOR_ATTACHMENT_READ
+                | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            stage: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        }
+    }
+
+    fn depth_attachment() -> Self {
+        Self {
+            layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            access: vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            stage: vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+        }
+    }
+
+    // Used outside of what the graph knows about, synchronized with everything
+    fn external(layout: vk::ImageLayout) -> Self {
+        Self {
+            layout,
+            access: vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE,
+            stage: vk::PipelineStageFlags::ALL_COMMANDS,
+        }
+    }
+
+    fn writes(&self) -> bool {
+        self.access.intersects(
+            vk::AccessFlags::SHADER_WRITE
+                | vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE
+                | vk::AccessFlags::TRANSFER_WRITE
+                | vk::AccessFlags::MEMORY_WRITE,
+        )
+    }
+}
+
+struct Barrier {
+    barriers: Vec<vk::ImageMemoryBarrier>,
+    src_stage: vk::PipelineStageFlags,
+    dst_stage: vk::PipelineStageFlags,
+}
+
+impl Barrier {
+    fn new() -> Self {
+        Self {
+            barriers: vec![],
+            src_stage: vk::PipelineStageFlags::empty(),
+            dst_stage: vk::PipelineStageFlags::empty(),
+        }
+    }
+
+    // Reads following reads in the same layout don't need to wait for each other
+    fn add(
+        &mut self,
+        image: &GraphImage,
+        old_layout: vk::ImageLayout,
+        current: &ImageState,
+        next: &ImageState,
+    ) {
+        if old_layout == next.layout && !current.writes() && !next.writes() {
+            return;
+        }
+
+        self.barriers.push(
+            vk::ImageMemoryBarrier::builder()
+                .old_layout(old_layout)
+                .new_layout(next.layout)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(image.image)
+                .subresource_range(
+                    vk::ImageSubresourceRange::builder()
+                        .aspect_mask(format_aspect_mask(image.format))
+                        .level_count(1)
+                        .base_mip_level(0)
+                        .base_array_layer(0)
+                        .layer_count(image.layers)
+                        .build(),
+                )
+                .src_access_mask(current.access)
+                .dst_access_mask(next.access)
+                .build(),
+        );
+        self.src_stage |= current.stage;
+        self.dst_stage |= next.stage;
+    }
+
+    fn record(&self, device: &Device, command_buffer: vk::CommandBuffer) {
+        if self.barriers.is_empty() {
+            return;
+        }
+
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            self.src_stage,
+            self.dst_stage,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &self.barriers,
+        );
+    }
+}
+
+struct TransientImage {
+    format: vk::Format,
+    extent: vk::Extent2D,
+    samples: vk::SampleCountFlags,
+    usage: vk::ImageUsageFlags,
+    image: Image,
+    view: ImageView,
+    state: ImageState,
+    last_used: u64,
+}
+
+impl TransientImage {
+    fn graph_image(&self) -> GraphImage {
+        GraphImage::from_image(&self.image, &self.view)
+    }
+}
+
+type RenderPassKey = (
+    Vec<AttachmentKey>,
+    Vec<Option<AttachmentKey>>,
+    Option<AttachmentKey>,
+);
+
+/// Objects kept between executions of render graphs of the same shape: render passes,
+/// framebuffers and transient images. Framebuffers and transient images not used during a
+/// frame are released by `end_frame`.
+pub struct RenderGraphCache {
+    device: Rc<Device>,
+    allocator: Rc<vk_mem::Allocator>,
+    render_passes: HashMap<RenderPassKey, RenderPass>,
+    // Keyed by view ids rather than handles, which the driver may reuse for new views
+    framebuffers: HashMap<(vk::RenderPass, Vec<u64>), (vk::Framebuffer, u64)>,
+    transients: Vec<TransientImage>,
+    frame: u64,
+}
+
+impl RenderGraphCache {
+    pub fn new(device: Rc<Device>, allocator: &Rc<vk_mem::Allocator>) -> Self {
+        Self {
+            device,
+            allocator: allocator.clone(),
+            render_passes: HashMap::new(),
+            framebuffers: HashMap::new(),
+            transients: vec![],
+            frame: 0,
+        }
+    }
+
+    /// Releases the framebuffers and transient images no graph used since the previous
+    /// call. The device must be done with all the graphs executed so far.
+    pub fn end_frame(&mut self) {
+        let frame = self.frame;
+        let device = &self.device;
+        self.framebuffers.retain(|_, (framebuffer, last_used)| {
+            if *last_used != frame {
+                device.destroy_framebuffer(*framebuffer);
+            }
+
+            *last_used == frame
+        });
+        self.transients.retain(|t| t.last_used == frame);
+        self.frame += 1;
+    }
+
+    fn get_render_pass(
+        &mut self,
+        color: Vec<AttachmentKey>,
+        resolve: Vec<Option<AttachmentKey>>,
+        depth: Option<AttachmentKey>,
+    ) -> Result<vk::RenderPass, Box<dyn Error>> {
+        let key = (color, resolve, depth);
+        if !self.render_passes.contains_key(&key) {
+            let description = |key: &AttachmentKey, layout: vk::ImageLayout| {
+                vk::AttachmentDescription::builder()
+                    .format(key.0)
+                    .samples(key.1)
+                    .load_op(key.2)
+                    .store_op(key.3)
+                    .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+                    .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+                    .initial_layout(layout)
+                    .final_layout(layout)
+                    .build()
+            };
+
+            let color_attachments: Vec<vk::AttachmentDescription> = key
+                .0
+                .iter()
+                .map(|k| description(k, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL))
+                .collect();
+            let resolve_attachments: Vec<Option<vk::AttachmentDescription>> = key
+                .1
+                .iter()
+                .map(|k| {
+                    k.as_ref()
+                        .map(|k| description(k, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL))
+                })
+                .collect();
+            let depth_attachment = key
+                .2
+                .as_ref()
+                .map(|k| description(k, vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL));
+            let render_pass = RenderPass::new_with_attachments(
+                self.device.clone(),
+                &color_attachments,
+                &resolve_attachments,
+                depth_attachment,
+            )?;
+            self.render_passes.insert(key.clone(), render_pass);
+        }
+
+        Ok(self.render_passes[&key].vk_render_pass())
+    }
+
+    fn get_framebuffer(
+        &mut self,
+        render_pass: vk::RenderPass,
+        views: Vec<(vk::ImageView, u64)>,
+        extent: vk::Extent2D,
+    ) -> Result<vk::Framebuffer, Box<dyn Error>> {
+        let key = (render_pass, views.iter().map(|(_, id)| *id).collect());
+        if let Some((framebuffer, last_used)) = self.framebuffers.get_mut(&key) {
+            *last_used = self.frame;
+            return Ok(*framebuffer);
+        }
+
+        let views: Vec<vk::ImageView> = views.iter().map(|(view, _)| *view).collect();
+        let create_info = vk::FramebufferCreateInfo::builder()
+            .render_pass(render_pass)
+            .attachments(&views)
+            .layers(1)
+            .width(extent.width)
+            .height(extent.height)
+            .build();
+        let framebuffer = self.device.create_framebuffer(&create_info)?;
+        self.framebuffers.insert(key, (framebuffer, self.frame));
+
+        Ok(framebuffer)
+    }
+
+    fn acquire_transient(
+        &mut self,
+        format: vk::Format,
+        extent: vk::Extent2D,
+        samples: vk::SampleCountFlags,
+        usage: vk::ImageUsageFlags,
+        busy: &[bool],
+    ) -> Result<usize, Box<dyn Error>> {
+        let free = self.transients.iter().enumerate().position(|(i, t)| {
+            !busy.get(i).copied().unwrap_or(false)
+                && t.format == format
+                && t.extent == extent
+                && t.samples == samples
+                && t.usage == usage
+        });
+        if let Some(index) = free {
+            self.transients[index].last_used = self.frame;
+            return Ok(index);
+        }
+
+        let image = Image::new_attachment_image(
+            &self.allocator,
+            extent.width,
+            extent.height,
+            format,
+            samples,
+            usage,
+        )?;
+        let view = if is_depth_format(format) {
+            ImageView::new_depth_image_view(self.device.clone(), image.vk_image(), format)?
+        } else {
+            ImageView::new_color_image_view(self.device.clone(), image.vk_image(), format)?
+        };
+        self.transients.push(TransientImage {
+            format,
+            extent,
+            samples,
+            usage,
+            image,
+            view,
+            state: ImageState {
+                layout: vk::ImageLayout::UNDEFINED,
+                access: vk::AccessFlags::empty(),
+                stage: vk::PipelineStageFlags::TOP_OF_PIPE,
+            },
+            last_used: self.frame,
+        });
+
+        Ok(self.transients.len() - 1)
+    }
+}
+
+impl Drop for RenderGraphCache {
+    fn drop(&mut self) {
+        for (framebuffer, _) in self.framebuffers.values() {
+            self.device.destroy_framebuffer(*framebuffer);
+        }
+    }
+}
@@ -1,10 +1,12 @@
 use super::buffer::{Buffer, BufferType};
 use super::material::VulkanMaterial;
+use super::morph::VulkanMorphTargets;
 use super::uniform_buffers::DynamicUniformBufferManager;
 use crate::rendering::vulkan::adhoc_command_runner::AdhocCommandRunner;
 use crate::rendering::vulkan::descriptor_managers::DescriptorManager;
-use crate::rendering::{Material, RenderObject, VertexBuffer};
+use crate::rendering::{Instance, Instances, Material, MorphTarget, RenderObject, VertexBuffer};
 use ash::vk;
+use std::cell::{Cell, RefCell};
 use std::error::Error;
 use std::rc::Rc;
 use std::sync::Arc;
@@ -16,11 +18,19 @@ pub struct VulkanRenderObject {
     dirty: bool,
 
     dub_manager: Arc<DynamicUniformBufferManager>,
+    bone_manager: Arc<DynamicUniformBufferManager>,
     vertex_buffer: Buffer,
     index_buffer: Buffer,
     material: Box<VulkanMaterial>,
     per_object_descriptor_sets: vk::DescriptorSet,
     dub_index: usize,
+    bone_index: Option<usize>,
+    morph_targets: Option<VulkanMorphTargets>,
+
+    allocator: Rc<vk_mem::Allocator>,
+    instance_buffer: RefCell<Option<Buffer>>,
+    instance_count: Cell<u32>,
+    instance_version: Cell<u64>,
 }
 
 impl RenderObject for VulkanRenderObject {
@@ -36,9 +46,11 @@ impl VulkanRenderObject {
         indices: Vec<u32>,
         material: Box<dyn Material>,
         host_dynamic: bool,
+        morph_targets: &[MorphTarget],
         allocator: &Rc<vk_mem::Allocator>,
         command_runner: &Rc<AdhocCommandRunner>,
         dub_manager: &Arc<DynamicUniformBufferManager>,
+        bone_manager: &Arc<DynamicUniformBufferManager>,
         descriptor_manager: &DescriptorManager,
     ) -> Result<Self, Box<dyn Error>> {
         let vertex_buffer = if host_dynamic {
@@ -63,6 +75,32 @@ impl VulkanRenderObject {
         let per_object_descriptor_sets =
             descriptor_manager.allocate_per_object_descriptor_set(&material)?;
         let dub_index = dub_manager.allocate_buffer();
+        let bone_index = if material.shader().is_skinned() {
+            Some(bone_manager.allocate_buffer())
+        } else {
+            None
+        };
+        let morph_targets = if material.shader().is_morph() {
+            Some(VulkanMorphTargets::new(
+                morph_targets,
+                vertices.count(),
+                allocator,
+                command_runner,
+                descriptor_manager,
+            )?)
+        } else {
+            if !morph_targets.is_empty() {
+                log::warn!("The material doesn't blend morph targets, they are ignored");
+            }
+
+            None
+        };
+
+        let instance_buffer = if material.shader().is_instanced() {
+            Some(Self::new_default_instance_buffer(allocator)?)
+        } else {
+            None
+        };
 
         Ok(Self {
             vertices: vertices.clone(),
@@ -71,10 +109,17 @@ impl VulkanRenderObject {
             host_dynamic,
             dirty: false,
             dub_manager: dub_manager.clone(),
+            bone_manager: bone_manager.clone(),
             vertex_buffer,
             index_buffer,
             per_object_descriptor_sets,
             dub_index,
+            bone_index,
+            morph_targets,
+            allocator: allocator.clone(),
+            instance_buffer: RefCell::new(instance_buffer),
+            instance_count: Cell::new(1),
+            instance_version: Cell::new(0),
         })
     }
 
@@ -90,6 +135,81 @@ impl VulkanRenderObject {
         self.dub_index
     }
 
+    /// Slot in the bone matrix buffer, only allocated for skinned materials
+    pub fn bone_index(&self) -> Option<usize> {
+        self.bone_index
+    }
+
+    /// Only created for materials blending morph targets
+    pub fn morph_targets(&self) -> Option<&VulkanMorphTargets> {
+        self.morph_targets.as_ref()
+    }
+
+    /// Uploads the instances drawn by instanced materials, unless they didn't change since the
+    /// last call. The buffer only grows, so that it's reallocated once the count peaks.
+    pub fn set_instances(&self, instances: &Instances) -> Result<(), Box<dyn Error>> {
+        if !self.material.shader().is_instanced()
+            || self.instance_version.get() == instances.version()
+        {
+            return Ok(());
+        }
+
+        let data = instances.instances();
+        let mut buffer = self.instance_buffer.borrow_mut();
+        let capacity = buffer
+            .as_ref()
+            .map_or(0, |buffer| buffer.element_count() as usize);
+        if capacity < data.len() {
+            *buffer = Some(Buffer::new_dynamic_buffer(
+                &self.allocator,
+                BufferType::Vertex,
+                std::mem::size_of::<Instance>(),
+                data.len().next_power_of_two(),
+            )?);
+        }
+
+        if let Some(buffer) = buffer.as_ref() {
+            buffer.copy_memory_from(data);
+        }
+
+        self.instance_count.set(data.len() as u32);
+        self.instance_version.set(instances.version());
+        Ok(())
+    }
+
+    /// Goes back to drawing a single instance once the entity's `Instances` is removed
+    pub fn clear_instances(&self) -> Result<(), Box<dyn Error>> {
+        if self.instance_version.get() == 0 {
+            return Ok(());
+        }
+
+        *self.instance_buffer.borrow_mut() =
+            Some(Self::new_default_instance_buffer(&self.allocator)?);
+        self.instance_count.set(1);
+        self.instance_version.set(0);
+        Ok(())
+    }
+
+    /// Buffer bound at binding 1, only for instanced materials
+    pub fn instance_buffer(&self) -> Option<vk::Buffer> {
+        self.instance_buffer
+            .borrow()
+            .as_ref()
+            .map(|buffer| buffer.vk_buffer())
+    }
+
+    /// Number of copies drawn, 1 when the material isn't instanced
+    pub fn instance_count(&self) -> u32 {
+        self.instance_count.get()
+    }
+
+    // A single untransformed instance, drawn until the entity gets an `Instances`
+    fn new_default_instance_buffer(
+        allocator: &Rc<vk_mem::Allocator>,
+    ) -> Result<Buffer, Box<dyn Error>> {
+        Buffer::new_dynamic_buffer_with_data(allocator, BufferType::Vertex, &[Instance::default()])
+    }
+
     pub fn material(&self) -> &VulkanMaterial {
         &self.material
     }
@@ -102,5 +222,8 @@ impl VulkanRenderObject {
 impl Drop for VulkanRenderObject {
     fn drop(&mut self) {
         self.dub_manager.deallocate_buffer(self.dub_index);
+        if let Some(bone_index) = self.bone_index {
+            self.bone_manager.deallocate_buffer(bone_index);
+        }
     }
 }
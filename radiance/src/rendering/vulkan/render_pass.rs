@@ -9,8 +9,24 @@ pub struct RenderPass {
 }
 
 impl RenderPass {
-    pub fn new(device: Rc<Device>, color_format: vk::Format, depth_format: vk::Format) -> Self {
-        let render_pass = Self::create_render_pass(&device, color_format, depth_format).unwrap();
+    pub fn new(
+        device: Rc<Device>,
+        color_format: vk::Format,
+        depth_format: vk::Format,
+        samples: vk::SampleCountFlags,
+    ) -> Self {
+        let render_pass =
+            Self::create_render_pass(&device, color_format, depth_format, samples).unwrap();
+
+        Self {
+            device,
+            render_pass,
+        }
+    }
+
+    /// Single depth attachment left readable by shaders, for shadow maps
+    pub fn new_depth_only(device: Rc<Device>, depth_format: vk::Format) -> Self {
+        let render_pass = Self::create_depth_only_render_pass(&device, depth_format).unwrap();
 
         Self {
             device,
@@ -18,6 +34,67 @@ impl RenderPass {
         }
     }
 
+    /// Single subpass writing the color attachments then the optional depth attachment,
+    /// layout transitions are left to the caller. `resolve_attachments` is either empty or
+    /// has an entry per color attachment, the multisampled colors being resolved into the
+    /// attachments that are set at the end of the subpass.
+    pub fn new_with_attachments(
+        device: Rc<Device>,
+        color_attachments: &[vk::AttachmentDescription],
+        resolve_attachments: &[Option<vk::AttachmentDescription>],
+        depth_attachment: Option<vk::AttachmentDescription>,
+    ) -> VkResult<Self> {
+        let reference = |index: u32| {
+            vk::AttachmentReference::builder()
+                .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                .attachment(index)
+                .build()
+        };
+
+        let mut attachments = color_attachments.to_vec();
+        let color_references: Vec<vk::AttachmentReference> = (0..color_attachments.len())
+            .map(|i| reference(i as u32))
+            .collect();
+        let resolve_references: Vec<vk::AttachmentReference> = resolve_attachments
+            .iter()
+            .map(|resolve| match resolve {
+                Some(description) => {
+                    attachments.push(*description);
+                    reference(attachments.len() as u32 - 1)
+                }
+                None => reference(vk::ATTACHMENT_UNUSED),
+            })
+            .collect();
+        let depth_reference = vk::AttachmentReference::builder()
+            .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+            .attachment(attachments.len() as u32)
+            .build();
+
+        let mut subpass_description = vk::SubpassDescription::builder()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(&color_references);
+        if !resolve_references.is_empty() {
+            subpass_description = subpass_description.resolve_attachments(&resolve_references);
+        }
+
+        if depth_attachment.is_some() {
+            subpass_description = subpass_description.depth_stencil_attachment(&depth_reference);
+        }
+
+        attachments.extend(depth_attachment);
+        let subpasses = [subpass_description.build()];
+        let render_pass_create_info = vk::RenderPassCreateInfo::builder()
+            .attachments(&attachments)
+            .subpasses(&subpasses)
+            .build();
+        let render_pass = device.create_render_pass(&render_pass_create_info)?;
+
+        Ok(Self {
+            device,
+            render_pass,
+        })
+    }
+
     pub fn vk_render_pass(&self) -> vk::RenderPass {
         self.render_pass
     }
@@ -26,10 +103,11 @@ impl RenderPass {
         device: &Rc<Device>,
         color_format: vk::Format,
         depth_format: vk::Format,
+        samples: vk::SampleCountFlags,
     ) -> VkResult<vk::RenderPass> {
         let color_attachment = vk::AttachmentDescription::builder()
             .format(color_format)
-            .samples(vk::SampleCountFlags::TYPE_1)
+            .samples(samples)
             .load_op(vk::AttachmentLoadOp::CLEAR)
             .store_op(vk::AttachmentStoreOp::STORE)
             .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
@@ -40,7 +118,7 @@ impl RenderPass {
 
         let depth_attachment = vk::AttachmentDescription::builder()
             .format(depth_format)
-            .samples(vk::SampleCountFlags::TYPE_1)
+            .samples(samples)
             .load_op(vk::AttachmentLoadOp::CLEAR)
             .store_op(vk::AttachmentStoreOp::DONT_CARE)
             .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
@@ -87,6 +165,63 @@ impl RenderPass {
 
         device.create_render_pass(&render_pass_create_info)
     }
+
+    fn create_depth_only_render_pass(
+        device: &Rc<Device>,
+        depth_format: vk::Format,
+    ) -> VkResult<vk::RenderPass> {
+        let depth_attachment = vk::AttachmentDescription::builder()
+            .format(depth_format)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL)
+            .build();
+
+        let depth_attachment_reference = vk::AttachmentReference::builder()
+            .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+            .attachment(0)
+            .build();
+
+        let subpass_description = vk::SubpassDescription::builder()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .depth_stencil_attachment(&depth_attachment_reference)
+            .build();
+
+        // Previous frame's lighting must finish reading before the map is overwritten, and
+        // the depth writes must finish before this frame's lighting reads them
+        let dependencies = [
+            vk::SubpassDependency::builder()
+                .src_subpass(vk::SUBPASS_EXTERNAL)
+                .dst_subpass(0)
+                .src_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+                .src_access_mask(vk::AccessFlags::SHADER_READ)
+                .dst_stage_mask(vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS)
+                .dst_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+                .build(),
+            vk::SubpassDependency::builder()
+                .src_subpass(0)
+                .dst_subpass(vk::SUBPASS_EXTERNAL)
+                .src_stage_mask(vk::PipelineStageFlags::LATE_FRAGMENT_TESTS)
+                .src_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+                .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+                .dst_access_mask(vk::AccessFlags::SHADER_READ)
+                .build(),
+        ];
+
+        let attachments = [depth_attachment];
+        let subpasses = [subpass_description];
+        let render_pass_create_info = vk::RenderPassCreateInfo::builder()
+            .attachments(&attachments)
+            .subpasses(&subpasses)
+            .dependencies(&dependencies)
+            .build();
+
+        device.create_render_pass(&render_pass_create_info)
+    }
 }
 
 impl Drop for RenderPass {
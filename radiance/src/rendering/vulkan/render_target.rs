@@ -0,0 +1,68 @@
+use super::{
+    adhoc_command_runner::AdhocCommandRunner, device::Device, image::Image, image_view::ImageView,
+    render_graph::GraphImage,
+};
+use crate::rendering::RenderTarget;
+use ash::vk;
+use std::error::Error;
+use std::rc::Rc;
+
+/// Image in the swapchain format, so that the post-processing pipelines can write to it. It
+/// stays in `SHADER_READ_ONLY_OPTIMAL` between the frames rendering it.
+pub struct VulkanRenderTarget {
+    image: Rc<Image>,
+    image_view: Rc<ImageView>,
+}
+
+impl RenderTarget for VulkanRenderTarget {
+    fn width(&self) -> u32 {
+        self.image.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.image.height()
+    }
+}
+
+impl VulkanRenderTarget {
+    pub fn new(
+        width: u32,
+        height: u32,
+        format: vk::Format,
+        device: &Rc<Device>,
+        allocator: &Rc<vk_mem::Allocator>,
+        command_runner: &AdhocCommandRunner,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut image = Image::new_attachment_image(
+            allocator,
+            width.max(1),
+            height.max(1),
+            format,
+            vk::SampleCountFlags::TYPE_1,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+        )?;
+        image.transit_layout(
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            command_runner,
+        )?;
+        let image_view = ImageView::new_color_image_view(device.clone(), image.vk_image(), format)?;
+
+        Ok(Self {
+            image: Rc::new(image),
+            image_view: Rc::new(image_view),
+        })
+    }
+
+    pub fn image(&self) -> &Rc<Image> {
+        &self.image
+    }
+
+    pub fn image_view(&self) -> &Rc<ImageView> {
+        &self.image_view
+    }
+
+    pub fn graph_image(&self) -> GraphImage {
+        GraphImage::from_image(&self.image, &self.image_view)
+    }
+}
@@ -1,4 +1,5 @@
 use super::device::Device;
+use crate::rendering::{AddressMode, FilterMode, SamplerDef};
 use ash::prelude::VkResult;
 use ash::vk;
 use std::rc::Rc;
@@ -9,13 +10,96 @@ pub struct Sampler {
 }
 
 impl Sampler {
-    pub fn new(device: Rc<Device>) -> VkResult<Self> {
+    /// Sampler for a texture with `mip_levels` levels
+    pub fn new_with_def(device: Rc<Device>, def: &SamplerDef, mip_levels: u32) -> VkResult<Self> {
+        let max_supported_anisotropy = device
+            .physical_device_properties()
+            .limits
+            .max_sampler_anisotropy;
+        let max_lod = def
+            .max_lod
+            .unwrap_or(f32::MAX)
+            .min((mip_levels - 1) as f32)
+            .max(def.min_lod);
+        let sampler_info = vk::SamplerCreateInfo::builder()
+            .mag_filter(filter(def.mag_filter))
+            .min_filter(filter(def.min_filter))
+            .address_mode_u(address_mode(def.address_mode_u))
+            .address_mode_v(address_mode(def.address_mode_v))
+            .address_mode_w(address_mode(def.address_mode_v))
+            .anisotropy_enable(def.max_anisotropy.is_some())
+            .max_anisotropy(
+                def.max_anisotropy
+                    .unwrap_or(1.)
+                    .min(max_supported_anisotropy),
+            )
+            .border_color(vk::BorderColor::INT_OPAQUE_BLACK)
+            .unnormalized_coordinates(false)
+            .compare_enable(false)
+            .compare_op(vk::CompareOp::ALWAYS)
+            .mipmap_mode(match def.mipmap_filter {
+                FilterMode::Nearest => vk::SamplerMipmapMode::NEAREST,
+                FilterMode::Linear => vk::SamplerMipmapMode::LINEAR,
+            })
+            .mip_lod_bias(def.lod_bias)
+            .min_lod(def.min_lod)
+            .max_lod(max_lod)
+            .build();
+        let sampler = device.create_sampler(&sampler_info)?;
+        Ok(Self { device, sampler })
+    }
+
+    /// Clamps at the edges so that cubemap faces don't bleed into each other
+    pub fn new_cubemap_sampler(device: Rc<Device>, mip_levels: u32) -> VkResult<Self> {
+        Self::create(
+            device,
+            vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            (mip_levels - 1) as f32,
+        )
+    }
+
+    /// Linear sampler for full screen passes, reading past the edges repeats the border texels
+    pub fn new_clamp_sampler(device: Rc<Device>) -> VkResult<Self> {
+        Self::create(device, vk::SamplerAddressMode::CLAMP_TO_EDGE, 0.)
+    }
+
+    /// Compares against the stored depth, linear filtering gives 2x2 PCF for free
+    pub fn new_shadow_sampler(device: Rc<Device>) -> VkResult<Self> {
         let sampler_info = vk::SamplerCreateInfo::builder()
             .mag_filter(vk::Filter::LINEAR)
             .min_filter(vk::Filter::LINEAR)
-            .address_mode_u(vk::SamplerAddressMode::REPEAT)
-            .address_mode_v(vk::SamplerAddressMode::REPEAT)
-            .address_mode_w(vk::SamplerAddressMode::REPEAT)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+            .anisotropy_enable(false)
+            .border_color(vk::BorderColor::FLOAT_OPAQUE_WHITE)
+            .unnormalized_coordinates(false)
+            .compare_enable(true)
+            .compare_op(vk::CompareOp::LESS_OR_EQUAL)
+            .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+            .mip_lod_bias(0.)
+            .min_lod(0.)
+            .max_lod(0.)
+            .build();
+        let sampler = device.create_sampler(&sampler_info)?;
+        Ok(Self { device, sampler })
+    }
+
+    pub fn vk_sampler(&self) -> vk::Sampler {
+        self.sampler
+    }
+
+    fn create(
+        device: Rc<Device>,
+        address_mode: vk::SamplerAddressMode,
+        max_lod: f32,
+    ) -> VkResult<Self> {
+        let sampler_info = vk::SamplerCreateInfo::builder()
+            .mag_filter(vk::Filter::LINEAR)
+            .min_filter(vk::Filter::LINEAR)
+            .address_mode_u(address_mode)
+            .address_mode_v(address_mode)
+            .address_mode_w(address_mode)
             .anisotropy_enable(true)
             .max_anisotropy(16.)
             .border_color(vk::BorderColor::INT_OPAQUE_BLACK)
@@ -25,7 +109,7 @@ impl Sampler {
             .mipmap_mode(vk::SamplerMipmapMode::LINEAR)
             .mip_lod_bias(0.)
             .min_lod(0.)
-            .max_lod(0.)
+            .max_lod(max_lod)
             .build();
         let sampler = device.create_sampler(&sampler_info)?;
         Ok(Self {
@@ -33,10 +117,6 @@ impl Sampler {
             sampler,
         })
     }
-
-    pub fn vk_sampler(&self) -> vk::Sampler {
-        self.sampler
-    }
 }
 
 impl Drop for Sampler {
@@ -44,3 +124,19 @@ impl Drop for Sampler {
         self.device.destroy_sampler(self.sampler);
     }
 }
+
+fn filter(mode: FilterMode) -> vk::Filter {
+    match mode {
+        FilterMode::Nearest => vk::Filter::NEAREST,
+        FilterMode::Linear => vk::Filter::LINEAR,
+    }
+}
+
+fn address_mode(mode: AddressMode) -> vk::SamplerAddressMode {
+    match mode {
+        AddressMode::Repeat => vk::SamplerAddressMode::REPEAT,
+        AddressMode::MirroredRepeat => vk::SamplerAddressMode::MIRRORED_REPEAT,
+        AddressMode::ClampToEdge => vk::SamplerAddressMode::CLAMP_TO_EDGE,
+        AddressMode::ClampToBorder => vk::SamplerAddressMode::CLAMP_TO_BORDER,
+    }
+}
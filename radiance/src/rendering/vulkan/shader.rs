@@ -1,17 +1,24 @@
 use super::device::Device;
-use crate::rendering::vertex_buffer::{VertexComponents, VertexMetadata};
-use crate::rendering::{Shader, ShaderDef};
+use super::morph::MorphPushConstants;
+use crate::math::Mat44;
+use crate::rendering::vertex_buffer::{VertexFormat, VertexMetadata, VertexSemantic};
+use crate::rendering::{Instance, Shader, ShaderDef};
 use ash::vk;
 use std::error::Error;
 use std::rc::Rc;
 use std::sync::Arc;
 
+// First shader location of the instance attributes, after the standard vertex ones
+const INSTANCE_LOCATION: u32 = 8;
+
 pub struct VulkanShader {
     device: Rc<Device>,
     vertex_metadata: Arc<VertexMetadata>,
     vert_shader: vk::ShaderModule,
     frag_shader: vk::ShaderModule,
     name: String,
+    morph: bool,
+    instanced: bool,
 }
 
 impl Shader for VulkanShader {
@@ -29,82 +36,110 @@ impl VulkanShader {
 
         Ok(Self {
             device,
-            vertex_metadata: VertexMetadata::get(shader_def.vertex_components()),
+            vertex_metadata: VertexMetadata::get(shader_def.vertex_layout()),
             vert_shader,
             frag_shader,
             name: shader_def.name().to_owned(),
+            morph: shader_def.is_morph(),
+            instanced: shader_def.is_instanced(),
         })
     }
 
-    pub fn get_binding_description(&self) -> vk::VertexInputBindingDescription {
-        vk::VertexInputBindingDescription::builder()
+    /// The vertices at binding 0, and the instances at binding 1 for instanced shaders
+    pub fn get_binding_descriptions(&self) -> Vec<vk::VertexInputBindingDescription> {
+        let mut descriptions = vec![vk::VertexInputBindingDescription::builder()
             .binding(0)
             .stride(self.vertex_metadata.size as u32)
             .input_rate(vk::VertexInputRate::VERTEX)
-            .build()
+            .build()];
+        if self.instanced {
+            descriptions.push(
+                vk::VertexInputBindingDescription::builder()
+                    .binding(1)
+                    .stride(std::mem::size_of::<Instance>() as u32)
+                    .input_rate(vk::VertexInputRate::INSTANCE)
+                    .build(),
+            );
+        }
+
+        descriptions
     }
 
     // A better way: reflect the shader code to get the desciprtions automatically
     pub fn get_attribute_descriptions(&self) -> Vec<vk::VertexInputAttributeDescription> {
-        let mut descs = vec![];
-
-        if let Some(&position_offset) = self
-            .vertex_metadata
-            .offsets
-            .get(&VertexComponents::POSITION)
-        {
-            let pos_attr = vk::VertexInputAttributeDescription::builder()
-                .offset(position_offset as u32)
-                .binding(0)
-                .location(0)
-                .format(vk::Format::R32G32B32_SFLOAT)
-                .build();
-
-            descs.push(pos_attr);
+        self.vertex_metadata
+            .layout
+            .attributes()
+            .iter()
+            .map(|attribute| {
+                vk::VertexInputAttributeDescription::builder()
+                    .offset(
+                        *self
+                            .vertex_metadata
+                            .offsets
+                            .get(&attribute.semantic)
+                            .unwrap() as u32,
+                    )
+                    .binding(0)
+                    .location(attribute.location)
+                    .format(Self::to_vk_format(attribute.format))
+                    .build()
+            })
+            .chain(self.get_instance_attribute_descriptions())
+            .collect()
+    }
+
+    // The transform takes a location per row, followed by the color
+    fn get_instance_attribute_descriptions(&self) -> Vec<vk::VertexInputAttributeDescription> {
+        if !self.instanced {
+            return vec![];
         }
 
-        if let Some(&normal_offset) = self.vertex_metadata.offsets.get(&VertexComponents::NORMAL) {
-            let normal_attr = vk::VertexInputAttributeDescription::builder()
-                .offset(normal_offset as u32)
-                .binding(0)
-                .location(1)
-                .format(vk::Format::R32G32B32_SFLOAT)
-                .build();
+        let row_size = std::mem::size_of::<[f32; 4]>() as u32;
+        (0..5)
+            .map(|i| {
+                let offset = if i < 4 {
+                    i * row_size
+                } else {
+                    std::mem::size_of::<Mat44>() as u32
+                };
+                vk::VertexInputAttributeDescription::builder()
+                    .offset(offset)
+                    .binding(1)
+                    .location(INSTANCE_LOCATION + i)
+                    .format(vk::Format::R32G32B32A32_SFLOAT)
+                    .build()
+            })
+            .collect()
+    }
 
-            descs.push(normal_attr);
-        }
+    pub fn is_instanced(&self) -> bool {
+        self.instanced
+    }
 
-        if let Some(&texcoord_offset) = self
-            .vertex_metadata
-            .offsets
-            .get(&VertexComponents::TEXCOORD)
-        {
-            let tex_attr = vk::VertexInputAttributeDescription::builder()
-                .offset(texcoord_offset as u32)
-                .binding(0)
-                .location(2)
-                .format(vk::Format::R32G32_SFLOAT)
-                .build();
-
-            descs.push(tex_attr);
-        }
+    pub fn is_skinned(&self) -> bool {
+        self.vertex_metadata
+            .layout
+            .contains(VertexSemantic::BoneIndices)
+    }
+
+    pub fn is_morph(&self) -> bool {
+        self.morph
+    }
 
-        if let Some(&texcoord2_offset) = self
-            .vertex_metadata
-            .offsets
-            .get(&VertexComponents::TEXCOORD2)
-        {
-            let texcoord2_attr = vk::VertexInputAttributeDescription::builder()
-                .offset(texcoord2_offset as u32)
-                .binding(0)
-                .location(3)
-                .format(vk::Format::R32G32_SFLOAT)
-                .build();
-
-            descs.push(texcoord2_attr);
+    pub fn get_push_constant_ranges(&self) -> Vec<vk::PushConstantRange> {
+        let mut ranges = vec![];
+        if self.is_morph() {
+            ranges.push(
+                vk::PushConstantRange::builder()
+                    .stage_flags(vk::ShaderStageFlags::VERTEX)
+                    .offset(0)
+                    .size(std::mem::size_of::<MorphPushConstants>() as u32)
+                    .build(),
+            );
         }
 
-        descs
+        ranges
     }
 
     pub fn vk_vert_shader_module(&self) -> vk::ShaderModule {
@@ -115,7 +150,20 @@ impl VulkanShader {
         self.frag_shader
     }
 
-    fn create_shader_module_from_memory(
+    fn to_vk_format(format: VertexFormat) -> vk::Format {
+        match format {
+            VertexFormat::Float32 => vk::Format::R32_SFLOAT,
+            VertexFormat::Float32x2 => vk::Format::R32G32_SFLOAT,
+            VertexFormat::Float32x3 => vk::Format::R32G32B32_SFLOAT,
+            VertexFormat::Float32x4 => vk::Format::R32G32B32A32_SFLOAT,
+            VertexFormat::UNorm8x4 => vk::Format::R8G8B8A8_UNORM,
+            VertexFormat::UInt8x4 => vk::Format::R8G8B8A8_UINT,
+            VertexFormat::UNorm16x4 => vk::Format::R16G16B16A16_UNORM,
+            VertexFormat::UInt16x4 => vk::Format::R16G16B16A16_UINT,
+        }
+    }
+
+    pub fn create_shader_module_from_memory(
         device: &Rc<Device>,
         code: &[u8],
     ) -> Result<vk::ShaderModule, Box<dyn Error>> {
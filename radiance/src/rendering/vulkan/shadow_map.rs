@@ -0,0 +1,206 @@
+use super::adhoc_command_runner::AdhocCommandRunner;
+use super::buffer::{Buffer, BufferType};
+use super::descriptor_managers::DescriptorManager;
+use super::device::Device;
+use super::image::Image;
+use super::image_view::ImageView;
+use super::pipeline_manager::PipelineManager;
+use super::render_graph::GraphImage;
+use super::render_object::VulkanRenderObject;
+use super::sampler::Sampler;
+use super::uniform_buffers::{DynamicUniformBufferManager, PerFrameUniformBuffer};
+use crate::rendering::shadow::{ShadowView, MAX_SHADOW_MAPS, SHADOW_MAP_SIZE};
+use crate::rendering::MAX_CAMERAS_PER_TARGET;
+use ash::vk;
+use std::error::Error;
+use std::rc::Rc;
+
+/// Depth image array holding every shadow map, rendered before the main pass
+pub struct ShadowMap {
+    device: Rc<Device>,
+    image: Image,
+    image_view: ImageView,
+    layer_views: Vec<ImageView>,
+    sampler: Sampler,
+    framebuffers: Vec<vk::Framebuffer>,
+    uniform_buffers: Vec<Buffer>,
+    descriptor_sets: Vec<vk::DescriptorSet>,
+    pipeline_manager: PipelineManager,
+}
+
+impl ShadowMap {
+    pub fn new(
+        device: Rc<Device>,
+        allocator: &Rc<vk_mem::Allocator>,
+        descriptor_manager: &Rc<DescriptorManager>,
+        command_runner: &AdhocCommandRunner,
+    ) -> Result<Self, Box<dyn Error>> {
+        let layers = MAX_SHADOW_MAPS as u32;
+        let mut image = Image::new_shadow_map_image(allocator, SHADOW_MAP_SIZE, layers)?;
+
+        // Unused layers are still sampled, keep them in a valid layout
+        image.transit_layout(
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+            command_runner,
+        )?;
+
+        let image_view = ImageView::new_depth_array_image_view(
+            device.clone(),
+            image.vk_image(),
+            image.vk_format(),
+            layers,
+        )?;
+        let layer_views = (0..layers)
+            .map(|layer| {
+                ImageView::new_depth_layer_image_view(
+                    device.clone(),
+                    image.vk_image(),
+                    image.vk_format(),
+                    layer,
+                )
+            })
+            .collect::<Result<Vec<ImageView>, vk::Result>>()?;
+        let sampler = Sampler::new_shadow_sampler(device.clone())?;
+
+        let extent = vk::Extent2D {
+            width: SHADOW_MAP_SIZE,
+            height: SHADOW_MAP_SIZE,
+        };
+        let pipeline_manager =
+            PipelineManager::new_depth_only(device.clone(), descriptor_manager, image.vk_format());
+
+        let framebuffers = layer_views
+            .iter()
+            .map(|view| {
+                let attachments = [view.vk_image_view()];
+                let create_info = vk::FramebufferCreateInfo::builder()
+                    .render_pass(pipeline_manager.render_pass().vk_render_pass())
+                    .attachments(&attachments)
+                    .layers(1)
+                    .width(extent.width)
+                    .height(extent.height)
+                    .build();
+                device.create_framebuffer(&create_info)
+            })
+            .collect::<Result<Vec<vk::Framebuffer>, vk::Result>>()?;
+
+        // The views of each camera drawn into a target have their own buffers, since the
+        // shadow maps are rendered again before each of them
+        let uniform_buffers: Vec<Buffer> = (0..layers as usize * MAX_CAMERAS_PER_TARGET)
+            .map(|_| {
+                Buffer::new_dynamic_buffer(
+                    allocator,
+                    BufferType::Uniform,
+                    std::mem::size_of::<PerFrameUniformBuffer>(),
+                    1,
+                )
+                .unwrap()
+            })
+            .collect();
+        let descriptor_sets =
+            descriptor_manager.allocate_shadow_descriptor_sets(&uniform_buffers)?;
+
+        Ok(Self {
+            device,
+            image,
+            image_view,
+            layer_views,
+            sampler,
+            framebuffers,
+            uniform_buffers,
+            descriptor_sets,
+            pipeline_manager,
+        })
+    }
+
+    pub fn image_view(&self) -> &ImageView {
+        &self.image_view
+    }
+
+    pub fn sampler(&self) -> &Sampler {
+        &self.sampler
+    }
+
+    pub fn graph_image(&self) -> GraphImage {
+        GraphImage::from_image(&self.image, &self.image_view)
+    }
+
+    pub fn update_views(&mut self, camera_index: usize, views: &[ShadowView]) {
+        let first = camera_index * self.layer_views.len();
+        for (buffer, view) in self.uniform_buffers[first..].iter().zip(views) {
+            buffer.copy_memory_from(&[PerFrameUniformBuffer::new(&view.view, &view.projection)]);
+        }
+    }
+
+    /// Renders the opaque objects into the first `count` shadow maps, using the views of the
+    /// `camera_index`th camera drawn into the target
+    pub fn record_command_buffer(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        camera_index: usize,
+        count: usize,
+        objects: &[&VulkanRenderObject],
+        dub_manager: &DynamicUniformBufferManager,
+        bone_manager: &DynamicUniformBufferManager,
+    ) {
+        let casters: Vec<&VulkanRenderObject> = objects
+            .iter()
+            .filter(|o| !o.material().use_alpha())
+            .copied()
+            .collect();
+
+        let clear_values = [vk::ClearValue {
+            depth_stencil: vk::ClearDepthStencilValue {
+                depth: 1.,
+                stencil: 0,
+            },
+        }];
+
+        for layer in 0..count.min(self.framebuffers.len()) {
+            let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+                .render_pass(self.pipeline_manager.render_pass().vk_render_pass())
+                .framebuffer(self.framebuffers[layer])
+                .render_area(
+                    vk::Rect2D::builder()
+                        .offset(vk::Offset2D::builder().x(0).y(0).build())
+                        .extent(vk::Extent2D {
+                            width: SHADOW_MAP_SIZE,
+                            height: SHADOW_MAP_SIZE,
+                        })
+                        .build(),
+                )
+                .clear_values(&clear_values)
+                .build();
+
+            self.device.cmd_begin_render_pass(
+                command_buffer,
+                &render_pass_begin_info,
+                vk::SubpassContents::INLINE,
+            );
+            self.pipeline_manager.record_draw_commands(
+                command_buffer,
+                vk::Rect2D {
+                    offset: vk::Offset2D { x: 0, y: 0 },
+                    extent: vk::Extent2D {
+                        width: SHADOW_MAP_SIZE,
+                        height: SHADOW_MAP_SIZE,
+                    },
+                },
+                self.descriptor_sets[camera_index * self.layer_views.len() + layer],
+                &casters,
+                dub_manager,
+                bone_manager,
+            );
+            self.device.cmd_end_render_pass(command_buffer);
+        }
+    }
+}
+
+impl Drop for ShadowMap {
+    fn drop(&mut self) {
+        for framebuffer in &self.framebuffers {
+            self.device.destroy_framebuffer(*framebuffer);
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use super::descriptor_managers::DescriptorManager;
+use super::descriptor_pool::{DescriptorPool, DescriptorPoolCreateInfo};
+use super::descriptor_set_layout::DescriptorSetLayout;
+use super::device::Device;
+use super::pipeline::Pipeline;
+use super::render_pass::RenderPass;
+use super::shader::VulkanShader;
+use super::texture::VulkanTexture;
+use crate::rendering::MAX_CAMERAS_PER_TARGET;
+use ash::vk;
+use std::error::Error;
+use std::rc::Rc;
+
+static SKYBOX_VERT: &'static [u8] = include_bytes!(concat!(env!("OUT_DIR"), "/skybox.vert.spv"));
+static SKYBOX_FRAG: &'static [u8] = include_bytes!(concat!(env!("OUT_DIR"), "/skybox.frag.spv"));
+
+/// Draws the skybox cubemap at the start of the scene pass
+pub struct SkyboxRenderer {
+    device: Rc<Device>,
+    descriptor_pool: DescriptorPool,
+    descriptor_set_layout: DescriptorSetLayout,
+    pipeline: Pipeline,
+}
+
+impl SkyboxRenderer {
+    /// `render_pass` and `samples` must match the scene pass
+    pub fn new(
+        device: Rc<Device>,
+        descriptor_manager: &DescriptorManager,
+        render_pass: &RenderPass,
+        samples: vk::SampleCountFlags,
+    ) -> Result<Self, Box<dyn Error>> {
+        let descriptor_pool = DescriptorPool::new(
+            device.clone(),
+            &[DescriptorPoolCreateInfo {
+                ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: MAX_CAMERAS_PER_TARGET as u32,
+            }],
+        );
+        let descriptor_set_layout = DescriptorSetLayout::new(
+            device.clone(),
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            vk::ShaderStageFlags::FRAGMENT,
+            1,
+        );
+
+        let set_layouts = [
+            descriptor_manager.per_frame_layout(),
+            descriptor_set_layout.vk_layout(),
+        ];
+        let push_constant_ranges = [vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .offset(0)
+            .size(std::mem::size_of::<[f32; 4]>() as u32)
+            .build()];
+        let vert_shader = VulkanShader::create_shader_module_from_memory(&device, SKYBOX_VERT)?;
+        let frag_shader = VulkanShader::create_shader_module_from_memory(&device, SKYBOX_FRAG)?;
+        let pipeline = Pipeline::new_background(
+            device.clone(),
+            render_pass,
+            &set_layouts,
+            &push_constant_ranges,
+            vert_shader,
+            frag_shader,
+            samples,
+        );
+        device.destroy_shader_module(vert_shader);
+        device.destroy_shader_module(frag_shader);
+
+        Ok(Self {
+            device,
+            descriptor_pool,
+            descriptor_set_layout,
+            pipeline,
+        })
+    }
+
+    /// Releases the descriptor sets of the previous frame, so it must no longer be in flight
+    pub fn begin_frame(&self) {
+        self.descriptor_pool.reset();
+    }
+
+    /// Records the skybox draw into the `viewport` of the scene pass, at most once per camera
+    /// between two calls to `begin_frame`
+    pub fn record_command_buffer(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        per_frame_descriptor_set: vk::DescriptorSet,
+        cubemap: &VulkanTexture,
+        intensity: f32,
+        viewport: vk::Rect2D,
+    ) {
+        let layouts = [self.descriptor_set_layout.vk_layout()];
+        let allocate_info = vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(self.descriptor_pool.vk_pool())
+            .set_layouts(&layouts)
+            .build();
+        let descriptor_set = self
+            .device
+            .allocate_descriptor_sets(&allocate_info)
+            .unwrap()[0];
+
+        let image_infos = [vk::DescriptorImageInfo::builder()
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .image_view(cubemap.image_view().vk_image_view())
+            .sampler(cubemap.sampler().vk_sampler())
+            .build()];
+        let write_descriptor_sets = [vk::WriteDescriptorSet::builder()
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .dst_set(descriptor_set)
+            .dst_binding(0)
+            .dst_array_element(0)
+            .image_info(&image_infos)
+            .build()];
+        self.device
+            .update_descriptor_sets(&write_descriptor_sets, &[]);
+
+        let layout = self.pipeline.pipeline_layout().vk_pipeline_layout();
+        self.device.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline.vk_pipeline(),
+        );
+        self.device.cmd_set_viewport(
+            command_buffer,
+            0,
+            &[vk::Viewport::builder()
+                .x(viewport.offset.x as f32)
+                .y(viewport.offset.y as f32)
+                .width(viewport.extent.width as f32)
+                .height(viewport.extent.height as f32)
+                .min_depth(0.)
+                .max_depth(1.)
+                .build()],
+        );
+        self.device.cmd_set_scissor(command_buffer, 0, &[viewport]);
+        self.device.cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            layout,
+            0,
+            &[per_frame_descriptor_set, descriptor_set],
+            &[],
+        );
+
+        let params = [intensity, 0., 0., 0.];
+        self.device.cmd_push_constants(
+            command_buffer,
+            layout,
+            vk::ShaderStageFlags::FRAGMENT,
+            0,
+            unsafe {
+                std::slice::from_raw_parts(
+                    params.as_ptr() as *const u8,
+                    std::mem::size_of_val(&params),
+                )
+            },
+        );
+        self.device.cmd_draw(command_buffer, 3, 1, 0, 0);
+    }
+}
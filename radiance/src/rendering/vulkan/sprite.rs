@@ -0,0 +1,521 @@
+use super::adhoc_command_runner::AdhocCommandRunner;
+use super::buffer::{Buffer, BufferType};
+use super::descriptor_managers::DescriptorManager;
+use super::descriptor_pool::{DescriptorPool, DescriptorPoolCreateInfo};
+use super::descriptor_set_layout::DescriptorSetLayout;
+use super::device::Device;
+use super::pipeline::Pipeline;
+use super::render_pass::RenderPass;
+use super::shader::VulkanShader;
+use super::texture::VulkanTexture;
+use crate::math::Mat44;
+use crate::rendering::{Sprite, TextureDef};
+use ash::vk;
+use std::error::Error;
+use std::rc::Rc;
+
+static SPRITE_VERT: &'static [u8] = include_bytes!(concat!(env!("OUT_DIR"), "/sprite.vert.spv"));
+static SPRITE_FRAG: &'static [u8] = include_bytes!(concat!(env!("OUT_DIR"), "/sprite.frag.spv"));
+static SPRITE_SCREEN_VERT: &'static [u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/sprite_screen.vert.spv"));
+
+// Atlases the descriptor pool is first sized for, it grows when a frame uses more
+const INITIAL_ATLAS_CAPACITY: u32 = 16;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct SpriteVertex {
+    position: [f32; 3],
+    tex_coord: [f32; 2],
+    color: [f32; 4],
+}
+
+/// Corners of the sprites of an atlas, in world space or in pixels, in drawing order
+pub struct SpriteBatch {
+    atlas: Rc<TextureDef>,
+    texture: VulkanTexture,
+    vertex_buffer: Option<Buffer>,
+}
+
+impl SpriteBatch {
+    pub fn texture(&self) -> &VulkanTexture {
+        &self.texture
+    }
+
+    pub fn vertex_buffer(&self) -> Option<&Buffer> {
+        self.vertex_buffer.as_ref()
+    }
+
+    // Only grows the buffer, so that it's reallocated once the scene reaches its size
+    fn upload(
+        &mut self,
+        vertices: &[SpriteVertex],
+        allocator: &Rc<vk_mem::Allocator>,
+    ) -> Result<(), Box<dyn Error>> {
+        let capacity = self
+            .vertex_buffer
+            .as_ref()
+            .map_or(0, |buffer| buffer.element_count() as usize);
+        if capacity < vertices.len() {
+            self.vertex_buffer = Some(Buffer::new_dynamic_buffer(
+                allocator,
+                BufferType::Vertex,
+                std::mem::size_of::<SpriteVertex>(),
+                vertices.len().next_power_of_two(),
+            )?);
+        }
+
+        if let Some(buffer) = self.vertex_buffer.as_ref() {
+            buffer.copy_memory_from(vertices);
+        }
+
+        Ok(())
+    }
+}
+
+/// Consecutive sprites of a batch drawn with a single call
+#[derive(Copy, Clone, Debug)]
+pub struct SpriteDraw {
+    pub batch: usize,
+    pub first_sprite: u32,
+    pub sprite_count: u32,
+}
+
+#[derive(Copy, Clone)]
+struct BatchedSprite {
+    batch: usize,
+    index: u32,
+    layer_bit: u32,
+}
+
+/// Packs the sprites of the scene into one vertex buffer per atlas, shared by every camera
+/// of the frame. The atlases are uploaded on first use and released once no sprite uses them.
+pub struct SpriteBatcher {
+    device: Rc<Device>,
+    allocator: Rc<vk_mem::Allocator>,
+    command_runner: Rc<AdhocCommandRunner>,
+    batches: Vec<SpriteBatch>,
+    index_buffer: Option<Buffer>,
+    sprites: Vec<BatchedSprite>,
+}
+
+impl SpriteBatcher {
+    pub fn new(
+        device: Rc<Device>,
+        allocator: Rc<vk_mem::Allocator>,
+        command_runner: Rc<AdhocCommandRunner>,
+    ) -> Self {
+        Self {
+            device,
+            allocator,
+            command_runner,
+            batches: vec![],
+            index_buffer: None,
+            sprites: vec![],
+        }
+    }
+
+    pub fn batches(&self) -> &[SpriteBatch] {
+        &self.batches
+    }
+
+    pub fn index_buffer(&self) -> Option<&Buffer> {
+        self.index_buffer.as_ref()
+    }
+
+    /// Rebuilds the batches from the sprites and the world transforms of their entities. The
+    /// buffers are rewritten, so the previous frame must no longer be in flight.
+    pub fn update(&mut self, sprites: &[(&Sprite, &Mat44)]) -> Result<(), Box<dyn Error>> {
+        let mut sprites = sprites.to_vec();
+        sprites.sort_by_key(|(sprite, _)| sprite.z_order);
+
+        self.batches.retain(|batch| {
+            sprites
+                .iter()
+                .any(|(sprite, _)| Rc::ptr_eq(&sprite.atlas, &batch.atlas))
+        });
+
+        let mut vertices: Vec<Vec<SpriteVertex>> = vec![vec![]; self.batches.len()];
+        self.sprites.clear();
+        for (sprite, transform) in sprites {
+            let batch = match self
+                .batches
+                .iter()
+                .position(|batch| Rc::ptr_eq(&sprite.atlas, &batch.atlas))
+            {
+                Some(batch) => batch,
+                None => {
+                    let texture = VulkanTexture::new(
+                        &sprite.atlas,
+                        &self.device,
+                        &self.allocator,
+                        &self.command_runner,
+                    )?;
+                    self.batches.push(SpriteBatch {
+                        atlas: sprite.atlas.clone(),
+                        texture,
+                        vertex_buffer: None,
+                    });
+                    vertices.push(vec![]);
+                    self.batches.len() - 1
+                }
+            };
+
+            let batch_vertices = &mut vertices[batch];
+            self.sprites.push(BatchedSprite {
+                batch,
+                index: (batch_vertices.len() / 4) as u32,
+                layer_bit: 1 << sprite.layer(),
+            });
+            for (position, tex_coord) in sprite.corners(transform).iter() {
+                batch_vertices.push(SpriteVertex {
+                    position: [position.x, position.y, position.z],
+                    tex_coord: [tex_coord.x, tex_coord.y],
+                    color: sprite.tint,
+                });
+            }
+        }
+
+        for (batch, vertices) in self.batches.iter_mut().zip(vertices.iter()) {
+            batch.upload(vertices, &self.allocator)?;
+        }
+
+        let max_sprites = vertices.iter().map(|v| v.len() / 4).max().unwrap_or(0);
+        self.update_index_buffer(max_sprites)
+    }
+
+    /// Draw calls of the sprites a camera sees, merging the runs of consecutive sprites of the
+    /// same atlas. Sprites sampling `target_image` are skipped, like the objects.
+    pub fn draws(&self, layer_mask: u32, target_image: Option<vk::Image>) -> Vec<SpriteDraw> {
+        let mut draws: Vec<SpriteDraw> = vec![];
+        for sprite in self.sprites.iter() {
+            if layer_mask & sprite.layer_bit == 0
+                || Some(self.batches[sprite.batch].texture.image().vk_image()) == target_image
+            {
+                continue;
+            }
+
+            match draws.last_mut() {
+                Some(draw)
+                    if draw.batch == sprite.batch
+                        && draw.first_sprite + draw.sprite_count == sprite.index =>
+                {
+                    draw.sprite_count += 1
+                }
+                _ => draws.push(SpriteDraw {
+                    batch: sprite.batch,
+                    first_sprite: sprite.index,
+                    sprite_count: 1,
+                }),
+            }
+        }
+
+        draws
+    }
+
+    // Two triangles per quad, the same indices serve every batch
+    fn update_index_buffer(&mut self, sprite_count: usize) -> Result<(), Box<dyn Error>> {
+        let capacity = self
+            .index_buffer
+            .as_ref()
+            .map_or(0, |buffer| buffer.element_count() as usize / 6);
+        if capacity >= sprite_count {
+            return Ok(());
+        }
+
+        let indices: Vec<u32> = (0..sprite_count.next_power_of_two() as u32)
+            .flat_map(|sprite| {
+                let first = sprite * 4;
+                vec![first, first + 1, first + 2, first + 2, first + 3, first]
+            })
+            .collect();
+        self.index_buffer = Some(Buffer::new_device_buffer_with_data(
+            &self.allocator,
+            BufferType::Index,
+            &indices,
+            &self.command_runner,
+        )?);
+
+        Ok(())
+    }
+}
+
+/// Draws the sprite batches in the scene pass after the objects, or in screen space over the
+/// final image
+pub struct SpriteRenderer {
+    device: Rc<Device>,
+    screen_space: bool,
+    descriptor_pool: DescriptorPool,
+    atlas_capacity: u32,
+    descriptor_set_layout: DescriptorSetLayout,
+    descriptor_sets: Vec<vk::DescriptorSet>,
+    pipeline: Pipeline,
+}
+
+impl SpriteRenderer {
+    /// `render_pass` and `samples` must match the scene pass
+    pub fn new(
+        device: Rc<Device>,
+        descriptor_manager: &DescriptorManager,
+        render_pass: &RenderPass,
+        samples: vk::SampleCountFlags,
+    ) -> Result<Self, Box<dyn Error>> {
+        Self::create(
+            device,
+            descriptor_manager,
+            render_pass,
+            samples,
+            SPRITE_VERT,
+            false,
+        )
+    }
+
+    /// Renderer for sprites whose corners are in pixels from the top left of the target,
+    /// drawn in a pass compatible with `render_pass` without depth nor multisampling
+    pub fn new_screen_space(
+        device: Rc<Device>,
+        descriptor_manager: &DescriptorManager,
+        render_pass: &RenderPass,
+    ) -> Result<Self, Box<dyn Error>> {
+        Self::create(
+            device,
+            descriptor_manager,
+            render_pass,
+            vk::SampleCountFlags::TYPE_1,
+            SPRITE_SCREEN_VERT,
+            true,
+        )
+    }
+
+    fn create(
+        device: Rc<Device>,
+        descriptor_manager: &DescriptorManager,
+        render_pass: &RenderPass,
+        samples: vk::SampleCountFlags,
+        vert_shader_code: &[u8],
+        screen_space: bool,
+    ) -> Result<Self, Box<dyn Error>> {
+        let descriptor_pool = Self::create_descriptor_pool(&device, INITIAL_ATLAS_CAPACITY);
+        let descriptor_set_layout = DescriptorSetLayout::new(
+            device.clone(),
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            vk::ShaderStageFlags::FRAGMENT,
+            1,
+        );
+
+        let set_layouts = [
+            descriptor_manager.per_frame_layout(),
+            descriptor_set_layout.vk_layout(),
+        ];
+        let vertex_binding = vk::VertexInputBindingDescription::builder()
+            .binding(0)
+            .stride(std::mem::size_of::<SpriteVertex>() as u32)
+            .input_rate(vk::VertexInputRate::VERTEX)
+            .build();
+        let attribute = |location: u32, format: vk::Format, offset: usize| {
+            vk::VertexInputAttributeDescription::builder()
+                .binding(0)
+                .location(location)
+                .format(format)
+                .offset(offset as u32)
+                .build()
+        };
+        let vertex_attributes = [
+            attribute(0, vk::Format::R32G32B32_SFLOAT, 0),
+            attribute(1, vk::Format::R32G32_SFLOAT, 12),
+            attribute(2, vk::Format::R32G32B32A32_SFLOAT, 20),
+        ];
+        // The screen space vertex shader doesn't read the per-frame set, only the target size
+        let push_constant_ranges = if screen_space {
+            vec![vk::PushConstantRange::builder()
+                .stage_flags(vk::ShaderStageFlags::VERTEX)
+                .offset(0)
+                .size(std::mem::size_of::<[f32; 2]>() as u32)
+                .build()]
+        } else {
+            vec![]
+        };
+        let vert_shader =
+            VulkanShader::create_shader_module_from_memory(&device, vert_shader_code)?;
+        let frag_shader = VulkanShader::create_shader_module_from_memory(&device, SPRITE_FRAG)?;
+        let pipeline = Pipeline::new_blended(
+            device.clone(),
+            render_pass,
+            &set_layouts,
+            &push_constant_ranges,
+            vertex_binding,
+            &vertex_attributes,
+            vert_shader,
+            frag_shader,
+            samples,
+        );
+        device.destroy_shader_module(vert_shader);
+        device.destroy_shader_module(frag_shader);
+
+        Ok(Self {
+            device,
+            screen_space,
+            descriptor_pool,
+            atlas_capacity: INITIAL_ATLAS_CAPACITY,
+            descriptor_set_layout,
+            descriptor_sets: vec![],
+            pipeline,
+        })
+    }
+
+    /// Binds the atlases of the frame's batches, the previous frame must no longer be in flight
+    pub fn begin_frame(&mut self, batches: &[SpriteBatch]) {
+        if batches.len() as u32 > self.atlas_capacity {
+            self.atlas_capacity = (batches.len() as u32).next_power_of_two();
+            self.descriptor_pool = Self::create_descriptor_pool(&self.device, self.atlas_capacity);
+        } else {
+            self.descriptor_pool.reset();
+        }
+
+        self.descriptor_sets.clear();
+        if batches.is_empty() {
+            return;
+        }
+
+        let layouts = vec![self.descriptor_set_layout.vk_layout(); batches.len()];
+        let allocate_info = vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(self.descriptor_pool.vk_pool())
+            .set_layouts(&layouts)
+            .build();
+        self.descriptor_sets = self
+            .device
+            .allocate_descriptor_sets(&allocate_info)
+            .unwrap();
+
+        for (batch, descriptor_set) in batches.iter().zip(self.descriptor_sets.iter()) {
+            let image_infos = [vk::DescriptorImageInfo::builder()
+                .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                .image_view(batch.texture.image_view().vk_image_view())
+                .sampler(batch.texture.sampler().vk_sampler())
+                .build()];
+            let write_descriptor_sets = [vk::WriteDescriptorSet::builder()
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .dst_set(*descriptor_set)
+                .dst_binding(0)
+                .dst_array_element(0)
+                .image_info(&image_infos)
+                .build()];
+            self.device
+                .update_descriptor_sets(&write_descriptor_sets, &[]);
+        }
+    }
+
+    /// Records the draws of a camera into the `viewport` of the scene pass. Screen space
+    /// renderers take no per-frame descriptor set and cover the `viewport` with the target.
+    pub fn record_command_buffer(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        per_frame_descriptor_set: Option<vk::DescriptorSet>,
+        batcher: &SpriteBatcher,
+        draws: &[SpriteDraw],
+        viewport: vk::Rect2D,
+    ) {
+        let index_buffer = match batcher.index_buffer() {
+            Some(buffer) if !draws.is_empty() => buffer,
+            _ => return,
+        };
+
+        let layout = self.pipeline.pipeline_layout().vk_pipeline_layout();
+        self.device.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline.vk_pipeline(),
+        );
+        self.device.cmd_set_viewport(
+            command_buffer,
+            0,
+            &[vk::Viewport::builder()
+                .x(viewport.offset.x as f32)
+                .y(viewport.offset.y as f32)
+                .width(viewport.extent.width as f32)
+                .height(viewport.extent.height as f32)
+                .min_depth(0.)
+                .max_depth(1.)
+                .build()],
+        );
+        self.device.cmd_set_scissor(command_buffer, 0, &[viewport]);
+        if self.screen_space {
+            let size = [viewport.extent.width as f32, viewport.extent.height as f32];
+            self.device.cmd_push_constants(
+                command_buffer,
+                layout,
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                unsafe {
+                    std::slice::from_raw_parts(
+                        size.as_ptr() as *const u8,
+                        std::mem::size_of_val(&size),
+                    )
+                },
+            );
+        }
+
+        self.device.cmd_bind_index_buffer(
+            command_buffer,
+            index_buffer.vk_buffer(),
+            0,
+            vk::IndexType::UINT32,
+        );
+
+        let mut bound_batch = None;
+        for draw in draws {
+            let vertex_buffer = match batcher.batches()[draw.batch].vertex_buffer() {
+                Some(buffer) => buffer,
+                None => continue,
+            };
+
+            if bound_batch != Some(draw.batch) {
+                self.device.cmd_bind_vertex_buffers(
+                    command_buffer,
+                    0,
+                    &[vertex_buffer.vk_buffer()],
+                    &[0],
+                );
+                let atlas_set = self.descriptor_sets[draw.batch];
+                match per_frame_descriptor_set {
+                    Some(per_frame_set) => self.device.cmd_bind_descriptor_sets(
+                        command_buffer,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        layout,
+                        0,
+                        &[per_frame_set, atlas_set],
+                        &[],
+                    ),
+                    None => self.device.cmd_bind_descriptor_sets(
+                        command_buffer,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        layout,
+                        1,
+                        &[atlas_set],
+                        &[],
+                    ),
+                }
+                bound_batch = Some(draw.batch);
+            }
+
+            self.device.cmd_draw_indexed(
+                command_buffer,
+                draw.sprite_count * 6,
+                1,
+                0,
+                (draw.first_sprite * 4) as i32,
+                0,
+            );
+        }
+    }
+
+    fn create_descriptor_pool(device: &Rc<Device>, atlas_capacity: u32) -> DescriptorPool {
+        DescriptorPool::new(
+            device.clone(),
+            &[DescriptorPoolCreateInfo {
+                ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: atlas_capacity,
+            }],
+        )
+    }
+}
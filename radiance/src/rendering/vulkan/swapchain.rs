@@ -3,8 +3,18 @@ use super::descriptor_managers::DescriptorManager;
 use super::image::Image;
 use super::image_view::ImageView;
 use super::pipeline_manager::PipelineManager;
+use super::render_graph::{GraphImage, RenderGraph, RenderGraphCache, RenderGraphPass};
+use super::post_processing::{PostProcessor, HDR_FORMAT};
 use super::render_object::VulkanRenderObject;
-use super::uniform_buffers::{DynamicUniformBufferManager, PerFrameUniformBuffer};
+use super::render_target::VulkanRenderTarget;
+use super::environment::VulkanEnvironment;
+use super::shadow_map::ShadowMap;
+use super::skybox::SkyboxRenderer;
+use super::sprite::{SpriteBatcher, SpriteDraw, SpriteRenderer};
+use super::texture::VulkanTexture;
+use super::uniform_buffers::{
+    DynamicUniformBufferManager, PerFrameLightUniformBuffer, PerFrameUniformBuffer,
+};
 use super::{adhoc_command_runner::AdhocCommandRunner, device::Device};
 use super::{
     buffer::{Buffer, BufferType},
@@ -12,11 +22,13 @@ use super::{
 };
 use crate::{
     imgui::{ImguiContext, ImguiFrame},
+    rendering::shadow::ShadowSetup,
+    rendering::{PostProcessing, MAX_CAMERAS_PER_TARGET},
     rendering::vulkan::imgui::ImguiVulkanContext,
 };
 use ash::prelude::VkResult;
 use ash::vk;
-use std::cmp::Ordering;
+use std::cell::RefCell;
 use std::rc::Rc;
 
 pub struct SwapChain {
@@ -25,14 +37,22 @@ pub struct SwapChain {
     handle: vk::SwapchainKHR,
     images: Vec<vk::Image>,
     image_views: Vec<ImageView>,
-    depth_image: Image,
-    depth_image_view: ImageView,
+    format: vk::Format,
+    depth_format: vk::Format,
+    samples: vk::SampleCountFlags,
     uniform_buffers: Vec<Buffer>,
+    light_uniform_buffers: Vec<Buffer>,
     per_frame_descriptor_sets: Vec<vk::DescriptorSet>,
-    framebuffers: Vec<vk::Framebuffer>,
+    graph_cache: RenderGraphCache,
     command_buffers: Vec<vk::CommandBuffer>,
+    offscreen_command_buffer: vk::CommandBuffer,
     capabilities: vk::SurfaceCapabilitiesKHR,
     pipeline_manager: PipelineManager,
+    shadow_map: ShadowMap,
+    skybox: SkyboxRenderer,
+    sprite_renderer: SpriteRenderer,
+    screen_sprite_renderer: SpriteRenderer,
+    post_processor: PostProcessor,
     imgui: ImguiVulkanContext,
 
     entry: ash::extensions::khr::Swapchain,
@@ -50,8 +70,10 @@ impl SwapChain {
         mut capabilities: vk::SurfaceCapabilitiesKHR,
         format: vk::SurfaceFormatKHR,
         present_mode: vk::PresentModeKHR,
+        samples: vk::SampleCountFlags,
         descriptor_manager: &Rc<DescriptorManager>,
         command_runner: &Rc<AdhocCommandRunner>,
+        environment: &VulkanEnvironment,
         gui_context: &mut ImguiContext,
     ) -> Result<Self, Box<dyn std::error::Error>> {
 
@@ -71,7 +93,8 @@ impl SwapChain {
 
         let images = unsafe { entry.get_swapchain_images(handle)? };
         let image_views = creation_helpers::create_image_views(&device, &images, format)?;
-        let uniform_buffers: Vec<Buffer> = (0..images.len())
+        // Each camera drawn into a target has its own per-frame buffers
+        let uniform_buffers: Vec<Buffer> = (0..images.len() * MAX_CAMERAS_PER_TARGET)
             .map(|_| {
                 Buffer::new_dynamic_buffer(
                     allocator,
@@ -82,54 +105,79 @@ impl SwapChain {
                 .unwrap()
             })
             .collect();
+        let light_uniform_buffers: Vec<Buffer> = (0..images.len() * MAX_CAMERAS_PER_TARGET)
+            .map(|_| {
+                Buffer::new_dynamic_buffer(
+                    allocator,
+                    BufferType::Uniform,
+                    std::mem::size_of::<PerFrameLightUniformBuffer>(),
+                    1,
+                )
+                .unwrap()
+            })
+            .collect();
 
-        let mut depth_image = Image::new_depth_image(
-            instance.vk_instance(),
-            physical_device,
-            &allocator,
-            capabilities.current_extent.width,
-            capabilities.current_extent.height,
-        )?;
-
-        depth_image.transit_layout(
-            vk::ImageLayout::UNDEFINED,
-            vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
-            command_runner,
-        )?;
-        let depth_image_view = ImageView::new_depth_image_view(
-            device.clone(),
-            depth_image.vk_image(),
-            depth_image.vk_format(),
-        )?;
+        let depth_format = Image::find_depth_format(instance.vk_instance(), physical_device)?;
 
         descriptor_manager.reset_per_frame_descriptor_pool();
         let pipeline_manager = PipelineManager::new(
             device.clone(),
             &descriptor_manager,
-            format.format,
-            depth_image.vk_format(),
-            capabilities.current_extent,
+            HDR_FORMAT,
+            depth_format,
+            samples,
         );
 
-        let per_frame_descriptor_sets =
-            descriptor_manager.allocate_per_frame_descriptor_sets(uniform_buffers.as_slice())?;
+        let skybox = SkyboxRenderer::new(
+            device.clone(),
+            descriptor_manager,
+            pipeline_manager.render_pass(),
+            samples,
+        )?;
+        let sprite_renderer = SpriteRenderer::new(
+            device.clone(),
+            descriptor_manager,
+            pipeline_manager.render_pass(),
+            samples,
+        )?;
+        let shadow_map = ShadowMap::new(
+            device.clone(),
+            allocator,
+            descriptor_manager,
+            command_runner,
+        )?;
+        let per_frame_descriptor_sets = descriptor_manager.allocate_per_frame_descriptor_sets(
+            uniform_buffers.as_slice(),
+            light_uniform_buffers.as_slice(),
+            environment.irradiance(),
+            environment.prefiltered(),
+            &shadow_map,
+        )?;
 
-        let framebuffers = creation_helpers::create_framebuffers(
-            &device,
-            &image_views,
-            &depth_image_view,
-            &capabilities.current_extent,
-            pipeline_manager.render_pass().vk_render_pass(),
+        let graph_cache = RenderGraphCache::new(device.clone(), allocator);
+        let post_processor = PostProcessor::new(device.clone(), format.format)?;
+        let screen_sprite_renderer = SpriteRenderer::new_screen_space(
+            device.clone(),
+            descriptor_manager,
+            post_processor.output_render_pass(),
         )?;
 
         let command_buffers = {
             let create_info = vk::CommandBufferAllocateInfo::builder()
                 .command_pool(command_pool)
-                .command_buffer_count(framebuffers.len() as u32)
+                .command_buffer_count(images.len() as u32)
                 .level(vk::CommandBufferLevel::PRIMARY)
                 .build();
             device.allocate_command_buffers(&create_info)?
         };
+        let offscreen_command_buffer = {
+            let create_info = vk::CommandBufferAllocateInfo::builder()
+                .command_pool(command_pool)
+                .command_buffer_count(1)
+                .level(vk::CommandBufferLevel::PRIMARY)
+                .build();
+            device.allocate_command_buffers(&create_info)?[0]
+        };
 
         let imgui = ImguiVulkanContext::new(
             instance.clone(),
@@ -137,7 +185,7 @@ impl SwapChain {
             device.clone(),
             queue,
             command_pool,
-            pipeline_manager.render_pass().vk_render_pass(),
+            post_processor.output_render_pass().vk_render_pass(),
             images.len(),
             gui_context,
         );
@@ -148,14 +196,22 @@ impl SwapChain {
             handle,
             images,
             image_views,
-            depth_image,
-            depth_image_view,
+            format: format.format,
+            depth_format,
+            samples,
             uniform_buffers,
+            light_uniform_buffers,
             per_frame_descriptor_sets,
-            framebuffers,
+            graph_cache,
             command_buffers,
+            offscreen_command_buffer,
             capabilities,
             pipeline_manager,
+            shadow_map,
+            skybox,
+            sprite_renderer,
+            screen_sprite_renderer,
+            post_processor,
             imgui,
             entry,
         })
@@ -165,6 +221,10 @@ impl SwapChain {
         &self.command_buffers
     }
 
+    pub fn extent(&self) -> vk::Extent2D {
+        self.capabilities.current_extent
+    }
+
     pub fn imgui_mut(&mut self) -> &mut ImguiVulkanContext {
         &mut self.imgui
     }
@@ -181,8 +241,34 @@ impl SwapChain {
         }
     }
 
-    pub fn update_ubo<T>(&mut self, image_index: usize, data: &[T]) {
-        self.uniform_buffers[image_index].copy_memory_from(data);
+    pub fn update_ubo<T>(&mut self, image_index: usize, camera_index: usize, data: &[T]) {
+        self.uniform_buffers[per_frame_index(image_index, camera_index)].copy_memory_from(data);
+    }
+
+    pub fn set_environment(
+        &mut self,
+        descriptor_manager: &DescriptorManager,
+        environment: &VulkanEnvironment,
+    ) {
+        descriptor_manager.update_per_frame_environment(
+            &self.per_frame_descriptor_sets,
+            environment.irradiance(),
+            environment.prefiltered(),
+        );
+    }
+
+    pub fn update_shadow_views(&mut self, camera_index: usize, shadows: &ShadowSetup) {
+        self.shadow_map.update_views(camera_index, shadows.views());
+    }
+
+    pub fn update_light_ubo(
+        &mut self,
+        image_index: usize,
+        camera_index: usize,
+        data: &[PerFrameLightUniformBuffer],
+    ) {
+        self.light_uniform_buffers[per_frame_index(image_index, camera_index)]
+            .copy_memory_from(data);
     }
 
     pub fn present(
@@ -202,17 +288,74 @@ impl SwapChain {
         unsafe { self.entry.queue_present(queue, &present_info) }
     }
 
+    /// Releases the render graph objects unused during the frame. The device must be idle.
+    pub fn end_frame(&mut self) {
+        self.graph_cache.end_frame();
+    }
+
     pub fn record_command_buffers(
         &mut self,
         image_index: usize,
-        objects: &[&VulkanRenderObject],
-        dub_manager: &DynamicUniformBufferManager,
+        frame: &SceneFrame,
         ui_frame: ImguiFrame,
-    ) -> Result<vk::CommandBuffer, vk::Result> {
+    ) -> Result<vk::CommandBuffer, Box<dyn std::error::Error>> {
         let command_buffer = self.command_buffers[image_index];
-        let framebuffer = self.framebuffers[image_index];
-        let per_frame_descriptor_set = self.per_frame_descriptor_sets[image_index];
+        let backbuffer = GraphImage {
+            image: self.images[image_index],
+            view: self.image_views[image_index].vk_image_view(),
+            view_id: self.image_views[image_index].id(),
+            format: self.format,
+            extent: self.capabilities.current_extent,
+            layers: 1,
+            samples: vk::SampleCountFlags::TYPE_1,
+        };
+
+        self.record_frame(
+            command_buffer,
+            image_index,
+            backbuffer,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::PRESENT_SRC_KHR,
+            frame,
+            Some(ui_frame),
+        )?;
+
+        Ok(command_buffer)
+    }
 
+    /// Records a frame rendered into `target` instead of the backbuffer, without the UI. The
+    /// per-frame buffers of `image_index` are used, so the returned command buffer must complete
+    /// before they are updated for another target.
+    pub fn record_offscreen_command_buffer(
+        &mut self,
+        image_index: usize,
+        target: &VulkanRenderTarget,
+        frame: &SceneFrame,
+    ) -> Result<vk::CommandBuffer, Box<dyn std::error::Error>> {
+        let command_buffer = self.offscreen_command_buffer;
+        self.record_frame(
+            command_buffer,
+            image_index,
+            target.graph_image(),
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            frame,
+            None,
+        )?;
+
+        Ok(command_buffer)
+    }
+
+    fn record_frame(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        image_index: usize,
+        output: GraphImage,
+        initial_layout: vk::ImageLayout,
+        final_layout: vk::ImageLayout,
+        frame: &SceneFrame,
+        ui_frame: Option<ImguiFrame>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
         let begin_info = vk::CommandBufferBeginInfo::builder()
             .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
             .build();
@@ -223,120 +366,212 @@ impl SwapChain {
         self.device
             .begin_command_buffer(command_buffer, &begin_info)?;
 
-        let clear_values = [
-            vk::ClearValue {
-                color: vk::ClearColorValue {
-                    float32: [0f32, 0f32, 0f32, 1f32],
-                },
-            },
-            vk::ClearValue {
-                depth_stencil: vk::ClearDepthStencilValue {
-                    depth: 1.,
-                    stencil: 0,
-                },
-            },
-        ];
-        let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
-            .render_pass(self.pipeline_manager.render_pass().vk_render_pass())
-            .framebuffer(framebuffer)
-            .render_area(
-                vk::Rect2D::builder()
-                    .offset(vk::Offset2D::builder().x(0).y(0).build())
-                    .extent(self.capabilities.current_extent)
-                    .build(),
-            )
-            .clear_values(&clear_values)
-            .build();
-
-        self.device.cmd_begin_render_pass(
-            command_buffer,
-            &render_pass_begin_info,
-            vk::SubpassContents::INLINE,
+        self.sprite_renderer.begin_frame(frame.sprites.batches());
+        self.screen_sprite_renderer
+            .begin_frame(frame.screen_sprites.batches());
+
+        // Shared by the passes of every camera, which the graph records one after another
+        let shadow_map_image = self.shadow_map.graph_image();
+        let shadow_map = RefCell::new(&mut self.shadow_map);
+        let pipeline_manager = RefCell::new(&mut self.pipeline_manager);
+        let skybox_renderer = &self.skybox;
+        skybox_renderer.begin_frame();
+        let sprite_renderer = &self.sprite_renderer;
+
+        let extent = output.extent;
+        let output_format = output.format;
+        let mut graph = RenderGraph::new();
+        let output = graph.import_image("output", output, initial_layout, Some(final_layout));
+        let shadow_maps = graph.import_image(
+            "shadow_maps",
+            shadow_map_image,
+            vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+            None,
         );
+        let hdr = graph.create_transient_image("hdr", HDR_FORMAT, extent);
+        let depth =
+            graph.create_multisampled_image("depth", self.depth_format, extent, self.samples);
+
+        // With MSAA the scene is drawn to a multisampled image resolved into the HDR one
+        let scene = if self.samples == vk::SampleCountFlags::TYPE_1 {
+            None
+        } else {
+            Some(graph.create_multisampled_image("hdr_msaa", HDR_FORMAT, extent, self.samples))
+        };
 
-        let mut objects_by_material = vec![];
-        for obj in objects {
-            let key = obj.material().name();
-            self.pipeline_manager
-                .create_pipeline_if_not_exist(obj.material());
-            objects_by_material.push((obj.material(), vec![obj]));
-        }
-
-        objects_by_material.sort_by(|a, b| {
-            if a.0.use_alpha() && !b.0.use_alpha() {
-                Ordering::Greater
-            } else if !a.0.use_alpha() && b.0.use_alpha() {
-                Ordering::Less
-            } else {
-                Ordering::Equal
-            }
-        });
-
-        for (material, object_group) in &objects_by_material {
-            let pipeline = self.pipeline_manager.get_pipeline(material.name());
-
-            self.device.cmd_bind_pipeline(
-                command_buffer,
-                vk::PipelineBindPoint::GRAPHICS,
-                pipeline.vk_pipeline(),
+        let dub_manager = frame.dub_manager;
+        let bone_manager = frame.bone_manager;
+        let sprites = frame.sprites;
+        for (camera_index, camera) in frame.cameras.iter().enumerate() {
+            let per_frame_descriptor_set =
+                self.per_frame_descriptor_sets[per_frame_index(image_index, camera_index)];
+            let objects = &camera.objects[..];
+            let shadow_map_count = camera.shadow_map_count;
+            let shadow_map = &shadow_map;
+            graph.add_pass(
+                RenderGraphPass::new("shadows", move |context| {
+                    shadow_map.borrow_mut().record_command_buffer(
+                        context.command_buffer,
+                        camera_index,
+                        shadow_map_count,
+                        objects,
+                        dub_manager,
+                        bone_manager,
+                    )
+                })
+                .with_external_output(
+                    shadow_maps,
+                    vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+                ),
             );
 
-            for obj in object_group {
-                let vertex_buffer = obj.vertex_buffer();
-                let index_buffer = obj.index_buffer();
-                self.device.cmd_bind_vertex_buffers(
-                    command_buffer,
-                    0,
-                    &[vertex_buffer.vk_buffer()],
-                    &[0],
-                );
-                self.device.cmd_bind_index_buffer(
-                    command_buffer,
-                    index_buffer.vk_buffer(),
-                    0,
-                    vk::IndexType::UINT32,
-                );
-
-                self.device.cmd_bind_descriptor_sets(
-                    command_buffer,
-                    vk::PipelineBindPoint::GRAPHICS,
-                    pipeline.pipeline_layout().vk_pipeline_layout(),
-                    0,
-                    &[
+            let skybox = camera.skybox;
+            let sprite_draws = &camera.sprites[..];
+            let viewport = camera.viewport;
+            let pipeline_manager = &pipeline_manager;
+            let scene_pass = RenderGraphPass::new("scene", move |context| {
+                if let Some((cubemap, intensity)) = skybox {
+                    skybox_renderer.record_command_buffer(
+                        context.command_buffer,
                         per_frame_descriptor_set,
-                        dub_manager.descriptor_set(),
-                        obj.vk_descriptor_set(),
-                    ],
-                    &[dub_manager.get_offset(obj.dub_index()) as u32],
+                        cubemap,
+                        intensity,
+                        viewport,
+                    );
+                }
+
+                pipeline_manager.borrow_mut().record_draw_commands(
+                    context.command_buffer,
+                    viewport,
+                    per_frame_descriptor_set,
+                    objects,
+                    dub_manager,
+                    bone_manager,
                 );
-                self.device.cmd_draw_indexed(
-                    command_buffer,
-                    index_buffer.element_count(),
-                    1,
-                    0,
-                    0,
-                    0,
+                sprite_renderer.record_command_buffer(
+                    context.command_buffer,
+                    Some(per_frame_descriptor_set),
+                    sprites,
+                    sprite_draws,
+                    viewport,
                 );
-            }
+            });
+
+            // The first camera starts the target, clearing it entirely so that the parts no
+            // viewport covers are defined. The others only touch their viewport.
+            let (scene_pass, clear_color, clear_depth) = if camera_index == 0 {
+                (scene_pass, Some(camera.clear_color.unwrap_or_default()), true)
+            } else {
+                (
+                    scene_pass.with_render_area(viewport),
+                    camera.clear_color,
+                    camera.clear_depth,
+                )
+            };
+            let scene_pass = match scene {
+                Some(scene) => scene_pass
+                    .with_color_attachment(scene, clear_color)
+                    .with_resolve_attachment(hdr),
+                None => scene_pass.with_color_attachment(hdr, clear_color),
+            };
+            graph.add_pass(
+                scene_pass
+                    .with_depth_attachment(depth, if clear_depth { Some(1.) } else { None })
+                    .with_sampled_input(shadow_maps),
+            );
         }
 
-        self.imgui.record_command_buffer(ui_frame, command_buffer);
+        self.post_processor.add_passes(
+            &mut graph,
+            hdr,
+            output,
+            output_format,
+            extent,
+            frame.post_processing,
+            frame.color_grading_lut,
+        );
+
+        if !frame.screen_draws.is_empty() {
+            let screen_sprite_renderer = &self.screen_sprite_renderer;
+            let screen_sprites = frame.screen_sprites;
+            let screen_draws = &frame.screen_draws[..];
+            let viewport = vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent,
+            };
+            graph.add_pass(
+                RenderGraphPass::new("screen_sprites", move |context| {
+                    screen_sprite_renderer.record_command_buffer(
+                        context.command_buffer,
+                        None,
+                        screen_sprites,
+                        screen_draws,
+                        viewport,
+                    )
+                })
+                .with_color_attachment(output, None),
+            );
+        }
+
+        if let Some(ui_frame) = ui_frame {
+            let imgui = &mut self.imgui;
+            graph.add_pass(
+                RenderGraphPass::new("ui", move |context| {
+                    imgui.record_command_buffer(ui_frame, context.command_buffer)
+                })
+                .with_color_attachment(output, None),
+            );
+        }
 
-        self.device.cmd_end_render_pass(command_buffer);
+        graph.execute(&mut self.graph_cache, command_buffer)?;
         self.device.end_command_buffer(command_buffer)?;
 
-        Ok(command_buffer)
+        Ok(())
     }
 }
 
+// Index of the per-frame buffers and descriptor set of a camera drawn into a target
+fn per_frame_index(image_index: usize, camera_index: usize) -> usize {
+    image_index * MAX_CAMERAS_PER_TARGET + camera_index
+}
+
+/// What a camera draws into its viewport of the target
+pub struct CameraFrame<'a> {
+    pub objects: Vec<&'a VulkanRenderObject>,
+    pub viewport: vk::Rect2D,
+    pub shadow_map_count: usize,
+
+    /// Color the viewport is cleared to, `None` keeps what the earlier cameras drew
+    pub clear_color: Option<[f32; 4]>,
+    pub clear_depth: bool,
+    pub skybox: Option<(&'a VulkanTexture, f32)>,
+
+    /// Drawn after the objects, in order
+    pub sprites: Vec<SpriteDraw>,
+}
+
+/// What the cameras of a target draw into a frame
+pub struct SceneFrame<'a> {
+    /// At most `MAX_CAMERAS_PER_TARGET`, in drawing order. Each camera uses the per-frame
+    /// buffers of its index.
+    pub cameras: Vec<CameraFrame<'a>>,
+    pub dub_manager: &'a DynamicUniformBufferManager,
+    pub bone_manager: &'a DynamicUniformBufferManager,
+    pub sprites: &'a SpriteBatcher,
+
+    /// Drawn over the final image, such as the texts in screen space
+    pub screen_sprites: &'a SpriteBatcher,
+    pub screen_draws: Vec<SpriteDraw>,
+    pub post_processing: &'a PostProcessing,
+    pub color_grading_lut: Option<&'a VulkanTexture>,
+}
+
 impl Drop for SwapChain {
     fn drop(&mut self) {
-        for buffer in &self.framebuffers {
-            self.device.destroy_framebuffer(*buffer);
-        }
-
         self.device
             .free_command_buffers(self.command_pool, &self.command_buffers);
+        self.device
+            .free_command_buffers(self.command_pool, &[self.offscreen_command_buffer]);
         unsafe {
             self.entry.destroy_swapchain(self.handle, None);
         }
@@ -1,15 +1,20 @@
 use super::{
     adhoc_command_runner::AdhocCommandRunner, buffer::Buffer, device::Device, image::Image,
-    image_view::ImageView, sampler::Sampler,
+    image_view::ImageView, render_target::VulkanRenderTarget, sampler::Sampler,
 };
-use crate::rendering::texture::{Texture, TextureDef};
+use crate::rendering::texture::{
+    ColorSpace, HdrImage, SamplerDef, Texture, TextureDef, TextureSource,
+};
+use crate::rendering::{CompressedFormat, CompressedImage};
 use ash::vk;
+use image::RgbaImage;
 use std::error::Error;
 use std::rc::Rc;
 
+// Shared with the render target when the texture samples one
 pub struct VulkanTexture {
-    image: Image,
-    image_view: ImageView,
+    image: Rc<Image>,
+    image_view: Rc<ImageView>,
     sampler: Sampler,
 }
 
@@ -29,51 +34,435 @@ impl VulkanTexture {
         device: &Rc<Device>,
         allocator: &Rc<vk_mem::Allocator>,
         command_runner: &Rc<AdhocCommandRunner>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let sampler = def.sampler.as_ref();
+        let color_space = def.color_space;
+        match &def.source {
+            TextureSource::Image(image) => Self::new_2d(
+                image.as_ref(),
+                &sampler.copied().unwrap_or_default(),
+                color_space.unwrap_or(ColorSpace::Srgb),
+                device,
+                allocator,
+                command_runner,
+            ),
+            TextureSource::Cubemap(mips) => Self::new_cubemap(
+                mips,
+                sampler,
+                color_space.unwrap_or(ColorSpace::Srgb),
+                device,
+                allocator,
+                command_runner,
+            ),
+            TextureSource::HdrCubemap(faces) => {
+                Self::new_hdr_cubemap(faces, sampler, device, allocator, command_runner)
+            }
+            TextureSource::Compressed(image) => {
+                let srgb = if image.srgb() {
+                    ColorSpace::Srgb
+                } else {
+                    ColorSpace::Linear
+                };
+                Self::new_compressed(
+                    image,
+                    &sampler.copied().unwrap_or_default(),
+                    color_space.unwrap_or(srgb),
+                    device,
+                    allocator,
+                    command_runner,
+                )
+            }
+            TextureSource::RenderTarget(target) => {
+                let target = target
+                    .downcast_ref::<VulkanRenderTarget>()
+                    .expect("render targets must be created by the Vulkan component factory");
+                Ok(Self {
+                    image: target.image().clone(),
+                    image_view: target.image_view().clone(),
+                    sampler: Sampler::new_with_def(
+                        device.clone(),
+                        &sampler.copied().unwrap_or_default(),
+                        1,
+                    )?,
+                })
+            }
+        }
+    }
+
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+
+    pub fn image_view(&self) -> &ImageView {
+        &self.image_view
+    }
+
+    pub fn sampler(&self) -> &Sampler {
+        &self.sampler
+    }
+
+    fn new_2d(
+        image: Option<&RgbaImage>,
+        sampler: &SamplerDef,
+        color_space: ColorSpace,
+        device: &Rc<Device>,
+        allocator: &Rc<vk_mem::Allocator>,
+        command_runner: &Rc<AdhocCommandRunner>,
     ) -> Result<Self, Box<dyn Error>> {
         let texture_missing =
             image::load_from_memory(radiance_assets::TEXTURE_MISSING_TEXTURE_FILE)
                 .unwrap()
                 .to_rgba8();
-        let rgba_image = match def {
-            TextureDef::ImageTextureDef(image) => {
-                image.as_ref().unwrap_or_else(|| &texture_missing)
+        let rgba_image = image.unwrap_or_else(|| &texture_missing);
+
+        let format = rgba_format(color_space);
+        let mip_levels = Image::full_mip_levels(rgba_image.width(), rgba_image.height());
+        let mut image = Image::new_color_image(
+            allocator,
+            rgba_image.width(),
+            rgba_image.height(),
+            mip_levels,
+            format,
+        )?;
+
+        // Blits from the first level when the GPU can filter the format, otherwise every level
+        // is downscaled on the CPU and uploaded. Blits between sRGB images average the decoded
+        // values, the CPU fallback averages the encoded ones.
+        image.transit_layout(
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            &command_runner,
+        )?;
+        if Image::supports_linear_blit(device, format) {
+            let buffer = Buffer::new_staging_buffer_with_data(allocator, &rgba_image)?;
+            image.copy_from(&buffer, 1, &command_runner)?;
+            image.generate_mipmaps(&command_runner)?;
+        } else {
+            let mut data = rgba_image.as_raw().clone();
+            let mut level = rgba_image.clone();
+            for _ in 1..mip_levels {
+                level = image::imageops::resize(
+                    &level,
+                    (level.width() / 2).max(1),
+                    (level.height() / 2).max(1),
+                    image::imageops::FilterType::Triangle,
+                );
+                data.extend_from_slice(level.as_raw());
+            }
+
+            let buffer = Buffer::new_staging_buffer_with_data(allocator, &data)?;
+            image.copy_from(&buffer, mip_levels, &command_runner)?;
+            image.transit_layout(
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                &command_runner,
+            )?;
+        }
+
+        let image_view = ImageView::new_color_image_view_with_mips(
+            device.clone(),
+            image.vk_image(),
+            format,
+            mip_levels,
+        )?;
+        let sampler = Sampler::new_with_def(device.clone(), sampler, mip_levels)?;
+
+        Ok(Self {
+            image: Rc::new(image),
+            image_view: Rc::new(image_view),
+            sampler,
+        })
+    }
+
+    fn new_compressed(
+        compressed: &CompressedImage,
+        sampler: &SamplerDef,
+        color_space: ColorSpace,
+        device: &Rc<Device>,
+        allocator: &Rc<vk_mem::Allocator>,
+        command_runner: &Rc<AdhocCommandRunner>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let format = match compressed_vk_format(compressed.format(), color_space) {
+            Some(format) if Self::supports_sampling(device, format) => format,
+            _ => {
+                log::warn!(
+                    "{:?} textures are not supported by the device, decompressing them",
+                    compressed.format()
+                );
+                let image = compressed.decompress()?;
+                return Self::new_2d(
+                    Some(&image),
+                    sampler,
+                    color_space,
+                    device,
+                    allocator,
+                    command_runner,
+                );
             }
         };
 
-        let buffer = Buffer::new_staging_buffer_with_data(allocator, &rgba_image)?;
-        let format = vk::Format::R8G8B8A8_UNORM;
-        let mut image = Image::new_color_image(allocator, rgba_image.width(), rgba_image.height())?;
+        let mut data = vec![];
+        let mut level_offsets = vec![];
+        for level in compressed.levels() {
+            level_offsets.push(data.len() as u64);
+            data.extend_from_slice(level);
+        }
+
+        let buffer = Buffer::new_staging_buffer_with_data(allocator, &data)?;
+        let mip_levels = level_offsets.len() as u32;
+        let mut image = Image::new_compressed_image(
+            allocator,
+            compressed.width(),
+            compressed.height(),
+            mip_levels,
+            format,
+        )?;
         image.transit_layout(
             vk::ImageLayout::UNDEFINED,
             vk::ImageLayout::TRANSFER_DST_OPTIMAL,
             &command_runner,
         )?;
-        image.copy_from(&buffer, &command_runner)?;
+        image.copy_levels_from(&buffer, &level_offsets, &command_runner)?;
         image.transit_layout(
             vk::ImageLayout::TRANSFER_DST_OPTIMAL,
             vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
             &command_runner,
         )?;
 
-        let image_view = ImageView::new_color_image_view(device.clone(), image.vk_image(), format)?;
-        let sampler = Sampler::new(device.clone())?;
+        let image_view = ImageView::new_color_image_view_with_mips(
+            device.clone(),
+            image.vk_image(),
+            format,
+            mip_levels,
+        )?;
+        let sampler = Sampler::new_with_def(device.clone(), sampler, mip_levels)?;
 
         Ok(Self {
-            image,
-            image_view,
+            image: Rc::new(image),
+            image_view: Rc::new(image_view),
             sampler,
         })
     }
 
-    pub fn image(&self) -> &Image {
-        &self.image
+    fn supports_sampling(device: &Device, format: vk::Format) -> bool {
+        device
+            .format_properties(format)
+            .optimal_tiling_features
+            .contains(
+                vk::FormatFeatureFlags::SAMPLED_IMAGE
+                    | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR,
+            )
     }
 
-    pub fn image_view(&self) -> &ImageView {
-        &self.image_view
+    fn new_cubemap(
+        mips: &[[RgbaImage; 6]],
+        sampler: Option<&SamplerDef>,
+        color_space: ColorSpace,
+        device: &Rc<Device>,
+        allocator: &Rc<vk_mem::Allocator>,
+        command_runner: &Rc<AdhocCommandRunner>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let data: Vec<u8> = mips
+            .iter()
+            .flat_map(|faces| faces.iter())
+            .flat_map(|face| face.as_raw().iter().copied())
+            .collect();
+
+        let buffer = Buffer::new_staging_buffer_with_data(allocator, &data)?;
+        let format = rgba_format(color_space);
+        let mip_levels = mips.len() as u32;
+        let mut image =
+            Image::new_cubemap_image(allocator, mips[0][0].width(), mip_levels, format)?;
+        Self::upload(&mut image, &buffer, command_runner)?;
+
+        let image_view =
+            ImageView::new_cube_image_view(device.clone(), image.vk_image(), format, mip_levels)?;
+        let sampler = match sampler {
+            Some(sampler) => Sampler::new_with_def(device.clone(), sampler, mip_levels)?,
+            None => Sampler::new_cubemap_sampler(device.clone(), mip_levels)?,
+        };
+
+        Ok(Self {
+            image: Rc::new(image),
+            image_view: Rc::new(image_view),
+            sampler,
+        })
     }
 
-    pub fn sampler(&self) -> &Sampler {
-        &self.sampler
+    // Shared exponent texels keep the HDR range in 4 bytes, and every device can filter them
+    fn new_hdr_cubemap(
+        faces: &[HdrImage; 6],
+        sampler: Option<&SamplerDef>,
+        device: &Rc<Device>,
+        allocator: &Rc<vk_mem::Allocator>,
+        command_runner: &Rc<AdhocCommandRunner>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let data: Vec<u8> = faces
+            .iter()
+            .flat_map(|face| face.pixels())
+            .flat_map(|p| pack_rgb9e5(p.0).to_le_bytes().to_vec())
+            .collect();
+
+        let buffer = Buffer::new_staging_buffer_with_data(allocator, &data)?;
+        let format = vk::Format::E5B9G9R9_UFLOAT_PACK32;
+        let mut image = Image::new_cubemap_image(allocator, faces[0].width(), 1, format)?;
+        Self::upload(&mut image, &buffer, command_runner)?;
+
+        let image_view =
+            ImageView::new_cube_image_view(device.clone(), image.vk_image(), format, 1)?;
+        let sampler = match sampler {
+            Some(sampler) => Sampler::new_with_def(device.clone(), sampler, 1)?,
+            None => Sampler::new_cubemap_sampler(device.clone(), 1)?,
+        };
+
+        Ok(Self {
+            image: Rc::new(image),
+            image_view: Rc::new(image_view),
+            sampler,
+        })
     }
+
+    fn upload(
+        image: &mut Image,
+        buffer: &Buffer,
+        command_runner: &Rc<AdhocCommandRunner>,
+    ) -> Result<(), Box<dyn Error>> {
+        image.transit_layout(
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            &command_runner,
+        )?;
+        let mip_levels = image.mip_levels();
+        image.copy_from(&buffer, mip_levels, &command_runner)?;
+        image.transit_layout(
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            &command_runner,
+        )?;
+
+        Ok(())
+    }
+}
+
+// 9 bits of mantissa per channel sharing a 5 bits exponent, as in the Vulkan specification
+fn pack_rgb9e5(rgb: [f32; 3]) -> u32 {
+    const MANTISSA_BITS: i32 = 9;
+    const EXPONENT_BIAS: i32 = 15;
+    const MAX_VALUE: f32 = 511. / 512. * 65536.;
+
+    let clamp = |c: f32| if c > 0. { c.min(MAX_VALUE) } else { 0. };
+    let (r, g, b) = (clamp(rgb[0]), clamp(rgb[1]), clamp(rgb[2]));
+    let max = r.max(g).max(b);
+    let mut exponent =
+        max.log2().floor().max(-(EXPONENT_BIAS as f32) - 1.) as i32 + 1 + EXPONENT_BIAS;
+    let mut scale = 2f32.powi(exponent - EXPONENT_BIAS - MANTISSA_BITS);
+    if (max / scale + 0.5).floor() as u32 == 1 << MANTISSA_BITS {
+        exponent += 1;
+        scale *= 2.;
+    }
+
+    let mantissa = |c: f32| (c / scale + 0.5).floor() as u32;
+    mantissa(r) | mantissa(g) << 9 | mantissa(b) << 18 | (exponent as u32) << 27
+}
+
+fn rgba_format(color_space: ColorSpace) -> vk::Format {
+    match color_space {
+        ColorSpace::Srgb => vk::Format::R8G8B8A8_SRGB,
+        ColorSpace::Linear => vk::Format::R8G8B8A8_UNORM,
+    }
+}
+
+/// BC4, BC5 and BC6H have no sRGB variant and are always sampled as is
+fn compressed_vk_format(format: CompressedFormat, color_space: ColorSpace) -> Option<vk::Format> {
+    let srgb = color_space == ColorSpace::Srgb;
+    let pick = |linear, srgb_format| if srgb { srgb_format } else { linear };
+    let format = match format {
+        CompressedFormat::Bc1 => pick(
+            vk::Format::BC1_RGBA_UNORM_BLOCK,
+            vk::Format::BC1_RGBA_SRGB_BLOCK,
+        ),
+        CompressedFormat::Bc2 => pick(vk::Format::BC2_UNORM_BLOCK, vk::Format::BC2_SRGB_BLOCK),
+        CompressedFormat::Bc3 => pick(vk::Format::BC3_UNORM_BLOCK, vk::Format::BC3_SRGB_BLOCK),
+        CompressedFormat::Bc4 => vk::Format::BC4_UNORM_BLOCK,
+        CompressedFormat::Bc5 => vk::Format::BC5_UNORM_BLOCK,
+        CompressedFormat::Bc6hUfloat => vk::Format::BC6H_UFLOAT_BLOCK,
+        CompressedFormat::Bc6hSfloat => vk::Format::BC6H_SFLOAT_BLOCK,
+        CompressedFormat::Bc7 => pick(vk::Format::BC7_UNORM_BLOCK, vk::Format::BC7_SRGB_BLOCK),
+        CompressedFormat::Etc2Rgb8 => pick(
+            vk::Format::ETC2_R8G8B8_UNORM_BLOCK,
+            vk::Format::ETC2_R8G8B8_SRGB_BLOCK,
+        ),
+        CompressedFormat::Etc2Rgb8A1 => pick(
+            vk::Format::ETC2_R8G8B8A1_UNORM_BLOCK,
+            vk::Format::ETC2_R8G8B8A1_SRGB_BLOCK,
+        ),
+        CompressedFormat::Etc2Rgba8 => pick(
+            vk::Format::ETC2_R8G8B8A8_UNORM_BLOCK,
+            vk::Format::ETC2_R8G8B8A8_SRGB_BLOCK,
+        ),
+        CompressedFormat::Astc {
+            block_width,
+            block_height,
+        } => match (block_width, block_height) {
+            (4, 4) => pick(
+                vk::Format::ASTC_4X4_UNORM_BLOCK,
+                vk::Format::ASTC_4X4_SRGB_BLOCK,
+            ),
+            (5, 4) => pick(
+                vk::Format::ASTC_5X4_UNORM_BLOCK,
+                vk::Format::ASTC_5X4_SRGB_BLOCK,
+            ),
+            (5, 5) => pick(
+                vk::Format::ASTC_5X5_UNORM_BLOCK,
+                vk::Format::ASTC_5X5_SRGB_BLOCK,
+            ),
+            (6, 5) => pick(
+                vk::Format::ASTC_6X5_UNORM_BLOCK,
+                vk::Format::ASTC_6X5_SRGB_BLOCK,
+            ),
+            (6, 6) => pick(
+                vk::Format::ASTC_6X6_UNORM_BLOCK,
+                vk::Format::ASTC_6X6_SRGB_BLOCK,
+            ),
+            (8, 5) => pick(
+                vk::Format::ASTC_8X5_UNORM_BLOCK,
+                vk::Format::ASTC_8X5_SRGB_BLOCK,
+            ),
+            (8, 6) => pick(
+                vk::Format::ASTC_8X6_UNORM_BLOCK,
+                vk::Format::ASTC_8X6_SRGB_BLOCK,
+            ),
+            (8, 8) => pick(
+                vk::Format::ASTC_8X8_UNORM_BLOCK,
+                vk::Format::ASTC_8X8_SRGB_BLOCK,
+            ),
+            (10, 5) => pick(
+                vk::Format::ASTC_10X5_UNORM_BLOCK,
+                vk::Format::ASTC_10X5_SRGB_BLOCK,
+            ),
+            (10, 6) => pick(
+                vk::Format::ASTC_10X6_UNORM_BLOCK,
+                vk::Format::ASTC_10X6_SRGB_BLOCK,
+            ),
+            (10, 8) => pick(
+                vk::Format::ASTC_10X8_UNORM_BLOCK,
+                vk::Format::ASTC_10X8_SRGB_BLOCK,
+            ),
+            (10, 10) => pick(
+                vk::Format::ASTC_10X10_UNORM_BLOCK,
+                vk::Format::ASTC_10X10_SRGB_BLOCK,
+            ),
+            (12, 10) => pick(
+                vk::Format::ASTC_12X10_UNORM_BLOCK,
+                vk::Format::ASTC_12X10_SRGB_BLOCK,
+            ),
+            (12, 12) => pick(
+                vk::Format::ASTC_12X12_UNORM_BLOCK,
+                vk::Format::ASTC_12X12_SRGB_BLOCK,
+            ),
+            _ => return None,
+        },
+    };
+
+    Some(format)
 }
@@ -1,9 +1,16 @@
 use super::buffer::{Buffer, BufferType};
-use crate::math::Mat44;
+use crate::animation::MAX_BONES;
+use crate::math::{Mat44, Vec3};
+use crate::rendering::shadow::{ShadowSetup, MAX_SHADOW_MAPS, SHADOW_CASCADE_COUNT};
 use crate::rendering::vulkan::descriptor_managers::DynamicUniformBufferDescriptorManager;
+use crate::rendering::{LightComponent, LightType, MAX_LIGHTS};
+use crate::scene::Camera;
 use ash::vk;
 use std::{rc::Rc, sync::Mutex};
 
+// Depth compared against shadow maps is offset towards the light by this much
+const SHADOW_DEPTH_BIAS: f32 = 0.0005;
+
 #[derive(Clone)]
 #[repr(C)]
 pub struct PerInstanceUniformBuffer {
@@ -16,6 +23,12 @@ impl PerInstanceUniformBuffer {
     }
 }
 
+#[derive(Clone)]
+#[repr(C)]
+pub struct PerSkinUniformBuffer {
+    bones: [Mat44; MAX_BONES],
+}
+
 pub struct DynamicUniformBufferManager {
     descriptor_set: vk::DescriptorSet,
     alignment: u64,
@@ -29,14 +42,35 @@ impl DynamicUniformBufferManager {
         descriptor_manager: &DynamicUniformBufferDescriptorManager,
         min_alignment: u64,
     ) -> Self {
-        let (alignment, buffer) = Self::allocate_dynamic_buffer(allocator, min_alignment, 10240);
+        Self::new_with_element_size(
+            allocator,
+            descriptor_manager,
+            min_alignment,
+            std::mem::size_of::<PerInstanceUniformBuffer>(),
+            10240,
+        )
+    }
+
+    pub fn new_with_element_size(
+        allocator: &Rc<vk_mem::Allocator>,
+        descriptor_manager: &DynamicUniformBufferDescriptorManager,
+        min_alignment: u64,
+        element_size: usize,
+        element_count: usize,
+    ) -> Self {
+        let (alignment, buffer) = Self::allocate_dynamic_buffer(
+            allocator,
+            min_alignment,
+            element_size,
+            element_count as u32,
+        );
         let descriptor_set = descriptor_manager.allocate_descriptor_sets(&buffer);
 
         Self {
             descriptor_set,
             alignment,
             buffer,
-            usage: Mutex::new(vec![false; 10240]),
+            usage: Mutex::new(vec![false; element_count]),
         }
     }
 
@@ -71,6 +105,25 @@ impl DynamicUniformBufferManager {
         });
     }
 
+    /// Copies as many elements as fit into the slot of each id
+    pub fn update_slice_do<T: Copy, F: Fn(&dyn Fn(usize, &[T]))>(&self, action: F) {
+        self.buffer.map_memory_do(|dst| {
+            let updater = |id: usize, data: &[T]| {
+                let capacity = self.alignment as usize / std::mem::size_of::<T>();
+                let count = data.len().min(capacity);
+                unsafe {
+                    std::ptr::copy_nonoverlapping(
+                        data.as_ptr(),
+                        dst.offset(self.get_offset(id) as isize) as *mut T,
+                        count,
+                    );
+                }
+            };
+
+            action(&updater);
+        });
+    }
+
     pub fn get_offset(&self, id: usize) -> u64 {
         self.alignment * id as u64
     }
@@ -83,10 +136,10 @@ impl DynamicUniformBufferManager {
     fn allocate_dynamic_buffer(
         allocator: &Rc<vk_mem::Allocator>,
         min_alignment: u64,
+        element_size: usize,
         buffer_count: u32,
     ) -> (u64, Buffer) {
-        let alignment = (std::mem::size_of::<PerInstanceUniformBuffer>() as u64 + min_alignment)
-            & !(min_alignment - 1);
+        let alignment = (element_size as u64 + min_alignment) & !(min_alignment - 1);
         let buffer = Buffer::new_dynamic_buffer(
             allocator,
             BufferType::Uniform,
@@ -113,3 +166,106 @@ impl PerFrameUniformBuffer {
         }
     }
 }
+
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+struct LightUniform {
+    position_range: [f32; 4],
+    direction_type: [f32; 4],
+    color_intensity: [f32; 4],
+    spot_cone: [f32; 4],
+    shadow: [f32; 4],
+}
+
+#[repr(C)]
+pub struct PerFrameLightUniformBuffer {
+    camera_position: [f32; 4],
+    camera_forward: [f32; 4],
+    ambient: [f32; 4],
+    environment: [f32; 4],
+    light_count: [u32; 4],
+    lights: [LightUniform; MAX_LIGHTS],
+    shadow_matrices: [Mat44; MAX_SHADOW_MAPS],
+    cascade_splits: [f32; SHADOW_CASCADE_COUNT],
+}
+
+impl PerFrameLightUniformBuffer {
+    /// Builds the buffer from lights paired with their world matrices. Ambient lights are
+    /// summed up and lights beyond `MAX_LIGHTS` are dropped.
+    pub fn new(
+        camera: &Camera,
+        lights: &[(&LightComponent, &Mat44)],
+        shadows: &ShadowSetup,
+    ) -> Self {
+        let camera_world = camera.transform().matrix();
+        let mut buffer = Self {
+            camera_position: [
+                camera_world[0][3],
+                camera_world[1][3],
+                camera_world[2][3],
+                1.,
+            ],
+            camera_forward: forward(camera_world),
+            ambient: [0.; 4],
+            environment: [0.; 4],
+            light_count: [0; 4],
+            lights: [LightUniform::default(); MAX_LIGHTS],
+            shadow_matrices: [Mat44::new_identity(); MAX_SHADOW_MAPS],
+            cascade_splits: *shadows.cascade_splits(),
+        };
+
+        for (matrix, view) in buffer.shadow_matrices.iter_mut().zip(shadows.views()) {
+            *matrix = Mat44::multiplied(&view.projection, &view.view);
+        }
+
+        let mut count = 0;
+        for (i, (light, world)) in lights.iter().enumerate() {
+            let (light_type, spot_cone) = match light.light_type {
+                LightType::Ambient => {
+                    buffer.ambient[0] += light.color.x * light.intensity;
+                    buffer.ambient[1] += light.color.y * light.intensity;
+                    buffer.ambient[2] += light.color.z * light.intensity;
+                    continue;
+                }
+                LightType::Directional => (1., [0.; 4]),
+                LightType::Point => (2., [0.; 4]),
+                LightType::Spot {
+                    inner_angle,
+                    outer_angle,
+                } => (3., [inner_angle.cos(), outer_angle.cos(), 0., 0.]),
+            };
+
+            if count >= MAX_LIGHTS {
+                continue;
+            }
+
+            let direction = forward(world);
+            let shadow = match shadows.light_maps(i) {
+                Some((first, count)) => [first as f32, count as f32, SHADOW_DEPTH_BIAS, 0.],
+                None => [-1., 0., 0., 0.],
+            };
+            buffer.lights[count] = LightUniform {
+                position_range: [world[0][3], world[1][3], world[2][3], light.range],
+                direction_type: [direction[0], direction[1], direction[2], light_type],
+                color_intensity: [light.color.x, light.color.y, light.color.z, light.intensity],
+                spot_cone,
+                shadow,
+            };
+            count += 1;
+        }
+
+        buffer.light_count[0] = count as u32;
+        buffer
+    }
+
+    pub fn with_environment(mut self, intensity: f32, prefiltered_mip_levels: u32) -> Self {
+        self.environment = [intensity, prefiltered_mip_levels as f32, 0., 0.];
+        self
+    }
+}
+
+// The forward axis is -Z
+fn forward(world: &Mat44) -> [f32; 4] {
+    let direction = Vec3::normalized(&Vec3::new(-world[0][2], -world[1][2], -world[2][2]));
+    [direction.x, direction.y, direction.z, 0.]
+}
@@ -1,18 +1,32 @@
 use super::descriptor_managers::DescriptorManager;
+use super::environment::VulkanEnvironment;
 use super::helpers;
 use super::render_object::VulkanRenderObject;
-use super::swapchain::SwapChain;
+use super::render_target::VulkanRenderTarget;
+use super::sprite::SpriteBatcher;
+use super::swapchain::{CameraFrame, SceneFrame, SwapChain};
+use super::texture::VulkanTexture;
 use super::{adhoc_command_runner::AdhocCommandRunner, device::Device};
 use super::{creation_helpers, instance::Instance};
 use super::{
     factory::VulkanComponentFactory,
-    uniform_buffers::{DynamicUniformBufferManager, PerFrameUniformBuffer},
+    uniform_buffers::{
+        DynamicUniformBufferManager, PerFrameLightUniformBuffer, PerFrameUniformBuffer,
+        PerSkinUniformBuffer,
+    },
 };
+use crate::animation::SkeletalAnimator;
 use crate::math::Mat44;
-use crate::scene::{entity_get_component, Scene};
+use crate::rendering::shadow::ShadowSetup;
+use crate::scene::{entity_get_component, Background, Camera, Scene};
 use crate::{
     imgui::{ImguiContext, ImguiFrame},
-    rendering::{ComponentFactory, RenderingComponent, RenderingEngine, Window},
+    rendering::{
+        ColorGradingLut, ComponentFactory, EnvironmentLight, EnvironmentMap, Instances,
+        LightComponent, MorphWeights, Msaa, PostProcessing, RenderTarget, RenderingComponent,
+        RenderingEngine, Skybox, Sprite, Text, TextSpace, TextureDef, Window,
+        MAX_CAMERAS_PER_TARGET,
+    },
 };
 use ash::extensions::ext::DebugReport;
 use ash::{vk, Entry};
@@ -30,6 +44,7 @@ pub struct VulkanRenderingEngine {
     surface: vk::SurfaceKHR,
     format: vk::SurfaceFormatKHR,
     present_mode: vk::PresentModeKHR,
+    msaa: Msaa,
     queue: vk::Queue,
     swapchain: Option<SwapChain>,
     command_pool: vk::CommandPool,
@@ -37,6 +52,12 @@ pub struct VulkanRenderingEngine {
 
     descriptor_manager: Option<Rc<DescriptorManager>>,
     dub_manager: Option<Arc<DynamicUniformBufferManager>>,
+    bone_manager: Option<Arc<DynamicUniformBufferManager>>,
+    environment: Option<VulkanEnvironment>,
+    color_grading: Option<(Rc<ColorGradingLut>, VulkanTexture)>,
+    skybox: Option<(Rc<TextureDef>, VulkanTexture)>,
+    sprite_batcher: Option<SpriteBatcher>,
+    screen_sprite_batcher: Option<SpriteBatcher>,
     adhoc_command_runner: Rc<AdhocCommandRunner>,
     component_factory: Rc<VulkanComponentFactory>,
 
@@ -67,7 +88,81 @@ impl RenderingEngine for VulkanRenderingEngine {
             }
         });
 
-        match self.render_objects(scene, ui_frame) {
+        self.bone_manager().update_slice_do(|updater| {
+            for entity in scene.entities() {
+                let animator = entity_get_component::<SkeletalAnimator>(entity);
+                let rc = entity_get_component::<RenderingComponent>(entity);
+                if let (Some(animator), Some(rc)) = (animator, rc) {
+                    for ro in rc.render_objects() {
+                        if let Some(bone_index) = ro
+                            .downcast_ref::<VulkanRenderObject>()
+                            .and_then(|vro| vro.bone_index())
+                        {
+                            updater(bone_index, animator.skinning_matrices());
+                        }
+                    }
+                }
+            }
+        });
+
+        for entity in scene.entities() {
+            let morph_weights = entity_get_component::<MorphWeights>(entity);
+            let rc = entity_get_component::<RenderingComponent>(entity);
+            if let (Some(morph_weights), Some(rc)) = (morph_weights, rc) {
+                for ro in rc.render_objects() {
+                    if let Some(vro) = ro.downcast_ref::<VulkanRenderObject>() {
+                        if let Some(morph_targets) = vro.morph_targets() {
+                            morph_targets.set_weights(morph_weights.weights());
+                        }
+                    }
+                }
+            }
+
+            let instances = entity_get_component::<Instances>(entity);
+            if let Some(rc) = rc {
+                for ro in rc.render_objects() {
+                    if let Some(vro) = ro.downcast_ref::<VulkanRenderObject>() {
+                        let result = match instances {
+                            Some(instances) => vro.set_instances(instances),
+                            None => vro.clear_instances(),
+                        };
+                        if let Err(err) = result {
+                            println!("{}", err);
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Err(err) = self.update_environment(scene) {
+            println!("{}", err);
+        }
+
+        let post_processing = scene
+            .entities()
+            .into_iter()
+            .find_map(|e| entity_get_component::<PostProcessing>(e))
+            .cloned()
+            .unwrap_or_default();
+        if let Err(err) = self.update_color_grading(&post_processing) {
+            println!("{}", err);
+        }
+
+        let skybox = scene
+            .entities()
+            .into_iter()
+            .find_map(|e| entity_get_component::<Skybox>(e))
+            .cloned();
+        if let Err(err) = self.update_skybox(skybox.as_ref()) {
+            println!("{}", err);
+        }
+
+        if let Err(err) = self.update_sprites(scene) {
+            println!("{}", err);
+        }
+
+        let skybox_intensity = skybox.map_or(0., |skybox| skybox.intensity);
+        match self.render_objects(scene, &post_processing, skybox_intensity, ui_frame) {
             Ok(()) => (),
             Err(err) => println!("{}", err),
         }
@@ -83,6 +178,29 @@ impl RenderingEngine for VulkanRenderingEngine {
     fn component_factory(&self) -> Rc<dyn ComponentFactory> {
         self.component_factory.as_component_factory()
     }
+
+    fn set_msaa(&mut self, msaa: Msaa) {
+        let limits = self
+            .instance
+            .get_physical_device_properties(self.physical_device)
+            .limits;
+        let supported =
+            limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts;
+        let msaa = [Msaa::X8, Msaa::X4, Msaa::X2]
+            .iter()
+            .copied()
+            .find(|m| *m <= msaa && supported.contains(sample_count_flags(*m)))
+            .unwrap_or(Msaa::Off);
+
+        if msaa != self.msaa {
+            self.msaa = msaa;
+            self.drop_swapchain();
+        }
+    }
+
+    fn msaa(&self) -> Msaa {
+        self.msaa
+    }
 }
 
 impl VulkanRenderingEngine {
@@ -128,6 +246,10 @@ impl VulkanRenderingEngine {
 
         let format =
             creation_helpers::get_surface_format(physical_device, &surface_entry, surface)?;
+        if creation_helpers::is_srgb_format(format.format) {
+            imgui_context.borrow_mut().use_linear_colors();
+        }
+
         let present_mode =
             creation_helpers::get_present_mode(physical_device, &surface_entry, surface)?;
         let capabilities = unsafe {
@@ -156,9 +278,22 @@ impl VulkanRenderingEngine {
             descriptor_manager.dub_descriptor_manager(),
             min_uniform_buffer_alignment,
         ));
+        let bone_manager = Arc::new(DynamicUniformBufferManager::new_with_element_size(
+            &allocator,
+            descriptor_manager.bone_descriptor_manager(),
+            min_uniform_buffer_alignment,
+            std::mem::size_of::<PerSkinUniformBuffer>(),
+            1024,
+        ));
 
         let adhoc_command_runner =
             Rc::new(AdhocCommandRunner::new(device.clone(), command_pool, queue));
+        let environment = VulkanEnvironment::new(
+            Rc::new(EnvironmentMap::from_color([0, 0, 0, 255])),
+            &device,
+            &allocator,
+            &adhoc_command_runner,
+        )?;
         let swapchain = SwapChain::new(
             &instance,
             device.clone(),
@@ -170,8 +305,10 @@ impl VulkanRenderingEngine {
             capabilities,
             format,
             present_mode,
+            vk::SampleCountFlags::TYPE_1,
             &descriptor_manager,
             &adhoc_command_runner,
+            &environment,
             &mut imgui_context.borrow_mut(),
         )
         .unwrap();
@@ -180,12 +317,24 @@ impl VulkanRenderingEngine {
         let image_available_semaphore = device.create_semaphore(&semaphore_create_info)?;
         let render_finished_semaphore = device.create_semaphore(&semaphore_create_info)?;
 
+        let sprite_batcher = SpriteBatcher::new(
+            device.clone(),
+            allocator.clone(),
+            adhoc_command_runner.clone(),
+        );
+        let screen_sprite_batcher = SpriteBatcher::new(
+            device.clone(),
+            allocator.clone(),
+            adhoc_command_runner.clone(),
+        );
         let component_factory = Rc::new(VulkanComponentFactory::new(
             device.clone(),
             &allocator,
             &descriptor_manager,
             &dub_manager,
+            &bone_manager,
             &adhoc_command_runner,
+            format.format,
         ));
 
         // DEBUG INFO
@@ -210,12 +359,19 @@ impl VulkanRenderingEngine {
             surface,
             format,
             present_mode,
+            msaa: Msaa::Off,
             queue,
             command_pool,
             swapchain: Some(swapchain),
             debug_callback,
             descriptor_manager: Some(descriptor_manager),
             dub_manager: Some(dub_manager),
+            bone_manager: Some(bone_manager),
+            environment: Some(environment),
+            color_grading: None,
+            skybox: None,
+            sprite_batcher: Some(sprite_batcher),
+            screen_sprite_batcher: Some(screen_sprite_batcher),
             adhoc_command_runner,
             component_factory,
             surface_entry,
@@ -236,6 +392,10 @@ impl VulkanRenderingEngine {
         self.dub_manager.as_ref().unwrap()
     }
 
+    pub fn bone_manager(&self) -> &Arc<DynamicUniformBufferManager> {
+        self.bone_manager.as_ref().unwrap()
+    }
+
     pub fn command_runner(&self) -> &Rc<AdhocCommandRunner> {
         &self.adhoc_command_runner
     }
@@ -276,17 +436,147 @@ impl VulkanRenderingEngine {
             capabilities,
             self.format,
             self.present_mode,
+            sample_count_flags(self.msaa),
             self.descriptor_manager(),
             &self.adhoc_command_runner,
+            self.environment.as_ref().unwrap(),
             &mut self.imgui_context.borrow_mut(),
         )?);
 
         Ok(())
     }
 
+    // Uploads the scene's environment map when it changes
+    fn update_environment(&mut self, scene: &dyn Scene) -> Result<(), Box<dyn Error>> {
+        let map = match scene
+            .entities()
+            .into_iter()
+            .find_map(|e| entity_get_component::<EnvironmentLight>(e))
+        {
+            Some(light) => light.map.clone(),
+            None => return Ok(()),
+        };
+
+        if Rc::ptr_eq(&map, self.environment.as_ref().unwrap().map()) {
+            return Ok(());
+        }
+
+        let environment = VulkanEnvironment::new(
+            map,
+            &self.device,
+            self.allocator(),
+            &self.adhoc_command_runner,
+        )?;
+        if let Some(swapchain) = self.swapchain.as_mut() {
+            swapchain.set_environment(self.descriptor_manager.as_ref().unwrap(), &environment);
+        }
+
+        self.environment = Some(environment);
+        Ok(())
+    }
+
+    // Uploads the color grading LUT when it changes
+    fn update_color_grading(&mut self, settings: &PostProcessing) -> Result<(), Box<dyn Error>> {
+        let lut = match settings.color_grading.as_ref() {
+            Some(lut) => lut,
+            None => {
+                self.color_grading = None;
+                return Ok(());
+            }
+        };
+
+        if let Some((current, _)) = self.color_grading.as_ref() {
+            if Rc::ptr_eq(lut, current) {
+                return Ok(());
+            }
+        }
+
+        let texture = VulkanTexture::new(
+            lut.texture(),
+            &self.device,
+            self.allocator(),
+            &self.adhoc_command_runner,
+        )?;
+        self.color_grading = Some((lut.clone(), texture));
+        Ok(())
+    }
+
+    // Uploads the skybox cubemap when it changes
+    fn update_skybox(&mut self, skybox: Option<&Skybox>) -> Result<(), Box<dyn Error>> {
+        let cubemap = match skybox {
+            Some(skybox) => &skybox.cubemap,
+            None => {
+                self.skybox = None;
+                return Ok(());
+            }
+        };
+
+        if let Some((current, _)) = self.skybox.as_ref() {
+            if Rc::ptr_eq(cubemap, current) {
+                return Ok(());
+            }
+        }
+
+        let texture = VulkanTexture::new(
+            cubemap,
+            &self.device,
+            self.allocator(),
+            &self.adhoc_command_runner,
+        )?;
+        self.skybox = Some((cubemap.clone(), texture));
+        Ok(())
+    }
+
+    // Batches the sprites and the glyphs of the texts, those in screen space apart
+    fn update_sprites(&mut self, scene: &dyn Scene) -> Result<(), Box<dyn Error>> {
+        let texts: Vec<(&Text, &Mat44)> = scene
+            .entities()
+            .into_iter()
+            .filter_map(|e| {
+                entity_get_component::<Text>(e).map(|t| (t, e.world_transform().matrix()))
+            })
+            .collect();
+
+        // Every glyph is rasterized before the layouts, so that they all refer to the final
+        // font textures
+        for (text, _) in &texts {
+            text.font.rasterize(&text.text, text.size);
+        }
+
+        let mut glyphs = vec![];
+        let mut screen_glyphs = vec![];
+        for (text, transform) in &texts {
+            match text.space {
+                TextSpace::World { .. } => glyphs.extend(text.sprites(transform)),
+                TextSpace::Screen { .. } => screen_glyphs.extend(text.sprites(transform)),
+            }
+        }
+
+        let mut sprites: Vec<(&Sprite, &Mat44)> = scene
+            .entities()
+            .into_iter()
+            .filter_map(|e| {
+                entity_get_component::<Sprite>(e).map(|s| (s, e.world_transform().matrix()))
+            })
+            .collect();
+        sprites.extend(glyphs.iter().map(|(sprite, transform)| (sprite, transform)));
+        self.sprite_batcher.as_mut().unwrap().update(&sprites)?;
+
+        let screen_sprites: Vec<(&Sprite, &Mat44)> = screen_glyphs
+            .iter()
+            .map(|(sprite, transform)| (sprite, transform))
+            .collect();
+        self.screen_sprite_batcher
+            .as_mut()
+            .unwrap()
+            .update(&screen_sprites)
+    }
+
     fn render_objects(
         &mut self,
         scene: &mut dyn Scene,
+        post_processing: &PostProcessing,
+        skybox_intensity: f32,
         ui_frame: ImguiFrame,
     ) -> Result<(), Box<dyn Error>> {
         macro_rules! swapchain {
@@ -296,6 +586,7 @@ impl VulkanRenderingEngine {
         }
 
         let dub_manager = self.dub_manager().clone();
+        let bone_manager = self.bone_manager().clone();
         let (image_index, _) = swapchain!()
             .acquire_next_image(
                 u64::max_value(),
@@ -304,33 +595,128 @@ impl VulkanRenderingEngine {
             )
             .unwrap();
         let x = &|ui| scene.draw_ui(ui);
-        let objects: Vec<&VulkanRenderObject> = scene
+        let objects: Vec<(&VulkanRenderObject, u32)> = scene
             .entities()
             .iter()
+            .filter_map(|e| entity_get_component::<RenderingComponent>(*e))
+            .flat_map(|c| {
+                c.render_objects()
+                    .iter()
+                    .filter_map(|o| o.downcast_ref())
+                    .map(move |o| (o, 1 << c.layer()))
+            })
+            .collect();
+        let lights: Vec<(&LightComponent, &Mat44)> = scene
+            .entities()
+            .into_iter()
             .filter_map(|e| {
-                entity_get_component::<RenderingComponent>(*e)
-                    .and_then(|c| Some(c.render_objects()))
+                entity_get_component::<LightComponent>(e)
+                    .and_then(|l| Some((l, e.world_transform().matrix())))
             })
-            .flatten()
-            .filter_map(|o| o.downcast_ref())
             .collect();
+        let environment_light = scene
+            .entities()
+            .into_iter()
+            .find_map(|e| entity_get_component::<EnvironmentLight>(e))
+            .map_or((0., 1), |light| {
+                (light.intensity, light.map.prefiltered_mip_levels())
+            });
+        let skybox = self.skybox.as_ref().map(|(_, texture)| texture);
+        let color_grading_lut = self.color_grading.as_ref().map(|(_, texture)| texture);
+        let sprites = self.sprite_batcher.as_ref().unwrap();
+        let screen_sprites = self.screen_sprite_batcher.as_ref().unwrap();
+
+        // Cameras drawing into the same target, the main camera always draws to the screen
+        let mut targets: Vec<(Option<&Rc<dyn RenderTarget>>, Vec<&Camera>)> = vec![(None, vec![])];
+        for (i, camera) in scene.cameras().iter().enumerate() {
+            let target = if i == 0 { None } else { camera.target() };
+            let same_target = |other: &Option<&Rc<dyn RenderTarget>>| match (other, target) {
+                (None, None) => true,
+                (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+                _ => false,
+            };
+            match targets.iter_mut().find(|(other, _)| same_target(other)) {
+                Some((_, cameras)) => cameras.push(camera),
+                None => targets.push((target, vec![camera])),
+            }
+        }
 
-        let command_buffer = swapchain!()
-            .record_command_buffers(image_index as usize, &objects, &dub_manager, ui_frame)
-            .unwrap();
+        for (_, cameras) in &mut targets {
+            cameras.sort_by_key(|camera| camera.order());
+            if cameras.len() > MAX_CAMERAS_PER_TARGET {
+                log::warn!("Too many cameras drawing into the same target, the last are skipped");
+                cameras.truncate(MAX_CAMERAS_PER_TARGET);
+            }
+        }
 
-        // Update Per-frame Uniform Buffers
-        {
-            let ubo = {
-                let camera = scene.camera();
-                let view = Mat44::inversed(camera.transform().matrix());
-                let proj = camera.projection_matrix();
-                PerFrameUniformBuffer::new(&view, proj)
+        // Cameras rendering to textures go first, so that the frame samples their result. The
+        // targets share the per-frame buffers, hence each one is waited for before the next.
+        for (target, cameras) in targets.iter().skip(1) {
+            let target = match target.and_then(|t| t.downcast_ref::<VulkanRenderTarget>()) {
+                Some(target) => target,
+                None => continue,
+            };
+
+            let frame = SceneFrame {
+                cameras: prepare_cameras(
+                    swapchain!(),
+                    image_index as usize,
+                    cameras,
+                    (target.width(), target.height()),
+                    &objects,
+                    sprites,
+                    Some(target.image().vk_image()),
+                    &lights,
+                    environment_light,
+                    skybox.map(|texture| (texture, skybox_intensity)),
+                ),
+                dub_manager: &dub_manager,
+                bone_manager: &bone_manager,
+                sprites,
+                screen_sprites,
+                screen_draws: vec![],
+                post_processing,
+                color_grading_lut,
             };
+            let command_buffer = swapchain!().record_offscreen_command_buffer(
+                image_index as usize,
+                target,
+                &frame,
+            )?;
 
-            swapchain!().update_ubo(image_index as usize, &[ubo]);
+            let commands = [command_buffer];
+            let submit_info = vk::SubmitInfo::builder().command_buffers(&commands).build();
+            self.device
+                .queue_submit(self.queue, &[submit_info], vk::Fence::default())?;
+            self.device.wait_idle();
         }
 
+        let extent = swapchain!().extent();
+        let frame = SceneFrame {
+            cameras: prepare_cameras(
+                swapchain!(),
+                image_index as usize,
+                &targets[0].1,
+                (extent.width, extent.height),
+                &objects,
+                sprites,
+                None,
+                &lights,
+                environment_light,
+                skybox.map(|texture| (texture, skybox_intensity)),
+            ),
+            dub_manager: &dub_manager,
+            bone_manager: &bone_manager,
+            sprites,
+            screen_sprites,
+            screen_draws: screen_sprites.draws(u32::max_value(), None),
+            post_processing,
+            color_grading_lut,
+        };
+        let command_buffer = swapchain!()
+            .record_command_buffers(image_index as usize, &frame, ui_frame)
+            .unwrap();
+
         // Submit commands
         {
             let commands = [command_buffer];
@@ -363,6 +749,9 @@ impl VulkanRenderingEngine {
 
         // Not an optimized way
         self.device.wait_idle();
+        if let Some(swapchain) = self.swapchain.as_mut() {
+            swapchain.end_frame();
+        }
 
         Ok(())
     }
@@ -386,6 +775,12 @@ impl Drop for VulkanRenderingEngine {
         self.swapchain = None;
         self.descriptor_manager = None;
         self.dub_manager = None;
+        self.bone_manager = None;
+        self.environment = None;
+        self.color_grading = None;
+        self.skybox = None;
+        self.sprite_batcher = None;
+        self.screen_sprite_batcher = None;
         self.allocator = None;
         unsafe {
             self.debug_entry
@@ -402,3 +797,76 @@ impl Drop for VulkanRenderingEngine {
         }
     }
 }
+
+// Builds what each camera draws into a `width` x `height` target, skipping the objects and
+// sprites of other layers and those sampling the target, and fills their per-frame buffers
+fn prepare_cameras<'a>(
+    swapchain: &mut SwapChain,
+    image_index: usize,
+    cameras: &[&Camera],
+    (width, height): (u32, u32),
+    objects: &[(&'a VulkanRenderObject, u32)],
+    sprites: &SpriteBatcher,
+    target_image: Option<vk::Image>,
+    lights: &[(&LightComponent, &Mat44)],
+    (environment_intensity, environment_mip_levels): (f32, u32),
+    skybox: Option<(&'a VulkanTexture, f32)>,
+) -> Vec<CameraFrame<'a>> {
+    let mut frames = vec![];
+    for (camera_index, camera) in cameras.iter().enumerate() {
+        let objects = objects
+            .iter()
+            .filter(|(_, layer)| camera.layer_mask() & layer != 0)
+            .map(|(o, _)| *o)
+            .filter(|o| {
+                !o.material()
+                    .textures()
+                    .iter()
+                    .any(|t| Some(t.image().vk_image()) == target_image)
+            })
+            .collect();
+
+        let shadows = ShadowSetup::new(camera, lights);
+        let view = Mat44::inversed(camera.transform().matrix());
+        let ubo = PerFrameUniformBuffer::new(&view, camera.projection_matrix());
+        swapchain.update_ubo(image_index, camera_index, &[ubo]);
+
+        let light_ubo = PerFrameLightUniformBuffer::new(camera, lights, &shadows)
+            .with_environment(environment_intensity, environment_mip_levels);
+        swapchain.update_light_ubo(image_index, camera_index, &[light_ubo]);
+        swapchain.update_shadow_views(camera_index, &shadows);
+
+        let (clear_color, skybox) = match camera.background() {
+            Background::Color => (Some(camera.clear_color()), None),
+            Background::Skybox => (Some(camera.clear_color()), skybox),
+            Background::None => (None, None),
+        };
+        let (x, y, width, height) = camera.viewport().pixel_rect(width, height);
+        frames.push(CameraFrame {
+            objects,
+            viewport: vk::Rect2D {
+                offset: vk::Offset2D {
+                    x: x as i32,
+                    y: y as i32,
+                },
+                extent: vk::Extent2D { width, height },
+            },
+            shadow_map_count: shadows.views().len(),
+            clear_color,
+            clear_depth: camera.clear_depth(),
+            skybox,
+            sprites: sprites.draws(camera.layer_mask(), target_image),
+        });
+    }
+
+    frames
+}
+
+fn sample_count_flags(msaa: Msaa) -> vk::SampleCountFlags {
+    match msaa {
+        Msaa::Off => vk::SampleCountFlags::TYPE_1,
+        Msaa::X2 => vk::SampleCountFlags::TYPE_2,
+        Msaa::X4 => vk::SampleCountFlags::TYPE_4,
+        Msaa::X8 => vk::SampleCountFlags::TYPE_8,
+    }
+}
@@ -1,13 +1,97 @@
 use crate::math::Mat44;
 use crate::math::Transform;
+use crate::math::Vec3;
+use crate::rendering::RenderTarget;
+use std::rc::Rc;
+
+/// What the camera draws behind the scene
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Background {
+    /// Clears to the camera's clear color
+    Color,
+
+    /// Draws the scene's `Skybox` over the clear color, or only clears when there is none
+    Skybox,
+
+    /// Keeps what earlier cameras drew into the target, starting from transparent black when
+    /// there is none. Used to layer scenes and overlays.
+    None,
+}
+
+/// How the camera projects the scene onto its viewport
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection {
+    /// Things shrink with distance, within the field of view
+    Perspective,
+
+    /// Parallel projection showing `size` world units vertically, divided by the zoom
+    Orthographic { size: f32 },
+
+    /// Parallel projection where a world unit covers `pixels_per_unit` pixels times the zoom
+    /// rounded to a whole number, with the world origin on a pixel corner. Sprites drawn at
+    /// that density stay sharp.
+    PixelPerfect { pixels_per_unit: f32 },
+}
+
+/// Part of the target a camera draws into, in fractions of the target size from its top left
+/// corner
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Viewport {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Left, top, width and height in pixels of the viewport in a `width` x `height` target,
+    /// clamped to the target and at least one pixel in size
+    pub fn pixel_rect(&self, width: u32, height: u32) -> (u32, u32, u32, u32) {
+        let span = |start: f32, length: f32, size: u32| {
+            let size = size.max(1) as f32;
+            let begin = (start * size).round().max(0.).min(size - 1.);
+            let end = ((start + length) * size).round().min(size).max(begin + 1.);
+            (begin as u32, (end - begin) as u32)
+        };
+
+        let (left, width) = span(self.x, self.width, width);
+        let (top, height) = span(self.y, self.height, height);
+        (left, top, width, height)
+    }
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::new(0., 0., 1., 1.)
+    }
+}
 
 pub struct Camera {
     transform: Transform,
     projection: Mat44,
+    projection_mode: Projection,
     fov: f32,
+    zoom: f32,
     aspect: f32,
+    view_size: (u32, u32),
     near_clip: f32,
     far_clip: f32,
+    background: Background,
+    clear_color: [f32; 4],
+    clear_depth: bool,
+    target: Option<Rc<dyn RenderTarget>>,
+    viewport: Viewport,
+    order: i32,
+    layer_mask: u32,
 }
 
 impl Camera {
@@ -19,10 +103,20 @@ impl Camera {
         Self {
             transform: Transform::new(),
             projection: Self::generate_projection_matrix(fov, aspect, near_clip, far_clip),
+            projection_mode: Projection::Perspective,
             fov,
+            zoom: 1.,
             aspect,
+            view_size: (0, 0),
             near_clip,
             far_clip,
+            background: Background::Skybox,
+            clear_color: [0., 0., 0., 1.],
+            clear_depth: true,
+            target: None,
+            viewport: Viewport::default(),
+            order: 0,
+            layer_mask: u32::MAX,
         }
     }
 
@@ -42,6 +136,157 @@ impl Camera {
         self.update_projection_matrix();
     }
 
+    pub fn projection(&self) -> Projection {
+        self.projection_mode
+    }
+
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.projection_mode = projection;
+        self.update_projection_matrix();
+    }
+
+    /// Magnification of the orthographic projections, ignored in perspective
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.zoom = zoom;
+        self.update_projection_matrix();
+    }
+
+    /// Size in pixels of the camera's viewport, set by the engine before each frame
+    pub fn view_size(&self) -> (u32, u32) {
+        self.view_size
+    }
+
+    /// Also sets the aspect ratio
+    pub fn set_view_size(&mut self, width: u32, height: u32) {
+        self.view_size = (width.max(1), height.max(1));
+        self.set_aspect(self.view_size.0 as f32 / self.view_size.1 as f32);
+    }
+
+    /// Point of the view space `distance` in front of the camera, seen at `x` and `y` normalized
+    /// device coordinates: from -1 to 1, right and up
+    pub fn view_point(&self, x: f32, y: f32, distance: f32) -> Vec3 {
+        // Perspective divides by the distance, the orthographic projections don't
+        let w = if self.projection[3][2] != 0. {
+            distance
+        } else {
+            1.
+        };
+        Vec3::new(
+            (x * w - self.projection[0][3]) / self.projection[0][0],
+            (y * w - self.projection[1][3]) / self.projection[1][1],
+            -distance,
+        )
+    }
+
+    /// Position in pixels of a world point in the camera's viewport, from its top left corner.
+    /// `z` is the distance in front of the camera, negative behind it.
+    pub fn world_to_screen(&self, point: &Vec3) -> Vec3 {
+        let view = Mat44::inversed(self.transform.matrix()).transform_point(point);
+        let p = &self.projection;
+        let w = p[3][2] * view.z + p[3][3];
+        let x = (p[0][0] * view.x + p[0][3]) / w;
+        let y = (p[1][1] * view.y + p[1][3]) / w;
+        Vec3::new(
+            (x + 1.) * 0.5 * self.view_size.0 as f32,
+            (1. - y) * 0.5 * self.view_size.1 as f32,
+            -view.z,
+        )
+    }
+
+    /// World point `distance` in front of the camera under a pixel of its viewport, the
+    /// inverse of `world_to_screen`
+    pub fn screen_to_world(&self, x: f32, y: f32, distance: f32) -> Vec3 {
+        let (x, y) = self.screen_to_ndc(x, y);
+        self.transform
+            .matrix()
+            .transform_point(&self.view_point(x, y, distance))
+    }
+
+    /// Origin and direction of the ray going through a pixel of the viewport, for picking
+    pub fn screen_ray(&self, x: f32, y: f32) -> (Vec3, Vec3) {
+        let (x, y) = self.screen_to_ndc(x, y);
+        let world = self.transform.matrix();
+        let origin = world.transform_point(&self.view_point(x, y, self.near_clip));
+        let target = world.transform_point(&self.view_point(x, y, self.far_clip));
+        (origin, Vec3::normalized(&Vec3::sub(&target, &origin)))
+    }
+
+    pub fn near_clip(&self) -> f32 {
+        self.near_clip
+    }
+
+    pub fn far_clip(&self) -> f32 {
+        self.far_clip
+    }
+
+    pub fn background(&self) -> Background {
+        self.background
+    }
+
+    pub fn set_background(&mut self, background: Background) {
+        self.background = background;
+    }
+
+    /// Linear color, its alpha is kept in render targets
+    pub fn clear_color(&self) -> [f32; 4] {
+        self.clear_color
+    }
+
+    pub fn set_clear_color(&mut self, color: [f32; 4]) {
+        self.clear_color = color;
+    }
+
+    /// Whether the depth left by earlier cameras drawing into the same target is cleared,
+    /// overlays that must be hidden by what they drew keep it
+    pub fn clear_depth(&self) -> bool {
+        self.clear_depth
+    }
+
+    pub fn set_clear_depth(&mut self, clear_depth: bool) {
+        self.clear_depth = clear_depth;
+    }
+
+    /// Offscreen image the camera renders into instead of the screen. The scene's main camera
+    /// always renders to the screen.
+    pub fn target(&self) -> Option<&Rc<dyn RenderTarget>> {
+        self.target.as_ref()
+    }
+
+    pub fn set_target(&mut self, target: Option<Rc<dyn RenderTarget>>) {
+        self.target = target;
+    }
+
+    pub fn viewport(&self) -> Viewport {
+        self.viewport
+    }
+
+    pub fn set_viewport(&mut self, viewport: Viewport) {
+        self.viewport = viewport;
+    }
+
+    /// Cameras drawing into the same target are drawn from the lowest order to the highest,
+    /// cameras of the same order keep the order they were added to the scene
+    pub fn order(&self) -> i32 {
+        self.order
+    }
+
+    pub fn set_order(&mut self, order: i32) {
+        self.order = order;
+    }
+
+    /// Bit `n` set draws the objects of layer `n`, all the layers are drawn by default
+    pub fn layer_mask(&self) -> u32 {
+        self.layer_mask
+    }
+
+    pub fn set_layer_mask(&mut self, layer_mask: u32) {
+        self.layer_mask = layer_mask;
+    }
+
     pub fn transform(&self) -> &Transform {
         &self.transform
     }
@@ -55,8 +300,50 @@ impl Camera {
     }
 
     fn update_projection_matrix(&mut self) {
-        self.projection =
-            Self::generate_projection_matrix(self.fov, self.aspect, self.near_clip, self.far_clip);
+        let height = match self.projection_mode {
+            Projection::Perspective => {
+                self.projection = Self::generate_projection_matrix(
+                    self.fov,
+                    self.aspect,
+                    self.near_clip,
+                    self.far_clip,
+                );
+                return;
+            }
+            Projection::Orthographic { size } => size / self.zoom,
+            Projection::PixelPerfect { pixels_per_unit } => {
+                let scale = pixels_per_unit * self.zoom.round().max(1.);
+                let (width, height) = self.view_size;
+                let left = -((width / 2) as f32) / scale;
+                let bottom = -((height / 2) as f32) / scale;
+                self.projection = Self::generate_orthographic_matrix(
+                    left,
+                    left + width as f32 / scale,
+                    bottom,
+                    bottom + height as f32 / scale,
+                    self.near_clip,
+                    self.far_clip,
+                );
+                return;
+            }
+        };
+
+        let width = height * self.aspect;
+        self.projection = Self::generate_orthographic_matrix(
+            -width / 2.,
+            width / 2.,
+            -height / 2.,
+            height / 2.,
+            self.near_clip,
+            self.far_clip,
+        );
+    }
+
+    fn screen_to_ndc(&self, x: f32, y: f32) -> (f32, f32) {
+        (
+            2. * x / self.view_size.0.max(1) as f32 - 1.,
+            1. - 2. * y / self.view_size.1.max(1) as f32,
+        )
     }
 
     fn generate_projection_matrix(fov: f32, aspect: f32, near_clip: f32, far_clip: f32) -> Mat44 {
@@ -71,4 +358,105 @@ impl Camera {
 
         mat
     }
+
+    fn generate_orthographic_matrix(
+        left: f32,
+        right: f32,
+        bottom: f32,
+        top: f32,
+        near_clip: f32,
+        far_clip: f32,
+    ) -> Mat44 {
+        let mut mat = Mat44::new_zero();
+        mat[0][0] = 2. / (right - left);
+        mat[0][3] = -(right + left) / (right - left);
+        mat[1][1] = 2. / (top - bottom);
+        mat[1][3] = -(top + bottom) / (top - bottom);
+        mat[2][2] = -2. / (far_clip - near_clip);
+        mat[2][3] = -(far_clip + near_clip) / (far_clip - near_clip);
+        mat[3][3] = 1.;
+
+        mat
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn assert_near(actual: &Vec3, expected: &Vec3) {
+        assert!(
+            Vec3::sub(actual, expected).norm() < 1e-3,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    // 90 degrees horizontally in a 4:3 viewport
+    fn new_camera(projection: Projection) -> Camera {
+        let mut camera = Camera::new_with_params(FRAC_PI_2, 1., 0.1, 1000.);
+        camera.set_view_size(320, 240);
+        camera.set_projection(projection);
+        camera
+    }
+
+    #[test]
+    fn view_point_reaches_the_frustum_edges() {
+        let camera = new_camera(Projection::Perspective);
+        assert_near(&camera.view_point(1., 0., 2.), &Vec3::new(2., 0., -2.));
+        assert_near(&camera.view_point(0., 1., 2.), &Vec3::new(0., 1.5, -2.));
+
+        let camera = new_camera(Projection::Orthographic { size: 6. });
+        assert_near(&camera.view_point(1., 1., 5.), &Vec3::new(4., 3., -5.));
+        assert_near(&camera.view_point(-1., -1., 1.), &Vec3::new(-4., -3., -1.));
+    }
+
+    #[test]
+    fn pixel_perfect_units_cover_whole_pixels() {
+        let mut camera = new_camera(Projection::PixelPerfect {
+            pixels_per_unit: 16.,
+        });
+        assert_near(
+            &camera.world_to_screen(&Vec3::new(0., 0., -1.)),
+            &Vec3::new(160., 120., 1.),
+        );
+        assert_near(
+            &camera.world_to_screen(&Vec3::new(1., 1., -1.)),
+            &Vec3::new(176., 104., 1.),
+        );
+
+        camera.set_zoom(2.);
+        assert_near(
+            &camera.world_to_screen(&Vec3::new(1., 1., -1.)),
+            &Vec3::new(192., 88., 1.),
+        );
+    }
+
+    #[test]
+    fn screen_to_world_inverts_world_to_screen() {
+        let point = Vec3::new(1., 2., -10.);
+        let projections = [
+            Projection::Perspective,
+            Projection::Orthographic { size: 6. },
+            Projection::PixelPerfect {
+                pixels_per_unit: 16.,
+            },
+        ];
+        for projection in &projections {
+            let mut camera = new_camera(*projection);
+            camera
+                .transform_mut()
+                .set_position(&Vec3::new(3., -1., 5.))
+                .rotate_axis_angle_local(&Vec3::UP, 0.3);
+
+            let screen = camera.world_to_screen(&point);
+            assert!(screen.z > 0.);
+            assert_near(
+                &camera.screen_to_world(screen.x, screen.y, screen.z),
+                &point,
+            );
+        }
+    }
 }
@@ -17,6 +17,7 @@ pub trait Entity: downcast_rs::Downcast {
     fn get_component_mut(&mut self, type_id: TypeId) -> Option<&mut Box<dyn Any>>;
     fn remove_component(&mut self, type_id: TypeId);
     fn children(&self) -> Vec<&dyn Entity>;
+    fn children_mut(&mut self) -> Vec<&mut dyn Entity>;
     fn visible(&self) -> bool;
 }
 
@@ -112,6 +113,18 @@ impl<TExtension: EntityExtension + 'static> DerefMut for CoreEntity<TExtension>
     }
 }
 
+mod private {
+    pub struct DefaultExtension {}
+    impl super::EntityExtension for DefaultExtension {}
+}
+
+pub struct DefaultEntity;
+impl DefaultEntity {
+    pub fn create(name: &str) -> CoreEntity<private::DefaultExtension> {
+        CoreEntity::new(private::DefaultExtension {}, name)
+    }
+}
+
 #[inline]
 pub fn entity_add_component<T: 'static>(entity: &mut dyn Entity, component: T) {
     entity.add_component(TypeId::of::<T>(), Box::new(component));
@@ -130,6 +143,31 @@ pub fn entity_get_component<T: 'static>(entity: &dyn Entity) -> Option<&T> {
         .and_then(|component| component.downcast_ref())
 }
 
+#[inline]
+pub fn entity_get_component_mut<T: 'static>(entity: &mut dyn Entity) -> Option<&mut T> {
+    let type_id = TypeId::of::<T>();
+    entity
+        .get_component_mut(type_id)
+        .and_then(|component| component.downcast_mut())
+}
+
+/// Runs `f` with the component taken out of the entity, so that the entity, its transform and
+/// its other components can be borrowed at the same time. The component can't be looked up
+/// from the entity while `f` runs.
+pub fn entity_with_component_mut<T: 'static, R>(
+    entity: &mut dyn Entity,
+    f: impl FnOnce(&mut T, &mut dyn Entity) -> R,
+) -> Option<R> {
+    let type_id = TypeId::of::<T>();
+    let mut component = std::mem::replace(entity.get_component_mut(type_id)?, Box::new(()));
+    let result = component.downcast_mut::<T>().map(|c| f(c, &mut *entity));
+    if let Some(slot) = entity.get_component_mut(type_id) {
+        *slot = component;
+    }
+
+    result
+}
+
 #[inline]
 pub fn entity_remove_component<T: 'static>(entity: &mut dyn Entity) {
     let type_id = TypeId::of::<T>();
@@ -226,4 +264,8 @@ impl<TExtension: EntityExtension + 'static> Entity for CoreEntity<TExtension> {
     fn children(&self) -> Vec<&dyn Entity> {
         self.children.iter().map(|e| e.as_ref()).collect()
     }
+
+    fn children_mut(&mut self) -> Vec<&mut dyn Entity> {
+        self.children.iter_mut().map(|e| e.as_mut()).collect()
+    }
 }
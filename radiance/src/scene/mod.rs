@@ -4,8 +4,11 @@ mod entity;
 mod scene;
 mod scene_manager;
 
-pub use camera::Camera;
+pub use camera::{Background, Camera, Projection, Viewport};
 pub use director::Director;
-pub use entity::{entity_add_component, entity_get_component, CoreEntity, Entity, EntityExtension};
+pub use entity::{
+    entity_add_component, entity_get_component, entity_get_component_mut,
+    entity_with_component_mut, CoreEntity, DefaultEntity, Entity, EntityExtension,
+};
 pub use scene::{CoreScene, DefaultScene, Scene, SceneExtension};
 pub use scene_manager::{DefaultSceneManager, SceneManager};
@@ -12,8 +12,17 @@ pub trait Scene: downcast_rs::Downcast {
     fn entities(&self) -> Vec<&dyn Entity>;
     fn root_entities(&self) -> &Vec<Box<dyn Entity>>;
     fn root_entities_mut(&mut self) -> &mut Vec<Box<dyn Entity>>;
+    /// The main camera, rendering to the screen
     fn camera(&self) -> &Camera;
     fn camera_mut(&mut self) -> &mut Camera;
+
+    /// Every camera of the scene, the main one first
+    fn cameras(&self) -> &[Camera];
+    fn cameras_mut(&mut self) -> &mut [Camera];
+    fn add_camera(&mut self, camera: Camera);
+
+    /// Removes the camera at `index` in `cameras`, the main camera can't be removed
+    fn remove_camera(&mut self, index: usize) -> Option<Camera>;
 }
 
 downcast_rs::impl_downcast!(Scene);
@@ -53,7 +62,7 @@ pub trait SceneExtension {
 pub struct CoreScene<TExtension: SceneExtension> {
     entities: Vec<Box<dyn Entity>>,
     extension: TExtension,
-    camera: Camera,
+    cameras: Vec<Camera>,
 }
 
 impl<TExtension: SceneExtension> CoreScene<TExtension> {
@@ -61,7 +70,7 @@ impl<TExtension: SceneExtension> CoreScene<TExtension> {
         Self {
             entities: vec![],
             extension: ext_calls,
-            camera: Camera::new(),
+            cameras: vec![Camera::new()],
         }
     }
 
@@ -163,10 +172,30 @@ impl<TExtension: 'static + SceneExtension> Scene for CoreScene<TExtension> {
     }
 
     fn camera(&self) -> &Camera {
-        &self.camera
+        &self.cameras[0]
     }
 
     fn camera_mut(&mut self) -> &mut Camera {
-        &mut self.camera
+        &mut self.cameras[0]
+    }
+
+    fn cameras(&self) -> &[Camera] {
+        &self.cameras
+    }
+
+    fn cameras_mut(&mut self) -> &mut [Camera] {
+        &mut self.cameras
+    }
+
+    fn add_camera(&mut self, camera: Camera) {
+        self.cameras.push(camera);
+    }
+
+    fn remove_camera(&mut self, index: usize) -> Option<Camera> {
+        if index == 0 || index >= self.cameras.len() {
+            return None;
+        }
+
+        Some(self.cameras.remove(index))
     }
 }
@@ -0,0 +1,30 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform sampler2D inputs[3];
+
+layout(push_constant) uniform BloomParams {
+    // x: threshold, y: 1 when the threshold applies
+    vec4 bloom;
+} params;
+
+layout(location = 0) in vec2 fragTexCoord;
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    vec2 texel = 1.0 / vec2(textureSize(inputs[0], 0));
+
+    // Four bilinear taps average a 4x4 block of the source
+    vec3 color = texture(inputs[0], fragTexCoord + texel * vec2(-1.0, -1.0)).rgb;
+    color += texture(inputs[0], fragTexCoord + texel * vec2(1.0, -1.0)).rgb;
+    color += texture(inputs[0], fragTexCoord + texel * vec2(-1.0, 1.0)).rgb;
+    color += texture(inputs[0], fragTexCoord + texel * vec2(1.0, 1.0)).rgb;
+    color *= 0.25;
+
+    if (params.bloom.y > 0.0) {
+        float brightness = max(color.r, max(color.g, color.b));
+        color *= max(brightness - params.bloom.x, 0.0) / max(brightness, 0.0001);
+    }
+
+    outColor = vec4(color, 1.0);
+}
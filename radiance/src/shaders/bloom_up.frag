@@ -0,0 +1,26 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// 0: the blurred lower mip, 1: the downsampled mip of the same size as the output
+layout(set = 0, binding = 0) uniform sampler2D inputs[3];
+
+layout(location = 0) in vec2 fragTexCoord;
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    vec2 texel = 1.0 / vec2(textureSize(inputs[0], 0));
+
+    // 3x3 tent filter
+    vec3 color = texture(inputs[0], fragTexCoord).rgb * 4.0;
+    color += texture(inputs[0], fragTexCoord + texel * vec2(-1.0, 0.0)).rgb * 2.0;
+    color += texture(inputs[0], fragTexCoord + texel * vec2(1.0, 0.0)).rgb * 2.0;
+    color += texture(inputs[0], fragTexCoord + texel * vec2(0.0, -1.0)).rgb * 2.0;
+    color += texture(inputs[0], fragTexCoord + texel * vec2(0.0, 1.0)).rgb * 2.0;
+    color += texture(inputs[0], fragTexCoord + texel * vec2(-1.0, -1.0)).rgb;
+    color += texture(inputs[0], fragTexCoord + texel * vec2(1.0, -1.0)).rgb;
+    color += texture(inputs[0], fragTexCoord + texel * vec2(-1.0, 1.0)).rgb;
+    color += texture(inputs[0], fragTexCoord + texel * vec2(1.0, 1.0)).rgb;
+    color /= 16.0;
+
+    outColor = vec4(color + texture(inputs[1], fragTexCoord).rgb, 1.0);
+}
@@ -0,0 +1,78 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// 0: the HDR scene, 1: bloom, 2: color grading LUT
+layout(set = 0, binding = 0) uniform sampler2D inputs[3];
+
+layout(push_constant) uniform CompositeParams {
+    // x: exposure, y: gamma, z: tone mapping, w: bloom intensity
+    vec4 toneMapping;
+
+    // x: vignette intensity, y: vignette smoothness, z: LUT size, 0 when disabled,
+    // w: 1 when writing to an sRGB image
+    vec4 grading;
+} params;
+
+layout(location = 0) in vec2 fragTexCoord;
+layout(location = 0) out vec4 outColor;
+
+const float TONE_MAPPING_REINHARD = 1.0;
+const float TONE_MAPPING_ACES = 2.0;
+
+// Krzysztof Narkowicz's fit of the ACES curve
+vec3 aces(vec3 color) {
+    color *= 0.6;
+    return (color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14);
+}
+
+// Grading happens on gamma encoded colors. sRGB images encode what they are given again,
+// so the colors are decoded before being written to them.
+vec3 srgbToLinear(vec3 color) {
+    return mix(
+        color / 12.92,
+        pow((color + 0.055) / 1.055, vec3(2.4)),
+        step(vec3(0.04045), color));
+}
+
+vec3 gradeColor(vec3 color, float size) {
+    float slice = color.b * (size - 1.0);
+    float slice0 = floor(slice);
+    float slice1 = min(slice0 + 1.0, size - 1.0);
+    vec2 uv = (color.rg * (size - 1.0) + 0.5) / vec2(size * size, size);
+    vec3 a = texture(inputs[2], uv + vec2(slice0 / size, 0.0)).rgb;
+    vec3 b = texture(inputs[2], uv + vec2(slice1 / size, 0.0)).rgb;
+    return mix(a, b, slice - slice0);
+}
+
+void main() {
+    vec3 color = texture(inputs[0], fragTexCoord).rgb;
+    if (params.toneMapping.w > 0.0) {
+        color += texture(inputs[1], fragTexCoord).rgb * params.toneMapping.w;
+    }
+
+    color *= params.toneMapping.x;
+    if (params.toneMapping.z == TONE_MAPPING_REINHARD) {
+        color = color / (color + 1.0);
+    } else if (params.toneMapping.z == TONE_MAPPING_ACES) {
+        color = aces(color);
+    }
+
+    color = pow(clamp(color, 0.0, 1.0), vec3(1.0 / params.toneMapping.y));
+
+    if (params.grading.z > 0.0) {
+        color = gradeColor(color, params.grading.z);
+    }
+
+    if (params.grading.x > 0.0) {
+        // 0 in the center, 1 in the corners
+        float distance = length(fragTexCoord - 0.5) * 1.41421356;
+        float falloff = smoothstep(1.0 - params.grading.y, 1.0, distance);
+        color *= 1.0 - params.grading.x * falloff;
+    }
+
+    if (params.grading.w > 0.0) {
+        color = srgbToLinear(color);
+    }
+
+    outColor = vec4(color, 1.0);
+}
@@ -0,0 +1,57 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// 0: the tone mapped scene
+layout(set = 0, binding = 0) uniform sampler2D inputs[3];
+
+layout(location = 0) in vec2 fragTexCoord;
+layout(location = 0) out vec4 outColor;
+
+const float FXAA_SPAN_MAX = 8.0;
+const float FXAA_REDUCE_MUL = 1.0 / 8.0;
+const float FXAA_REDUCE_MIN = 1.0 / 128.0;
+
+float luma(vec3 color) {
+    return dot(color, vec3(0.299, 0.587, 0.114));
+}
+
+vec3 sampleAt(vec2 uv) {
+    return texture(inputs[0], uv).rgb;
+}
+
+// Blurs along the edge direction estimated from the luma of the neighbours
+void main() {
+    vec2 texel = 1.0 / vec2(textureSize(inputs[0], 0));
+    float lumaNW = luma(sampleAt(fragTexCoord + texel * vec2(-1.0, -1.0)));
+    float lumaNE = luma(sampleAt(fragTexCoord + texel * vec2(1.0, -1.0)));
+    float lumaSW = luma(sampleAt(fragTexCoord + texel * vec2(-1.0, 1.0)));
+    float lumaSE = luma(sampleAt(fragTexCoord + texel * vec2(1.0, 1.0)));
+    vec3 colorM = sampleAt(fragTexCoord);
+    float lumaM = luma(colorM);
+
+    float lumaMin = min(lumaM, min(min(lumaNW, lumaNE), min(lumaSW, lumaSE)));
+    float lumaMax = max(lumaM, max(max(lumaNW, lumaNE), max(lumaSW, lumaSE)));
+
+    vec2 direction = vec2(
+        -((lumaNW + lumaNE) - (lumaSW + lumaSE)),
+        (lumaNW + lumaSW) - (lumaNE + lumaSE));
+    float directionReduce = max(
+        (lumaNW + lumaNE + lumaSW + lumaSE) * 0.25 * FXAA_REDUCE_MUL,
+        FXAA_REDUCE_MIN);
+    float inverseDirectionMin = 1.0 / (min(abs(direction.x), abs(direction.y)) + directionReduce);
+    direction = clamp(direction * inverseDirectionMin, -FXAA_SPAN_MAX, FXAA_SPAN_MAX) * texel;
+
+    vec3 colorA = 0.5 * (
+        sampleAt(fragTexCoord + direction * (1.0 / 3.0 - 0.5)) +
+        sampleAt(fragTexCoord + direction * (2.0 / 3.0 - 0.5)));
+    vec3 colorB = colorA * 0.5 + 0.25 * (
+        sampleAt(fragTexCoord - direction * 0.5) +
+        sampleAt(fragTexCoord + direction * 0.5));
+
+    float lumaB = luma(colorB);
+    if (lumaB < lumaMin || lumaB > lumaMax) {
+        outColor = vec4(colorA, 1.0);
+    } else {
+        outColor = vec4(colorB, 1.0);
+    }
+}
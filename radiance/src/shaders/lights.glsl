@@ -0,0 +1,99 @@
+#define MAX_LIGHTS 16
+#define MAX_SHADOW_MAPS 8
+#define SHADOW_CASCADE_COUNT 4
+
+#define LIGHT_DIRECTIONAL 1
+#define LIGHT_POINT 2
+#define LIGHT_SPOT 3
+
+struct Light {
+    vec4 positionRange;
+    vec4 directionType;
+    vec4 colorIntensity;
+    vec4 spotCone;
+    // x: first shadow map or -1, y: shadow map count, z: depth bias
+    vec4 shadow;
+};
+
+layout(set = 0, binding = 1) uniform PerFrameLights {
+    vec4 cameraPosition;
+    vec4 cameraForward;
+    vec4 ambient;
+    // x: intensity, y: mip levels of the prefiltered environment map
+    vec4 environment;
+    uvec4 lightCount;
+    Light lights[MAX_LIGHTS];
+    mat4 shadowMatrices[MAX_SHADOW_MAPS];
+    vec4 cascadeSplits;
+} perFrameLights;
+
+layout(set = 0, binding = 4) uniform sampler2DArrayShadow shadowMaps;
+
+const mat4 shadowClip = mat4(vec4(1.0, 0.0, 0.0, 0.0),
+                             vec4(0.0, -1.0, 0.0, 0.0),
+                             vec4(0.0, 0.0, 0.5, 0.5),
+                             vec4(0.0, 0.0, 0, 1.0));
+
+// Fraction of the light reaching worldPosition, filtered with 3x3 PCF
+float shadowFactor(Light light, vec3 worldPosition) {
+    int index = int(light.shadow.x);
+    if (index < 0) {
+        return 1.0;
+    }
+
+    int count = int(light.shadow.y);
+    if (count > 1) {
+        float depth = dot(worldPosition - perFrameLights.cameraPosition.xyz, perFrameLights.cameraForward.xyz);
+        if (depth > perFrameLights.cascadeSplits[count - 1]) {
+            return 1.0;
+        }
+
+        for (int i = 0; i < count - 1 && depth > perFrameLights.cascadeSplits[i]; i++) {
+            index++;
+        }
+    }
+
+    vec4 position = vec4(worldPosition, 1.0) * perFrameLights.shadowMatrices[index] * shadowClip;
+    vec3 coords = position.xyz / position.w;
+    vec2 uv = coords.xy * 0.5 + 0.5;
+    if (coords.z > 1.0 || any(lessThan(uv, vec2(0.0))) || any(greaterThan(uv, vec2(1.0)))) {
+        return 1.0;
+    }
+
+    vec2 texel = 1.0 / vec2(textureSize(shadowMaps, 0).xy);
+    float lit = 0.0;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            vec4 shadowCoords = vec4(uv + vec2(x, y) * texel, float(index), coords.z - light.shadow.z);
+            lit += texture(shadowMaps, shadowCoords);
+        }
+    }
+
+    return lit / 9.0;
+}
+
+// Returns the radiance arriving at worldPosition, L is set to the direction towards the light
+vec3 lightRadiance(Light light, vec3 worldPosition, out vec3 L) {
+    int type = int(light.directionType.w);
+    vec3 radiance = light.colorIntensity.rgb * light.colorIntensity.a;
+    if (type == LIGHT_DIRECTIONAL) {
+        L = normalize(-light.directionType.xyz);
+        return radiance * shadowFactor(light, worldPosition);
+    }
+
+    vec3 toLight = light.positionRange.xyz - worldPosition;
+    float distance = length(toLight);
+    L = toLight / max(distance, 0.0001);
+
+    float range = light.positionRange.w;
+    float window = range > 0.0 ? clamp(1.0 - pow(distance / range, 4.0), 0.0, 1.0) : 1.0;
+    float attenuation = window * window / max(distance * distance, 0.0001);
+
+    if (type == LIGHT_SPOT) {
+        float cosAngle = dot(-L, normalize(light.directionType.xyz));
+        attenuation *= smoothstep(light.spotCone.y, light.spotCone.x, cosAngle);
+        attenuation *= shadowFactor(light, worldPosition);
+    }
+
+    return radiance * attenuation;
+}
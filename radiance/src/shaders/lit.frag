@@ -0,0 +1,50 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_GOOGLE_include_directive : require
+
+#include "lights.glsl"
+
+layout(set = 2, binding = 0) uniform sampler2D texSampler;
+
+layout(push_constant) uniform MaterialParams {
+    // rgb: specular color, a: shininess
+    layout(offset = 16) vec4 specular;
+} params;
+
+layout(location = 0) in vec2 fragTexCoord;
+layout(location = 1) in vec3 fragWorldPosition;
+layout(location = 2) in vec3 fragWorldNormal;
+
+#ifdef INSTANCED
+layout(location = 3) in vec4 fragColor;
+#endif
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    // Lighting is computed in linear space, the sampler decodes sRGB textures and
+    // post-processing encodes the result for the display
+    vec4 albedo = texture(texSampler, fragTexCoord);
+#ifdef INSTANCED
+    albedo *= fragColor;
+#endif
+    if (albedo.a == 0.0) {
+        discard;
+    }
+
+    vec3 N = normalize(fragWorldNormal);
+    vec3 V = normalize(perFrameLights.cameraPosition.xyz - fragWorldPosition);
+    vec3 color = perFrameLights.ambient.rgb * albedo.rgb;
+
+    uint lightCount = min(perFrameLights.lightCount.x, MAX_LIGHTS);
+    for (uint i = 0; i < lightCount; i++) {
+        vec3 L;
+        vec3 radiance = lightRadiance(perFrameLights.lights[i], fragWorldPosition, L);
+        float NdotL = max(dot(N, L), 0.0);
+        vec3 H = normalize(L + V);
+        float specular = NdotL > 0.0 ? pow(max(dot(N, H), 0.0), params.specular.a) : 0.0;
+        color += (albedo.rgb * NdotL + params.specular.rgb * specular) * radiance;
+    }
+
+    outColor = vec4(color, albedo.a);
+}
@@ -0,0 +1,49 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform PerFrameUbo {
+    mat4 view;
+    mat4 proj;
+} perFrameUbo;
+
+layout(set = 1, binding = 0) uniform PerInstanceUbo {
+    mat4 model;
+} perInstanceUbo;
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+layout(location = 2) in vec2 inTexCoord;
+
+layout(location = 0) out vec2 fragTexCoord;
+layout(location = 1) out vec3 fragWorldPosition;
+layout(location = 2) out vec3 fragWorldNormal;
+
+#ifdef INSTANCED
+// Per-instance attributes, the transform is relative to the entity
+layout(location = 8) in mat4 instanceModel;
+layout(location = 12) in vec4 instanceColor;
+
+layout(location = 3) out vec4 fragColor;
+#endif
+
+mat4 clip = mat4(vec4(1.0, 0.0, 0.0, 0.0),
+                 vec4(0.0, -1.0, 0.0, 0.0),
+                 vec4(0.0, 0.0, 0.5, 0.5),
+                 vec4(0.0, 0.0, 0, 1.0));
+
+void main() {
+#ifdef INSTANCED
+    mat4 model = instanceModel * perInstanceUbo.model;
+    fragColor = instanceColor;
+#else
+    mat4 model = perInstanceUbo.model;
+#endif
+
+    vec4 worldPosition = vec4(position, 1.0) * model;
+    gl_Position = worldPosition * perFrameUbo.view * perFrameUbo.proj * clip;
+
+    // Assumes the model matrix has no non-uniform scaling
+    fragWorldNormal = (vec4(normal, 0.0) * model).xyz;
+    fragWorldPosition = worldPosition.xyz;
+    fragTexCoord = inTexCoord;
+}
@@ -0,0 +1,62 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform PerFrameUbo {
+    mat4 view;
+    mat4 proj;
+} perFrameUbo;
+
+layout(set = 1, binding = 0) uniform PerInstanceUbo {
+    mat4 model;
+} perInstanceUbo;
+
+struct MorphDelta {
+    vec4 position;
+    vec4 normal;
+};
+
+// The deltas of every vertex of the first target, then of the second one...
+layout(std430, set = 3, binding = 0) readonly buffer MorphDeltas {
+    MorphDelta deltas[];
+} morphDeltas;
+
+layout(std430, set = 3, binding = 1) readonly buffer MorphWeights {
+    float weights[];
+} morphWeights;
+
+layout(push_constant) uniform MorphTargets {
+    uint targetCount;
+    uint vertexCount;
+} morph;
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+layout(location = 2) in vec2 inTexCoord;
+
+layout(location = 0) out vec2 fragTexCoord;
+layout(location = 1) out vec3 fragWorldPosition;
+layout(location = 2) out vec3 fragWorldNormal;
+
+mat4 clip = mat4(vec4(1.0, 0.0, 0.0, 0.0),
+                 vec4(0.0, -1.0, 0.0, 0.0),
+                 vec4(0.0, 0.0, 0.5, 0.5),
+                 vec4(0.0, 0.0, 0, 1.0));
+
+void main() {
+    vec3 morphedPosition = position;
+    vec3 morphedNormal = normal;
+    for (uint i = 0; i < morph.targetCount; i++) {
+        float weight = morphWeights.weights[i];
+        MorphDelta delta = morphDeltas.deltas[i * morph.vertexCount + uint(gl_VertexIndex)];
+        morphedPosition += weight * delta.position.xyz;
+        morphedNormal += weight * delta.normal.xyz;
+    }
+
+    vec4 worldPosition = vec4(morphedPosition, 1.0) * perInstanceUbo.model;
+    gl_Position = worldPosition * perFrameUbo.view * perFrameUbo.proj * clip;
+
+    // Assumes the model matrix has no non-uniform scaling
+    fragWorldNormal = (vec4(morphedNormal, 0.0) * perInstanceUbo.model).xyz;
+    fragWorldPosition = worldPosition.xyz;
+    fragTexCoord = inTexCoord;
+}
@@ -0,0 +1,144 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_GOOGLE_include_directive : require
+
+#include "lights.glsl"
+
+#define PI 3.14159265359
+
+layout(set = 0, binding = 2) uniform samplerCube irradianceMap;
+layout(set = 0, binding = 3) uniform samplerCube prefilteredMap;
+
+layout(set = 2, binding = 0) uniform sampler2D baseColorMap;
+layout(set = 2, binding = 1) uniform sampler2D normalMap;
+layout(set = 2, binding = 2) uniform sampler2D metallicRoughnessMap;
+layout(set = 2, binding = 3) uniform sampler2D occlusionMap;
+layout(set = 2, binding = 4) uniform sampler2D emissiveMap;
+
+layout(push_constant) uniform MaterialParams {
+    layout(offset = 16) vec4 baseColorFactor;
+    // rgb: emissive factor, a: normal scale
+    vec4 emissiveFactor;
+    // x: metallic, y: roughness, z: occlusion strength, w: alpha cutoff
+    vec4 factors;
+} params;
+
+layout(location = 0) in vec2 fragTexCoord;
+layout(location = 1) in vec3 fragWorldPosition;
+layout(location = 2) in vec3 fragWorldNormal;
+
+#ifdef INSTANCED
+layout(location = 3) in vec4 fragColor;
+#endif
+
+layout(location = 0) out vec4 outColor;
+
+// Tangent frame from screen space derivatives, so meshes don't need tangents
+vec3 perturbNormal(vec3 N, vec3 mapNormal) {
+    vec3 dp1 = dFdx(fragWorldPosition);
+    vec3 dp2 = dFdy(fragWorldPosition);
+    vec2 duv1 = dFdx(fragTexCoord);
+    vec2 duv2 = dFdy(fragTexCoord);
+
+    vec3 dp2perp = cross(dp2, N);
+    vec3 dp1perp = cross(N, dp1);
+    vec3 T = dp2perp * duv1.x + dp1perp * duv2.x;
+    // glTF texture coordinates grow downwards while normal maps point green upwards
+    vec3 B = -(dp2perp * duv1.y + dp1perp * duv2.y);
+
+    float scale = inversesqrt(max(max(dot(T, T), dot(B, B)), 1e-12));
+    return normalize(mat3(T * scale, B * scale, N) * mapNormal);
+}
+
+float distributionGGX(float NdotH, float alpha) {
+    float alpha2 = alpha * alpha;
+    float d = NdotH * NdotH * (alpha2 - 1.0) + 1.0;
+    return alpha2 / (PI * d * d);
+}
+
+float geometrySmith(float NdotV, float NdotL, float roughness) {
+    float k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+    float ggxV = NdotV / (NdotV * (1.0 - k) + k);
+    float ggxL = NdotL / (NdotL * (1.0 - k) + k);
+    return ggxV * ggxL;
+}
+
+vec3 fresnelSchlick(float cosTheta, vec3 F0) {
+    return F0 + (1.0 - F0) * pow(1.0 - cosTheta, 5.0);
+}
+
+vec3 fresnelSchlickRoughness(float cosTheta, vec3 F0, float roughness) {
+    return F0 + (max(vec3(1.0 - roughness), F0) - F0) * pow(1.0 - cosTheta, 5.0);
+}
+
+// Analytic fit of the split sum BRDF lookup table
+vec2 environmentBrdf(float NdotV, float roughness) {
+    const vec4 c0 = vec4(-1.0, -0.0275, -0.572, 0.022);
+    const vec4 c1 = vec4(1.0, 0.0425, 1.04, -0.04);
+    vec4 r = roughness * c0 + c1;
+    float a004 = min(r.x * r.x, exp2(-9.28 * NdotV)) * r.x + r.y;
+    return vec2(-1.04, 1.04) * a004 + r.zw;
+}
+
+void main() {
+    // Base color, emissive and environment maps are sRGB textures decoded by the samplers
+    vec4 baseColor = texture(baseColorMap, fragTexCoord) * params.baseColorFactor;
+#ifdef INSTANCED
+    baseColor *= fragColor;
+#endif
+    if (baseColor.a < params.factors.w || baseColor.a == 0.0) {
+        discard;
+    }
+
+    // glTF stores roughness in G and metalness in B
+    vec4 metallicRoughness = texture(metallicRoughnessMap, fragTexCoord);
+    float metallic = clamp(params.factors.x * metallicRoughness.b, 0.0, 1.0);
+    float roughness = clamp(params.factors.y * metallicRoughness.g, 0.04, 1.0);
+    float alpha = roughness * roughness;
+
+    vec3 mapNormal = texture(normalMap, fragTexCoord).xyz * 2.0 - 1.0;
+    mapNormal.xy *= params.emissiveFactor.a;
+    vec3 N = perturbNormal(normalize(fragWorldNormal), normalize(mapNormal));
+    vec3 V = normalize(perFrameLights.cameraPosition.xyz - fragWorldPosition);
+    float NdotV = max(dot(N, V), 1e-4);
+
+    vec3 F0 = mix(vec3(0.04), baseColor.rgb, metallic);
+    vec3 diffuseColor = baseColor.rgb * (1.0 - metallic);
+    vec3 color = vec3(0.0);
+
+    uint lightCount = min(perFrameLights.lightCount.x, MAX_LIGHTS);
+    for (uint i = 0; i < lightCount; i++) {
+        vec3 L;
+        vec3 radiance = lightRadiance(perFrameLights.lights[i], fragWorldPosition, L);
+        float NdotL = max(dot(N, L), 0.0);
+        if (NdotL == 0.0) {
+            continue;
+        }
+
+        vec3 H = normalize(L + V);
+        vec3 F = fresnelSchlick(max(dot(H, V), 0.0), F0);
+        float D = distributionGGX(max(dot(N, H), 0.0), alpha);
+        float G = geometrySmith(NdotV, NdotL, roughness);
+        vec3 specular = D * G * F / (4.0 * NdotV * NdotL);
+        vec3 diffuse = (1.0 - F) * diffuseColor / PI;
+        color += (diffuse + specular) * radiance * NdotL;
+    }
+
+    float occlusion = 1.0 + params.factors.z * (texture(occlusionMap, fragTexCoord).r - 1.0);
+
+    // Image based lighting
+    vec3 F = fresnelSchlickRoughness(NdotV, F0, roughness);
+    vec3 irradiance = texture(irradianceMap, N).rgb;
+    float lod = roughness * (perFrameLights.environment.y - 1.0);
+    vec3 prefiltered = textureLod(prefilteredMap, reflect(-V, N), lod).rgb;
+    vec2 brdf = environmentBrdf(NdotV, roughness);
+    vec3 ambient = (1.0 - F) * diffuseColor * irradiance + prefiltered * (F * brdf.x + brdf.y);
+    color += ambient * perFrameLights.environment.x * occlusion;
+    color += perFrameLights.ambient.rgb * diffuseColor * occlusion;
+
+    vec3 emissive = texture(emissiveMap, fragTexCoord).rgb;
+    color += emissive * params.emissiveFactor.rgb;
+
+    // Linear HDR, post-processing maps it to the display
+    outColor = vec4(color, baseColor.a);
+}
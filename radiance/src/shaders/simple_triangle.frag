@@ -4,10 +4,18 @@
 layout(set = 2, binding = 0) uniform sampler2D texSampler;
 
 layout(location = 0) in vec2 fragTexCoord;
+
+#ifdef INSTANCED
+layout(location = 3) in vec4 fragColor;
+#endif
 layout(location = 0) out vec4 outColor;
 
 void main() {
+    // The scene is rendered in linear space, which sRGB textures are decoded to when sampled
     outColor = texture(texSampler, fragTexCoord);
+#ifdef INSTANCED
+    outColor *= fragColor;
+#endif
     if (outColor.a == 0.0) {
         discard;
     }
@@ -15,12 +15,27 @@ layout(location = 2) in vec2 inTexCoord;
 
 layout(location = 0) out vec2 fragTexCoord;
 
+#ifdef INSTANCED
+// Per-instance attributes, the transform is relative to the entity
+layout(location = 8) in mat4 instanceModel;
+layout(location = 12) in vec4 instanceColor;
+
+layout(location = 3) out vec4 fragColor;
+#endif
+
 mat4 clip = mat4(vec4(1.0, 0.0, 0.0, 0.0),
                  vec4(0.0, -1.0, 0.0, 0.0),
                  vec4(0.0, 0.0, 0.5, 0.5),
                  vec4(0.0, 0.0, 0, 1.0));
 
 void main() {
-    gl_Position = vec4(position, 1.0) * perInstanceUbo.model * perFrameUbo.view * perFrameUbo.proj * clip;
+#ifdef INSTANCED
+    mat4 model = instanceModel * perInstanceUbo.model;
+    fragColor = instanceColor;
+#else
+    mat4 model = perInstanceUbo.model;
+#endif
+
+    gl_Position = vec4(position, 1.0) * model * perFrameUbo.view * perFrameUbo.proj * clip;
     fragTexCoord = inTexCoord;
 }
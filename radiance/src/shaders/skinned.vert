@@ -0,0 +1,46 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+#define MAX_BONES 64
+
+layout(set = 0, binding = 0) uniform PerFrameUbo {
+    mat4 view;
+    mat4 proj;
+} perFrameUbo;
+
+layout(set = 1, binding = 0) uniform PerInstanceUbo {
+    mat4 model;
+} perInstanceUbo;
+
+layout(set = 3, binding = 0) uniform PerSkinUbo {
+    mat4 bones[MAX_BONES];
+} perSkinUbo;
+
+layout(location = 0) in vec3 position;
+layout(location = 2) in vec2 inTexCoord;
+layout(location = 6) in uvec4 boneIndices;
+layout(location = 7) in vec4 boneWeights;
+
+layout(location = 0) out vec2 fragTexCoord;
+
+mat4 clip = mat4(vec4(1.0, 0.0, 0.0, 0.0),
+                 vec4(0.0, -1.0, 0.0, 0.0),
+                 vec4(0.0, 0.0, 0.5, 0.5),
+                 vec4(0.0, 0.0, 0, 1.0));
+
+void main() {
+    // Never read past the bone array, whatever the vertex buffer holds
+    uvec4 bones = min(boneIndices, uvec4(MAX_BONES - 1));
+    mat4 skin = boneWeights.x * perSkinUbo.bones[bones.x]
+              + boneWeights.y * perSkinUbo.bones[bones.y]
+              + boneWeights.z * perSkinUbo.bones[bones.z]
+              + boneWeights.w * perSkinUbo.bones[bones.w];
+
+    // Vertices without any weight are not attached to the skeleton
+    if (dot(boneWeights, vec4(1.0)) == 0.0) {
+        skin = mat4(1.0);
+    }
+
+    gl_Position = vec4(position, 1.0) * skin * perInstanceUbo.model * perFrameUbo.view * perFrameUbo.proj * clip;
+    fragTexCoord = inTexCoord;
+}
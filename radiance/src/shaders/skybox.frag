@@ -0,0 +1,17 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 1, binding = 0) uniform samplerCube skybox;
+
+layout(push_constant) uniform SkyboxParams {
+    // x: intensity
+    vec4 params;
+} skyboxParams;
+
+layout(location = 0) in vec3 fragDirection;
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    vec3 color = texture(skybox, normalize(fragDirection)).rgb;
+    outColor = vec4(color * skyboxParams.params.x, 1.0);
+}
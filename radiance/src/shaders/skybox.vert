@@ -0,0 +1,19 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform PerFrameUbo {
+    mat4 view;
+    mat4 proj;
+} perFrameUbo;
+
+layout(location = 0) out vec3 fragDirection;
+
+// A single triangle covering the screen, each corner unprojected to the world direction it
+// looks at. The Y axis is flipped back since the clip matrix isn't applied here.
+void main() {
+    vec2 position = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2) * 2.0 - 1.0;
+    gl_Position = vec4(position, 0.0, 1.0);
+
+    vec4 viewPosition = vec4(position.x, -position.y, -1.0, 1.0) * inverse(perFrameUbo.proj);
+    fragDirection = (viewPosition.xyz / viewPosition.w) * inverse(mat3(perFrameUbo.view));
+}
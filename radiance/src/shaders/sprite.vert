@@ -0,0 +1,26 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform PerFrameUbo {
+    mat4 view;
+    mat4 proj;
+} perFrameUbo;
+
+// Sprite corners are already in world space, batched per atlas
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec2 inTexCoord;
+layout(location = 2) in vec4 inColor;
+
+layout(location = 0) out vec2 fragTexCoord;
+layout(location = 1) out vec4 fragColor;
+
+mat4 clip = mat4(vec4(1.0, 0.0, 0.0, 0.0),
+                 vec4(0.0, -1.0, 0.0, 0.0),
+                 vec4(0.0, 0.0, 0.5, 0.5),
+                 vec4(0.0, 0.0, 0, 1.0));
+
+void main() {
+    gl_Position = vec4(position, 1.0) * perFrameUbo.view * perFrameUbo.proj * clip;
+    fragTexCoord = inTexCoord;
+    fragColor = inColor;
+}
@@ -0,0 +1,21 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(push_constant) uniform ScreenParams {
+    // Width and height of the target in pixels
+    vec2 size;
+} screenParams;
+
+// Corners in pixels from the top left of the target
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec2 inTexCoord;
+layout(location = 2) in vec4 inColor;
+
+layout(location = 0) out vec2 fragTexCoord;
+layout(location = 1) out vec4 fragColor;
+
+void main() {
+    gl_Position = vec4(position.xy / screenParams.size * 2.0 - 1.0, 0.0, 1.0);
+    fragTexCoord = inTexCoord;
+    fragColor = inColor;
+}